@@ -0,0 +1,36 @@
+CREATE TABLE users (
+    id UUID PRIMARY KEY,
+    -- 登録順に並べるための連番
+    seq BIGINT GENERATED ALWAYS AS IDENTITY,
+    name TEXT NOT NULL,
+    -- 検索用に小文字にした名前
+    name_key TEXT NOT NULL,
+    avatar_url TEXT,
+    role TEXT NOT NULL,
+    password_hash TEXT
+);
+
+CREATE INDEX users_seq ON users (seq);
+CREATE INDEX users_name ON users (name);
+
+CREATE TABLE posts (
+    id UUID PRIMARY KEY,
+    title TEXT NOT NULL,
+    slug TEXT NOT NULL UNIQUE,
+    author_id UUID NOT NULL,
+    body TEXT NOT NULL,
+    tags TEXT[] NOT NULL DEFAULT '{}',
+    -- 前後の空白を除いて小文字にしたタグ（絞り込み用）
+    tag_keys TEXT[] NOT NULL DEFAULT '{}',
+    status TEXT NOT NULL,
+    published_at TIMESTAMPTZ NOT NULL,
+    scheduled_at TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ NOT NULL,
+    deleted_at TIMESTAMPTZ,
+    -- 更新履歴（PostRevisionの配列）
+    revisions JSONB NOT NULL DEFAULT '[]'
+);
+
+CREATE INDEX posts_published_at ON posts (published_at DESC, id DESC);
+CREATE INDEX posts_author_id ON posts (author_id);
+CREATE INDEX posts_tag_keys ON posts USING GIN (tag_keys);
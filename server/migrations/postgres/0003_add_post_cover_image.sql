@@ -0,0 +1,3 @@
+-- カバー画像のURLと代替テキスト
+ALTER TABLE posts ADD COLUMN cover_image_url TEXT;
+ALTER TABLE posts ADD COLUMN cover_image_alt TEXT;
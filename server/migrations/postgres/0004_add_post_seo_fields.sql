@@ -0,0 +1,3 @@
+-- 検索エンジン向けの説明と正規URL
+ALTER TABLE posts ADD COLUMN seo_description TEXT;
+ALTER TABLE posts ADD COLUMN canonical_url TEXT;
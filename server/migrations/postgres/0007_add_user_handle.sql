@@ -0,0 +1,4 @@
+-- URLや@メンションに使う一意のハンドル（小文字）
+-- 既存のユーザーには起動時に名前かIDから作ったハンドルを付ける
+ALTER TABLE users ADD COLUMN handle TEXT;
+CREATE UNIQUE INDEX users_handle ON users (handle);
@@ -0,0 +1,2 @@
+-- 本文で@メンションしたユーザーのID
+ALTER TABLE posts ADD COLUMN mentioned_user_ids UUID[] NOT NULL DEFAULT '{}';
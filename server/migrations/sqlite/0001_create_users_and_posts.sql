@@ -0,0 +1,41 @@
+CREATE TABLE users (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    -- 検索用に小文字にした名前
+    name_key TEXT NOT NULL,
+    avatar_url TEXT,
+    role TEXT NOT NULL,
+    password_hash TEXT
+);
+
+CREATE INDEX users_name ON users (name);
+
+CREATE TABLE posts (
+    id TEXT PRIMARY KEY NOT NULL,
+    title TEXT NOT NULL,
+    slug TEXT NOT NULL UNIQUE,
+    author_id TEXT NOT NULL,
+    body TEXT NOT NULL,
+    status TEXT NOT NULL,
+    -- 日時はナノ秒まで固定桁のRFC 3339（文字列の大小が時刻の前後と一致する）
+    published_at TEXT NOT NULL,
+    scheduled_at TEXT,
+    updated_at TEXT NOT NULL,
+    deleted_at TEXT,
+    -- 更新履歴（PostRevisionの配列のJSON）
+    revisions TEXT NOT NULL DEFAULT '[]'
+);
+
+CREATE INDEX posts_published_at ON posts (published_at DESC, id DESC);
+CREATE INDEX posts_author_id ON posts (author_id);
+
+CREATE TABLE post_tags (
+    post_id TEXT NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    -- 前後の空白を除いて小文字にしたタグ（絞り込み用）
+    normalized TEXT NOT NULL,
+    PRIMARY KEY (post_id, position)
+);
+
+CREATE INDEX post_tags_normalized ON post_tags (normalized);
@@ -0,0 +1,3 @@
+-- 本文の語数（空白で区切る言語の単語数とCJKの文字数）。NULLの場合は読み出すときに数える
+ALTER TABLE posts ADD COLUMN latin_words INTEGER;
+ALTER TABLE posts ADD COLUMN cjk_chars INTEGER;
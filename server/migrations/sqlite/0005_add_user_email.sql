@@ -0,0 +1,2 @@
+-- メールアドレス（Gravatarのアバターに使う。スキーマには公開しない）
+ALTER TABLE users ADD COLUMN email TEXT;
@@ -0,0 +1,6 @@
+-- プロフィール（自己紹介のMarkdown、WebサイトのURL、所在地）
+-- bio_keyは検索用に小文字にした自己紹介
+ALTER TABLE users ADD COLUMN bio TEXT;
+ALTER TABLE users ADD COLUMN bio_key TEXT;
+ALTER TABLE users ADD COLUMN website TEXT;
+ALTER TABLE users ADD COLUMN location TEXT;
@@ -0,0 +1,2 @@
+-- 本文で@メンションしたユーザーのID（JSONの配列）
+ALTER TABLE posts ADD COLUMN mentioned_user_ids TEXT NOT NULL DEFAULT '[]';
@@ -0,0 +1,2 @@
+-- 自分の投稿へのコメントをメールで通知するか（デフォルトは通知しない）
+ALTER TABLE users ADD COLUMN email_on_comment INTEGER NOT NULL DEFAULT 0;
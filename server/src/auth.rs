@@ -0,0 +1,346 @@
+use actix_web::cookie::time::Duration as CookieDuration;
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::{http::header, HttpRequest};
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use async_graphql::{Guard, SimpleObject, ID};
+use chrono::{DateTime, Utc};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use uuid::Uuid;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::error::AppError;
+use crate::models::{Comment, Post, Role, User};
+use crate::scalars::DateTimeScalar;
+use crate::settings::{Settings, env_or};
+use crate::store::{ApiKeyStore, AppStorage, LockExt};
+
+// 認証
+// JWTの署名・検証に使う鍵
+#[derive(Clone)]
+pub(crate) struct JwtKeys {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    expiry: chrono::Duration,
+}
+
+impl JwtKeys {
+    pub(crate) fn from_env() -> Self {
+        let secret = std::env::var("JWT_SECRET").unwrap_or_else(|_| {
+            tracing::warn!("JWT_SECRET is not set; using a random secret for this process");
+            Uuid::new_v4().to_string()
+        });
+        JwtKeys {
+            encoding: EncodingKey::from_secret(secret.as_bytes()),
+            decoding: DecodingKey::from_secret(secret.as_bytes()),
+            expiry: chrono::Duration::seconds(env_or("JWT_EXPIRY_SECS", 3600)),
+        }
+    }
+
+    fn issue(&self, user_id: &ID) -> jsonwebtoken::errors::Result<(String, DateTime<Utc>)> {
+        let now = Utc::now();
+        let expires_at = now + self.expiry;
+        let claims = Claims {
+            sub: user_id.to_string(),
+            iat: now.timestamp(),
+            exp: expires_at.timestamp(),
+        };
+        let token = jsonwebtoken::encode(&Header::new(Algorithm::HS256), &claims, &self.encoding)?;
+        Ok((token, expires_at))
+    }
+
+    fn verify(&self, token: &str) -> jsonwebtoken::errors::Result<ID> {
+        let data = jsonwebtoken::decode::<Claims>(
+            token,
+            &self.decoding,
+            &Validation::new(Algorithm::HS256),
+        )?;
+        Ok(ID::from(data.claims.sub))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Claims {
+    sub: String,
+    iat: i64,
+    exp: i64,
+}
+
+// 認証済みのリクエストでコンテキストに入るユーザー
+#[derive(Clone)]
+pub(crate) struct Viewer {
+    pub(crate) user_id: ID,
+}
+
+#[derive(SimpleObject)]
+pub(crate) struct AuthPayload {
+    /// アクセストークン（JWT）
+    token: String,
+    #[graphql(name = "expiresAt")]
+    expires_at: DateTimeScalar,
+    /// アクセストークンの再発行に使うリフレッシュトークン
+    #[graphql(name = "refreshToken")]
+    refresh_token: String,
+    user: User,
+}
+
+// リフレッシュトークン（キーはトークンのハッシュ）
+pub(crate) struct RefreshToken {
+    pub(crate) user_id: ID,
+    // ログインごとに発行し、ローテーションしても引き継ぐ
+    pub(crate) family_id: String,
+    pub(crate) expires_at: DateTime<Utc>,
+    // ローテーション済みのトークンは再利用検知のため期限まで残す
+    pub(crate) used: bool,
+}
+
+pub(crate) type RefreshTokenStore = Arc<Mutex<HashMap<String, RefreshToken>>>;
+
+// Cookie認証のセッション（キーはセッションIDのハッシュ）
+pub(crate) struct Session {
+    pub(crate) user_id: ID,
+    pub(crate) expires_at: DateTime<Utc>,
+}
+
+pub(crate) type SessionStore = Arc<Mutex<HashMap<String, Session>>>;
+
+pub(crate) const SESSION_COOKIE_NAME: &str = "blog_session";
+
+// Cookieで認証されたリクエストのセッションID
+#[derive(Clone)]
+pub(crate) struct SessionCookie(pub(crate) String);
+
+pub(crate) fn session_cookie(
+    value: String,
+    max_age: chrono::Duration,
+    same_site: SameSite,
+) -> Cookie<'static> {
+    Cookie::build(SESSION_COOKIE_NAME, value)
+        .path("/")
+        .secure(true)
+        .http_only(true)
+        .same_site(same_site)
+        .max_age(CookieDuration::seconds(max_age.num_seconds()))
+        .finish()
+}
+
+// アクセストークンとリフレッシュトークンを発行する。family_idがなければ新しいセッションになる
+pub(crate) fn issue_session(
+    ctx: &async_graphql::Context<'_>,
+    user: User,
+    family_id: Option<String>,
+) -> async_graphql::Result<AuthPayload> {
+    let jwt_keys = ctx.data::<JwtKeys>()?;
+    let settings = ctx.data::<Settings>()?;
+    let (token, expires_at) = jwt_keys
+        .issue(&user.id)
+        .map_err(|e| AppError::Internal(format!("Failed to issue token: {}", e)))?;
+
+    let refresh_token = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    let refresh_token_store = ctx.data::<RefreshTokenStore>()?;
+    refresh_token_store.lock_or_recover().insert(
+        hash_token(&refresh_token),
+        RefreshToken {
+            user_id: user.id.clone(),
+            family_id: family_id.unwrap_or_else(|| Uuid::new_v4().to_string()),
+            expires_at: Utc::now() + settings.refresh_token_expiry,
+            used: false,
+        },
+    );
+
+    Ok(AuthPayload {
+        token,
+        expires_at: DateTimeScalar(expires_at),
+        refresh_token,
+        user,
+    })
+}
+
+pub(crate) fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(Argon2::default()
+        .hash_password(password.as_bytes(), &salt)?
+        .to_string())
+}
+
+pub(crate) fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|parsed| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &parsed)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+pub(crate) fn hash_token(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))
+}
+
+// 権限チェック
+pub(crate) const AUTHOR_ROLES: &[Role] = &[Role::Admin, Role::Author];
+pub(crate) const ADMIN_ROLES: &[Role] = &[Role::Admin];
+// ログインしていれば誰でも（いいね・コメント・フォローなど）
+pub(crate) const USER_ROLES: &[Role] = &[Role::Admin, Role::Author, Role::Reader];
+
+// ログイン中のユーザーが指定した権限を持っているか確認するガード
+pub(crate) struct RoleGuard {
+    roles: &'static [Role],
+}
+
+impl RoleGuard {
+    pub(crate) fn new(roles: &'static [Role]) -> Self {
+        RoleGuard { roles }
+    }
+}
+
+impl Guard for RoleGuard {
+    async fn check(&self, ctx: &async_graphql::Context<'_>) -> async_graphql::Result<()> {
+        let current_user = current_user(ctx).await?;
+        if !self.roles.contains(&current_user.role) {
+            return Err(AppError::Forbidden("Permission denied".into()).into());
+        }
+        Ok(())
+    }
+}
+
+// ログイン中のユーザーとその権限
+pub(crate) struct CurrentUser {
+    pub(crate) id: ID,
+    role: Role,
+}
+
+impl CurrentUser {
+    // 投稿の著者本人か管理者でなければFORBIDDEN
+    pub(crate) fn ensure_can_modify(&self, post: &Post) -> async_graphql::Result<()> {
+        if self.role != Role::Admin && post.author_id != self.id {
+            return Err(AppError::Forbidden(format!(
+                "Not allowed to modify post: {}",
+                post.id.as_str()
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    // コメントの著者本人か管理者でなければFORBIDDEN
+    pub(crate) fn ensure_can_delete(&self, comment: &Comment) -> async_graphql::Result<()> {
+        if self.role != Role::Admin && comment.author_id != self.id {
+            return Err(AppError::Forbidden(format!(
+                "Not allowed to delete comment: {}",
+                comment.id.as_str()
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    // 下書き・予約・ゴミ箱の投稿を見られる範囲。管理者は全員分（None）、著者は自分の分だけ
+    // 読者はFORBIDDEN
+    pub(crate) fn unpublished_posts_owner(&self) -> async_graphql::Result<Option<ID>> {
+        match self.role {
+            Role::Admin => Ok(None),
+            Role::Author => Ok(Some(self.id.clone())),
+            _ => Err(AppError::Forbidden("Not allowed to view unpublished posts".into()).into()),
+        }
+    }
+
+    // 操作するユーザー。未指定ならログイン中のユーザーで、他のユーザーを指定できるのは管理者のみ
+    pub(crate) fn acting_as(&self, user_id: Option<ID>) -> async_graphql::Result<ID> {
+        match user_id {
+            Some(id) if id != self.id && self.role != Role::Admin => Err(AppError::Forbidden(
+                format!("Not allowed to act as user: {}", id.as_str()),
+            )
+            .into()),
+            Some(id) => Ok(id),
+            None => Ok(self.id.clone()),
+        }
+    }
+}
+
+pub(crate) async fn current_user(
+    ctx: &async_graphql::Context<'_>,
+) -> async_graphql::Result<CurrentUser> {
+    // 未ログインならストレージを見る前に弾く
+    let viewer = ctx
+        .data_opt::<Viewer>()
+        .ok_or_else(|| AppError::Unauthenticated("Authentication required".into()))?;
+    let storage = ctx.data::<AppStorage>()?;
+    let role = storage
+        .get_user(&viewer.user_id)
+        .await?
+        .map(|u| u.role)
+        .ok_or_else(|| AppError::Unauthenticated("User no longer exists".into()))?;
+    Ok(CurrentUser {
+        id: viewer.user_id.clone(),
+        role,
+    })
+}
+
+// Authorizationヘッダー、X-Api-Keyヘッダー、セッションCookieの順に検証する（どれもなければ匿名）
+pub(crate) fn authenticate(
+    req: &HttpRequest,
+    jwt_keys: &JwtKeys,
+    api_key_store: &ApiKeyStore,
+    session_store: &SessionStore,
+) -> async_graphql::Result<Option<Viewer>> {
+    if let Some(value) = req.headers().get(header::AUTHORIZATION) {
+        return authenticate_bearer(value, jwt_keys);
+    }
+    if let Some(key) = req.headers().get("X-Api-Key") {
+        return authenticate_api_key(key.to_str().unwrap_or_default(), api_key_store);
+    }
+    if let Some(cookie) = req.cookie(SESSION_COOKIE_NAME) {
+        return Ok(authenticate_session(cookie.value(), session_store));
+    }
+    Ok(None)
+}
+
+fn authenticate_bearer(
+    value: &header::HeaderValue,
+    jwt_keys: &JwtKeys,
+) -> async_graphql::Result<Option<Viewer>> {
+    let token = value
+        .to_str()
+        .ok()
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or_else(|| AppError::Unauthenticated("Malformed Authorization header".into()))?;
+    let user_id = jwt_keys.verify(token.trim()).map_err(|e| match e.kind() {
+        jsonwebtoken::errors::ErrorKind::ExpiredSignature => {
+            AppError::Unauthenticated("Token has expired".into())
+        }
+        _ => AppError::Unauthenticated("Invalid token".into()),
+    })?;
+    Ok(Some(Viewer { user_id }))
+}
+
+fn authenticate_api_key(
+    key: &str,
+    api_key_store: &ApiKeyStore,
+) -> async_graphql::Result<Option<Viewer>> {
+    let key_hash = hash_token(key.trim());
+    let mut keys = api_key_store.lock_or_recover();
+    let api_key = keys
+        .iter_mut()
+        .find(|k| k.key_hash == key_hash)
+        .ok_or_else(|| AppError::Unauthenticated("Invalid API key".into()))?;
+    api_key.last_used_at = Some(DateTimeScalar(Utc::now()));
+    Ok(Some(Viewer {
+        user_id: api_key.user_id.clone(),
+    }))
+}
+
+// 期限切れや不明なセッションのCookieは匿名として扱う
+fn authenticate_session(session_id: &str, session_store: &SessionStore) -> Option<Viewer> {
+    let sessions = session_store.lock_or_recover();
+    sessions
+        .get(&hash_token(session_id))
+        .filter(|s| s.expires_at > Utc::now())
+        .map(|s| Viewer {
+            user_id: s.user_id.clone(),
+        })
+}
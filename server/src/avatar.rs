@@ -0,0 +1,44 @@
+use md5::{Digest, Md5};
+use url::form_urlencoded;
+
+use crate::models::User;
+use crate::scalars::UrlScalar;
+use crate::settings::Settings;
+use crate::upload::resized_url;
+
+const GRAVATAR_BASE_URL: &str = "https://www.gravatar.com/avatar/";
+
+// Gravatarのハッシュ（前後の空白を除いて小文字にしたメールアドレスのMD5）
+pub(crate) fn gravatar_hash(email: &str) -> String {
+    format!("{:x}", Md5::digest(email.trim().to_lowercase().as_bytes()))
+}
+
+// 設定したアバター、メールアドレスのGravatar、IDから作るプレースホルダーの順に使う
+// sizeは幅と高さの上限（ピクセル）
+pub(crate) fn avatar_url(user: &User, settings: &Settings, size: Option<u32>) -> UrlScalar {
+    if let Some(url) = &user.avatar_url {
+        return match size {
+            Some(size) => resized_url(url, settings, size, size),
+            None => url.clone(),
+        };
+    }
+    match &user.email {
+        Some(email) => {
+            gravatar_url(&gravatar_hash(email), &settings.gravatar_default, false, size)
+        }
+        // 同じユーザーには常に同じ模様のアイコン（f=yで登録済みの画像があっても使わない）
+        None => gravatar_url(&gravatar_hash(user.id.as_str()), "identicon", true, size),
+    }
+}
+
+fn gravatar_url(hash: &str, default: &str, force_default: bool, size: Option<u32>) -> UrlScalar {
+    let default: String = form_urlencoded::byte_serialize(default.as_bytes()).collect();
+    let mut url = format!("{}{}?d={}", GRAVATAR_BASE_URL, hash, default);
+    if force_default {
+        url.push_str("&f=y");
+    }
+    if let Some(size) = size {
+        url.push_str(&format!("&s={}", size));
+    }
+    UrlScalar(url)
+}
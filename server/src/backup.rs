@@ -0,0 +1,351 @@
+use async_graphql::{Enum, SimpleObject, ID};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
+use std::sync::atomic::AtomicU64;
+
+use crate::error::AppError;
+use crate::markdown::TextStats;
+use crate::models::{Comment, Post, User};
+use crate::pagination::Page;
+use crate::scalars::DateTimeScalar;
+use crate::search::UserFilter;
+use crate::store::{
+    ApiKeyStore, AppStorage, BookmarkStore, CommentStore, FollowStore, LikeStore, LockExt,
+    ReactionStore, ViewStore,
+};
+use crate::validation::{
+    assign_missing_handles, handle_taken, is_well_formed_id, validate_handle,
+};
+
+// バックアップ（exportData / importData）
+// 形式を変えたらバージョンを上げ、古いバージョンの読み込みを残す
+pub(crate) const EXPORT_VERSION: u64 = 8;
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ExportDocument {
+    version: u64,
+    exported_at: DateTimeScalar,
+    users: Vec<User>,
+    // ゴミ箱や予約中のものも含める
+    posts: Vec<Post>,
+    // 投稿に付いているタグの一覧（参照用。インポート時は投稿のタグを使う）
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    comments: Vec<Comment>,
+}
+
+/// インポートの方法
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ImportMode {
+    /// 既存のユーザー・投稿・コメントを全て削除してから読み込む
+    Replace,
+    /// IDが一致するものは投稿のみ`updatedAt`が新しい方を残し、ないものは追加する
+    Merge,
+}
+
+/// 種類ごとの件数
+#[derive(SimpleObject, Default)]
+pub(crate) struct ImportCounts {
+    created: i32,
+    updated: i32,
+    skipped: i32,
+}
+
+/// インポートの結果
+#[derive(SimpleObject, Default)]
+pub(crate) struct ImportResult {
+    users: ImportCounts,
+    posts: ImportCounts,
+    comments: ImportCounts,
+}
+
+pub(crate) async fn export_data(
+    ctx: &async_graphql::Context<'_>,
+) -> async_graphql::Result<ExportDocument> {
+    let storage = ctx.data::<AppStorage>()?;
+    let comment_store = ctx.data::<CommentStore>()?;
+
+    let users = storage.list_users(&UserFilter::default(), Page::ALL).await?;
+    // 語数は本文から数え直せるので書き出さない
+    let posts: Vec<Post> = storage
+        .list_all_posts()
+        .await?
+        .into_iter()
+        .map(|post| Post { text_stats: None, ..post })
+        .collect();
+    let tags: BTreeSet<String> = posts.iter().flat_map(|p| p.tags.iter().cloned()).collect();
+    let comments = comment_store.lock_or_recover().clone();
+    Ok(ExportDocument {
+        version: EXPORT_VERSION,
+        exported_at: DateTimeScalar(Utc::now()),
+        users,
+        posts,
+        tags: tags.into_iter().collect(),
+        comments,
+    })
+}
+
+// 新しいバージョンの形式は知らないフィールドを読み落とすので、読み込む前に断る
+pub(crate) fn parse_document(value: serde_json::Value) -> async_graphql::Result<ExportDocument> {
+    // ファイルの内容を文字列のまま渡されてもよい
+    let value = match value {
+        serde_json::Value::String(text) => serde_json::from_str(&text).map_err(|e| {
+            AppError::ValidationFailed(format!("Export document is not valid JSON: {}", e))
+        })?,
+        value => value,
+    };
+    let Some(version) = value.get("version").and_then(serde_json::Value::as_u64) else {
+        return Err(AppError::ValidationFailed(
+            "Export document has no version".into(),
+        ).into());
+    };
+    if version > EXPORT_VERSION {
+        return Err(AppError::ValidationFailed(format!(
+            "Export document version {} is newer than this server supports ({}); \
+             upgrade the server before importing",
+            version, EXPORT_VERSION
+        )).into());
+    }
+    if version == 0 {
+        return Err(AppError::ValidationFailed(
+            "Unsupported export document version 0".into(),
+        ).into());
+    }
+    let mut document: ExportDocument = serde_json::from_value(value).map_err(|e| {
+        AppError::ValidationFailed(format!("Invalid export document: {}", e))
+    })?;
+    for post in &mut document.posts {
+        post.text_stats = Some(TextStats::of(&post.body));
+    }
+    // バージョン5までの形式にはハンドルがない
+    assign_missing_handles(&mut document.users);
+    Ok(document)
+}
+
+pub(crate) async fn import_data(
+    ctx: &async_graphql::Context<'_>,
+    document: ExportDocument,
+    mode: ImportMode,
+) -> async_graphql::Result<ImportResult> {
+    let storage = ctx.data::<AppStorage>()?;
+    let comment_store = ctx.data::<CommentStore>()?;
+
+    // 書き込む前に参照を確認し、途中で失敗して半端に読み込まれるのを避ける
+    check_document(storage, comment_store, &document, mode).await?;
+
+    let mut result = ImportResult::default();
+    match mode {
+        ImportMode::Replace => {
+            for post in storage.list_all_posts().await? {
+                storage.delete_post(&post.id).await?;
+            }
+            for user in storage.list_users(&UserFilter::default(), Page::ALL).await? {
+                storage.delete_user(&user.id).await?;
+            }
+            for user in document.users {
+                storage.insert_user(user).await?;
+                result.users.created += 1;
+            }
+            for post in document.posts {
+                storage.insert_post(post).await?;
+                result.posts.created += 1;
+            }
+            result.comments.created = document.comments.len() as i32;
+            *comment_store.lock_or_recover() = document.comments;
+        }
+        ImportMode::Merge => {
+            // ユーザーには更新日時がないので、既にあるものはそのまま残す
+            for user in document.users {
+                if storage.get_user(&user.id).await?.is_some() {
+                    result.users.skipped += 1;
+                } else {
+                    storage.insert_user(user).await?;
+                    result.users.created += 1;
+                }
+            }
+            for post in document.posts {
+                let Some(existing) = storage.get_post(&post.id).await? else {
+                    storage.insert_post(post).await?;
+                    result.posts.created += 1;
+                    continue;
+                };
+                if post.updated_at.0 <= existing.updated_at.0 {
+                    result.posts.skipped += 1;
+                    continue;
+                }
+                let id = post.id.clone();
+                let update = move |current: &mut Post| -> async_graphql::Result<()> {
+                    *current = post;
+                    Ok(())
+                };
+                storage.update_post(&id, Box::new(update)).await?;
+                result.posts.updated += 1;
+            }
+            let mut comments = comment_store.lock_or_recover();
+            let existing: HashSet<ID> = comments.iter().map(|c| c.id.clone()).collect();
+            for comment in document.comments {
+                if existing.contains(&comment.id) {
+                    result.comments.skipped += 1;
+                } else {
+                    comments.push(comment);
+                    result.comments.created += 1;
+                }
+            }
+        }
+    }
+
+    remove_orphaned_data(ctx, storage).await?;
+    Ok(result)
+}
+
+// IDの重複と、投稿の著者・コメントの投稿や返信先が存在するかを確認する
+async fn check_document(
+    storage: &AppStorage,
+    comment_store: &CommentStore,
+    document: &ExportDocument,
+    mode: ImportMode,
+) -> async_graphql::Result<()> {
+    let invalid = |message: String| -> async_graphql::Error {
+        AppError::ValidationFailed(message).into()
+    };
+
+    let mut user_ids = HashSet::new();
+    let mut handles = HashSet::new();
+    for user in &document.users {
+        if !is_well_formed_id(&user.id) {
+            return Err(invalid(format!("Invalid user id: {}", user.id.as_str())));
+        }
+        if !user_ids.insert(user.id.clone()) {
+            return Err(invalid(format!("Duplicate user id: {}", user.id.as_str())));
+        }
+        if validate_handle(&user.handle).ok().as_ref() != Some(&user.handle) {
+            return Err(invalid(format!("Invalid handle: {}", user.handle)));
+        }
+        if !handles.insert(user.handle.as_str()) {
+            return Err(invalid(format!("Duplicate handle: {}", user.handle)));
+        }
+    }
+    let mut post_ids = HashSet::new();
+    let mut slugs = HashSet::new();
+    for post in &document.posts {
+        if !is_well_formed_id(&post.id) {
+            return Err(invalid(format!("Invalid post id: {}", post.id.as_str())));
+        }
+        if !post_ids.insert(post.id.clone()) {
+            return Err(invalid(format!("Duplicate post id: {}", post.id.as_str())));
+        }
+        if !slugs.insert(post.slug.as_str()) {
+            return Err(invalid(format!("Duplicate slug: {}", post.slug)));
+        }
+    }
+    let mut comment_ids = HashSet::new();
+    for comment in &document.comments {
+        if !comment_ids.insert(comment.id.clone()) {
+            return Err(invalid(format!("Duplicate comment id: {}", comment.id.as_str())));
+        }
+    }
+
+    // マージする場合は既存のデータも参照先になる
+    if mode == ImportMode::Merge {
+        for user in &document.users {
+            // 既にあるユーザーはスキップするので、別のユーザーのハンドルと重なる場合だけ困る
+            if let Some(other) = storage.get_user_by_handle(&user.handle).await? {
+                if other.id != user.id && storage.get_user(&user.id).await?.is_none() {
+                    return Err(handle_taken(&user.handle));
+                }
+            }
+        }
+        for user in storage.list_users(&UserFilter::default(), Page::ALL).await? {
+            user_ids.insert(user.id);
+        }
+        for post in &document.posts {
+            // 別の投稿が同じスラッグを使っていれば、読み込むとスラッグが重複する
+            if let Some(other) = storage.get_post_by_slug(&post.slug).await? {
+                if other.id != post.id {
+                    return Err(AppError::Conflict(format!(
+                        "Slug is already used by post {}: {}",
+                        other.id.as_str(),
+                        post.slug
+                    )).into());
+                }
+            }
+        }
+        for post in storage.list_all_posts().await? {
+            post_ids.insert(post.id);
+        }
+        for comment in comment_store.lock_or_recover().iter() {
+            comment_ids.insert(comment.id.clone());
+        }
+    }
+
+    for post in &document.posts {
+        if !user_ids.contains(&post.author_id) {
+            return Err(invalid(format!(
+                "Post {} refers to a missing author: {}",
+                post.id.as_str(),
+                post.author_id.as_str()
+            )));
+        }
+    }
+    for comment in &document.comments {
+        let parent_missing = comment
+            .parent_comment_id
+            .as_ref()
+            .is_some_and(|parent| !comment_ids.contains(parent));
+        if !post_ids.contains(&comment.post_id)
+            || !user_ids.contains(&comment.author_id)
+            || parent_missing
+        {
+            return Err(invalid(format!(
+                "Comment {} refers to a missing post, author or parent comment",
+                comment.id.as_str()
+            )));
+        }
+    }
+    Ok(())
+}
+
+// いいね・ブックマークなど、バックアップに含まれないデータのうち参照先がなくなったものを削除する
+async fn remove_orphaned_data(
+    ctx: &async_graphql::Context<'_>,
+    storage: &AppStorage,
+) -> async_graphql::Result<()> {
+    let user_ids: HashSet<ID> = storage
+        .list_users(&UserFilter::default(), Page::ALL)
+        .await?
+        .into_iter()
+        .map(|u| u.id)
+        .collect();
+    let post_ids: HashSet<ID> = storage
+        .list_all_posts()
+        .await?
+        .into_iter()
+        .map(|p| p.id)
+        .collect();
+
+    ctx.data::<LikeStore>()?
+        .lock_or_recover()
+        .retain(|(post_id, user_id)| post_ids.contains(post_id) && user_ids.contains(user_id));
+    ctx.data::<BookmarkStore>()?
+        .lock_or_recover()
+        .retain(|b| post_ids.contains(&b.post_id) && user_ids.contains(&b.user_id));
+    ctx.data::<ReactionStore>()?
+        .lock_or_recover()
+        .retain(|(post_id, user_id, _)| post_ids.contains(post_id) && user_ids.contains(user_id));
+    ctx.data::<FollowStore>()?
+        .lock_or_recover()
+        .retain(|(follower, followee)| user_ids.contains(follower) && user_ids.contains(followee));
+    ctx.data::<ApiKeyStore>()?
+        .lock_or_recover()
+        .retain(|k| user_ids.contains(&k.user_id));
+
+    let view_store = ctx.data::<ViewStore>()?;
+    view_store.retain(|post_id, _| post_ids.contains(post_id));
+    for post_id in post_ids {
+        view_store.entry(post_id).or_insert_with(|| AtomicU64::new(0));
+    }
+    Ok(())
+}
@@ -0,0 +1,141 @@
+use clap::Parser;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use url::Url;
+
+use crate::logging::LogFormat;
+
+pub const DEFAULT_GRAPHQL_PATH: &str = "/api/graphql";
+pub const DEFAULT_MAX_BODY_SIZE: usize = 1024 * 1024;
+pub const DEFAULT_EXECUTION_TIMEOUT_SECS: u64 = 30;
+
+// 起動時の設定（環境変数で指定し、同じ名前のフラグで上書きできる）
+// 値が不正な場合はclapがどの設定が悪いかを表示して終了する
+#[derive(Parser, Clone)]
+#[command(version, about = "ブログのGraphQLサーバー")]
+pub struct ServerConfig {
+    /// 待ち受けるアドレス（コンテナで動かす場合は0.0.0.0）
+    #[arg(long, env = "BIND_ADDR", default_value = "127.0.0.1")]
+    pub bind_addr: IpAddr,
+
+    /// 待ち受けるポート
+    #[arg(long, env = "PORT", default_value_t = 8000)]
+    pub port: u16,
+
+    /// 許可するオリジン（カンマ区切り）。指定すると資格情報付きのリクエストを許可する
+    #[arg(
+        long,
+        env = "CORS_ALLOWED_ORIGINS",
+        value_delimiter = ',',
+        value_parser = parse_origin
+    )]
+    // 未指定なら全てのオリジンを許可する（Cookie認証はクロスオリジンでは使えない）
+    pub cors_allowed_origins: Vec<String>,
+
+    /// GraphQLのエンドポイントのパス（WebSocketは<パス>/ws、SSEは<パス>/sse）
+    #[arg(
+        long,
+        env = "GRAPHQL_PATH",
+        default_value = DEFAULT_GRAPHQL_PATH,
+        value_parser = parse_graphql_path
+    )]
+    pub graphql_path: String,
+
+    /// GraphQLのリクエストボディの上限（バイト）。超えると413を返す
+    #[arg(long, env = "MAX_BODY_SIZE", default_value_t = DEFAULT_MAX_BODY_SIZE)]
+    pub max_body_size: usize,
+
+    /// GraphQLの操作ごとの実行時間の上限（秒）。超えると中断してTIMEOUTのエラーを返す
+    #[arg(
+        long,
+        env = "EXECUTION_TIMEOUT_SECS",
+        default_value_t = DEFAULT_EXECUTION_TIMEOUT_SECS,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    // サブスクリプションは長時間続くので対象外
+    pub execution_timeout_secs: u64,
+
+    /// HTTPSで待ち受ける場合の証明書（PEM、中間証明書を含めてよい）。SIGHUPで読み直す
+    #[arg(long, env = "TLS_CERT_PATH", requires = "tls_key_path")]
+    pub tls_cert_path: Option<PathBuf>,
+
+    /// HTTPSで待ち受ける場合の秘密鍵（PEM）
+    #[arg(long, env = "TLS_KEY_PATH", requires = "tls_cert_path")]
+    pub tls_key_path: Option<PathBuf>,
+
+    /// HTTPSの場合に、HTTPのリクエストをHTTPSに転送するポート
+    #[arg(long, env = "HTTP_REDIRECT_PORT", requires = "tls_cert_path")]
+    pub http_redirect_port: Option<u16>,
+
+    /// /metricsを公開するポート。指定すると通常のポートでは/metricsを公開しない
+    #[arg(long, env = "METRICS_PORT")]
+    pub metrics_port: Option<u16>,
+
+    /// METRICS_PORTで待ち受けるアドレス
+    #[arg(long, env = "METRICS_BIND_ADDR", default_value = "127.0.0.1")]
+    pub metrics_bind_addr: IpAddr,
+
+    /// SIGTERM / SIGINTを受け取ってから処理中のリクエストの完了を待つ秒数
+    #[arg(long, env = "SHUTDOWN_TIMEOUT_SECS", default_value_t = 30)]
+    pub shutdown_timeout_secs: u64,
+
+    /// ログの形式（jsonはログの収集基盤向け）。出力するレベルはRUST_LOGで指定する
+    #[arg(long, env = "LOG_FORMAT", value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// スキーマのSDLを出力して終了する（CIでのスナップショット用）
+    #[arg(long)]
+    pub print_schema: bool,
+
+    /// SEED_FILEの内容を確認して終了する（デプロイ前のチェック用）
+    #[arg(long)]
+    pub validate_seed: bool,
+
+    /// DATA_FILEが壊れていても初期データで起動する（ファイルは上書きされる）
+    #[arg(long)]
+    pub force: bool,
+}
+
+impl ServerConfig {
+    pub fn load() -> Self {
+        let mut config = ServerConfig::parse();
+        // CORS_ALLOWED_ORIGINS=""や末尾のカンマは未指定と同じ扱いにする
+        config.cors_allowed_origins.retain(|origin| !origin.is_empty());
+        config
+    }
+}
+
+// スキームとホスト（とポート）だけを受け付ける。末尾の/は取り除く
+fn parse_origin(value: &str) -> Result<String, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(String::new());
+    }
+    let invalid = || format!("expected an origin like https://example.com, got \"{}\"", value);
+    let url = Url::parse(value).map_err(|_| invalid())?;
+    let is_origin = matches!(url.scheme(), "http" | "https")
+        && url.host_str().is_some()
+        && url.path() == "/"
+        && url.query().is_none()
+        && url.fragment().is_none()
+        && url.username().is_empty();
+    if !is_origin {
+        return Err(invalid());
+    }
+    Ok(value.trim_end_matches('/').to_string())
+}
+
+fn parse_graphql_path(value: &str) -> Result<String, String> {
+    let valid = value.starts_with('/')
+        && value.len() > 1
+        && !value.ends_with('/')
+        && !value.contains(|c: char| c.is_whitespace() || c == '?' || c == '#' || c == '{');
+    if !valid {
+        return Err(format!(
+            "expected a path like /api/graphql (starting with / and without a trailing /), \
+             got \"{}\"",
+            value
+        ));
+    }
+    Ok(value.to_string())
+}
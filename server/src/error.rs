@@ -0,0 +1,57 @@
+use async_graphql::ErrorExtensions;
+
+// エラー（extensions.codeに種類を設定する）
+#[derive(Debug)]
+pub(crate) enum AppError {
+    NotFound(String),
+    ValidationFailed(String),
+    // IDの形式が不正（UUIDでも整数でもない）
+    InvalidId(String),
+    Forbidden(String),
+    Unauthenticated(String),
+    Conflict(String),
+    Timeout(String),
+    // 詳細はサーバーのログにだけ出し、クライアントには返さない
+    Internal(String),
+}
+
+impl AppError {
+    fn code(&self) -> &'static str {
+        match self {
+            AppError::NotFound(_) => "NOT_FOUND",
+            AppError::ValidationFailed(_) => "VALIDATION_FAILED",
+            AppError::InvalidId(_) => "INVALID_ID",
+            AppError::Forbidden(_) => "FORBIDDEN",
+            AppError::Unauthenticated(_) => "UNAUTHENTICATED",
+            AppError::Conflict(_) => "CONFLICT",
+            AppError::Timeout(_) => "TIMEOUT",
+            AppError::Internal(_) => "INTERNAL",
+        }
+    }
+}
+
+impl From<AppError> for async_graphql::Error {
+    fn from(error: AppError) -> Self {
+        let code = error.code();
+        let message = match error {
+            AppError::Internal(detail) => {
+                tracing::error!("internal error: {}", detail);
+                "Internal server error".to_string()
+            }
+            AppError::NotFound(message)
+            | AppError::ValidationFailed(message)
+            | AppError::InvalidId(message)
+            | AppError::Forbidden(message)
+            | AppError::Unauthenticated(message)
+            | AppError::Conflict(message)
+            | AppError::Timeout(message) => message,
+        };
+        async_graphql::Error::new(message).extend_with(|_, e| e.set("code", code))
+    }
+}
+
+// 存在しない場合のエラー（extensions.entityに種類を入れる）
+pub(crate) fn not_found(entity: &'static str) -> async_graphql::Error {
+    let error: async_graphql::Error = AppError::NotFound(format!("{} not found", entity)).into();
+    error.extend_with(|_, e| e.set("entity", entity))
+}
@@ -0,0 +1,202 @@
+use async_graphql::{
+    ErrorExtensionValues, Request, Response, ServerError, ServerResult, ValidationResult, Value,
+    Variables,
+};
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextParseQuery, NextPrepareRequest,
+    NextRequest, NextValidation,
+};
+use async_graphql::parser::types::{ExecutableDocument, OperationType, Selection};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use crate::error::AppError;
+use crate::http::is_operation;
+use crate::pagination::MAX_PAGE_SIZE;
+
+// イントロスペクションのみのクエリの深さの上限
+// GraphiQLなどが送るクエリは型の参照（ofType）の入れ子が深くなるので、別に高めの上限にする
+pub(crate) const INTROSPECTION_MAX_DEPTH: usize = 20;
+
+// クエリの深さ・複雑度の制限
+// イントロスペクションのみのクエリは深さだけINTROSPECTION_MAX_DEPTHまで許す（複雑度は同じ上限）
+pub(crate) struct QueryLimits {
+    pub(crate) max_depth: usize,
+    pub(crate) max_complexity: usize,
+    // trueなら受け付けたクエリの複雑度をレスポンスのextensionsに含める
+    pub(crate) expose_complexity: bool,
+}
+
+impl ExtensionFactory for QueryLimits {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(QueryLimitsExtension {
+            max_depth: self.max_depth,
+            max_complexity: self.max_complexity,
+            expose_complexity: self.expose_complexity,
+            introspection_only: AtomicBool::new(false),
+            complexity: OnceLock::new(),
+        })
+    }
+}
+
+struct QueryLimitsExtension {
+    max_depth: usize,
+    max_complexity: usize,
+    expose_complexity: bool,
+    introspection_only: AtomicBool,
+    complexity: OnceLock<usize>,
+}
+
+#[async_trait::async_trait]
+impl Extension for QueryLimitsExtension {
+    async fn request(&self, ctx: &ExtensionContext<'_>, next: NextRequest<'_>) -> Response {
+        let mut response = next.run(ctx).await;
+        if self.expose_complexity {
+            if let Some(complexity) = self.complexity.get() {
+                response
+                    .extensions
+                    .insert("complexity".to_string(), Value::from(*complexity as u64));
+            }
+        }
+        response
+    }
+
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let document = next.run(ctx, query, variables).await?;
+        let introspection_only = document.operations.iter().all(|(_, op)| {
+            op.node.selection_set.node.items.iter().all(|selection| match &selection.node {
+                Selection::Field(field) => field.node.name.node.starts_with("__"),
+                _ => false,
+            })
+        });
+        self.introspection_only.store(introspection_only, Ordering::Relaxed);
+        Ok(document)
+    }
+
+    async fn validation(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        next: NextValidation<'_>,
+    ) -> Result<ValidationResult, Vec<ServerError>> {
+        let result = next.run(ctx).await?;
+        let max_depth = if self.introspection_only.load(Ordering::Relaxed) {
+            self.max_depth.max(INTROSPECTION_MAX_DEPTH)
+        } else {
+            self.max_depth
+        };
+        if result.depth > max_depth {
+            let mut error = ServerError::new(
+                format!(
+                    "Query depth {} exceeds the maximum allowed depth of {}",
+                    result.depth, max_depth
+                ),
+                None,
+            );
+            let mut extensions = ErrorExtensionValues::default();
+            extensions.set("code", "QUERY_TOO_DEEP");
+            extensions.set("depth", result.depth as u64);
+            extensions.set("maxDepth", max_depth as u64);
+            error.extensions = Some(extensions);
+            return Err(vec![error]);
+        }
+        if result.complexity > self.max_complexity {
+            let mut error = ServerError::new(
+                format!(
+                    "Query complexity {} exceeds the maximum allowed complexity of {}",
+                    result.complexity, self.max_complexity
+                ),
+                None,
+            );
+            let mut extensions = ErrorExtensionValues::default();
+            extensions.set("code", "QUERY_TOO_COMPLEX");
+            extensions.set("complexity", result.complexity as u64);
+            extensions.set("maxComplexity", self.max_complexity as u64);
+            error.extensions = Some(extensions);
+            return Err(vec![error]);
+        }
+        let _ = self.complexity.set(result.complexity);
+        Ok(result)
+    }
+}
+
+// イントロスペクション無効時に __schema / __type を含むクエリをエラーにする
+// （disable_introspectionだけではエラーにならずnullが返るため）
+pub(crate) struct IntrospectionDisabled;
+
+impl ExtensionFactory for IntrospectionDisabled {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(IntrospectionDisabled)
+    }
+}
+
+#[async_trait::async_trait]
+impl Extension for IntrospectionDisabled {
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let document = next.run(ctx, query, variables).await?;
+        let uses_introspection = document.operations.iter().any(|(_, op)| {
+            op.node.selection_set.node.items.iter().any(|selection| match &selection.node {
+                Selection::Field(field) => {
+                    matches!(field.node.name.node.as_str(), "__schema" | "__type")
+                }
+                _ => false,
+            })
+        });
+        if uses_introspection {
+            return Err(ServerError::new("GraphQL introspection is disabled", None));
+        }
+        Ok(document)
+    }
+}
+
+// WebSocketの接続のデータ（graphql_ws_handlerが入れる）
+pub(crate) struct WebSocketConnection;
+
+// WebSocketではサブスクリプションだけを実行する
+// クエリやミューテーションは、レート制限や実行時間の上限を当てる/api/graphqlを使わせる
+pub(crate) struct SubscriptionsOnlyOverWebSocket;
+
+impl ExtensionFactory for SubscriptionsOnlyOverWebSocket {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(SubscriptionsOnlyOverWebSocket)
+    }
+}
+
+#[async_trait::async_trait]
+impl Extension for SubscriptionsOnlyOverWebSocket {
+    async fn prepare_request(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        request: Request,
+        next: NextPrepareRequest<'_>,
+    ) -> ServerResult<Request> {
+        let over_websocket = ctx.data_opt::<WebSocketConnection>().is_some();
+        if over_websocket && !is_operation(&request, OperationType::Subscription) {
+            let e: async_graphql::Error = AppError::Forbidden(
+                "Only subscriptions can be executed over WebSocket; use the GraphQL endpoint"
+                    .to_string(),
+            )
+            .into();
+            let mut error = ServerError::new(e.message, None);
+            error.extensions = e.extensions;
+            return Err(error);
+        }
+        next.run(ctx, request).await
+    }
+}
+
+// リストを返すフィールドの複雑度（件数 × 子フィールドの複雑度）
+pub(crate) fn list_complexity(limit: i32, child_complexity: usize) -> usize {
+    limit.clamp(0, MAX_PAGE_SIZE) as usize * child_complexity
+}
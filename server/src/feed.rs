@@ -0,0 +1,325 @@
+use actix_web::http::header::{self, HttpDate};
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use chrono::{DateTime, SecondsFormat, SubsecRound, Utc};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use url::form_urlencoded;
+use std::time::SystemTime;
+
+use crate::markdown::{excerpt, MarkdownCache, MentionLinks};
+use crate::mention::mention_links;
+use crate::models::{deleted_user, Post, User};
+use crate::pagination::Page;
+use crate::search::PostFilter;
+use crate::settings::Settings;
+use crate::store::AppStorage;
+
+// フィードの概要に使う抜粋の長さ
+const FEED_SUMMARY_LENGTH: usize = 200;
+
+// フィードに載せる投稿と著者
+struct FeedEntry {
+    post: Post,
+    author: User,
+    link: String,
+    // 本文の@メンションのリンク
+    mentions: MentionLinks,
+}
+
+struct Feed {
+    entries: Vec<FeedEntry>,
+    // 最も新しい投稿の公開日時か更新日時（投稿がなければNone）
+    last_modified: Option<DateTime<Utc>>,
+}
+
+pub(crate) async fn rss_handler(
+    storage: web::Data<AppStorage>,
+    settings: web::Data<Settings>,
+    markdown: web::Data<MarkdownCache>,
+    http_req: HttpRequest,
+) -> HttpResponse {
+    let content_type = "application/rss+xml; charset=utf-8";
+    feed_response(&storage, &settings, &http_req, None, content_type, |feed| {
+        render_rss(feed, &settings, &markdown)
+    })
+    .await
+}
+
+pub(crate) async fn atom_handler(
+    storage: web::Data<AppStorage>,
+    settings: web::Data<Settings>,
+    markdown: web::Data<MarkdownCache>,
+    http_req: HttpRequest,
+) -> HttpResponse {
+    let content_type = "application/atom+xml; charset=utf-8";
+    feed_response(&storage, &settings, &http_req, None, content_type, |feed| {
+        render_atom(feed, &settings, &markdown)
+    })
+    .await
+}
+
+#[derive(Deserialize)]
+pub(crate) struct JsonFeedQuery {
+    // 指定した場合はそのタグの投稿だけにする（該当する投稿がなければitemsは空）
+    tag: Option<String>,
+}
+
+pub(crate) async fn json_feed_handler(
+    storage: web::Data<AppStorage>,
+    settings: web::Data<Settings>,
+    markdown: web::Data<MarkdownCache>,
+    query: web::Query<JsonFeedQuery>,
+    http_req: HttpRequest,
+) -> HttpResponse {
+    let query = query.into_inner();
+    let tag = query.tag.filter(|tag| !tag.trim().is_empty());
+    feed_response(&storage, &settings, &http_req, tag.clone(), "application/feed+json", |feed| {
+        render_json_feed(feed, &settings, &markdown, tag.as_deref())
+    })
+    .await
+}
+
+// If-Modified-Sinceが最新の投稿以降なら本文を作らずに304を返す
+async fn feed_response(
+    storage: &AppStorage,
+    settings: &Settings,
+    http_req: &HttpRequest,
+    tag: Option<String>,
+    content_type: &str,
+    render: impl FnOnce(&Feed) -> String,
+) -> HttpResponse {
+    let feed = match load_feed(storage, settings, tag).await {
+        Ok(feed) => feed,
+        Err(e) => {
+            tracing::error!(error = %e.message, "failed to load feed");
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    // HTTPの日付は秒単位なので、比較の前に秒未満を切り捨てる
+    let last_modified = feed
+        .last_modified
+        .map(|dt| HttpDate::from(SystemTime::from(dt.trunc_subsecs(0))));
+    if let (Some(last_modified), Some(header::IfModifiedSince(since))) =
+        (last_modified, http_req.get_header::<header::IfModifiedSince>())
+    {
+        if last_modified <= since {
+            return HttpResponse::NotModified()
+                .insert_header(header::LastModified(last_modified))
+                .finish();
+        }
+    }
+    let mut response = HttpResponse::Ok();
+    response.content_type(content_type);
+    if let Some(last_modified) = last_modified {
+        response.insert_header(header::LastModified(last_modified));
+    }
+    response.body(render(&feed))
+}
+
+// 公開済みでゴミ箱にない投稿を新しい順にFEED_SIZE件
+async fn load_feed(
+    storage: &AppStorage,
+    settings: &Settings,
+    tag: Option<String>,
+) -> async_graphql::Result<Feed> {
+    let filter = PostFilter { tag, ..PostFilter::default() }.validate()?;
+    let page = Page {
+        limit: Some(settings.feed_size),
+        offset: 0,
+    };
+    let posts = storage.list_posts(&filter, page).await?;
+    // 著者とメンションしたユーザーをまとめて読み込む
+    let user_ids: Vec<_> = posts
+        .iter()
+        .flat_map(|p| std::iter::once(&p.author_id).chain(&p.mentioned_user_ids))
+        .cloned()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let users: HashMap<_, _> = storage
+        .get_users(&user_ids)
+        .await?
+        .into_iter()
+        .map(|user| (user.id.clone(), user))
+        .collect();
+    let last_modified = posts.iter().map(|p| p.published_at.0.max(p.updated_at.0)).max();
+    let entries = posts
+        .into_iter()
+        .map(|post| FeedEntry {
+            author: users
+                .get(&post.author_id)
+                .cloned()
+                .unwrap_or_else(|| deleted_user(&post.author_id)),
+            mentions: mention_links(
+                post.mentioned_user_ids.iter().filter_map(|id| users.get(id)),
+                settings,
+            ),
+            link: format!("{}/posts/{}", settings.site_base_url, post.slug),
+            post,
+        })
+        .collect();
+    Ok(Feed { entries, last_modified })
+}
+
+fn render_rss(feed: &Feed, settings: &Settings, markdown: &MarkdownCache) -> String {
+    let base = settings.site_base_url.as_str();
+    let mut xml = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    xml.push_str(
+        "\n<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\" \
+         xmlns:content=\"http://purl.org/rss/1.0/modules/content/\" \
+         xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n<channel>\n",
+    );
+    push_element(&mut xml, "title", &settings.site_title);
+    push_element(&mut xml, "link", &format!("{}/", base));
+    push_element(&mut xml, "description", &settings.site_title);
+    xml.push_str(&format!(
+        "<atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>\n",
+        escape_xml(&format!("{}/feed.rss", base))
+    ));
+    if let Some(last_modified) = feed.last_modified {
+        push_element(&mut xml, "lastBuildDate", &last_modified.to_rfc2822());
+    }
+    for entry in &feed.entries {
+        let post = &entry.post;
+        xml.push_str("<item>\n");
+        push_element(&mut xml, "title", &post.title);
+        push_element(&mut xml, "link", &entry.link);
+        xml.push_str(&format!(
+            "<guid isPermaLink=\"true\">{}</guid>\n",
+            escape_xml(&entry.link)
+        ));
+        push_element(&mut xml, "dc:creator", &entry.author.name);
+        for tag in &post.tags {
+            push_element(&mut xml, "category", tag);
+        }
+        push_element(&mut xml, "pubDate", &post.published_at.0.to_rfc2822());
+        push_element(&mut xml, "description", &excerpt(&post.body, FEED_SUMMARY_LENGTH));
+        let html = markdown.render(&post.id, post.updated_at.0, &post.body, &entry.mentions);
+        push_element(&mut xml, "content:encoded", &html.html);
+        xml.push_str("</item>\n");
+    }
+    xml.push_str("</channel>\n</rss>\n");
+    xml
+}
+
+fn render_atom(feed: &Feed, settings: &Settings, markdown: &MarkdownCache) -> String {
+    let base = settings.site_base_url.as_str();
+    let mut xml = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    xml.push_str("\n<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    push_element(&mut xml, "title", &settings.site_title);
+    push_element(&mut xml, "id", &format!("{}/", base));
+    push_link(&mut xml, &format!("{}/", base), None);
+    push_link(&mut xml, &format!("{}/feed.atom", base), Some("self"));
+    // updatedは必須なので、投稿がない場合は現在時刻にする
+    let updated = feed.last_modified.unwrap_or_else(Utc::now);
+    push_element(&mut xml, "updated", &atom_datetime(updated));
+    for entry in &feed.entries {
+        let post = &entry.post;
+        xml.push_str("<entry>\n");
+        push_element(&mut xml, "title", &post.title);
+        push_element(&mut xml, "id", &entry.link);
+        push_link(&mut xml, &entry.link, None);
+        xml.push_str("<author>");
+        push_element(&mut xml, "name", &entry.author.name);
+        xml.push_str("</author>\n");
+        for tag in &post.tags {
+            xml.push_str(&format!("<category term=\"{}\"/>\n", escape_xml(tag)));
+        }
+        push_element(&mut xml, "published", &atom_datetime(post.published_at.0));
+        let updated = post.published_at.0.max(post.updated_at.0);
+        push_element(&mut xml, "updated", &atom_datetime(updated));
+        push_element(&mut xml, "summary", &excerpt(&post.body, FEED_SUMMARY_LENGTH));
+        let html = markdown.render(&post.id, post.updated_at.0, &post.body, &entry.mentions);
+        xml.push_str(&format!("<content type=\"html\">{}</content>\n", escape_xml(&html.html)));
+        xml.push_str("</entry>\n");
+    }
+    xml.push_str("</feed>\n");
+    xml
+}
+
+fn render_json_feed(
+    feed: &Feed,
+    settings: &Settings,
+    markdown: &MarkdownCache,
+    tag: Option<&str>,
+) -> String {
+    let base = settings.site_base_url.as_str();
+    let feed_url = match tag {
+        Some(tag) => {
+            let tag: String = form_urlencoded::byte_serialize(tag.as_bytes()).collect();
+            format!("{}/feed.json?tag={}", base, tag)
+        }
+        None => format!("{}/feed.json", base),
+    };
+    let items: Vec<_> = feed
+        .entries
+        .iter()
+        .map(|entry| {
+            let post = &entry.post;
+            let html = markdown.render(&post.id, post.updated_at.0, &post.body, &entry.mentions);
+            let updated = post.published_at.0.max(post.updated_at.0);
+            // 値のない省略可能な項目はnullにせず出力しない
+            let mut author = json!({ "name": entry.author.name });
+            if let Some(avatar_url) = &entry.author.avatar_url {
+                author["avatar"] = json!(avatar_url.as_str());
+            }
+            let mut item = json!({
+                "id": entry.link,
+                "url": entry.link,
+                "title": post.title,
+                "content_html": html.html,
+                "summary": excerpt(&post.body, FEED_SUMMARY_LENGTH),
+                "date_published": atom_datetime(post.published_at.0),
+                "date_modified": atom_datetime(updated),
+                "authors": [author],
+                "tags": post.tags,
+            });
+            if let Some(cover_image_url) = &post.cover_image_url {
+                item["image"] = json!(cover_image_url.as_str());
+            }
+            item
+        })
+        .collect();
+    let document = json!({
+        "version": "https://jsonfeed.org/version/1.1",
+        "title": settings.site_title,
+        "home_page_url": format!("{}/", base),
+        "feed_url": feed_url,
+        "items": items,
+    });
+    document.to_string()
+}
+
+pub(crate) fn atom_datetime(dt: DateTime<Utc>) -> String {
+    dt.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn push_element(xml: &mut String, name: &str, text: &str) {
+    xml.push_str(&format!("<{}>{}</{}>\n", name, escape_xml(text), name));
+}
+
+fn push_link(xml: &mut String, href: &str, rel: Option<&str>) {
+    let href = escape_xml(href);
+    match rel {
+        Some(rel) => xml.push_str(&format!("<link rel=\"{}\" href=\"{}\"/>\n", rel, href)),
+        None => xml.push_str(&format!("<link href=\"{}\"/>\n", href)),
+    }
+}
+
+pub(crate) fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // XML 1.0で使えない制御文字は除く
+            c if c.is_control() && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
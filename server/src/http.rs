@@ -0,0 +1,471 @@
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::http::Method;
+use actix_web::{web, Either, HttpRequest, HttpResponse, Responder};
+use async_graphql::{BatchResponse, Data, ServerError};
+use async_graphql::http::GraphiQLSource;
+use async_graphql::parser::types::OperationType;
+use async_graphql_actix_web::{GraphQLRequest, GraphQLResponse, GraphQLSubscription};
+use futures_util::StreamExt;
+use sha2::{Digest, Sha256};
+use crate::AppSchema;
+use std::convert::Infallible;
+use std::time::{Duration, Instant};
+
+use crate::auth::{JwtKeys, SESSION_COOKIE_NAME, SessionCookie, SessionStore, authenticate};
+use crate::error::AppError;
+use crate::extensions::WebSocketConnection;
+use crate::persisted_query::PersistedQueryCache;
+use crate::rate_limit::{OperationKind, RateLimiter, operation_kind, throttled_fields};
+use crate::request_id::RequestId;
+use crate::settings::Settings;
+use crate::store::{ApiKeyStore, AppStorage, StoreRevision};
+
+// JSON配列のバッチリクエストと通常のリクエストの両方を受け付ける
+pub(crate) type GraphQLBody = Either<web::Json<Vec<serde_json::Value>>, GraphQLRequest>;
+
+// multipart/form-data（ファイルのアップロード）はEitherでバッファリングせずに直接読む
+// 上限はMultipartOptionsで指定する
+pub(crate) trait IntoGraphQLBody {
+    fn into_graphql_body(self) -> GraphQLBody;
+}
+
+impl IntoGraphQLBody for GraphQLBody {
+    fn into_graphql_body(self) -> GraphQLBody {
+        self
+    }
+}
+
+impl IntoGraphQLBody for GraphQLRequest {
+    fn into_graphql_body(self) -> GraphQLBody {
+        Either::Right(self)
+    }
+}
+
+// バッチ内の各リクエスト（不正なものはエラーのまま最後まで運ぶ）
+type BatchItem = Result<async_graphql::Request, ServerError>;
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn graphql_handler<B: IntoGraphQLBody>(
+    schema: web::Data<AppSchema>,
+    settings: web::Data<Settings>,
+    jwt_keys: web::Data<JwtKeys>,
+    api_key_store: web::Data<ApiKeyStore>,
+    session_store: web::Data<SessionStore>,
+    rate_limiter: web::Data<RateLimiter>,
+    persisted_queries: web::Data<PersistedQueryCache>,
+    limits: web::Data<RequestLimits>,
+    revision: web::Data<StoreRevision>,
+    http_req: HttpRequest,
+    body: B,
+) -> Either<GraphQLResponse, HttpResponse> {
+    // 不正な要素があってもバッチ全体は失敗させず、その位置にエラーを返す
+    let (requests, is_batch): (Vec<BatchItem>, bool) = match body.into_graphql_body() {
+        Either::Left(batch) => {
+            if batch.len() > settings.max_batch_size {
+                return Either::Right(HttpResponse::BadRequest().body(format!(
+                    "Batch size {} exceeds the maximum of {}",
+                    batch.len(),
+                    settings.max_batch_size
+                )));
+            }
+            let requests = batch
+                .into_inner()
+                .into_iter()
+                .map(|value| {
+                    serde_json::from_value(value)
+                        .map_err(|e| ServerError::new(format!("Invalid request: {}", e), None))
+                })
+                .collect();
+            (requests, true)
+        }
+        Either::Right(req) => {
+            let mut request = req.into_inner();
+            // async-graphqlはGETではoperation_nameしか読まないので、仕様どおりのoperationNameも受け付ける
+            if request.operation_name.is_none() && http_req.method() == Method::GET {
+                let params = url::form_urlencoded::parse(http_req.query_string().as_bytes());
+                request.operation_name = params
+                    .into_iter()
+                    .find(|(key, _)| key == "operationName")
+                    .map(|(_, value)| value.into_owned());
+            }
+            (vec![Ok(request)], false)
+        }
+    };
+
+    let requests: Vec<BatchItem> = requests
+        .into_iter()
+        .map(|request| {
+            let mut request = request?;
+            persisted_queries.resolve(&mut request)?;
+            Ok(request)
+        })
+        .collect();
+
+    // GETで受け付けるのはクエリだけ（リンクを踏ませるだけでミューテーションを実行させない）
+    let is_get = http_req.method() == Method::GET;
+    if is_get && !requests.iter().flatten().all(|r| is_operation(r, OperationType::Query)) {
+        return Either::Right(
+            HttpResponse::MethodNotAllowed()
+                .insert_header((header::ALLOW, "POST"))
+                .body("Only queries can be executed over GET; use POST for mutations"),
+        );
+    }
+
+    if let Some(res) = rate_limited(&rate_limiter, &http_req, requests.iter().flatten()) {
+        return Either::Right(res);
+    }
+
+    // GETのクエリはETagで再検証できるようにする
+    // 実行前に版を読むので、実行中に変更があっても次の再検証では必ず取り直す
+    let etag = match requests.as_slice() {
+        [Ok(request)] if is_get => Some(etag(revision.current(), request, &http_req)),
+        _ => None,
+    };
+    if let Some(etag) = etag.as_deref().filter(|etag| if_none_match(&http_req, etag)) {
+        return Either::Right(
+            HttpResponse::NotModified()
+                .insert_header((header::ETAG, etag))
+                .insert_header((header::CACHE_CONTROL, REVALIDATE))
+                .finish(),
+        );
+    }
+
+    let session_cookie = http_req
+        .cookie(SESSION_COOKIE_NAME)
+        .map(|cookie| SessionCookie(cookie.value().to_string()));
+    let viewer = authenticate(&http_req, &jwt_keys, &api_key_store, &session_store).map_err(|e| {
+        let mut error = ServerError::new(e.message, None);
+        error.extensions = e.extensions;
+        error
+    });
+    // バッチの要素は同じリクエストIDを共有する
+    let request_id = RequestId::of(&http_req);
+    let requests: Vec<BatchItem> = requests
+        .into_iter()
+        .map(|request| {
+            let mut request = request?.data(request_id.clone());
+            if let Some(session_cookie) = &session_cookie {
+                request = request.data(session_cookie.clone());
+            }
+            if let Some(viewer) = viewer.clone()? {
+                request = request.data(viewer);
+            }
+            Ok(request)
+        })
+        .collect();
+
+    // ミューテーションを含む場合は順番に、クエリだけなら並行して実行する
+    let execute = |request: BatchItem| {
+        let schema = schema.clone();
+        let request_id = &request_id;
+        let limits = &limits;
+        async move {
+            match request {
+                // 時間のかかりすぎる操作は中断する（ワーカーを占有させない）
+                Ok(request) => {
+                    let timeout = limits.execution_timeout;
+                    match tokio::time::timeout(timeout, schema.execute(request)).await {
+                        Ok(response) => response,
+                        Err(_) => {
+                            let mut response = async_graphql::Response::from_errors(vec![
+                                timeout_error(timeout),
+                            ]);
+                            request_id.attach(&mut response.errors);
+                            response
+                        }
+                    }
+                }
+                // 実行前のエラー（不正なリクエストや認証の失敗）にもIDを付ける
+                Err(error) => {
+                    let mut response = async_graphql::Response::from_errors(vec![error]);
+                    request_id.attach(&mut response.errors);
+                    response
+                }
+            }
+        }
+    };
+    let has_mutation = requests
+        .iter()
+        .flatten()
+        .any(|r| operation_kind(r) == OperationKind::Mutation);
+    let mut responses = Vec::with_capacity(requests.len());
+    if has_mutation {
+        for request in requests {
+            responses.push(execute(request).await);
+        }
+    } else {
+        responses = futures_util::future::join_all(requests.into_iter().map(execute)).await;
+    }
+
+    if is_batch {
+        return Either::Left(BatchResponse::Batch(responses).into());
+    }
+    let response = responses.remove(0);
+    if !is_get {
+        return Either::Left(response.into());
+    }
+    // エラーになったレスポンスやミューテーションはキャッシュさせない
+    let etag = etag.filter(|_| response.is_ok());
+    let mut res = GraphQLResponse::from(response)
+        .respond_to(&http_req)
+        .map_into_boxed_body();
+    let headers = res.headers_mut();
+    match etag.map(|etag| HeaderValue::from_str(&etag)) {
+        Some(Ok(etag)) => {
+            headers.insert(header::ETAG, etag);
+            headers.insert(header::CACHE_CONTROL, HeaderValue::from_static(REVALIDATE));
+        }
+        _ => {
+            headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        }
+    }
+    Either::Right(res)
+}
+
+// クライアントIPごとのレート制限。超えていれば429のレスポンスを返す
+fn rate_limited<'a>(
+    rate_limiter: &RateLimiter,
+    http_req: &HttpRequest,
+    requests: impl IntoIterator<Item = &'a async_graphql::Request>,
+) -> Option<HttpResponse> {
+    let ip = rate_limiter.client_ip(http_req)?;
+    for request in requests {
+        // メールを送るミューテーションなどは、フィールドごとに別のバケットからも消費する
+        let kinds = std::iter::once(operation_kind(request)).chain(throttled_fields(request));
+        for kind in kinds {
+            if let Err(retry_after) = rate_limiter.check(ip, kind) {
+                return Some(too_many_requests(retry_after));
+            }
+        }
+    }
+    None
+}
+
+fn too_many_requests(retry_after: Duration) -> HttpResponse {
+    HttpResponse::TooManyRequests()
+        .insert_header((header::RETRY_AFTER, retry_after.as_secs_f64().ceil() as u64))
+        .body("Too many requests")
+}
+
+// 実行する操作が指定した種類か。複数の操作を含む場合はoperationNameで選んだものを見る
+// 構文エラーや存在しないoperationNameは、実行時にGraphQLのエラーになるのでここでは通す
+pub(crate) fn is_operation(request: &async_graphql::Request, ty: OperationType) -> bool {
+    let Ok(document) = async_graphql::parser::parse_query(&request.query) else {
+        return true;
+    };
+    document
+        .operations
+        .iter()
+        .filter(|(name, _)| match (&request.operation_name, name) {
+            (Some(operation_name), Some(name)) => operation_name == name.as_str(),
+            (Some(_), None) => false,
+            (None, _) => true,
+        })
+        .all(|(_, op)| op.node.ty == ty)
+}
+
+// 毎回ETagで再検証させる（ユーザーごとに結果が変わるので共有キャッシュには置かせない）
+const REVALIDATE: &str = "private, max-age=0, must-revalidate";
+
+// データの版と、クエリ・変数・認証情報のハッシュから作る弱いETag
+// 認証情報を含めるので、同じブラウザでユーザーを切り替えても前のユーザーの結果は使われない
+fn etag(revision: u64, request: &async_graphql::Request, http_req: &HttpRequest) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(request.query.as_bytes());
+    hasher.update([0]);
+    hasher.update(request.operation_name.as_deref().unwrap_or("").as_bytes());
+    hasher.update([0]);
+    hasher.update(serde_json::to_vec(&request.variables).unwrap_or_default());
+    for name in [header::AUTHORIZATION.as_str(), "x-api-key"] {
+        hasher.update([0]);
+        if let Some(value) = http_req.headers().get(name) {
+            hasher.update(value.as_bytes());
+        }
+    }
+    hasher.update([0]);
+    if let Some(cookie) = http_req.cookie(SESSION_COOKIE_NAME) {
+        hasher.update(cookie.value().as_bytes());
+    }
+    let hash = format!("{:x}", hasher.finalize());
+    format!("W/\"{}-{}\"", revision, &hash[..16])
+}
+
+// If-None-Matchのどれかが一致するか（弱い比較なのでW/の有無は区別しない）
+pub(crate) fn if_none_match(http_req: &HttpRequest, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let etag = opaque(etag);
+    http_req
+        .headers()
+        .get_all(header::IF_NONE_MATCH)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|tag| tag.trim() == "*" || opaque(tag) == etag)
+}
+
+fn timeout_error(timeout: Duration) -> ServerError {
+    let e: async_graphql::Error = AppError::Timeout(format!(
+        "Execution exceeded the time limit of {}s",
+        timeout.as_secs()
+    ))
+    .into();
+    let mut error = ServerError::new(e.message, None);
+    error.extensions = e.extensions;
+    error
+}
+
+// 接続ごとに認証し、接続の開始をクエリとして数える（SSEの購読の開始と同じ）
+// 接続では購読だけを実行する（SubscriptionsOnlyOverWebSocketが操作ごとに確かめる）
+pub(crate) async fn graphql_ws_handler(
+    schema: web::Data<AppSchema>,
+    jwt_keys: web::Data<JwtKeys>,
+    api_key_store: web::Data<ApiKeyStore>,
+    session_store: web::Data<SessionStore>,
+    rate_limiter: web::Data<RateLimiter>,
+    req: HttpRequest,
+    payload: web::Payload,
+) -> actix_web::Result<HttpResponse> {
+    if let Some(ip) = rate_limiter.client_ip(&req) {
+        if let Err(retry_after) = rate_limiter.check(ip, OperationKind::Query) {
+            return Ok(too_many_requests(retry_after));
+        }
+    }
+    let viewer = match authenticate(&req, &jwt_keys, &api_key_store, &session_store) {
+        Ok(viewer) => viewer,
+        Err(e) => return Ok(HttpResponse::Unauthorized().body(e.message)),
+    };
+    let mut data = Data::default();
+    data.insert(WebSocketConnection);
+    data.insert(RequestId::of(&req));
+    if let Some(viewer) = viewer {
+        data.insert(viewer);
+    }
+    GraphQLSubscription::new(AppSchema::clone(&schema))
+        .with_data(data)
+        .start(&req, payload)
+}
+
+// SSEのキープアライブ間隔
+const SSE_KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+// クエリパラメータで受け取ったサブスクリプションを text/event-stream で配信する
+pub(crate) async fn graphql_sse_handler(
+    schema: web::Data<AppSchema>,
+    jwt_keys: web::Data<JwtKeys>,
+    api_key_store: web::Data<ApiKeyStore>,
+    session_store: web::Data<SessionStore>,
+    rate_limiter: web::Data<RateLimiter>,
+    limits: web::Data<RequestLimits>,
+    req: HttpRequest,
+) -> HttpResponse {
+    // クエリ文字列で受け取るのでボディと同じ上限を当てる（実行時間の上限は対象外）
+    if req.query_string().len() > limits.max_body_size {
+        return HttpResponse::PayloadTooLarge().finish();
+    }
+    let request = match async_graphql::http::parse_query_string(req.query_string()) {
+        Ok(request) => request,
+        Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+    };
+    // GETで受け付けるので、クエリやミューテーションは実行しない（それぞれ/api/graphqlを使う）
+    if !is_operation(&request, OperationType::Subscription) {
+        return HttpResponse::BadRequest()
+            .body("Only subscriptions can be executed over SSE; use the GraphQL endpoint");
+    }
+    // 購読の開始をクエリとして数える
+    if let Some(res) = rate_limited(&rate_limiter, &req, [&request]) {
+        return res;
+    }
+    // EventSourceはヘッダーを付けられないので、ブラウザからはセッションのCookieで認証する
+    let viewer = match authenticate(&req, &jwt_keys, &api_key_store, &session_store) {
+        Ok(viewer) => viewer,
+        Err(e) => return HttpResponse::Unauthorized().body(e.message),
+    };
+    let mut request = request.data(RequestId::of(&req));
+    if let Some(viewer) = viewer {
+        request = request.data(viewer);
+    }
+
+    let responses = AppSchema::clone(&schema).execute_stream(request);
+    let keep_alive = tokio::time::interval_at(
+        tokio::time::Instant::now() + SSE_KEEP_ALIVE_INTERVAL,
+        SSE_KEEP_ALIVE_INTERVAL,
+    );
+    // クライアントが切断するとactixがストリームを破棄し、購読も解除される
+    let events = futures_util::stream::unfold(
+        Some((responses, keep_alive)),
+        |state| async move {
+            let (mut responses, mut keep_alive) = state?;
+            let chunk = tokio::select! {
+                response = responses.next() => match response {
+                    Some(response) => format!(
+                        "event: next\ndata: {}\n\n",
+                        serde_json::to_string(&response).unwrap_or_default()
+                    ),
+                    None => return Some(("event: complete\ndata:\n\n".to_string(), None)),
+                },
+                _ = keep_alive.tick() => ": keep-alive\n\n".to_string(),
+            };
+            Some((chunk, Some((responses, keep_alive))))
+        },
+    )
+    .map(|chunk| Ok::<_, Infallible>(web::Bytes::from(chunk)));
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(events)
+}
+
+// スキーマのSDL（コード生成用）
+pub(crate) async fn graphql_schema_handler(schema: web::Data<AppSchema>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/plain; charset=utf-8")
+        .body(schema.sdl())
+}
+
+// GraphQLのリクエストの上限（ServerConfigのMAX_BODY_SIZEとEXECUTION_TIMEOUT_SECS）
+#[derive(Clone, Copy)]
+pub(crate) struct RequestLimits {
+    pub(crate) max_body_size: usize,
+    pub(crate) execution_timeout: Duration,
+}
+
+// 起動した時刻（/healthzのuptime用）
+#[derive(Clone, Copy)]
+pub(crate) struct StartedAt(pub(crate) Instant);
+
+// 生存確認。ストレージには触れないので、書き込みが詰まっていてもすぐに返る
+pub(crate) async fn healthz_handler(started_at: web::Data<StartedAt>) -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "ok",
+        "uptime_secs": started_at.0.elapsed().as_secs(),
+        "version": env!("CARGO_PKG_VERSION"),
+    }))
+}
+
+// データベースが応答しない場合も待ち続けない
+const READY_TIMEOUT: Duration = Duration::from_secs(1);
+
+// 準備完了の確認。ストレージに接続できなければ理由を付けて503を返す
+pub(crate) async fn readyz_handler(storage: web::Data<AppStorage>) -> HttpResponse {
+    let reason = match tokio::time::timeout(READY_TIMEOUT, storage.ping()).await {
+        Ok(Ok(())) => return HttpResponse::Ok().json(serde_json::json!({ "status": "ready" })),
+        Ok(Err(reason)) => reason,
+        Err(_) => format!("storage did not respond within {}ms", READY_TIMEOUT.as_millis()),
+    };
+    HttpResponse::ServiceUnavailable().json(serde_json::json!({
+        "status": "unavailable",
+        "reason": reason,
+    }))
+}
+
+// 開発用のGraphiQL
+pub(crate) async fn graphiql_handler(graphql_path: String) -> HttpResponse {
+    let subscription_path = format!("{}/ws", graphql_path);
+    HttpResponse::Ok().content_type("text/html; charset=utf-8").body(
+        GraphiQLSource::build()
+            .endpoint(&graphql_path)
+            .subscription_endpoint(&subscription_path)
+            .title("Blog GraphQL")
+            .finish(),
+    )
+}
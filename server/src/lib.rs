@@ -0,0 +1,499 @@
+// ブログのGraphQLサーバー
+// アプリケーションの組み立てはここで行い、main.rsは設定の読み込みとHttpServerの起動だけを行う
+use actix_web::middleware::from_fn;
+use actix_web::http::header;
+use actix_web::{guard, web};
+use async_graphql::dataloader::DataLoader;
+use async_graphql::extensions::ApolloTracing;
+use async_graphql::Schema;
+use async_graphql_actix_web::GraphQLRequest;
+use async_graphql::http::MultipartOptions;
+use tracing_actix_web::TracingLogger;
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinHandle;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+mod auth;
+mod avatar;
+mod backup;
+mod config;
+mod error;
+mod extensions;
+mod feed;
+mod http;
+mod loaders;
+mod logging;
+mod mail;
+mod markdown;
+mod mention;
+mod metrics;
+mod models;
+mod mutation;
+mod newsletter;
+mod notification;
+mod pagination;
+mod persisted_query;
+mod query;
+mod rate_limit;
+mod request_id;
+mod rest;
+mod scalars;
+mod search;
+mod seed;
+mod settings;
+mod sitemap;
+mod store;
+mod subscription;
+mod tasks;
+mod telemetry;
+mod tls;
+mod upload;
+mod validation;
+mod webhook;
+
+pub use config::ServerConfig;
+pub use logging::{init_logging, LogFormat};
+pub use models::{Post, User};
+pub use mutation::Mutation;
+pub use pagination::Page;
+pub use query::Query;
+pub use scalars::{DateTimeScalar, UrlScalar};
+pub use search::{PostFilter, UserFilter};
+pub use seed::Seed;
+pub use store::{open_database, open_memory_storage, AppStorage, DataFile, MemoryStorage, Storage};
+pub use subscription::Subscription;
+pub use telemetry::Telemetry;
+pub use tls::{configure_https_redirect, spawn_certificate_reloader, TlsCertificates};
+
+use auth::{JwtKeys, RefreshTokenStore, SessionStore};
+use config::{DEFAULT_EXECUTION_TIMEOUT_SECS, DEFAULT_GRAPHQL_PATH, DEFAULT_MAX_BODY_SIZE};
+use extensions::{IntrospectionDisabled, QueryLimits, SubscriptionsOnlyOverWebSocket};
+use http::{GraphQLBody, RequestLimits, StartedAt};
+use loaders::{
+    CommentCountLoader, LikeCountLoader, PostsByAuthorLoader, UserLoader, LOADER_DELAY,
+};
+use logging::{GraphQLLogging, SlowQueryLogging};
+use mail::{Mailer, PendingMailQueue};
+use markdown::MarkdownCache;
+use newsletter::NewsletterKey;
+use sitemap::SitemapCache;
+use metrics::{GraphQLMetrics, Metrics};
+use persisted_query::PersistedQueryCache;
+use rate_limit::RateLimiter;
+use request_id::{GraphQLRequestId, RequestIdRootSpan};
+use search::{LinearScanIndex, SearchIndexStore};
+use settings::{env_or, Settings};
+use store::{
+    ApiKeyStore, BookmarkStore, CommentStore, ContactMessageStore, FollowStore, LikeStore,
+    NewsletterStore, NotificationStore, ReactionStore, WebhookStore,
+};
+use store::{LockExt, StoreRevision, TracedStorage, ViewStore};
+use subscription::{BlogEvent, EventBus, EVENT_BUS_CAPACITY};
+use telemetry::GraphQLTracing;
+
+// GraphQL Schema
+pub type AppSchema = Schema<Query, Mutation, Subscription>;
+
+// スキーマとHTTPハンドラーが共有するデータ（ワーカーごとにクローンする）
+#[derive(Clone)]
+pub struct AppState {
+    schema: AppSchema,
+    storage: AppStorage,
+    data_file: Option<DataFile>,
+    settings: web::Data<Settings>,
+    jwt_keys: JwtKeys,
+    api_key_store: ApiKeyStore,
+    notification_store: NotificationStore,
+    webhook_store: WebhookStore,
+    newsletter_store: NewsletterStore,
+    newsletter_key: NewsletterKey,
+    event_bus: EventBus,
+    mail_queue: PendingMailQueue,
+    session_store: SessionStore,
+    refresh_token_store: RefreshTokenStore,
+    rate_limiter: web::Data<RateLimiter>,
+    persisted_queries: web::Data<PersistedQueryCache>,
+    revision: StoreRevision,
+    introspection_enabled: bool,
+    graphql_path: String,
+    request_limits: RequestLimits,
+    started_at: StartedAt,
+    metrics: web::Data<Metrics>,
+    metrics_route: bool,
+    markdown_cache: MarkdownCache,
+    sitemap_cache: SitemapCache,
+}
+
+impl AppState {
+    pub fn schema(&self) -> &AppSchema {
+        &self.schema
+    }
+
+    pub fn introspection_enabled(&self) -> bool {
+        self.introspection_enabled
+    }
+
+    // 終了時に呼ぶ。DATA_FILEに書き出し、データベースの接続を閉じる
+    pub async fn close(&self) {
+        if let Some(data_file) = &self.data_file {
+            data_file.persist().await;
+        }
+        self.storage.close().await;
+    }
+
+    pub fn graphql_path(&self) -> &str {
+        &self.graphql_path
+    }
+
+    // GraphQLのエンドポイントを/api/graphql以外にする場合（WebSocketとSSEも<パス>/ws、<パス>/sseになる）
+    pub fn with_graphql_path(mut self, path: impl Into<String>) -> Self {
+        self.graphql_path = path.into();
+        self
+    }
+
+    // GraphQLのリクエストボディの上限と、操作ごとの実行時間の上限
+    pub fn with_request_limits(
+        mut self,
+        max_body_size: usize,
+        execution_timeout: Duration,
+    ) -> Self {
+        self.request_limits = RequestLimits {
+            max_body_size,
+            execution_timeout,
+        };
+        self
+    }
+
+    // /metricsを公開のポートから外す（configure_metricsで別のサーバーに載せる）
+    pub fn without_metrics_route(mut self) -> Self {
+        self.metrics_route = false;
+        self
+    }
+}
+
+// SDL（--print-schema用）
+pub fn schema_sdl() -> String {
+    Schema::build(Query, Mutation, Subscription).finish().sdl()
+}
+
+// ストレージ以外の設定は環境変数から読む。data_fileはメモリ上のストレージを使う場合のみ
+pub async fn build_app_state(
+    storage: AppStorage,
+    data_file: Option<DataFile>,
+) -> async_graphql::Result<AppState> {
+    let storage: AppStorage = Arc::new(TracedStorage(storage));
+    let posts = storage.list_all_posts().await?;
+
+    let comment_store: CommentStore = Default::default();
+    let like_store: LikeStore = Default::default();
+    let bookmark_store: BookmarkStore = Default::default();
+    let follow_store: FollowStore = Default::default();
+    let reaction_store: ReactionStore = Default::default();
+    let api_key_store: ApiKeyStore = Default::default();
+    let notification_store: NotificationStore = Default::default();
+    let webhook_store: WebhookStore = Default::default();
+    let newsletter_store: NewsletterStore = Default::default();
+    let contact_store: ContactMessageStore = Default::default();
+    let view_store: ViewStore = Arc::new(
+        posts
+            .into_iter()
+            .map(|p| (p.id, Default::default()))
+            .collect(),
+    );
+
+    let refresh_token_store: RefreshTokenStore = Default::default();
+    let session_store: SessionStore = Default::default();
+
+    let posts_by_author_loader = DataLoader::new(
+        PostsByAuthorLoader {
+            storage: storage.clone(),
+        },
+        tokio::spawn,
+    )
+    .delay(LOADER_DELAY);
+
+    let comment_count_loader = DataLoader::new(
+        CommentCountLoader {
+            comment_store: comment_store.clone(),
+        },
+        tokio::spawn,
+    )
+    .delay(LOADER_DELAY);
+
+    let user_loader = DataLoader::new(
+        UserLoader {
+            storage: storage.clone(),
+        },
+        tokio::spawn,
+    )
+    .delay(LOADER_DELAY);
+
+    let like_count_loader = DataLoader::new(
+        LikeCountLoader {
+            like_store: like_store.clone(),
+        },
+        tokio::spawn,
+    )
+    .delay(LOADER_DELAY);
+
+    let (event_bus, _) = broadcast::channel::<BlogEvent>(EVENT_BUS_CAPACITY);
+
+    let jwt_keys = JwtKeys::from_env();
+    let newsletter_key = NewsletterKey::from_env();
+
+    let persisted_queries =
+        web::Data::new(PersistedQueryCache::new(env_or("APQ_CACHE_SIZE", 1000)));
+    let rate_limiter = web::Data::new(RateLimiter::from_env());
+
+    let settings = Settings::from_env();
+
+    // 本番環境ではGRAPHQL_INTROSPECTION=offでスキーマを公開しない
+    let introspection_enabled = !matches!(
+        std::env::var("GRAPHQL_INTROSPECTION").as_deref(),
+        Ok("off" | "false" | "0")
+    );
+    // 開発用: GRAPHQL_TRACING=1でレスポンスのextensions.tracingにリゾルバーごとの処理時間を含める
+    // イントロスペクションを無効にした本番の設定では、内部の情報を出さないよう起動させない
+    let apollo_tracing = matches!(
+        std::env::var("GRAPHQL_TRACING").as_deref(),
+        Ok("on" | "true" | "1")
+    );
+    if apollo_tracing && !introspection_enabled {
+        return Err(async_graphql::Error::new(
+            "GRAPHQL_TRACING cannot be enabled while GRAPHQL_INTROSPECTION is off",
+        ));
+    }
+
+    // SMTP_HOSTを指定した場合のみメールを送る。送信元のアドレスが不正なら起動しない
+    let (mailer, mail_queue) = match &settings.smtp {
+        Some(smtp) => {
+            let (mailer, queue) = mail::mail_queue(smtp).map_err(async_graphql::Error::new)?;
+            (mailer, Some(queue))
+        }
+        None => (Mailer::default(), None),
+    };
+
+    let metrics = web::Data::new(Metrics::new());
+    let revision = StoreRevision::default();
+    let markdown_cache = MarkdownCache::new(env_or("MARKDOWN_CACHE_SIZE", 1000));
+    let mut schema_builder = Schema::build(Query, Mutation, Subscription)
+        .extension(GraphQLMetrics(metrics.clone().into_inner()))
+        .extension(GraphQLLogging)
+        .extension(SlowQueryLogging {
+            threshold: settings.slow_query_threshold,
+            metrics: metrics.clone().into_inner(),
+        })
+        .extension(GraphQLRequestId)
+        .extension(GraphQLTracing)
+        .extension(revision.clone())
+        .extension(SubscriptionsOnlyOverWebSocket)
+        .extension(QueryLimits {
+            max_depth: settings.max_query_depth,
+            max_complexity: settings.max_query_complexity,
+            expose_complexity: settings.debug,
+        })
+        .data(settings.clone())
+        .data(jwt_keys.clone())
+        .data(storage.clone())
+        .data(comment_store)
+        .data(posts_by_author_loader)
+        .data(like_store)
+        .data(bookmark_store)
+        .data(follow_store)
+        .data(notification_store.clone())
+        .data(webhook_store.clone())
+        .data(newsletter_store.clone())
+        .data(newsletter_key.clone())
+        .data(contact_store)
+        .data(reaction_store)
+        .data(view_store)
+        .data(api_key_store.clone())
+        .data(refresh_token_store.clone())
+        .data(session_store.clone())
+        .data(event_bus.clone())
+        .data(mailer)
+        .data(comment_count_loader)
+        .data(like_count_loader)
+        .data(user_loader)
+        .data(markdown_cache.clone())
+        .data::<SearchIndexStore>(Arc::new(LinearScanIndex));
+    if !introspection_enabled {
+        schema_builder = schema_builder
+            .disable_introspection()
+            .extension(IntrospectionDisabled);
+    }
+    if apollo_tracing {
+        tracing::warn!("GRAPHQL_TRACING is enabled; responses include resolver timings");
+        schema_builder = schema_builder.extension(ApolloTracing);
+    }
+    if let Some(data_file) = &data_file {
+        schema_builder = schema_builder.extension(data_file.clone());
+    }
+
+    Ok(AppState {
+        schema: schema_builder.finish(),
+        storage,
+        data_file,
+        settings: web::Data::new(settings),
+        jwt_keys,
+        api_key_store,
+        notification_store,
+        webhook_store,
+        newsletter_store,
+        newsletter_key,
+        event_bus,
+        mail_queue: Arc::new(std::sync::Mutex::new(mail_queue)),
+        session_store,
+        refresh_token_store,
+        rate_limiter,
+        persisted_queries,
+        revision,
+        introspection_enabled,
+        graphql_path: DEFAULT_GRAPHQL_PATH.to_string(),
+        request_limits: RequestLimits {
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            execution_timeout: Duration::from_secs(DEFAULT_EXECUTION_TIMEOUT_SECS),
+        },
+        started_at: StartedAt(Instant::now()),
+        metrics,
+        metrics_route: true,
+        markdown_cache,
+        sitemap_cache: SitemapCache::default(),
+    })
+}
+
+// 予約投稿の公開、Webhookとメールの送信、期限切れのトークンとレート制限のバケットの削除
+pub fn spawn_background_tasks(state: &AppState) -> BackgroundTasks {
+    let (shutdown, receiver) = watch::channel(false);
+    let mut handles = vec![
+        tokio::spawn(tasks::run_scheduler(
+            state.storage.clone(),
+            state.notification_store.clone(),
+            state.event_bus.clone(),
+            state.data_file.clone(),
+            state.revision.clone(),
+            receiver.clone(),
+        )),
+        tokio::spawn(webhook::run_webhook_dispatcher(
+            state.webhook_store.clone(),
+            state.settings.clone().into_inner(),
+            state.event_bus.subscribe(),
+            receiver.clone(),
+        )),
+        tokio::spawn(tasks::run_auth_sweeper(
+            state.refresh_token_store.clone(),
+            state.session_store.clone(),
+            receiver.clone(),
+        )),
+        tokio::spawn(rate_limit::run_rate_limit_sweeper(
+            state.rate_limiter.clone().into_inner(),
+            receiver.clone(),
+        )),
+    ];
+    // メールの送信キューは1つのタスクだけが受け取る（SMTPを設定していなければない）
+    if let Some(queue) = state.mail_queue.lock_or_recover().take() {
+        handles.push(tokio::spawn(mail::run_mail_sender(queue, receiver)));
+    }
+    BackgroundTasks { shutdown, handles }
+}
+
+// spawn_background_tasksで起動したタスク（破棄した場合も止まる）
+#[must_use = "dropping BackgroundTasks stops the background tasks"]
+pub struct BackgroundTasks {
+    shutdown: watch::Sender<bool>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl BackgroundTasks {
+    // 実行中の処理（予約投稿の公開など）は最後まで行ってから止める
+    pub async fn shutdown(self) {
+        let _ = self.shutdown.send(true);
+        for handle in self.handles {
+            let _ = handle.await;
+        }
+    }
+}
+
+// ルーティングとハンドラーが参照するデータ（CORSなどのミドルウェアは呼び出し側で設定する）
+pub fn configure_app(cfg: &mut web::ServiceConfig, state: &AppState) {
+    let path = state.graphql_path.as_str();
+    cfg.app_data(web::Data::new(state.schema.clone()))
+        .app_data(state.settings.clone())
+        .app_data(web::Data::new(state.jwt_keys.clone()))
+        .app_data(web::Data::new(state.api_key_store.clone()))
+        .app_data(web::Data::new(state.session_store.clone()))
+        .app_data(web::Data::new(state.newsletter_store.clone()))
+        .app_data(web::Data::new(state.newsletter_key.clone()))
+        .app_data(state.rate_limiter.clone())
+        .app_data(state.persisted_queries.clone())
+        .app_data(web::Data::new(state.revision.clone()))
+        .app_data(web::Data::new(state.storage.clone()))
+        .app_data(web::Data::new(state.started_at))
+        .app_data(web::Data::new(state.request_limits))
+        // バッチ（JSON配列）も単独のリクエストも同じ上限。超えると413を返す
+        .app_data(web::PayloadConfig::new(state.request_limits.max_body_size))
+        .app_data(web::JsonConfig::default().limit(state.request_limits.max_body_size))
+        .app_data(state.metrics.clone())
+        .app_data(web::Data::new(state.markdown_cache.clone()))
+        .app_data(web::Data::new(state.sitemap_cache.clone()));
+    let mut routes = web::scope("")
+        // オーケストレーター向け（認証やレート制限の対象外）
+        .route("/healthz", web::get().to(http::healthz_handler))
+        .route("/readyz", web::get().to(http::readyz_handler))
+        .route("/feed.rss", web::get().to(feed::rss_handler))
+        .route("/feed.atom", web::get().to(feed::atom_handler))
+        .route("/feed.json", web::get().to(feed::json_feed_handler))
+        .route("/sitemap.xml", web::get().to(sitemap::sitemap_handler))
+        // ニュースレターの確認・購読をやめるメールのリンク
+        .route("/newsletter/confirm", web::get().to(newsletter::confirm_handler))
+        .route("/newsletter/unsubscribe", web::get().to(newsletter::unsubscribe_handler))
+        .configure(rest::configure_rest)
+        .route("/uploads/{filename}", web::get().to(upload::uploads_handler))
+        // ファイルのアップロード（multipart/form-data）は1ファイルごとにMAX_UPLOAD_SIZEまで
+        .service(
+            web::resource(path)
+                .guard(guard::fn_guard(|ctx| {
+                    ctx.header::<header::ContentType>()
+                        .is_some_and(|ct| ct.0.essence_str() == "multipart/form-data")
+                }))
+                .app_data(
+                    MultipartOptions::default()
+                        .max_file_size(state.settings.max_upload_size)
+                        .max_num_files(state.settings.max_batch_size),
+                )
+                .route(web::post().to(http::graphql_handler::<GraphQLRequest>)),
+        )
+        .route(path, web::post().to(http::graphql_handler::<GraphQLBody>))
+        .route(path, web::get().to(http::graphql_handler::<GraphQLBody>))
+        .route(&format!("{}/ws", path), web::get().to(http::graphql_ws_handler))
+        .route(&format!("{}/sse", path), web::get().to(http::graphql_sse_handler));
+    // イントロスペクションを無効にした環境ではスキーマを見せるUIも出さない
+    if state.introspection_enabled {
+        let graphiql_path = path.to_string();
+        routes = routes
+            .route(
+                "/api/graphiql",
+                web::get().to(move || http::graphiql_handler(graphiql_path.clone())),
+            )
+            .route(&format!("{}/schema", path), web::get().to(http::graphql_schema_handler));
+    }
+    if state.metrics_route {
+        routes = routes.route("/metrics", web::get().to(metrics::metrics_handler));
+    }
+    cfg.service(
+        routes
+            .wrap(from_fn(metrics::track_http_request))
+            // リクエストごとのspan（終了時にメソッド・ルート・ステータス・処理時間を出す）
+            .wrap(TracingLogger::<RequestIdRootSpan>::new())
+            .wrap(from_fn(request_id::assign_request_id)),
+    );
+}
+
+// METRICS_PORTを指定した場合に、内部向けのサーバーで/metricsだけを公開する
+pub fn configure_metrics(cfg: &mut web::ServiceConfig, state: &AppState) {
+    cfg.app_data(web::Data::new(state.storage.clone()))
+        .app_data(web::Data::new(state.started_at))
+        .app_data(state.metrics.clone())
+        .route("/metrics", web::get().to(metrics::metrics_handler));
+}
@@ -0,0 +1,111 @@
+use async_graphql::ID;
+use async_graphql::dataloader::Loader;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::time::Duration;
+use tracing::instrument;
+
+use crate::models::{Post, User};
+use crate::pagination::Page;
+use crate::search::PostFilter;
+use crate::store::{AppStorage, CommentStore, LikeStore, LockExt, count_likes};
+
+// 同じバッチに集める待ち時間。デフォルトの1msでは、一覧の途中でフィールドの解決が
+// 一度ほかのタスクに譲った（tokioの協調スケジューリング）間に締め切られ、問い合わせが分かれる
+pub(crate) const LOADER_DELAY: Duration = Duration::from_millis(5);
+
+// ユーザーをまとめて取得するローダー（投稿一覧の著者をストレージへの問い合わせ1回で引く）
+pub(crate) struct UserLoader {
+    pub(crate) storage: AppStorage,
+}
+
+impl Loader<ID> for UserLoader {
+    type Value = User;
+    type Error = async_graphql::Error;
+
+    // 存在しないIDは結果に含めない（load_oneはNoneになる）
+    async fn load(&self, keys: &[ID]) -> Result<HashMap<ID, Self::Value>, Self::Error> {
+        let users = self.storage.get_users(keys).await?;
+        Ok(users.into_iter().map(|user| (user.id.clone(), user)).collect())
+    }
+}
+
+// 著者ごとの投稿をまとめて取得するローダー（ストレージへの問い合わせは1回で済む）
+pub(crate) struct PostsByAuthorLoader {
+    pub(crate) storage: AppStorage,
+}
+
+impl Loader<ID> for PostsByAuthorLoader {
+    type Value = Vec<Post>;
+    type Error = async_graphql::Error;
+
+    async fn load(&self, keys: &[ID]) -> Result<HashMap<ID, Self::Value>, Self::Error> {
+        let mut result: HashMap<ID, Vec<Post>> =
+            keys.iter().map(|id| (id.clone(), Vec::new())).collect();
+        let filter = PostFilter {
+            author_ids: Some(keys.to_vec()),
+            ..PostFilter::default()
+        };
+        // ストレージは新しい順に返すので、そのまま著者ごとに分ける
+        for post in self.storage.list_posts(&filter, Page::ALL).await? {
+            if let Some(author_posts) = result.get_mut(&post.author_id) {
+                author_posts.push(post);
+            }
+        }
+        Ok(result)
+    }
+}
+
+// 投稿ごとのコメント数をまとめて数えるローダー
+pub(crate) struct CommentCountLoader {
+    pub(crate) comment_store: CommentStore,
+}
+
+#[derive(Clone, Copy, Default)]
+pub(crate) struct CommentCounts {
+    pub(crate) visible: usize,
+    pub(crate) hidden: usize,
+}
+
+impl Loader<ID> for CommentCountLoader {
+    type Value = CommentCounts;
+    type Error = Infallible;
+
+    // ページ内の投稿をまとめて1回のロックで数える
+    #[instrument(level = "debug", name = "loader.comment_counts", skip_all)]
+    async fn load(&self, keys: &[ID]) -> Result<HashMap<ID, Self::Value>, Self::Error> {
+        let mut result: HashMap<ID, CommentCounts> = keys
+            .iter()
+            .map(|id| (id.clone(), CommentCounts::default()))
+            .collect();
+        let comments = self.comment_store.lock_or_recover();
+        for comment in comments.iter().filter(|c| !c.deleted) {
+            if let Some(counts) = result.get_mut(&comment.post_id) {
+                if comment.hidden {
+                    counts.hidden += 1;
+                } else {
+                    counts.visible += 1;
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+// 投稿ごとのいいね数をまとめて数えるローダー
+pub(crate) struct LikeCountLoader {
+    pub(crate) like_store: LikeStore,
+}
+
+impl Loader<ID> for LikeCountLoader {
+    type Value = usize;
+    type Error = Infallible;
+
+    async fn load(&self, keys: &[ID]) -> Result<HashMap<ID, Self::Value>, Self::Error> {
+        let counts = count_likes(&self.like_store.lock_or_recover());
+        Ok(keys
+            .iter()
+            .map(|id| (id.clone(), counts.get(id).copied().unwrap_or(0)))
+            .collect())
+    }
+}
@@ -0,0 +1,274 @@
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextExecute, NextParseQuery, NextRequest,
+    NextResolve, ResolveInfo,
+};
+use async_graphql::parser::types::ExecutableDocument;
+use async_graphql::{Name, Response, ServerResult, Value, Variables};
+use clap::ValueEnum;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+use crate::metrics::Metrics;
+use crate::telemetry::Telemetry;
+
+// ログの形式（LOG_FORMAT）
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+// 出力する内容はRUST_LOGで絞り込む（未指定ならinfo以上）
+// spanの終了も出すので、RUST_LOG=blog_server=debugにするとミューテーションごとの処理時間がわかる
+// OTLPの設定があればspanも送る。戻り値は終了時にshutdownを呼んで送り切る
+pub fn init_logging(format: LogFormat) -> Telemetry {
+    let (telemetry, telemetry_error) = match Telemetry::from_env() {
+        Ok(telemetry) => (telemetry, None),
+        Err(e) => (Telemetry::disabled(), Some(e)),
+    };
+
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let fmt_layer = tracing_subscriber::fmt::layer().with_span_events(FmtSpan::CLOSE);
+    let fmt_layer = match format {
+        LogFormat::Text => fmt_layer.boxed(),
+        LogFormat::Json => fmt_layer.json().flatten_event(true).boxed(),
+    };
+    // GraphQLの操作やストレージのspan（debug）はRUST_LOGによらず送る
+    let otel_layer = telemetry.tracer().map(|tracer| {
+        tracing_opentelemetry::layer()
+            .with_tracer(tracer)
+            .with_filter(EnvFilter::new(OTEL_SPAN_FILTER))
+    });
+    tracing_subscriber::registry()
+        .with(fmt_layer.with_filter(filter))
+        .with(otel_layer)
+        .init();
+
+    if let Some(e) = telemetry_error {
+        tracing::warn!("{}; spans will not be exported", e);
+    }
+    telemetry
+}
+
+const OTEL_SPAN_FILTER: &str = "info,blog_server=debug";
+
+// 変数のうち、名前にこれらを含むもの（大文字小文字は区別しない）はログに出さない
+const REDACTED_FIELDS: &[&str] = &["password", "token", "secret", "apikey"];
+
+fn redact(value: &Value) -> Value {
+    match value {
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(name, value)| (name.clone(), redact_field(name, value)))
+                .collect(),
+        ),
+        Value::List(items) => Value::List(items.iter().map(redact).collect()),
+        value => value.clone(),
+    }
+}
+
+fn redact_field(name: &Name, value: &Value) -> Value {
+    let name = name.to_lowercase();
+    if REDACTED_FIELDS.iter().any(|field| name.contains(field)) {
+        Value::String("[REDACTED]".to_string())
+    } else {
+        redact(value)
+    }
+}
+
+// GraphQLの操作ごとに、操作名・変数・処理時間・エラー数をログに出す
+pub(crate) struct GraphQLLogging;
+
+impl ExtensionFactory for GraphQLLogging {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(GraphQLLoggingExtension {
+            variables: OnceLock::new(),
+            operation_name: OnceLock::new(),
+        })
+    }
+}
+
+struct GraphQLLoggingExtension {
+    variables: OnceLock<Value>,
+    // 構文エラーなどで実行まで進まなかった場合は未設定
+    operation_name: OnceLock<Option<String>>,
+}
+
+#[async_trait::async_trait]
+impl Extension for GraphQLLoggingExtension {
+    async fn request(&self, ctx: &ExtensionContext<'_>, next: NextRequest<'_>) -> Response {
+        let started = Instant::now();
+        let response = next.run(ctx).await;
+        let duration_ms = started.elapsed().as_secs_f64() * 1000.0;
+        let operation = match self.operation_name.get() {
+            Some(Some(name)) => name.as_str(),
+            Some(None) => "anonymous",
+            None => "invalid",
+        };
+        let variables = self
+            .variables
+            .get()
+            .and_then(|v| serde_json::to_string(v).ok())
+            .unwrap_or_default();
+        let errors = response.errors.len();
+        if errors == 0 {
+            tracing::info!(operation, variables, duration_ms, errors, "graphql operation");
+        } else {
+            let first_error = response.errors[0].message.as_str();
+            tracing::warn!(
+                operation,
+                variables,
+                duration_ms,
+                errors,
+                first_error,
+                "graphql operation failed"
+            );
+        }
+        response
+    }
+
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let _ = self.variables.set(redact(&variables.clone().into_value()));
+        next.run(ctx, query, variables).await
+    }
+
+    async fn execute(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        operation_name: Option<&str>,
+        next: NextExecute<'_>,
+    ) -> Response {
+        let _ = self.operation_name.set(operation_name.map(str::to_string));
+        next.run(ctx, operation_name).await
+    }
+}
+
+// 遅い操作のログに含めるクエリの最大文字数
+const MAX_LOGGED_QUERY_CHARS: usize = 500;
+// これより速いリゾルバーは記録しない（速い操作ではロックを取らないように）
+const MIN_RECORDED_RESOLVER_DURATION: Duration = Duration::from_millis(1);
+// 遅い操作のログに含めるリゾルバーの数
+const SLOWEST_RESOLVERS: usize = 5;
+
+// SLOW_QUERY_MSより時間のかかった操作を、クエリ・変数名・遅いリゾルバーとあわせてWARNで出す
+// 変数は値ではなく名前だけを出す
+pub(crate) struct SlowQueryLogging {
+    pub(crate) threshold: Duration,
+    pub(crate) metrics: Arc<Metrics>,
+}
+
+impl ExtensionFactory for SlowQueryLogging {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(SlowQueryLoggingExtension {
+            threshold: self.threshold,
+            metrics: self.metrics.clone(),
+            query: OnceLock::new(),
+            operation_name: OnceLock::new(),
+            resolvers: Mutex::new(Vec::new()),
+        })
+    }
+}
+
+struct SlowQueryLoggingExtension {
+    threshold: Duration,
+    metrics: Arc<Metrics>,
+    // 切り詰めたクエリと変数名
+    query: OnceLock<(String, Vec<Name>)>,
+    operation_name: OnceLock<Option<String>>,
+    // MIN_RECORDED_RESOLVER_DURATION以上かかったリゾルバーのパスと処理時間
+    resolvers: Mutex<Vec<(String, Duration)>>,
+}
+
+#[async_trait::async_trait]
+impl Extension for SlowQueryLoggingExtension {
+    async fn request(&self, ctx: &ExtensionContext<'_>, next: NextRequest<'_>) -> Response {
+        let started = Instant::now();
+        let response = next.run(ctx).await;
+        let duration = started.elapsed();
+        if duration <= self.threshold {
+            return response;
+        }
+
+        self.metrics.record_slow_operation();
+        let operation = match self.operation_name.get() {
+            Some(Some(name)) => name.as_str(),
+            Some(None) => "anonymous",
+            None => "invalid",
+        };
+        let (query, variables) = match self.query.get() {
+            Some((query, names)) => (query.as_str(), names.join(", ")),
+            None => ("", String::new()),
+        };
+        let mut resolvers = std::mem::take(&mut *self.resolvers.lock().unwrap());
+        resolvers.sort_by_key(|(_, duration)| std::cmp::Reverse(*duration));
+        let slowest_resolvers = resolvers
+            .iter()
+            .take(SLOWEST_RESOLVERS)
+            .map(|(path, duration)| format!("{} {:.1}ms", path, duration.as_secs_f64() * 1000.0))
+            .collect::<Vec<_>>()
+            .join(", ");
+        tracing::warn!(
+            operation,
+            query,
+            variables,
+            duration_ms = duration.as_secs_f64() * 1000.0,
+            threshold_ms = self.threshold.as_millis() as u64,
+            slowest_resolvers,
+            "slow graphql operation"
+        );
+        response
+    }
+
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let mut truncated: String = query.chars().take(MAX_LOGGED_QUERY_CHARS).collect();
+        if truncated.len() < query.len() {
+            truncated.push('…');
+        }
+        let _ = self.query.set((truncated, variables.keys().cloned().collect()));
+        next.run(ctx, query, variables).await
+    }
+
+    async fn execute(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        operation_name: Option<&str>,
+        next: NextExecute<'_>,
+    ) -> Response {
+        let _ = self.operation_name.set(operation_name.map(str::to_string));
+        next.run(ctx, operation_name).await
+    }
+
+    async fn resolve(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        info: ResolveInfo<'_>,
+        next: NextResolve<'_>,
+    ) -> ServerResult<Option<Value>> {
+        let path_node = info.path_node;
+        let started = Instant::now();
+        let result = next.run(ctx, info).await;
+        let duration = started.elapsed();
+        if duration >= MIN_RECORDED_RESOLVER_DURATION {
+            let path = path_node.to_string();
+            self.resolvers.lock().unwrap().push((path, duration));
+        }
+        result
+    }
+}
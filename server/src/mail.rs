@@ -0,0 +1,261 @@
+// メールでの通知（SMTP）
+// ミューテーションは送信キューに入れるだけで、送信はspawn_background_tasksで起動するタスクが行う
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Address, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::feed::escape_xml;
+use crate::markdown::excerpt;
+use crate::models::{deleted_user, Comment, ContactMessage, Post, Role};
+use crate::pagination::Page;
+use crate::search::UserFilter;
+use crate::settings::{Settings, SmtpSettings, SmtpTls};
+use crate::store::AppStorage;
+use crate::tasks::ShutdownReceiver;
+
+// 送信待ちのメールの上限（超えた分は捨ててログに出す）
+const MAIL_QUEUE_CAPACITY: usize = 256;
+// 1回の送信を待つ時間と、失敗した場合の再試行（待ち時間を2倍にしながら）
+const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_SEND_RETRIES: u32 = 3;
+const RETRY_BASE: Duration = Duration::from_secs(5);
+// メールに載せるコメントの抜粋の文字数
+const COMMENT_EXCERPT_LENGTH: usize = 200;
+
+type SmtpTransport = AsyncSmtpTransport<Tokio1Executor>;
+
+pub(crate) struct Email {
+    pub(crate) to: Mailbox,
+    // 返信先（お問い合わせの送り主など。未指定なら送信元）
+    pub(crate) reply_to: Option<Mailbox>,
+    pub(crate) subject: String,
+    pub(crate) text: String,
+    pub(crate) html: String,
+}
+
+// スキーマに登録する送信キュー（SMTPを設定していなければ何もしない）
+#[derive(Clone, Default)]
+pub(crate) struct Mailer {
+    sender: Option<mpsc::Sender<Email>>,
+}
+
+impl Mailer {
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.sender.is_some()
+    }
+
+    // 待たずにキューに入れる（いっぱいなら捨てる）
+    pub(crate) fn enqueue(&self, email: Email) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        if sender.try_send(email).is_err() {
+            tracing::warn!("mail queue is full; dropping email");
+        }
+    }
+}
+
+// 送信タスクが受け取るキューとSMTPの接続先（spawn_background_tasksで1度だけ取り出す）
+pub(crate) struct MailQueue {
+    transport: SmtpTransport,
+    from: Mailbox,
+    receiver: mpsc::Receiver<Email>,
+}
+
+pub(crate) type PendingMailQueue = Arc<Mutex<Option<MailQueue>>>;
+
+// SMTPの設定からキューを作る。送信元のアドレスが不正ならエラー
+pub(crate) fn mail_queue(smtp: &SmtpSettings) -> Result<(Mailer, MailQueue), String> {
+    let from: Mailbox = smtp
+        .from
+        .parse()
+        .map_err(|e| format!("SMTP_FROM must be a valid email address ({})", e))?;
+    let builder = match smtp.tls {
+        SmtpTls::Tls => SmtpTransport::relay(&smtp.host),
+        SmtpTls::StartTls => SmtpTransport::starttls_relay(&smtp.host),
+        SmtpTls::None => Ok(SmtpTransport::builder_dangerous(&smtp.host)),
+    }
+    .map_err(|e| format!("Invalid SMTP_HOST \"{}\": {}", smtp.host, e))?;
+    let mut builder = builder.port(smtp.port).timeout(Some(SEND_TIMEOUT));
+    if let Some((username, password)) = &smtp.credentials {
+        builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+    }
+
+    let (sender, receiver) = mpsc::channel(MAIL_QUEUE_CAPACITY);
+    let queue = MailQueue {
+        transport: builder.build(),
+        from,
+        receiver,
+    };
+    Ok((Mailer { sender: Some(sender) }, queue))
+}
+
+// 投稿の著者がメールでの通知を有効にしていればコメントを知らせる（自分のコメントは除く）
+// コメントは保存済みなので、ここでの失敗はコメントした側には返さない
+pub(crate) async fn notify_comment_by_email(
+    mailer: &Mailer,
+    storage: &AppStorage,
+    settings: &Settings,
+    post: &Post,
+    comment: &Comment,
+) {
+    if !mailer.is_enabled() || comment.author_id == post.author_id {
+        return;
+    }
+    // 内部エラーの詳細はasync_graphql::Errorへの変換時にログに出ている
+    let Ok(Some(author)) = storage.get_user(&post.author_id).await else {
+        return;
+    };
+    let Some(email) = author.email.as_deref().filter(|_| author.email_on_comment) else {
+        return;
+    };
+    let Ok(address) = email.parse::<Address>() else {
+        tracing::warn!(user_id = %author.id.as_str(), "cannot send email to an invalid address");
+        return;
+    };
+    let Ok(commenter) = storage.get_user(&comment.author_id).await else {
+        return;
+    };
+    let commenter = commenter.unwrap_or_else(|| deleted_user(&comment.author_id));
+    let to = Mailbox::new(Some(author.name), address);
+    mailer.enqueue(comment_email(to, post, &commenter.name, comment, settings));
+}
+
+fn comment_email(
+    to: Mailbox,
+    post: &Post,
+    commenter: &str,
+    comment: &Comment,
+    settings: &Settings,
+) -> Email {
+    let url = format!("{}/posts/{}", settings.site_base_url, post.slug);
+    let excerpt = excerpt(&comment.body, COMMENT_EXCERPT_LENGTH);
+    let footer = "You received this email because comment notifications are enabled \
+                  in your notification settings.";
+    let text = format!(
+        "{} commented on \"{}\":\n\n{}\n\n{}\n\n--\n{}\n",
+        commenter, post.title, excerpt, url, footer
+    );
+    let html = format!(
+        "<p>{} commented on <a href=\"{}\">{}</a>:</p>\n<blockquote>{}</blockquote>\n\
+         <p><small>{}</small></p>\n",
+        escape_xml(commenter),
+        escape_xml(&url),
+        escape_xml(&post.title),
+        escape_xml(&excerpt),
+        footer
+    );
+    Email {
+        to,
+        reply_to: None,
+        subject: format!("New comment on \"{}\"", post.title),
+        text,
+        html,
+    }
+}
+
+// お問い合わせのメッセージを、メールアドレスを登録した管理者に転送する（返信先は送り主）
+// メッセージは保存済みなので、ここでの失敗は送り主には返さない
+pub(crate) async fn forward_contact_message(
+    mailer: &Mailer,
+    storage: &AppStorage,
+    settings: &Settings,
+    message: &ContactMessage,
+) {
+    if !mailer.is_enabled() {
+        return;
+    }
+    // 内部エラーの詳細はasync_graphql::Errorへの変換時にログに出ている
+    let Ok(users) = storage.list_users(&UserFilter::default(), Page::ALL).await else {
+        return;
+    };
+    let reply_to = message
+        .email
+        .parse::<Address>()
+        .ok()
+        .map(|address| Mailbox::new(Some(message.name.clone()), address));
+    for admin in users.into_iter().filter(|user| user.role == Role::Admin) {
+        let Some(address) = admin.email.as_deref().and_then(|e| e.parse::<Address>().ok()) else {
+            continue;
+        };
+        let to = Mailbox::new(Some(admin.name), address);
+        mailer.enqueue(contact_email(to, reply_to.clone(), message, settings));
+    }
+}
+
+fn contact_email(
+    to: Mailbox,
+    reply_to: Option<Mailbox>,
+    message: &ContactMessage,
+    settings: &Settings,
+) -> Email {
+    let intro = format!(
+        "{} <{}> sent a message via the contact form of {}:",
+        message.name, message.email, settings.site_title
+    );
+    let footer = "Reply to this email to answer the sender.";
+    let text = format!("{}\n\n{}\n\n--\n{}\n", intro, message.message, footer);
+    let html = format!(
+        "<p>{}</p>\n<blockquote style=\"white-space: pre-wrap\">{}</blockquote>\n\
+         <p><small>{}</small></p>\n",
+        escape_xml(&intro),
+        escape_xml(&message.message),
+        footer
+    );
+    Email {
+        to,
+        reply_to,
+        subject: format!("Contact message from {}", message.name),
+        text,
+        html,
+    }
+}
+
+// キューのメールを順に送る。送信はメールごとに別のタスクで行い、再試行で後続を待たせない
+pub(crate) async fn run_mail_sender(queue: MailQueue, mut shutdown: ShutdownReceiver) {
+    let MailQueue {
+        transport,
+        from,
+        mut receiver,
+    } = queue;
+    loop {
+        let email = tokio::select! {
+            email = receiver.recv() => email,
+            _ = shutdown.changed() => return,
+        };
+        let Some(email) = email else {
+            return;
+        };
+        let mut builder = Message::builder().from(from.clone()).to(email.to);
+        if let Some(reply_to) = email.reply_to {
+            builder = builder.reply_to(reply_to);
+        }
+        let message = builder
+            .subject(email.subject)
+            .multipart(MultiPart::alternative_plain_html(email.text, email.html));
+        match message {
+            Ok(message) => {
+                tokio::spawn(send_with_retries(transport.clone(), message));
+            }
+            Err(e) => tracing::warn!(error = %e, "failed to build email"),
+        }
+    }
+}
+
+async fn send_with_retries(transport: SmtpTransport, message: Message) {
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let Err(e) = transport.send(message.clone()).await else {
+            return;
+        };
+        if attempts > MAX_SEND_RETRIES {
+            tracing::warn!(attempts, error = %e, "failed to send email");
+            return;
+        }
+        tokio::time::sleep(RETRY_BASE.saturating_mul(2u32.saturating_pow(attempts - 1))).await;
+    }
+}
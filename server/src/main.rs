@@ -1,218 +1,241 @@
 use actix_cors::Cors;
-use actix_web::{web, App, HttpServer};
-use async_graphql::{
-    EmptySubscription, Object, Schema, SimpleObject, InputObject, ID, Scalar, ScalarType, Value,
+use actix_web::{App, HttpServer};
+use blog_server::{
+    build_app_state, configure_app, configure_https_redirect, configure_metrics, init_logging,
+    open_database, open_memory_storage, schema_sdl, spawn_background_tasks,
+    spawn_certificate_reloader, AppStorage, DataFile, Seed, ServerConfig, TlsCertificates,
 };
-use async_graphql_actix_web::{GraphQLRequest, GraphQLResponse};
-use std::sync::{Arc, Mutex};
-use chrono::{DateTime, Utc};
-use uuid::Uuid;
-use serde::{Deserialize, Serialize};
+use futures_util::future::{join_all, try_join_all};
+use std::net::SocketAddr;
+use std::time::Duration;
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
 
-// DateTimeスカラー型
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
-pub struct DateTimeScalar(DateTime<Utc>);
-
-#[Scalar]
-impl ScalarType for DateTimeScalar {
-    fn parse(value: Value) -> async_graphql::InputValueResult<Self> {
-        if let Value::String(s) = value {
-            let dt = DateTime::parse_from_rfc3339(&s)
-                .map_err(|_| async_graphql::InputValueError::custom("Invalid DateTime format"))?
-                .with_timezone(&Utc);
-            Ok(DateTimeScalar(dt))
-        } else {
-            Err(async_graphql::InputValueError::expected_type(value))
-        }
-    }
-
-    fn to_value(&self) -> Value {
-        Value::String(self.0.to_rfc3339())
-    }
-}
-
-// データモデル
-#[derive(Clone, SimpleObject)]
-struct User {
-    id: ID,
-    name: String,
-    #[graphql(name = "avatarUrl")]
-    avatar_url: Option<String>,
-}
-
-#[derive(Clone, SimpleObject)]
-struct Post {
-    id: ID,
-    title: String,
-    author: User,
-    body: String,
-    tags: Vec<String>,
-    published_at: DateTimeScalar,
-}
-
-#[derive(InputObject)]
-struct CreatePostInput {
-    title: String,
-    body: String,
-    tags: Option<Vec<String>>,
-    author_id: ID,
-}
-
-// メモリストア
-type UserStore = Arc<Mutex<Vec<User>>>;
-type PostStore = Arc<Mutex<Vec<Post>>>;
-
-// GraphQL Query
-struct Query;
-
-#[Object]
-impl Query {
-    async fn posts(&self, ctx: &async_graphql::Context<'_>) -> Vec<Post> {
-        let post_store = ctx.data_unchecked::<PostStore>();
-        let posts = post_store.lock().unwrap();
-        posts.clone()
-    }
-
-    async fn post(&self, ctx: &async_graphql::Context<'_>, id: ID) -> Option<Post> {
-        let post_store = ctx.data_unchecked::<PostStore>();
-        let posts = post_store.lock().unwrap();
-        posts.iter().find(|p| p.id == id).cloned()
-    }
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    // 不正な値（数値でないPORTなど）はここでエラーを表示して終了する
+    let config = ServerConfig::load();
 
-    async fn user(&self, ctx: &async_graphql::Context<'_>, id: ID) -> Option<User> {
-        let user_store = ctx.data_unchecked::<UserStore>();
-        let users = user_store.lock().unwrap();
-        users.iter().find(|u| u.id == id).cloned()
+    if config.print_schema {
+        print!("{}", schema_sdl());
+        return Ok(());
     }
-}
-
-// GraphQL Mutation
-struct Mutation;
-
-#[Object]
-impl Mutation {
-    async fn create_post(
-        &self,
-        ctx: &async_graphql::Context<'_>,
-        input: CreatePostInput,
-    ) -> async_graphql::Result<Post> {
-        let user_store = ctx.data_unchecked::<UserStore>();
-        let post_store = ctx.data_unchecked::<PostStore>();
-
-        // ユーザーを検索
-        let users = user_store.lock().unwrap();
-        let author = users
-            .iter()
-            .find(|u| u.id == input.author_id)
-            .cloned()
-            .ok_or_else(|| async_graphql::Error::new("User not found"))?;
-        drop(users);
+    let telemetry = init_logging(config.log_format);
 
-        // 投稿を作成
-        let post = Post {
-            id: ID::from(Uuid::new_v4().to_string()),
-            title: input.title,
-            author,
-            body: input.body,
-            tags: input.tags.unwrap_or_default(),
-            published_at: DateTimeScalar(Utc::now()),
-        };
-
-        let mut posts = post_store.lock().unwrap();
-        posts.push(post.clone());
-        Ok(post)
+    if config.validate_seed && std::env::var_os("SEED_FILE").is_none() {
+        eprintln!("SEED_FILE is not set");
+        std::process::exit(1);
     }
-
-    async fn delete_post(
-        &self,
-        ctx: &async_graphql::Context<'_>,
-        id: ID,
-    ) -> async_graphql::Result<bool> {
-        let post_store = ctx.data_unchecked::<PostStore>();
-        let mut posts = post_store.lock().unwrap();
-        let initial_len = posts.len();
-        posts.retain(|p| p.id != id);
-        Ok(posts.len() < initial_len)
+    // 使うのはストレージが空の場合だけだが、ファイルの誤りは起動時に知らせる
+    let seed = match Seed::from_env() {
+        Ok(seed) => seed,
+        Err(e) => {
+            eprintln!("Failed to load SEED_FILE:\n{}", e);
+            std::process::exit(1);
+        }
+    };
+    if config.validate_seed {
+        println!(
+            "Seed file is valid: {} users, {} posts",
+            seed.user_count(),
+            seed.post_count()
+        );
+        return Ok(());
     }
-}
-
-// GraphQL Schema
-type AppSchema = Schema<Query, Mutation, EmptySubscription>;
-
-async fn graphql_handler(
-    schema: web::Data<AppSchema>,
-    req: GraphQLRequest,
-) -> GraphQLResponse {
-    schema.execute(req.into_inner()).await.into()
-}
 
-#[actix_web::main]
-async fn main() -> std::io::Result<()> {
-    // 初期ユーザーデータ
-    let user_store: UserStore = Arc::new(Mutex::new(vec![
-        User {
-            id: ID::from("1"),
-            name: "髙橋慶祐".to_string(),
-            avatar_url: Some("https://example.com/avatar.png".to_string()),
-        },
-        User {
-            id: ID::from("2"),
-            name: "佐藤太郎".to_string(),
-            avatar_url: None,
+    // 証明書の誤りはTLSのハンドシェイクまで持ち越さず、ここで理由を表示して終了する
+    let tls = match (&config.tls_cert_path, &config.tls_key_path) {
+        (Some(cert_path), Some(key_path)) => match TlsCertificates::load(cert_path, key_path) {
+            Ok(certificates) => Some(certificates),
+            Err(e) => {
+                tracing::error!("{}", e);
+                std::process::exit(1);
+            }
         },
-        User {
-            id: ID::from("3"),
-            name: "鈴木花子".to_string(),
-            avatar_url: None,
-        },
-        User {
-            id: ID::from("4"),
-            name: "伊藤次郎".to_string(),
-            avatar_url: None,
+        _ => None,
+    };
+
+    // STORAGE（redb:パス）かDATABASE_URLを指定した場合はそこに、指定しない場合はメモリ上に保存する
+    let storage_url = ["STORAGE", "DATABASE_URL"]
+        .into_iter()
+        .find_map(|name| std::env::var(name).ok().map(|url| (name, url)));
+    let (storage, data_file): (AppStorage, Option<DataFile>) = match &storage_url {
+        Some((name, url)) => match open_database(url, &seed).await {
+            Ok(storage) => (storage, None),
+            Err(e) => {
+                tracing::error!("Failed to open {}: {}", name, e);
+                std::process::exit(1);
+            }
         },
-        User {
-            id: ID::from("5"),
-            name: "加藤三郎".to_string(),
-            avatar_url: None,
+        None => open_memory_storage(&seed, config.force),
+    };
+    let storage_description = match &storage_url {
+        Some((_, url)) => without_password(url),
+        None => match std::env::var("DATA_FILE") {
+            Ok(path) => format!("memory (DATA_FILE={})", path),
+            Err(_) => "memory".to_string(),
         },
-    ]));
-
-    // 初期投稿データ
-    let first_user = User {
-        id: ID::from("1"),
-        name: "髙橋慶祐".to_string(),
-        avatar_url: Some("https://example.com/avatar.png".to_string()),
     };
-    let post_store: PostStore = Arc::new(Mutex::new(vec![Post {
-        id: ID::from("1"),
-        title: "はじめまして".to_string(),
-        author: first_user.clone(),
-        body: "これは最初の投稿です。".to_string(),
-        tags: vec!["はじめに".to_string(), "ブログ".to_string()],
-        published_at: DateTimeScalar(Utc::now()),
-    }]));
-
-    let schema = Schema::build(Query, Mutation, EmptySubscription)
-        .data(user_store)
-        .data(post_store)
-        .finish();
+    let execution_timeout = Duration::from_secs(config.execution_timeout_secs);
+    let state = match build_app_state(storage, data_file).await {
+        Ok(state) => state
+            .with_graphql_path(&config.graphql_path)
+            .with_request_limits(config.max_body_size, execution_timeout),
+        Err(e) => {
+            tracing::error!("Failed to initialize: {}", e.message);
+            std::process::exit(1);
+        }
+    };
+    let state = if config.metrics_port.is_some() {
+        state.without_metrics_route()
+    } else {
+        state
+    };
+    let background_tasks = spawn_background_tasks(&state);
 
-    println!("GraphQL server running at http://127.0.0.1:8000/api/graphql");
+    // 実際に使う設定（秘密鍵やパスワードは表示しない）
+    let cors_description = if config.cors_allowed_origins.is_empty() {
+        "any origin".to_string()
+    } else {
+        config.cors_allowed_origins.join(", ")
+    };
+    let tls_description = match &config.tls_cert_path {
+        Some(cert_path) => format!("enabled ({})", cert_path.display()),
+        None => "disabled".to_string(),
+    };
+    let metrics_description = match config.metrics_port {
+        Some(port) => format!("{}:{}/metrics", config.metrics_bind_addr, port),
+        None => "/metrics".to_string(),
+    };
+    let address = SocketAddr::new(config.bind_addr, config.port);
+    tracing::info!(
+        bind_address = %address,
+        graphql_path = %config.graphql_path,
+        max_body_size = config.max_body_size,
+        execution_timeout_secs = config.execution_timeout_secs,
+        cors_origins = %cors_description,
+        storage = %storage_description,
+        tls = %tls_description,
+        http_redirect_port = ?config.http_redirect_port,
+        metrics = %metrics_description,
+        introspection = state.introspection_enabled(),
+        "Configuration"
+    );
+
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    let base_url = format!("{}://{}", scheme, address);
+    tracing::info!("GraphQL server running at {}{}", base_url, config.graphql_path);
+    if state.introspection_enabled() {
+        tracing::info!("GraphiQL available at {}/api/graphiql", base_url);
+    }
 
-    HttpServer::new(move || {
-        let cors = Cors::default()
-            .allow_any_origin()
+    let cors_allowed_origins = config.cors_allowed_origins.clone();
+    let app_state = state.clone();
+    let server = HttpServer::new(move || {
+        let mut cors = Cors::default()
             .allow_any_method()
             .allow_any_header()
+            // ブラウザのクライアントからもリクエストIDを読めるようにする
+            .expose_headers(["x-request-id"])
             .max_age(3600);
+        // Cookie認証を使う場合はオリジンを明示して資格情報を許可する
+        if cors_allowed_origins.is_empty() {
+            cors = cors.allow_any_origin();
+        } else {
+            for origin in &cors_allowed_origins {
+                cors = cors.allowed_origin(origin);
+            }
+            cors = cors.supports_credentials();
+        }
 
         App::new()
-            .app_data(web::Data::new(schema.clone()))
             .wrap(cors)
-            .route("/api/graphql", web::post().to(graphql_handler))
-            .route("/api/graphql", web::get().to(graphql_handler))
+            .configure(|cfg| configure_app(cfg, &app_state))
     })
-    .bind("127.0.0.1:8000")?
-    .run()
-    .await
+    .shutdown_timeout(config.shutdown_timeout_secs)
+    // SIGTERM / SIGINTはwait_for_shutdown_signalで受け取り、全てのサーバーをまとめて止める
+    .disable_signals();
+    let server = match tls {
+        Some(certificates) => {
+            let server = server.bind_rustls_0_23(address, certificates.server_config())?;
+            spawn_certificate_reloader(certificates);
+            server
+        }
+        None => server.bind(address)?,
+    };
+    let mut servers = vec![server.run()];
+    if let Some(redirect_port) = config.http_redirect_port {
+        let https_port = config.port;
+        let redirect_server = HttpServer::new(move || {
+            App::new().configure(|cfg| configure_https_redirect(cfg, https_port))
+        })
+        .shutdown_timeout(config.shutdown_timeout_secs)
+        .disable_signals()
+        .bind((config.bind_addr, redirect_port))?;
+        servers.push(redirect_server.run());
+    }
+    if let Some(metrics_port) = config.metrics_port {
+        let metrics_state = state.clone();
+        let metrics_server = HttpServer::new(move || {
+            App::new().configure(|cfg| configure_metrics(cfg, &metrics_state))
+        })
+        .workers(1)
+        .shutdown_timeout(config.shutdown_timeout_secs)
+        .disable_signals()
+        .bind((config.metrics_bind_addr, metrics_port))?;
+        servers.push(metrics_server.run());
+    }
+
+    let handles: Vec<_> = servers.iter().map(|server| server.handle()).collect();
+    let shutdown_timeout_secs = config.shutdown_timeout_secs;
+    tokio::spawn(async move {
+        wait_for_shutdown_signal().await;
+        tracing::info!(
+            "Shutting down: no longer accepting connections, \
+             waiting up to {}s for in-flight requests",
+            shutdown_timeout_secs
+        );
+        join_all(handles.iter().map(|handle| handle.stop(true))).await;
+    });
+    try_join_all(servers).await?;
+
+    tracing::info!("Stopping background tasks and flushing data");
+    background_tasks.shutdown().await;
+    state.close().await;
+    telemetry.shutdown().await;
+    tracing::info!("Shutdown complete");
+    Ok(())
+}
+
+// SIGTERM（コンテナの停止）かSIGINT（Ctrl+C）を待つ。2回目を受け取ったら待たずに終了する
+async fn wait_for_shutdown_signal() {
+    next_shutdown_signal().await;
+    tokio::spawn(async {
+        next_shutdown_signal().await;
+        tracing::warn!("Received a second signal; exiting without waiting for in-flight requests");
+        std::process::exit(1);
+    });
+}
+
+async fn next_shutdown_signal() {
+    #[cfg(unix)]
+    if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+        tokio::select! {
+            _ = terminate.recv() => {}
+            _ = tokio::signal::ctrl_c() => {}
+        }
+        return;
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+// DATABASE_URLのパスワードを伏せて表示する
+fn without_password(url: &str) -> String {
+    match url::Url::parse(url) {
+        Ok(mut parsed) if parsed.password().is_some() => {
+            let _ = parsed.set_password(Some("****"));
+            parsed.to_string()
+        }
+        _ => url.to_string(),
+    }
 }
@@ -0,0 +1,448 @@
+use async_graphql::{SimpleObject, ID};
+use chrono::{DateTime, Utc};
+use lru::LruCache;
+use pulldown_cmark::{
+    html, CowStr, Event, LinkType, Options, Parser, Tag, TagEnd, TextMergeStream,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+
+use crate::store::LockExt;
+
+// 目次に含める見出しの深さ（# 〜 ####）
+const MAX_TOC_LEVEL: i32 = 4;
+
+/// 目次の項目（本文の見出し）
+#[derive(SimpleObject, Clone)]
+pub(crate) struct TocEntry {
+    /// 見出しの深さ（1〜4）
+    level: i32,
+    /// 見出しのテキスト（記法を除く）
+    text: String,
+    /// bodyHtmlの見出しのid（`#` を付けるとページ内リンクになる）
+    anchor: String,
+}
+
+// 描画したHTMLと目次（同じ解析結果から作る）
+pub(crate) struct RenderedBody {
+    pub(crate) html: String,
+    pub(crate) toc: Vec<TocEntry>,
+}
+
+// @メンションのハンドル（小文字）とリンク先のURL
+pub(crate) type MentionLinks = BTreeMap<String, String>;
+
+// 投稿本文のMarkdown（CommonMark + GFMの表・取り消し線）をHTMLにする
+// 本文中の生のHTMLは出力せず、さらにammoniaで許可したタグと属性以外を取り除く
+// 見出しにはidを付け、#〜####の見出しを目次にする
+// linksにあるハンドルへの@メンションはリンクにする（それ以外の@はテキストのまま）
+pub(crate) fn render_markdown(body: &str, links: &MentionLinks) -> RenderedBody {
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+    // 強調の記号などで分かれたテキストをつなげてから@メンションを探す
+    let mut events: Vec<Event> = TextMergeStream::new(Parser::new_ext(body, options))
+        .filter(|event| !matches!(event, Event::Html(_) | Event::InlineHtml(_)))
+        .collect();
+
+    let mut toc = Vec::new();
+    let mut anchors = HashSet::new();
+    let mut heading: Option<(usize, String)> = None;
+    for i in 0..events.len() {
+        match &events[i] {
+            Event::Start(Tag::Heading { .. }) => heading = Some((i, String::new())),
+            Event::Text(s) | Event::Code(s) => {
+                if let Some((_, text)) = &mut heading {
+                    text.push_str(s);
+                }
+            }
+            Event::SoftBreak | Event::HardBreak => {
+                if let Some((_, text)) = &mut heading {
+                    text.push(' ');
+                }
+            }
+            Event::End(TagEnd::Heading(level)) => {
+                let level = *level as i32;
+                let Some((start, text)) = heading.take() else {
+                    continue;
+                };
+                let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                let anchor = unique_anchor(&mut anchors, &heading_anchor(&text));
+                if let Event::Start(Tag::Heading { id, .. }) = &mut events[start] {
+                    *id = Some(anchor.clone().into());
+                }
+                if level <= MAX_TOC_LEVEL {
+                    toc.push(TocEntry { level, text, anchor });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if !links.is_empty() {
+        events = link_mentions(events, links);
+    }
+
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, events.into_iter());
+
+    // scriptやイベントハンドラーの属性は除かれ、リンクには rel="noopener noreferrer" が付く
+    // コードブロックの言語（class="language-rust"）はシンタックスハイライト用に残す
+    let mut builder = ammonia::Builder::default();
+    builder.add_tag_attributes("code", &["class"]);
+    for tag in ["h1", "h2", "h3", "h4", "h5", "h6"] {
+        builder.add_tag_attributes(tag, &["id"]);
+    }
+    let html = builder.clean(&unsafe_html).to_string();
+    RenderedBody { html, toc }
+}
+
+// 1つの本文から取り出す@メンションの上限（超えた分は無視する）
+pub(crate) const MAX_MENTIONS: usize = 50;
+
+// 本文の@メンションのハンドル（小文字にして重複を除き、出てきた順）
+// コードブロック・インラインコード・リンクの中と、メールアドレスのような英数字に続く@は除く
+pub(crate) fn mentioned_handles(body: &str) -> Vec<String> {
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+    let mut handles: Vec<String> = Vec::new();
+    let mut context = MentionContext::default();
+    for event in TextMergeStream::new(Parser::new_ext(body, options)) {
+        let Event::Text(text) = &event else {
+            context.update(&event);
+            continue;
+        };
+        if !context.allows_mentions() {
+            continue;
+        }
+        for (start, end) in mention_spans(text) {
+            let handle = text[start + 1..end].to_lowercase();
+            if !handles.contains(&handle) {
+                handles.push(handle);
+            }
+            if handles.len() == MAX_MENTIONS {
+                return handles;
+            }
+        }
+    }
+    handles
+}
+
+// @メンションを探さない場所（コードブロックとリンクの中）にいるか
+#[derive(Default)]
+struct MentionContext {
+    code_block: bool,
+    link_depth: usize,
+}
+
+impl MentionContext {
+    fn update(&mut self, event: &Event) {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => self.code_block = true,
+            Event::End(TagEnd::CodeBlock) => self.code_block = false,
+            Event::Start(Tag::Link { .. } | Tag::Image { .. }) => self.link_depth += 1,
+            Event::End(TagEnd::Link | TagEnd::Image) => {
+                self.link_depth = self.link_depth.saturating_sub(1);
+            }
+            _ => {}
+        }
+    }
+
+    fn allows_mentions(&self) -> bool {
+        !self.code_block && self.link_depth == 0
+    }
+}
+
+// テキスト中の @handle のバイト位置（@を含む）
+// ハンドルは英数字と_の3〜30文字で、前後に英数字や_が続くものは含めない
+fn mention_spans(text: &str) -> Vec<(usize, usize)> {
+    let is_handle_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut spans = Vec::new();
+    let mut previous: Option<char> = None;
+    for (start, c) in text.char_indices() {
+        let after_word = previous.is_some_and(|p| is_handle_char(p) || p == '@');
+        previous = Some(c);
+        if c != '@' || after_word {
+            continue;
+        }
+        let rest = &text[start + 1..];
+        let length = rest.find(|c: char| !is_handle_char(c)).unwrap_or(rest.len());
+        if (3..=30).contains(&length) {
+            spans.push((start, start + 1 + length));
+        }
+    }
+    spans
+}
+
+// linksにあるハンドルへの@メンションをリンクのイベントに置き換える
+fn link_mentions<'a>(events: Vec<Event<'a>>, links: &MentionLinks) -> Vec<Event<'a>> {
+    let mut linked = Vec::with_capacity(events.len());
+    let mut context = MentionContext::default();
+    for event in events {
+        let Event::Text(text) = &event else {
+            context.update(&event);
+            linked.push(event);
+            continue;
+        };
+        if !context.allows_mentions() {
+            linked.push(event);
+            continue;
+        }
+        let mut rest = 0;
+        for (start, end) in mention_spans(text) {
+            let Some(url) = links.get(&text[start + 1..end].to_lowercase()) else {
+                continue;
+            };
+            if rest < start {
+                linked.push(Event::Text(CowStr::from(text[rest..start].to_string())));
+            }
+            linked.push(Event::Start(Tag::Link {
+                link_type: LinkType::Inline,
+                dest_url: CowStr::from(url.clone()),
+                title: CowStr::from(""),
+                id: CowStr::from(""),
+            }));
+            linked.push(Event::Text(CowStr::from(text[start..end].to_string())));
+            linked.push(Event::End(TagEnd::Link));
+            rest = end;
+        }
+        if rest == 0 {
+            linked.push(event);
+        } else if rest < text.len() {
+            linked.push(Event::Text(CowStr::from(text[rest..].to_string())));
+        }
+    }
+    linked
+}
+
+// 見出しのid。小文字にして文字と数字以外はハイフンにまとめる（日本語の文字は残す）
+fn heading_anchor(text: &str) -> String {
+    let mut anchor = String::new();
+    for c in text.to_lowercase().chars() {
+        if c.is_alphanumeric() {
+            anchor.push(c);
+        } else if !anchor.is_empty() && !anchor.ends_with('-') {
+            anchor.push('-');
+        }
+    }
+    let anchor = anchor.trim_end_matches('-');
+    if anchor.is_empty() {
+        "section".to_string()
+    } else {
+        anchor.to_string()
+    }
+}
+
+// 同じidの見出しが既にあれば -1, -2 … を付ける
+fn unique_anchor(used: &mut HashSet<String>, base: &str) -> String {
+    let mut anchor = base.to_string();
+    let mut n = 0;
+    while !used.insert(anchor.clone()) {
+        n += 1;
+        anchor = format!("{}-{}", base, n);
+    }
+    anchor
+}
+
+// 抜粋の文字数の上限と、これ以上さかのぼらない単語の長さ
+pub(crate) const MAX_EXCERPT_LENGTH: usize = 1000;
+const MAX_WORD_BACKTRACK: usize = 20;
+const MORE_MARKER: &str = "<!--more-->";
+
+// 本文の抜粋（Markdownの記法を除いたテキスト）。<!--more-->があればその前の全体を使う
+// 長さは文字数で数え、英単語の途中では切らない。切り詰めた場合は末尾に…を付ける
+pub(crate) fn excerpt(body: &str, length: usize) -> String {
+    if let Some((before, _)) = body.split_once(MORE_MARKER) {
+        return plain_text(before, usize::MAX).0;
+    }
+    // 単語の途中かどうかを判定するため1文字多く取り出す
+    let (text, more) = plain_text(body, length + 1);
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() <= length && !more {
+        return text;
+    }
+    let mut end = length.min(chars.len());
+    let in_word = |i: usize| chars.get(i).is_some_and(char::is_ascii_alphanumeric);
+    if end > 0 && in_word(end) && in_word(end - 1) {
+        let word_start = (end.saturating_sub(MAX_WORD_BACKTRACK)..end)
+            .rev()
+            .find(|&i| chars[i].is_whitespace());
+        if let Some(start) = word_start {
+            end = start;
+        }
+    }
+    let truncated: String = chars[..end].iter().collect();
+    format!("{}…", truncated.trim_end())
+}
+
+// 記法を除いたテキスト。ブロックや改行の区切りは空白1つにし、max_chars文字を超えたら打ち切る
+// 打ち切った場合は2番目の値がtrue
+fn plain_text(body: &str, max_chars: usize) -> (String, bool) {
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+    let mut text = String::new();
+    let mut count = 0;
+    let mut pending_space = false;
+    for event in Parser::new_ext(body, options) {
+        let fragment = match &event {
+            Event::Text(s) | Event::Code(s) => s.as_ref(),
+            Event::SoftBreak | Event::HardBreak => {
+                pending_space = !text.is_empty();
+                continue;
+            }
+            Event::End(tag) if is_block(tag) => {
+                pending_space = !text.is_empty();
+                continue;
+            }
+            _ => continue,
+        };
+        for c in fragment.chars() {
+            if c.is_whitespace() {
+                pending_space = !text.is_empty();
+                continue;
+            }
+            let needed = if pending_space { 2 } else { 1 };
+            if count + needed > max_chars {
+                return (text, true);
+            }
+            if pending_space {
+                text.push(' ');
+                pending_space = false;
+            }
+            text.push(c);
+            count += needed;
+        }
+    }
+    (text, false)
+}
+
+fn is_block(tag: &TagEnd) -> bool {
+    matches!(
+        tag,
+        TagEnd::Paragraph
+            | TagEnd::Heading(_)
+            | TagEnd::BlockQuote(_)
+            | TagEnd::CodeBlock
+            | TagEnd::Item
+            | TagEnd::TableCell
+            | TagEnd::TableRow
+            | TagEnd::TableHead
+    )
+}
+
+// 本文の語数。英語などの空白で区切る言語は単語数、日本語などのCJKの文字は1文字ずつ数える
+// Markdownの記法、生のHTML、コードブロックは数えない
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub(crate) struct TextStats {
+    pub(crate) words: u32,
+    pub(crate) cjk_chars: u32,
+}
+
+impl TextStats {
+    pub(crate) fn of(body: &str) -> Self {
+        let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+        let mut stats = TextStats::default();
+        let mut in_code_block = false;
+        let mut in_word = false;
+        for event in Parser::new_ext(body, options) {
+            let text = match &event {
+                Event::Start(Tag::CodeBlock(_)) => {
+                    in_code_block = true;
+                    continue;
+                }
+                Event::End(TagEnd::CodeBlock) => {
+                    in_code_block = false;
+                    in_word = false;
+                    continue;
+                }
+                Event::Text(s) | Event::Code(s) if !in_code_block => s.as_ref(),
+                Event::SoftBreak | Event::HardBreak => {
+                    in_word = false;
+                    continue;
+                }
+                Event::End(tag) if is_block(tag) => {
+                    in_word = false;
+                    continue;
+                }
+                _ => continue,
+            };
+            for c in text.chars() {
+                if is_cjk(c) {
+                    stats.cjk_chars += 1;
+                    in_word = false;
+                } else if c.is_alphanumeric() {
+                    if !in_word {
+                        stats.words += 1;
+                        in_word = true;
+                    }
+                } else if !(in_word && matches!(c, '\'' | '’' | '-' | '_')) {
+                    // don't や well-known は1語として数える
+                    in_word = false;
+                }
+            }
+        }
+        stats
+    }
+
+    pub(crate) fn word_count(&self) -> u32 {
+        self.words + self.cjk_chars
+    }
+
+    // 単語と文字をそれぞれの速さで読んだ時間の合計（分単位で切り上げ、最低1分）
+    pub(crate) fn reading_minutes(&self, words_per_minute: u32, chars_per_minute: u32) -> u32 {
+        let minutes = f64::from(self.words) / f64::from(words_per_minute.max(1))
+            + f64::from(self.cjk_chars) / f64::from(chars_per_minute.max(1));
+        (minutes.ceil() as u32).max(1)
+    }
+}
+
+// 漢字・ひらがな・カタカナ・ハングル（句読点や全角の英数字は含まない）
+fn is_cjk(c: char) -> bool {
+    matches!(
+        c,
+        '\u{3005}'
+            | '\u{3041}'..='\u{309F}'
+            | '\u{30A0}'..='\u{30FF}'
+            | '\u{3400}'..='\u{4DBF}'
+            | '\u{4E00}'..='\u{9FFF}'
+            | '\u{AC00}'..='\u{D7AF}'
+            | '\u{F900}'..='\u{FAFF}'
+            | '\u{FF66}'..='\u{FF9F}'
+            | '\u{20000}'..='\u{2FFFF}'
+    )
+}
+
+// 投稿IDとupdated_at（本文を変えると変わる）の組で投稿の版を表す
+// メンションしたユーザーが削除されるとリンクが変わるので、リンクもキーに含める
+type PostVersion = (ID, DateTime<Utc>, MentionLinks);
+
+// 描画結果のキャッシュ（投稿の版ごと）。GraphQLとフィードで共有する
+#[derive(Clone)]
+pub(crate) struct MarkdownCache {
+    rendered: Arc<Mutex<LruCache<PostVersion, Arc<RenderedBody>>>>,
+}
+
+impl MarkdownCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        MarkdownCache {
+            rendered: Arc::new(Mutex::new(LruCache::new(
+                NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN),
+            ))),
+        }
+    }
+
+    pub(crate) fn render(
+        &self,
+        id: &ID,
+        updated_at: DateTime<Utc>,
+        body: &str,
+        links: &MentionLinks,
+    ) -> Arc<RenderedBody> {
+        let key = (id.clone(), updated_at, links.clone());
+        if let Some(rendered) = self.rendered.lock_or_recover().get(&key) {
+            return rendered.clone();
+        }
+        // 描画中はロックを持たない（同時に描画した場合は後のものが残る）
+        let rendered = Arc::new(render_markdown(body, links));
+        self.rendered.lock_or_recover().put(key, rendered.clone());
+        rendered
+    }
+}
@@ -0,0 +1,52 @@
+// 本文の@メンション（@handle）とユーザーの結び付け
+use async_graphql::dataloader::DataLoader;
+use async_graphql::ID;
+
+use crate::loaders::UserLoader;
+use crate::markdown::{mentioned_handles, MentionLinks};
+use crate::models::User;
+use crate::settings::Settings;
+use crate::store::Storage;
+
+// 本文でメンションしたユーザーのID（出てきた順）。存在しないハンドルは無視する
+pub(crate) async fn resolve_mentions(
+    storage: &dyn Storage,
+    body: &str,
+) -> async_graphql::Result<Vec<ID>> {
+    let mut ids = Vec::new();
+    for handle in mentioned_handles(body) {
+        if let Some(user) = storage.get_user_by_handle(&handle).await? {
+            ids.push(user.id);
+        }
+    }
+    Ok(ids)
+}
+
+// 保存したIDのユーザー（削除されたユーザーは含めない）
+pub(crate) async fn mentioned_users(
+    ctx: &async_graphql::Context<'_>,
+    ids: &[ID],
+) -> async_graphql::Result<Vec<User>> {
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let loader = ctx.data::<DataLoader<UserLoader>>()?;
+    let mut users = loader.load_many(ids.iter().cloned()).await?;
+    Ok(ids.iter().filter_map(|id| users.remove(id)).collect())
+}
+
+// ユーザーのプロフィールのURL
+pub(crate) fn profile_url(settings: &Settings, user: &User) -> String {
+    format!("{}/users/{}", settings.site_base_url, user.handle)
+}
+
+// 描画時にリンクにするハンドルとプロフィールのURL
+pub(crate) fn mention_links<'a>(
+    users: impl IntoIterator<Item = &'a User>,
+    settings: &Settings,
+) -> MentionLinks {
+    users
+        .into_iter()
+        .map(|user| (user.handle.clone(), profile_url(settings, user)))
+        .collect()
+}
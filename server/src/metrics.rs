@@ -0,0 +1,183 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, HttpResponse};
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextExecute, NextRequest,
+};
+use async_graphql::Response;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+
+use crate::http::StartedAt;
+use crate::search::UserFilter;
+use crate::store::AppStorage;
+
+// Prometheusのメトリクス（/metrics）
+pub(crate) struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    graphql_operations: IntCounterVec,
+    slow_graphql_operations: IntCounter,
+    posts: IntGauge,
+    users: IntGauge,
+    uptime: IntGauge,
+}
+
+impl Metrics {
+    pub(crate) fn new() -> Self {
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests by route and status"),
+            &["route", "status"],
+        )
+        .unwrap();
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "HTTP request latency by route and status",
+            ),
+            &["route", "status"],
+        )
+        .unwrap();
+        let graphql_operations = IntCounterVec::new(
+            Opts::new(
+                "graphql_operations_total",
+                "GraphQL operations by operation name and whether errors occurred",
+            ),
+            &["operation", "errors"],
+        )
+        .unwrap();
+        let slow_graphql_operations = IntCounter::new(
+            "graphql_slow_operations_total",
+            "GraphQL operations that took longer than SLOW_QUERY_MS",
+        )
+        .unwrap();
+        let posts = IntGauge::new("blog_posts", "Posts in the storage (including drafts and trash)")
+            .unwrap();
+        let users = IntGauge::new("blog_users", "Users in the storage").unwrap();
+        let uptime = IntGauge::new("process_uptime_seconds", "Seconds since the server started")
+            .unwrap();
+
+        let registry = Registry::new();
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry.register(Box::new(http_request_duration.clone())).unwrap();
+        registry.register(Box::new(graphql_operations.clone())).unwrap();
+        registry.register(Box::new(slow_graphql_operations.clone())).unwrap();
+        registry.register(Box::new(posts.clone())).unwrap();
+        registry.register(Box::new(users.clone())).unwrap();
+        registry.register(Box::new(uptime.clone())).unwrap();
+        Metrics {
+            registry,
+            http_requests,
+            http_request_duration,
+            graphql_operations,
+            slow_graphql_operations,
+            posts,
+            users,
+            uptime,
+        }
+    }
+
+    pub(crate) fn record_slow_operation(&self) {
+        self.slow_graphql_operations.inc();
+    }
+}
+
+// ルートごとのリクエスト数と処理時間
+// ラベルはURLではなくルートのパターンにし、存在しないパスはまとめる（系列が増えすぎないように）
+pub(crate) async fn track_http_request(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let metrics = req.app_data::<web::Data<Metrics>>().cloned();
+    let route = req.match_pattern().unwrap_or_else(|| "unmatched".to_string());
+    let started = Instant::now();
+    let result = next.call(req).await;
+    if let Some(metrics) = metrics {
+        let status = match &result {
+            Ok(res) => res.status(),
+            Err(e) => e.as_response_error().status_code(),
+        };
+        let labels = [route.as_str(), status.as_str()];
+        metrics.http_requests.with_label_values(&labels).inc();
+        metrics
+            .http_request_duration
+            .with_label_values(&labels)
+            .observe(started.elapsed().as_secs_f64());
+    }
+    result
+}
+
+// 件数はスクレイプのたびに数える
+pub(crate) async fn metrics_handler(
+    metrics: web::Data<Metrics>,
+    storage: web::Data<AppStorage>,
+    started_at: web::Data<StartedAt>,
+) -> HttpResponse {
+    if let Ok(posts) = storage.list_all_posts().await {
+        metrics.posts.set(posts.len() as i64);
+    }
+    if let Ok(users) = storage.count_users(&UserFilter::default()).await {
+        metrics.users.set(users as i64);
+    }
+    metrics.uptime.set(started_at.0.elapsed().as_secs() as i64);
+
+    let encoder = TextEncoder::new();
+    let mut body = Vec::new();
+    if let Err(e) = encoder.encode(&metrics.registry.gather(), &mut body) {
+        tracing::error!("Failed to encode metrics: {}", e);
+        return HttpResponse::InternalServerError().finish();
+    }
+    HttpResponse::Ok().content_type(encoder.format_type()).body(body)
+}
+
+// GraphQLの操作ごとの件数（バッチリクエストは要素ごとに数える）
+pub(crate) struct GraphQLMetrics(pub(crate) Arc<Metrics>);
+
+impl ExtensionFactory for GraphQLMetrics {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(GraphQLMetricsExtension {
+            metrics: self.0.clone(),
+            operation_name: OnceLock::new(),
+        })
+    }
+}
+
+struct GraphQLMetricsExtension {
+    metrics: Arc<Metrics>,
+    // 実行した操作の名前（構文エラーなどで実行まで進まなかった場合は未設定）
+    operation_name: OnceLock<Option<String>>,
+}
+
+#[async_trait::async_trait]
+impl Extension for GraphQLMetricsExtension {
+    async fn request(&self, ctx: &ExtensionContext<'_>, next: NextRequest<'_>) -> Response {
+        let response = next.run(ctx).await;
+        let operation = match self.operation_name.get() {
+            Some(Some(name)) => name.as_str(),
+            Some(None) => "anonymous",
+            None => "invalid",
+        };
+        let errors = if response.errors.is_empty() { "false" } else { "true" };
+        self.metrics
+            .graphql_operations
+            .with_label_values(&[operation, errors])
+            .inc();
+        response
+    }
+
+    async fn execute(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        operation_name: Option<&str>,
+        next: NextExecute<'_>,
+    ) -> Response {
+        let _ = self.operation_name.set(operation_name.map(str::to_string));
+        next.run(ctx, operation_name).await
+    }
+}
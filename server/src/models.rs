@@ -0,0 +1,893 @@
+use async_graphql::{ComplexObject, Enum, Guard, InputObject, MaybeUndefined, SimpleObject, ID};
+use async_graphql::dataloader::DataLoader;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::auth::{ADMIN_ROLES, RoleGuard, Viewer};
+use crate::error::{not_found, AppError};
+use crate::extensions::list_complexity;
+use crate::loaders::{CommentCountLoader, LikeCountLoader, PostsByAuthorLoader, UserLoader};
+use crate::markdown::{
+    excerpt, render_markdown, MarkdownCache, MentionLinks, RenderedBody, TextStats, TocEntry,
+    MAX_EXCERPT_LENGTH,
+};
+use crate::mention::{mention_links, mentioned_users};
+use crate::notification::{ensure_recipient, user_notifications};
+use crate::pagination::{DEFAULT_PAGE_SIZE, paginate};
+use crate::scalars::{DateTimeFormat, DateTimeScalar, UrlScalar};
+use crate::settings::Settings;
+use crate::avatar::{self, gravatar_hash};
+use crate::upload::MAX_RESIZE_DIMENSION;
+use crate::validation::MAX_SEO_DESCRIPTION_LENGTH;
+use crate::store::{
+    ApiKeyStore, AppStorage, CommentStore, FollowStore, LikeStore, LockExt, NotificationStore,
+    ReactionStore, ViewStore, view_count,
+};
+
+// データモデル
+#[derive(Clone, SimpleObject, Serialize, Deserialize)]
+#[graphql(complex)]
+pub struct User {
+    pub(crate) id: ID,
+    pub(crate) name: String,
+    // URLや@メンションに使う一意の名前（作成後は変えられない）
+    // 以前のバージョンで保存したデータにはないので、読み込んだ後に名前かIDから付ける
+    #[serde(default)]
+    pub(crate) handle: String,
+    #[graphql(skip)]
+    pub(crate) avatar_url: Option<UrlScalar>,
+    // メールアドレス（スキーマにはGravatar用のハッシュだけを公開する）
+    #[graphql(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) email: Option<String>,
+    // 自分の投稿へのコメントをメールで通知する（notificationSettingsで本人だけが参照・変更できる）
+    #[graphql(skip)]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) email_on_comment: bool,
+    // 自己紹介（Markdown）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) bio: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) website: Option<UrlScalar>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) location: Option<String>,
+    pub(crate) role: Role,
+    // パスワードのハッシュ（スキーマには公開しない）
+    #[graphql(skip)]
+    pub(crate) password_hash: Option<String>,
+}
+
+#[derive(Clone, SimpleObject, Serialize, Deserialize)]
+#[graphql(complex)]
+pub struct Post {
+    pub(crate) id: ID,
+    pub(crate) title: String,
+    // URL用の識別子（作成後はタイトルを変えても変わらない）
+    pub(crate) slug: String,
+    #[graphql(skip)]
+    pub(crate) author_id: ID,
+    pub(crate) body: String,
+    pub(crate) tags: Vec<String>,
+    // カバー画像と代替テキスト
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) cover_image_url: Option<UrlScalar>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) cover_image_alt: Option<String>,
+    // 検索エンジン向けの説明（未設定なら本文の抜粋を返す）と正規URL
+    #[graphql(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) seo_description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) canonical_url: Option<UrlScalar>,
+    pub(crate) status: PostStatus,
+    // 下書きの場合は作成日時、公開時に公開日時で上書きする
+    #[graphql(skip)]
+    pub(crate) published_at: DateTimeScalar,
+    // 予約投稿の公開予定日時（公開されるとNoneになる）
+    pub(crate) scheduled_at: Option<DateTimeScalar>,
+    pub(crate) updated_at: DateTimeScalar,
+    // ゴミ箱に移動した日時
+    pub(crate) deleted_at: Option<DateTimeScalar>,
+    // 更新前の内容（古い順）
+    pub(crate) revisions: Vec<PostRevision>,
+    // 本文の語数（本文を書き込むときに数える。ない場合は読み出すときに数える）
+    #[graphql(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) text_stats: Option<TextStats>,
+    // 本文で@メンションしたユーザー（本文を書き込むときに解決する）
+    #[graphql(skip)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) mentioned_user_ids: Vec<ID>,
+}
+
+#[derive(Clone, SimpleObject, Serialize, Deserialize)]
+pub(crate) struct PostRevision {
+    pub(crate) revision: i32,
+    pub(crate) title: String,
+    pub(crate) body: String,
+    pub(crate) tags: Vec<String>,
+    created_at: DateTimeScalar,
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum PostStatus {
+    Draft,
+    Published,
+}
+
+impl Post {
+    pub(crate) fn is_published(&self) -> bool {
+        self.status == PostStatus::Published
+    }
+
+    pub(crate) fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
+    pub(crate) fn set_body(&mut self, body: String) {
+        self.text_stats = Some(TextStats::of(&body));
+        self.body = body;
+    }
+
+    fn body_stats(&self) -> TextStats {
+        self.text_stats.unwrap_or_else(|| TextStats::of(&self.body))
+    }
+
+    // 本文の描画結果（メンションしたユーザーのうち、残っているユーザーへの@はリンクにする）
+    async fn rendered_body(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> async_graphql::Result<Arc<RenderedBody>> {
+        let cache = ctx.data::<MarkdownCache>()?;
+        let settings = ctx.data::<Settings>()?;
+        let users = mentioned_users(ctx, &self.mentioned_user_ids).await?;
+        let links = mention_links(&users, settings);
+        Ok(cache.render(&self.id, self.updated_at.0, &self.body, &links))
+    }
+
+    // 現在の内容を履歴に残す（上限を超えた古い履歴は削除）
+    pub(crate) fn save_revision(&mut self, max_revisions: usize) {
+        let revision = self.revisions.last().map(|r| r.revision).unwrap_or(0) + 1;
+        self.revisions.push(PostRevision {
+            revision,
+            title: self.title.clone(),
+            body: self.body.clone(),
+            tags: self.tags.clone(),
+            created_at: DateTimeScalar(Utc::now()),
+        });
+        if self.revisions.len() > max_revisions {
+            let excess = self.revisions.len() - max_revisions;
+            self.revisions.drain(..excess);
+        }
+    }
+
+    // ゴミ箱の投稿は常に除外し、下書きは指定時のみ含める
+    pub(crate) fn is_visible(&self, include_drafts: bool) -> bool {
+        !self.is_deleted() && (include_drafts || self.is_published())
+    }
+
+    // 予約日時が過去なら即公開し、未来なら下書きのまま予約する
+    pub(crate) fn schedule(&mut self, scheduled_at: DateTimeScalar, now: DateTime<Utc>) {
+        if scheduled_at.0 <= now {
+            self.status = PostStatus::Published;
+            self.published_at = scheduled_at;
+            self.scheduled_at = None;
+        } else {
+            self.status = PostStatus::Draft;
+            self.scheduled_at = Some(scheduled_at);
+        }
+    }
+}
+
+#[ComplexObject]
+impl Post {
+    /// 公開日時（下書きの場合は作成日時）。
+    /// 省略時はUTCのRFC 3339で、`timezone`（IANAの名前、例: `Asia/Tokyo`）と `format` を指定できる
+    async fn published_at(
+        &self,
+        timezone: Option<String>,
+        #[graphql(default)] format: DateTimeFormat,
+    ) -> async_graphql::Result<String> {
+        self.published_at.format(timezone.as_deref(), format)
+    }
+
+    /// 本文のMarkdownを描画したHTML（生のHTMLやscriptは取り除く）
+    async fn body_html(&self, ctx: &async_graphql::Context<'_>) -> async_graphql::Result<String> {
+        Ok(self.rendered_body(ctx).await?.html.clone())
+    }
+
+    /// 本文の見出し（#〜####）の目次。`anchor` はbodyHtmlの見出しのidと同じ
+    async fn table_of_contents(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> async_graphql::Result<Vec<TocEntry>> {
+        Ok(self.rendered_body(ctx).await?.toc.clone())
+    }
+
+    /// 本文で@メンションしたユーザー（出てきた順。削除されたユーザーは含めない）
+    async fn mentions(&self, ctx: &async_graphql::Context<'_>) -> async_graphql::Result<Vec<User>> {
+        mentioned_users(ctx, &self.mentioned_user_ids).await
+    }
+
+    /// 本文の抜粋（Markdownの記法を除き、`length` 文字を超える場合は切り詰めて末尾に…を付ける）。
+    /// 本文に `<!--more-->` があればその前の部分全体を返す。`length` は1〜1000の範囲に収める
+    async fn excerpt(&self, #[graphql(default = 200)] length: i32) -> String {
+        let length = length.clamp(1, MAX_EXCERPT_LENGTH as i32) as usize;
+        excerpt(&self.body, length)
+    }
+
+    /// 本文の語数（英語などは単語数、日本語などは文字数。Markdownの記法とコードブロックは数えない）
+    async fn word_count(&self) -> i32 {
+        self.body_stats().word_count() as i32
+    }
+
+    /// 本文を読むのにかかる時間の目安（分単位で切り上げ、最低1分）
+    async fn reading_time_minutes(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> async_graphql::Result<i32> {
+        let settings = ctx.data::<Settings>()?;
+        let minutes = self
+            .body_stats()
+            .reading_minutes(settings.reading_words_per_minute, settings.reading_chars_per_minute);
+        Ok(minutes as i32)
+    }
+
+    /// 検索エンジン向けの説明（meta description）。未設定の場合は本文の抜粋（160文字以下）
+    async fn seo_description(&self) -> String {
+        match &self.seo_description {
+            Some(description) => description.clone(),
+            None => excerpt(&self.body, MAX_SEO_DESCRIPTION_LENGTH),
+        }
+    }
+
+    /// 著者が削除済みの場合は「退会したユーザー」を返す
+    async fn author(&self, ctx: &async_graphql::Context<'_>) -> async_graphql::Result<User> {
+        find_author(ctx, &self.author_id).await
+    }
+
+    /// トップレベルのコメント（デフォルトは古い順）。返信は `replies` で取得する
+    /// 非表示のコメントは `includeHidden: true` の場合のみ含める（管理者のみ）
+    #[graphql(complexity = "list_complexity(limit, child_complexity)")]
+    async fn comments(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        #[graphql(default)] order: CommentOrder,
+        #[graphql(default)] include_hidden: bool,
+        #[graphql(default_with = "DEFAULT_PAGE_SIZE")] limit: i32,
+        #[graphql(default = 0)] offset: i32,
+    ) -> async_graphql::Result<Vec<Comment>> {
+        ensure_can_view_hidden(ctx, include_hidden).await?;
+        let comment_store = ctx.data::<CommentStore>()?;
+        let comments = comment_store.lock_or_recover();
+        let mut comments: Vec<Comment> = comments
+            .iter()
+            .filter(|c| c.post_id == self.id && c.parent_comment_id.is_none())
+            .filter(|c| c.is_visible(include_hidden))
+            .cloned()
+            .collect();
+        match order {
+            CommentOrder::OldestFirst => comments.sort_by_key(|c| c.created_at.0),
+            CommentOrder::NewestFirst => {
+                comments.sort_by_key(|c| std::cmp::Reverse(c.created_at.0))
+            }
+        }
+        Ok(paginate(comments, limit, offset))
+    }
+
+    /// リアクションの種類ごとの件数
+    async fn reactions(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        viewer_id: Option<ID>,
+    ) -> async_graphql::Result<Vec<ReactionCount>> {
+        let reaction_store = ctx.data::<ReactionStore>()?;
+        let reactions = reaction_store.lock_or_recover();
+        Ok(Reaction::ALL
+            .into_iter()
+            .map(|reaction| ReactionCount {
+                reaction,
+                count: reactions
+                    .iter()
+                    .filter(|(post_id, _, r)| post_id == &self.id && *r == reaction)
+                    .count(),
+                reacted_by_viewer: viewer_id.as_ref().is_some_and(|viewer_id| {
+                    reactions.contains(&(self.id.clone(), viewer_id.clone(), reaction))
+                }),
+            })
+            .collect())
+    }
+
+    async fn view_count(&self, ctx: &async_graphql::Context<'_>) -> async_graphql::Result<u64> {
+        Ok(view_count(ctx.data::<ViewStore>()?, &self.id))
+    }
+
+    async fn like_count(&self, ctx: &async_graphql::Context<'_>) -> async_graphql::Result<usize> {
+        let loader = ctx.data::<DataLoader<LikeCountLoader>>()?;
+        let count = loader.load_one(self.id.clone()).await.ok().flatten();
+        Ok(count.unwrap_or(0))
+    }
+
+    /// viewerIdのユーザーがいいねしているか
+    async fn liked_by_viewer(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        viewer_id: ID,
+    ) -> async_graphql::Result<bool> {
+        let like_store = ctx.data::<LikeStore>()?;
+        let likes = like_store.lock_or_recover();
+        Ok(likes.contains(&(self.id.clone(), viewer_id)))
+    }
+
+    /// 返信を含むコメント数（削除済みのコメントは含まない）
+    /// 非表示のコメントは `includeHidden: true` の場合のみ含める（管理者のみ）
+    async fn comment_count(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        #[graphql(default)] include_hidden: bool,
+    ) -> async_graphql::Result<usize> {
+        ensure_can_view_hidden(ctx, include_hidden).await?;
+        let loader = ctx.data::<DataLoader<CommentCountLoader>>()?;
+        let counts = loader.load_one(self.id.clone()).await.ok().flatten();
+        let counts = counts.unwrap_or_default();
+        if include_hidden {
+            Ok(counts.visible + counts.hidden)
+        } else {
+            Ok(counts.visible)
+        }
+    }
+}
+
+#[derive(Clone, SimpleObject, Serialize, Deserialize)]
+#[graphql(complex)]
+pub(crate) struct Comment {
+    pub(crate) id: ID,
+    pub(crate) post_id: ID,
+    // 返信先のコメント（トップレベルならNone）
+    pub(crate) parent_comment_id: Option<ID>,
+    #[graphql(skip)]
+    pub(crate) author_id: ID,
+    pub(crate) body: String,
+    pub(crate) created_at: DateTimeScalar,
+    // モデレーションで非表示にされたコメント
+    pub(crate) hidden: bool,
+    // 返信が残っているため本文だけ消したコメント
+    pub(crate) deleted: bool,
+    // 本文で@メンションしたユーザー
+    #[graphql(skip)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) mentioned_user_ids: Vec<ID>,
+}
+
+impl Comment {
+    fn is_visible(&self, include_hidden: bool) -> bool {
+        include_hidden || !self.hidden
+    }
+}
+
+// 非表示のコメントを含められるのは管理者だけ（未ログインはUNAUTHENTICATED、それ以外はFORBIDDEN）
+async fn ensure_can_view_hidden(
+    ctx: &async_graphql::Context<'_>,
+    include_hidden: bool,
+) -> async_graphql::Result<()> {
+    if include_hidden {
+        RoleGuard::new(ADMIN_ROLES).check(ctx).await?;
+    }
+    Ok(())
+}
+
+#[ComplexObject]
+impl Comment {
+    /// 著者が削除済みの場合は「退会したユーザー」を返す
+    async fn author(&self, ctx: &async_graphql::Context<'_>) -> async_graphql::Result<User> {
+        find_author(ctx, &self.author_id).await
+    }
+
+    /// 本文で@メンションしたユーザー（出てきた順。削除されたユーザーは含めない）
+    async fn mentions(&self, ctx: &async_graphql::Context<'_>) -> async_graphql::Result<Vec<User>> {
+        mentioned_users(ctx, &self.mentioned_user_ids).await
+    }
+
+    /// このコメントへの返信（古い順）
+    /// 非表示のコメントは `includeHidden: true` の場合のみ含める（管理者のみ）
+    #[graphql(complexity = "list_complexity(DEFAULT_PAGE_SIZE, child_complexity)")]
+    async fn replies(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        #[graphql(default)] include_hidden: bool,
+    ) -> async_graphql::Result<Vec<Comment>> {
+        ensure_can_view_hidden(ctx, include_hidden).await?;
+        let comment_store = ctx.data::<CommentStore>()?;
+        let comments = comment_store.lock_or_recover();
+        let mut replies: Vec<Comment> = comments
+            .iter()
+            .filter(|c| c.parent_comment_id.as_ref() == Some(&self.id))
+            .filter(|c| c.is_visible(include_hidden))
+            .cloned()
+            .collect();
+        replies.sort_by_key(|c| c.created_at.0);
+        Ok(replies)
+    }
+
+    /// 非表示のコメントは `includeHidden: true` の場合のみ含める（管理者のみ）
+    async fn reply_count(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        #[graphql(default)] include_hidden: bool,
+    ) -> async_graphql::Result<usize> {
+        ensure_can_view_hidden(ctx, include_hidden).await?;
+        let comment_store = ctx.data::<CommentStore>()?;
+        let comments = comment_store.lock_or_recover();
+        Ok(comments
+            .iter()
+            .filter(|c| c.parent_comment_id.as_ref() == Some(&self.id))
+            .filter(|c| c.is_visible(include_hidden) && !c.deleted)
+            .count())
+    }
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq, Default)]
+enum CommentOrder {
+    #[default]
+    OldestFirst,
+    NewestFirst,
+}
+
+// コメントのネストの深さ（トップレベルは0）
+pub(crate) fn comment_depth(comments: &[Comment], comment: &Comment) -> usize {
+    let mut depth = 0;
+    let mut parent_id = comment.parent_comment_id.as_ref();
+    while let Some(id) = parent_id {
+        depth += 1;
+        parent_id = comments
+            .iter()
+            .find(|c| &c.id == id)
+            .and_then(|c| c.parent_comment_id.as_ref());
+    }
+    depth
+}
+
+// 公開中の投稿とユーザーの存在を確認する
+pub(crate) async fn find_post_and_user(
+    ctx: &async_graphql::Context<'_>,
+    post_id: &ID,
+    user_id: &ID,
+) -> async_graphql::Result<Post> {
+    let storage = ctx.data::<AppStorage>()?;
+    if storage.get_user(user_id).await?.is_none() {
+        return Err(not_found("User"));
+    }
+    storage
+        .get_post(post_id)
+        .await?
+        .filter(|p| p.is_visible(false))
+        .ok_or_else(|| not_found("Post"))
+}
+
+async fn find_author(
+    ctx: &async_graphql::Context<'_>,
+    author_id: &ID,
+) -> async_graphql::Result<User> {
+    let loader = ctx.data::<DataLoader<UserLoader>>()?;
+    let user = loader.load_one(author_id.clone()).await?;
+    Ok(user.unwrap_or_else(|| deleted_user(author_id)))
+}
+
+#[ComplexObject]
+impl User {
+    /// アバターのURL。未設定ならメールアドレスのGravatar、それもなければIDから作るアイコン
+    /// sizeを指定すると、その幅と高さ（ピクセル）以下の画像のURLを返す
+    async fn avatar_url(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        size: Option<i32>,
+    ) -> async_graphql::Result<UrlScalar> {
+        if size.is_some_and(|size| !(1..=MAX_RESIZE_DIMENSION as i32).contains(&size)) {
+            return Err(AppError::ValidationFailed(format!(
+                "size must be between 1 and {}",
+                MAX_RESIZE_DIMENSION
+            ))
+            .into());
+        }
+        let settings = ctx.data::<Settings>()?;
+        Ok(avatar::avatar_url(self, settings, size.map(|size| size as u32)))
+    }
+
+    /// メールアドレスのMD5（Gravatar用）。メールアドレスそのものは公開しない
+    async fn email_hash(&self) -> Option<String> {
+        self.email.as_deref().map(gravatar_hash)
+    }
+
+    /// 自己紹介のMarkdownを描画したHTML（投稿の本文と同じく生のHTMLやscriptは取り除く）
+    async fn bio_html(&self) -> Option<String> {
+        self.bio.as_deref().map(|bio| render_markdown(bio, &MentionLinks::new()).html)
+    }
+
+    /// このユーザーのAPIキー（本人のみ参照可能）
+    async fn api_keys(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> async_graphql::Result<Vec<ApiKey>> {
+        match ctx.data_opt::<Viewer>() {
+            Some(viewer) if viewer.user_id == self.id => {}
+            _ => return Err(AppError::Forbidden("Not allowed to view API keys".into()).into()),
+        }
+        let api_key_store = ctx.data::<ApiKeyStore>()?;
+        let keys = api_key_store.lock_or_recover();
+        Ok(keys.iter().filter(|k| k.user_id == self.id).cloned().collect())
+    }
+
+    /// 未読の通知の数（本人のみ参照可能）
+    async fn unread_notification_count(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> async_graphql::Result<usize> {
+        ensure_recipient(ctx, &self.id)?;
+        let notification_store = ctx.data::<NotificationStore>()?;
+        let storage = ctx.data::<AppStorage>()?;
+        Ok(user_notifications(notification_store, storage, &self.id, true).await?.len())
+    }
+
+    /// 通知の設定（本人のみ参照可能）
+    async fn notification_settings(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> async_graphql::Result<NotificationSettings> {
+        ensure_recipient(ctx, &self.id)?;
+        Ok(NotificationSettings::of(self))
+    }
+
+    /// このユーザーの投稿（新しい順）
+    #[graphql(complexity = "list_complexity(limit, child_complexity)")]
+    async fn posts(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        #[graphql(default_with = "DEFAULT_PAGE_SIZE")] limit: i32,
+        #[graphql(default = 0)] offset: i32,
+    ) -> async_graphql::Result<Vec<Post>> {
+        let loader = ctx.data::<DataLoader<PostsByAuthorLoader>>()?;
+        let posts = loader.load_one(self.id.clone()).await?;
+        Ok(paginate(posts.unwrap_or_default(), limit, offset))
+    }
+
+    async fn follower_count(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> async_graphql::Result<usize> {
+        let follow_store = ctx.data::<FollowStore>()?;
+        let follows = follow_store.lock_or_recover();
+        Ok(follows.iter().filter(|(_, followee)| followee == &self.id).count())
+    }
+
+    async fn following_count(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> async_graphql::Result<usize> {
+        let follow_store = ctx.data::<FollowStore>()?;
+        let follows = follow_store.lock_or_recover();
+        Ok(follows.iter().filter(|(follower, _)| follower == &self.id).count())
+    }
+
+    async fn post_count(&self, ctx: &async_graphql::Context<'_>) -> async_graphql::Result<usize> {
+        let loader = ctx.data::<DataLoader<PostsByAuthorLoader>>()?;
+        let posts = loader.load_one(self.id.clone()).await?;
+        Ok(posts.map(|posts| posts.len()).unwrap_or(0))
+    }
+}
+
+/// ユーザーの権限
+#[derive(Enum, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum Role {
+    /// 全ての操作ができる管理者
+    Admin,
+    /// 投稿できるユーザー
+    Author,
+    /// 閲覧のみのユーザー
+    Reader,
+}
+
+pub(crate) fn deleted_user(id: &ID) -> User {
+    User {
+        id: id.clone(),
+        name: "退会したユーザー".to_string(),
+        handle: String::new(),
+        avatar_url: None,
+        email: None,
+        email_on_comment: false,
+        bio: None,
+        website: None,
+        location: None,
+        role: Role::Reader,
+        password_hash: None,
+    }
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Reaction {
+    ThumbsUp,
+    Heart,
+    Laugh,
+    Eyes,
+}
+
+impl Reaction {
+    const ALL: [Reaction; 4] = [
+        Reaction::ThumbsUp,
+        Reaction::Heart,
+        Reaction::Laugh,
+        Reaction::Eyes,
+    ];
+}
+
+#[derive(SimpleObject)]
+struct ReactionCount {
+    reaction: Reaction,
+    count: usize,
+    // viewerIdを指定した場合、そのユーザーがリアクション済みか
+    reacted_by_viewer: bool,
+}
+
+#[derive(SimpleObject)]
+pub(crate) struct Bookmark {
+    pub(crate) post: Post,
+    pub(crate) bookmarked_at: DateTimeScalar,
+}
+
+/// APIキー（キー本体は作成時に一度だけ返す）
+#[derive(Clone, SimpleObject)]
+pub(crate) struct ApiKey {
+    pub(crate) id: ID,
+    pub(crate) label: String,
+    pub(crate) created_at: DateTimeScalar,
+    pub(crate) last_used_at: Option<DateTimeScalar>,
+    #[graphql(skip)]
+    pub(crate) user_id: ID,
+    // キーのSHA-256ハッシュ
+    #[graphql(skip)]
+    pub(crate) key_hash: String,
+}
+
+#[derive(SimpleObject)]
+pub(crate) struct CreatedApiKey {
+    /// APIキー本体。再表示はできないので控えておくこと
+    pub(crate) key: String,
+    pub(crate) api_key: ApiKey,
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum NotificationKind {
+    // 自分の投稿へのコメント
+    Comment,
+    // 自分のコメントへの返信
+    Reply,
+    // 投稿やコメントでの@メンション
+    Mention,
+}
+
+/// アプリ内の通知（本人のみ参照可能）
+#[derive(Clone, SimpleObject)]
+#[graphql(complex)]
+pub(crate) struct Notification {
+    pub(crate) id: ID,
+    #[graphql(skip)]
+    pub(crate) recipient_id: ID,
+    // 通知のきっかけになったユーザー（コメントやメンションを書いたユーザー）
+    #[graphql(skip)]
+    pub(crate) actor_id: ID,
+    pub(crate) kind: NotificationKind,
+    #[graphql(skip)]
+    pub(crate) post_id: ID,
+    // 投稿の本文でのメンションならNone
+    #[graphql(skip)]
+    pub(crate) comment_id: Option<ID>,
+    pub(crate) created_at: DateTimeScalar,
+    pub(crate) read: bool,
+}
+
+#[ComplexObject]
+impl Notification {
+    async fn recipient(&self, ctx: &async_graphql::Context<'_>) -> async_graphql::Result<User> {
+        find_author(ctx, &self.recipient_id).await
+    }
+
+    /// 削除済みの場合は「退会したユーザー」を返す
+    async fn actor(&self, ctx: &async_graphql::Context<'_>) -> async_graphql::Result<User> {
+        find_author(ctx, &self.actor_id).await
+    }
+
+    async fn post(&self, ctx: &async_graphql::Context<'_>) -> async_graphql::Result<Post> {
+        let storage = ctx.data::<AppStorage>()?;
+        storage.get_post(&self.post_id).await?.ok_or_else(|| not_found("Post"))
+    }
+
+    /// コメントへの通知ならそのコメント（投稿の本文でのメンションはnull）
+    async fn comment(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> async_graphql::Result<Option<Comment>> {
+        let Some(comment_id) = &self.comment_id else {
+            return Ok(None);
+        };
+        let comment_store = ctx.data::<CommentStore>()?;
+        let comments = comment_store.lock_or_recover();
+        Ok(comments.iter().find(|c| &c.id == comment_id).cloned())
+    }
+}
+
+/// 通知の設定
+#[derive(SimpleObject)]
+pub(crate) struct NotificationSettings {
+    /// 自分の投稿にコメントされたらメールで知らせる（メールアドレスが必要。デフォルトはfalse）
+    pub(crate) email_on_comment: bool,
+}
+
+impl NotificationSettings {
+    pub(crate) fn of(user: &User) -> Self {
+        NotificationSettings {
+            email_on_comment: user.email_on_comment,
+        }
+    }
+}
+
+#[derive(InputObject)]
+pub(crate) struct UpdateNotificationSettingsInput {
+    pub(crate) user_id: ID,
+    // 未指定なら変更しない
+    pub(crate) email_on_comment: Option<bool>,
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum WebhookEvent {
+    PostCreated,
+    PostUpdated,
+    PostDeleted,
+    CommentAdded,
+}
+
+/// 登録したWebhook（管理者のみ参照可能。署名の鍵は返さない）
+#[derive(Clone, SimpleObject)]
+pub(crate) struct Webhook {
+    pub(crate) id: ID,
+    pub(crate) url: UrlScalar,
+    pub(crate) events: Vec<WebhookEvent>,
+    // X-Blog-Signatureの計算に使う鍵
+    #[graphql(skip)]
+    pub(crate) secret: String,
+    pub(crate) created_at: DateTimeScalar,
+    /// 最後の送信の結果（まだ送っていなければnull）
+    pub(crate) last_delivery: Option<WebhookDelivery>,
+}
+
+/// Webhookの送信の結果（再試行した場合は最後の試行の結果）
+#[derive(Clone, SimpleObject)]
+pub(crate) struct WebhookDelivery {
+    pub(crate) event: WebhookEvent,
+    pub(crate) succeeded: bool,
+    /// レスポンスのステータスコード（接続できなかった場合などはnull）
+    pub(crate) status_code: Option<i32>,
+    pub(crate) error: Option<String>,
+    /// 最初の送信を含む試行の回数
+    pub(crate) attempts: i32,
+    pub(crate) delivered_at: DateTimeScalar,
+}
+
+/// お問い合わせフォームから送られたメッセージ（管理者のみ参照可能）
+#[derive(Clone, SimpleObject)]
+pub(crate) struct ContactMessage {
+    pub(crate) id: ID,
+    pub(crate) name: String,
+    pub(crate) email: String,
+    pub(crate) message: String,
+    /// 管理者が既読にしたか
+    pub(crate) read: bool,
+    pub(crate) created_at: DateTimeScalar,
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum NewsletterSubscriberStatus {
+    /// 確認のメールを送り、リンクが開かれるのを待っている
+    Pending,
+    Confirmed,
+}
+
+/// ニュースレターの購読者（管理者のみ参照可能）
+#[derive(Clone, SimpleObject)]
+pub(crate) struct NewsletterSubscriber {
+    pub(crate) id: ID,
+    pub(crate) email: String,
+    pub(crate) status: NewsletterSubscriberStatus,
+    pub(crate) created_at: DateTimeScalar,
+    /// 確認のリンクが開かれた日時（確認前はnull）
+    pub(crate) confirmed_at: Option<DateTimeScalar>,
+    // 確認のメールを最後に送った日時（再送の間隔とリンクの期限に使う）
+    #[graphql(skip)]
+    pub(crate) confirmation_sent_at: DateTime<Utc>,
+    // 購読をやめるリンクに含めるトークン
+    #[graphql(skip)]
+    pub(crate) unsubscribe_token: String,
+}
+
+/// ニュースレターの購読者の一覧と状態ごとの数
+#[derive(SimpleObject)]
+pub(crate) struct NewsletterSubscribers {
+    pub(crate) subscribers: Vec<NewsletterSubscriber>,
+    pub(crate) pending_count: i32,
+    pub(crate) confirmed_count: i32,
+}
+
+#[derive(SimpleObject)]
+pub(crate) struct TagCount {
+    pub(crate) name: String,
+    pub(crate) count: i32,
+}
+
+#[derive(InputObject)]
+pub(crate) struct CreatePostInput {
+    pub(crate) title: String,
+    pub(crate) body: String,
+    pub(crate) tags: Option<Vec<String>>,
+    pub(crate) cover_image_url: Option<UrlScalar>,
+    pub(crate) cover_image_alt: Option<String>,
+    // 160文字以下
+    pub(crate) seo_description: Option<String>,
+    pub(crate) canonical_url: Option<UrlScalar>,
+    // 未指定ならログイン中のユーザー（他のユーザーを指定できるのは管理者のみ）
+    pub(crate) author_id: Option<ID>,
+    // trueの場合は下書きとして作成する
+    pub(crate) draft: Option<bool>,
+    // 指定した日時に自動で公開する
+    pub(crate) scheduled_at: Option<DateTimeScalar>,
+    // 未指定ならタイトルから生成する
+    pub(crate) slug: Option<String>,
+}
+
+#[derive(InputObject)]
+pub(crate) struct AddCommentInput {
+    pub(crate) post_id: ID,
+    // 未指定ならログイン中のユーザー（他のユーザーを指定できるのは管理者のみ）
+    pub(crate) author_id: Option<ID>,
+    pub(crate) body: String,
+    // 返信先のコメント（同じ投稿のコメントに限る）
+    pub(crate) parent_comment_id: Option<ID>,
+}
+
+#[derive(InputObject)]
+pub(crate) struct CreateUserInput {
+    pub(crate) name: String,
+    pub(crate) handle: String,
+    pub(crate) avatar_url: Option<UrlScalar>,
+    pub(crate) email: Option<String>,
+    // 省略時はAUTHOR
+    pub(crate) role: Option<Role>,
+}
+
+#[derive(InputObject)]
+pub(crate) struct UpdateUserInput {
+    pub(crate) id: ID,
+    pub(crate) name: Option<String>,
+    pub(crate) avatar_url: MaybeUndefined<UrlScalar>,
+    // 未指定なら変更しない、nullなら削除する
+    pub(crate) email: MaybeUndefined<String>,
+    pub(crate) bio: MaybeUndefined<String>,
+    pub(crate) website: MaybeUndefined<UrlScalar>,
+    pub(crate) location: MaybeUndefined<String>,
+}
+
+#[derive(InputObject)]
+pub(crate) struct UpdatePostInput {
+    pub(crate) id: ID,
+    pub(crate) title: Option<String>,
+    pub(crate) body: Option<String>,
+    // 未指定なら変更しない、nullなら空にする
+    pub(crate) tags: MaybeUndefined<Vec<String>>,
+    // 未指定なら変更しない、nullなら削除する
+    pub(crate) cover_image_url: MaybeUndefined<UrlScalar>,
+    pub(crate) cover_image_alt: MaybeUndefined<String>,
+    pub(crate) seo_description: MaybeUndefined<String>,
+    pub(crate) canonical_url: MaybeUndefined<UrlScalar>,
+    // nullなら予約を取り消す
+    pub(crate) scheduled_at: MaybeUndefined<DateTimeScalar>,
+    // 指定した場合、保存されているupdatedAtと異なればCONFLICTエラーにする
+    pub(crate) expected_updated_at: Option<DateTimeScalar>,
+}
@@ -0,0 +1,1365 @@
+use async_graphql::{
+    ErrorExtensions, Guard, Json, MaybeUndefined, Object, ScalarType, Upload, ID,
+};
+use chrono::Utc;
+use uuid::Uuid;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::instrument;
+
+use crate::auth::{
+    ADMIN_ROLES, AUTHOR_ROLES, AuthPayload, RefreshTokenStore, RoleGuard, Session, SessionCookie,
+    SessionStore, USER_ROLES, current_user, hash_password, hash_token, issue_session,
+    session_cookie, verify_password,
+};
+use crate::backup::{self, ImportMode, ImportResult};
+use crate::error::{not_found, AppError};
+use crate::mail::{forward_contact_message, notify_comment_by_email, Mailer};
+use crate::mention::resolve_mentions;
+use crate::newsletter::{self, NewsletterKey};
+use crate::notification::{ensure_recipient, notify_comment, notify_post_mentions};
+use crate::models::{
+    AddCommentInput, ApiKey, Bookmark, Comment, ContactMessage, CreatePostInput, CreateUserInput,
+    CreatedApiKey, Notification, NotificationSettings, Post, PostStatus, Reaction, Role,
+    UpdateNotificationSettingsInput, UpdatePostInput, UpdateUserInput, User, Webhook, WebhookEvent,
+    comment_depth, find_post_and_user,
+};
+use crate::scalars::{DateTimeScalar, UrlScalar};
+use crate::settings::Settings;
+use crate::store::{
+    ApiKeyStore, AppStorage, BookmarkEntry, BookmarkStore, CommentStore, ContactMessageStore,
+    FollowStore, LikeStore, LockExt, NewsletterStore, NotificationStore, ReactionStore, ViewStore,
+    WebhookStore, remove_post_data,
+};
+use crate::subscription::{BlogEvent, EventBus};
+use crate::upload::{store_image, ImagePurpose};
+use crate::validation::{
+    handle_taken, slugify, unique_slug, validate_comment_body, validate_contact_fields,
+    validate_email, validate_handle, validate_password, normalize_tags, parse_id,
+    validate_post_fields, validate_profile_fields, validate_slug, validate_user_name,
+    ContactFields, PostFields, ProfileFields,
+};
+
+// GraphQL Mutation
+pub struct Mutation;
+
+#[Object]
+impl Mutation {
+    /// パスワード付きでユーザーを登録する
+    /// handleは英小文字・数字・_の3〜30文字（大文字は小文字にする）で、使われていればCONFLICT
+    #[instrument(level = "debug", skip_all)]
+    async fn register(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        name: String,
+        handle: String,
+        password: String,
+        avatar_url: Option<UrlScalar>,
+        email: Option<String>,
+    ) -> async_graphql::Result<User> {
+        let storage = ctx.data::<AppStorage>()?;
+        let settings = ctx.data::<Settings>()?;
+
+        // 入力チェック
+        let name = validate_user_name(&name)?;
+        let handle = validate_handle(&handle)?;
+        let email = email.as_deref().map(validate_email).transpose()?;
+        validate_password(&password, &name, settings.min_password_length)?;
+        if !storage.get_users_by_name(&name).await?.is_empty() {
+            return Err(AppError::Conflict(format!("Name is already taken: {}", name)).into());
+        }
+        if storage.get_user_by_handle(&handle).await?.is_some() {
+            return Err(handle_taken(&handle));
+        }
+
+        // ハッシュ化は重いのでブロッキングスレッドで行う
+        let password_hash = tokio::task::spawn_blocking(move || hash_password(&password))
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))?
+            .map_err(|e| AppError::Internal(format!("Failed to hash password: {}", e)))?;
+
+        let user = User {
+            id: ID::from(Uuid::new_v4().to_string()),
+            name: name.clone(),
+            handle,
+            avatar_url,
+            email,
+            email_on_comment: false,
+            bio: None,
+            website: None,
+            location: None,
+            role: Role::Reader,
+            password_hash: Some(password_hash),
+        };
+
+        // ハッシュ化の間に同名のユーザーが登録されていないか確認し直す
+        if !storage.get_users_by_name(&name).await?.is_empty() {
+            return Err(AppError::Conflict(format!("Name is already taken: {}", name)).into());
+        }
+        storage.insert_user(user.clone()).await?;
+        Ok(user)
+    }
+
+    /// 名前とパスワードでログインし、アクセストークンを発行する
+    #[instrument(level = "debug", skip_all)]
+    async fn login(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        name: String,
+        password: String,
+        // trueならセッションCookieも発行する
+        #[graphql(default = false)] use_cookie: bool,
+    ) -> async_graphql::Result<AuthPayload> {
+        let storage = ctx.data::<AppStorage>()?;
+        let candidates: Vec<User> = storage
+            .get_users_by_name(name.trim())
+            .await?
+            .into_iter()
+            .filter(|u| u.password_hash.is_some())
+            .collect();
+
+        let user = candidates
+            .into_iter()
+            .find(|u| verify_password(&password, u.password_hash.as_deref().unwrap_or_default()))
+            .ok_or_else(|| AppError::Unauthenticated("Invalid name or password".into()))?;
+
+        if use_cookie {
+            let settings = ctx.data::<Settings>()?;
+            let session_id = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+            let session_store = ctx.data::<SessionStore>()?;
+            session_store.lock_or_recover().insert(
+                hash_token(&session_id),
+                Session {
+                    user_id: user.id.clone(),
+                    expires_at: Utc::now() + settings.session_expiry,
+                },
+            );
+            let cookie = session_cookie(
+                session_id,
+                settings.session_expiry,
+                settings.session_cookie_same_site,
+            );
+            ctx.append_http_header("Set-Cookie", cookie.to_string());
+        }
+
+        issue_session(ctx, user, None)
+    }
+
+    /// リフレッシュトークンでアクセストークンを再発行する（リフレッシュトークンも入れ替わる）
+    #[instrument(level = "debug", skip_all)]
+    async fn refresh_session(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        refresh_token: String,
+    ) -> async_graphql::Result<AuthPayload> {
+        let refresh_token_store = ctx.data::<RefreshTokenStore>()?;
+        let (user_id, family_id) = {
+            let mut tokens = refresh_token_store.lock_or_recover();
+            let token_hash = hash_token(&refresh_token);
+            let token = tokens
+                .get_mut(&token_hash)
+                .ok_or_else(|| AppError::Unauthenticated("Invalid refresh token".into()))?;
+            if token.used {
+                // 使用済みトークンの再利用は漏洩とみなし、セッションごと無効にする
+                let family_id = token.family_id.clone();
+                tokens.retain(|_, t| t.family_id != family_id);
+                let message = "Refresh token has already been used";
+                return Err(AppError::Unauthenticated(message.into()).into());
+            }
+            if token.expires_at <= Utc::now() {
+                tokens.remove(&token_hash);
+                return Err(AppError::Unauthenticated("Refresh token has expired".into()).into());
+            }
+            token.used = true;
+            (token.user_id.clone(), token.family_id.clone())
+        };
+
+        let storage = ctx.data::<AppStorage>()?;
+        let user = storage
+            .get_user(&user_id)
+            .await?
+            .ok_or_else(|| AppError::Unauthenticated("User no longer exists".into()))?;
+        issue_session(ctx, user, Some(family_id))
+    }
+
+    /// ログアウトする（リフレッシュトークンのセッションとセッションCookieを無効にする）
+    #[instrument(level = "debug", skip_all)]
+    async fn logout(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        refresh_token: Option<String>,
+    ) -> async_graphql::Result<bool> {
+        let mut logged_out = false;
+
+        if let Some(refresh_token) = refresh_token {
+            let refresh_token_store = ctx.data::<RefreshTokenStore>()?;
+            let mut tokens = refresh_token_store.lock_or_recover();
+            if let Some(token) = tokens.get(&hash_token(&refresh_token)) {
+                let family_id = token.family_id.clone();
+                tokens.retain(|_, t| t.family_id != family_id);
+                logged_out = true;
+            }
+        }
+
+        if let Some(SessionCookie(session_id)) = ctx.data_opt::<SessionCookie>() {
+            let session_store = ctx.data::<SessionStore>()?;
+            logged_out |= session_store
+                .lock_or_recover()
+                .remove(&hash_token(session_id))
+                .is_some();
+            let settings = ctx.data::<Settings>()?;
+            let cookie = session_cookie(
+                String::new(),
+                chrono::Duration::zero(),
+                settings.session_cookie_same_site,
+            );
+            ctx.append_http_header("Set-Cookie", cookie.to_string());
+        }
+        Ok(logged_out)
+    }
+
+    /// ログイン中のユーザーのAPIキーを発行する
+    #[instrument(level = "debug", skip_all)]
+    async fn create_api_key(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        label: String,
+    ) -> async_graphql::Result<CreatedApiKey> {
+        let current_user = current_user(ctx).await?;
+        let label = label.trim();
+        if label.is_empty() {
+            return Err(AppError::ValidationFailed("Label must not be empty".into()).into());
+        }
+
+        let key = format!("blog_{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+        let api_key = ApiKey {
+            id: ID::from(Uuid::new_v4().to_string()),
+            label: label.to_string(),
+            created_at: DateTimeScalar(Utc::now()),
+            last_used_at: None,
+            user_id: current_user.id,
+            key_hash: hash_token(&key),
+        };
+
+        let api_key_store = ctx.data::<ApiKeyStore>()?;
+        api_key_store.lock_or_recover().push(api_key.clone());
+        Ok(CreatedApiKey { key, api_key })
+    }
+
+    /// 自分のAPIキーを失効させる
+    #[instrument(level = "debug", skip_all)]
+    async fn revoke_api_key(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        id: ID,
+    ) -> async_graphql::Result<bool> {
+        let current_user = current_user(ctx).await?;
+        let api_key_store = ctx.data::<ApiKeyStore>()?;
+        let mut keys = api_key_store.lock_or_recover();
+        let initial_len = keys.len();
+        keys.retain(|k| !(k.id == id && k.user_id == current_user.id));
+        Ok(keys.len() < initial_len)
+    }
+
+    /// Webhookを登録する（管理者のみ）。指定した種類のイベントが起きるとJSONをurlにPOSTし、
+    /// X-Blog-Signatureヘッダーに `sha256=<secretで計算した本文のHMAC-SHA256>` を付ける
+    #[graphql(guard = "RoleGuard::new(ADMIN_ROLES)")]
+    #[instrument(level = "debug", skip_all)]
+    async fn register_webhook(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        url: UrlScalar,
+        events: Vec<WebhookEvent>,
+        secret: String,
+    ) -> async_graphql::Result<Webhook> {
+        let mut unique_events: Vec<WebhookEvent> = Vec::new();
+        for event in events {
+            if !unique_events.contains(&event) {
+                unique_events.push(event);
+            }
+        }
+        if unique_events.is_empty() {
+            return Err(AppError::ValidationFailed("Events must not be empty".into()).into());
+        }
+        if secret.is_empty() {
+            return Err(AppError::ValidationFailed("Secret must not be empty".into()).into());
+        }
+
+        let webhook = Webhook {
+            id: ID::from(Uuid::new_v4().to_string()),
+            url,
+            events: unique_events,
+            secret,
+            created_at: DateTimeScalar(Utc::now()),
+            last_delivery: None,
+        };
+        let webhook_store = ctx.data::<WebhookStore>()?;
+        webhook_store.lock_or_recover().push(webhook.clone());
+        Ok(webhook)
+    }
+
+    /// Webhookを削除する（管理者のみ）。存在しない場合は `false`
+    #[graphql(guard = "RoleGuard::new(ADMIN_ROLES)")]
+    #[instrument(level = "debug", skip_all)]
+    async fn delete_webhook(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        id: ID,
+    ) -> async_graphql::Result<bool> {
+        let webhook_store = ctx.data::<WebhookStore>()?;
+        let mut webhooks = webhook_store.lock_or_recover();
+        let initial_len = webhooks.len();
+        webhooks.retain(|w| w.id != id);
+        Ok(webhooks.len() < initial_len)
+    }
+
+    /// ニュースレターを購読する（ログイン不要）。確認のメールを送り、リンクが開かれたら購読を確定する
+    /// 既に購読済み・確認待ちのアドレスでも同じ結果を返す（確認待ちなら確認のメールを送り直す）
+    #[instrument(level = "debug", skip_all)]
+    async fn subscribe_newsletter(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        email: String,
+    ) -> async_graphql::Result<bool> {
+        let mailer = ctx.data::<Mailer>()?;
+        if !mailer.is_enabled() {
+            return Err(AppError::Forbidden(
+                "Newsletter subscriptions are not available on this server".into(),
+            ).into());
+        }
+        let email = validate_email(&email)?;
+        let address = email.parse().map_err(|_| {
+            AppError::ValidationFailed(format!("Invalid email address: {}", email))
+        })?;
+        newsletter::subscribe(
+            ctx.data::<NewsletterStore>()?,
+            ctx.data::<NewsletterKey>()?,
+            mailer,
+            ctx.data::<Settings>()?,
+            address,
+        );
+        Ok(true)
+    }
+
+    /// お問い合わせフォームのメッセージを送る（ログイン不要）。メッセージは管理者だけが参照できる
+    /// nameは100文字以下、messageは10〜5000文字（前後の空白を除く）
+    /// websiteはボット対策の隠しフィールドで、フォームには表示しない。値が入っていれば保存せずに `true` を返す
+    #[instrument(level = "debug", skip_all)]
+    async fn submit_contact_message(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        name: String,
+        email: String,
+        message: String,
+        website: Option<String>,
+    ) -> async_graphql::Result<bool> {
+        if website.is_some_and(|website| !website.trim().is_empty()) {
+            tracing::debug!("discarding a contact message with the honeypot field filled");
+            return Ok(true);
+        }
+        let fields = validate_contact_fields(ContactFields { name, email, message })?;
+        let contact_message = ContactMessage {
+            id: ID::from(Uuid::new_v4().to_string()),
+            name: fields.name,
+            email: fields.email,
+            message: fields.message,
+            read: false,
+            created_at: DateTimeScalar(Utc::now()),
+        };
+        let contact_store = ctx.data::<ContactMessageStore>()?;
+        contact_store.lock_or_recover().push(contact_message.clone());
+        let mailer = ctx.data::<Mailer>()?;
+        let storage = ctx.data::<AppStorage>()?;
+        let settings = ctx.data::<Settings>()?;
+        forward_contact_message(mailer, storage, settings, &contact_message).await;
+        Ok(true)
+    }
+
+    /// お問い合わせのメッセージを既読にする（管理者のみ）
+    #[graphql(guard = "RoleGuard::new(ADMIN_ROLES)")]
+    #[instrument(level = "debug", skip_all)]
+    async fn mark_contact_read(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        id: ID,
+    ) -> async_graphql::Result<ContactMessage> {
+        let contact_store = ctx.data::<ContactMessageStore>()?;
+        let mut messages = contact_store.lock_or_recover();
+        let message = messages
+            .iter_mut()
+            .find(|m| m.id == id)
+            .ok_or_else(|| not_found("ContactMessage"))?;
+        message.read = true;
+        Ok(message.clone())
+    }
+
+    #[graphql(guard = "RoleGuard::new(AUTHOR_ROLES)")]
+    #[instrument(level = "debug", skip_all)]
+    async fn create_post(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        input: CreatePostInput,
+    ) -> async_graphql::Result<Post> {
+        let settings = ctx.data::<Settings>()?;
+        let storage = ctx.data::<AppStorage>()?;
+
+        let fields = PostFields {
+            title: Some(input.title),
+            body: Some(input.body),
+            tags: Some(input.tags.unwrap_or_default()),
+            cover_image_alt: input.cover_image_alt,
+            seo_description: input.seo_description,
+        };
+        let fields = validate_post_fields(fields, settings.max_post_length)?;
+        let title = fields.title.unwrap_or_default();
+        if let Some(slug) = &input.slug {
+            validate_slug(slug)?;
+        }
+
+        // ユーザーの存在確認
+        let author_id = input
+            .author_id
+            .map(|id| parse_id(id, "input.authorId"))
+            .transpose()?;
+        let author_id = current_user(ctx).await?.acting_as(author_id)?;
+        if storage.get_user(&author_id).await?.is_none() {
+            return Err(not_found("User"));
+        }
+
+        // 投稿を作成
+        let id = ID::from(Uuid::new_v4().to_string());
+        let mut post = Post {
+            slug: slugify(&title, &id),
+            id,
+            title,
+            author_id,
+            body: String::new(),
+            tags: fields.tags.unwrap_or_default(),
+            cover_image_url: input.cover_image_url,
+            cover_image_alt: fields.cover_image_alt.filter(|alt| !alt.is_empty()),
+            seo_description: fields.seo_description.filter(|d| !d.is_empty()),
+            canonical_url: input.canonical_url,
+            status: if input.draft.unwrap_or(false) {
+                PostStatus::Draft
+            } else {
+                PostStatus::Published
+            },
+            published_at: DateTimeScalar(Utc::now()),
+            scheduled_at: None,
+            updated_at: DateTimeScalar(Utc::now()),
+            deleted_at: None,
+            revisions: Vec::new(),
+            text_stats: None,
+            mentioned_user_ids: Vec::new(),
+        };
+        post.set_body(fields.body.unwrap_or_default());
+        post.mentioned_user_ids = resolve_mentions(storage.as_ref(), &post.body).await?;
+        if let Some(scheduled_at) = input.scheduled_at {
+            post.schedule(scheduled_at, Utc::now());
+        }
+        post.updated_at = post.published_at;
+
+        // 指定されたスラッグが使用中ならinsert_postがCONFLICTを返す
+        post.slug = match input.slug {
+            Some(slug) => slug,
+            None => unique_slug(storage.as_ref(), &post.slug).await?,
+        };
+        let post = storage.insert_post(post).await?;
+        notify_post_mentions(ctx.data::<NotificationStore>()?, &post);
+
+        let view_store = ctx.data::<ViewStore>()?;
+        view_store.insert(post.id.clone(), AtomicU64::new(0));
+
+        // 購読者がいなくてもエラーにしない
+        if post.is_visible(false) {
+            let event_bus = ctx.data::<EventBus>()?;
+            let _ = event_bus.send(BlogEvent::PostCreated(post.clone()));
+        }
+        Ok(post)
+    }
+
+    #[graphql(guard = "RoleGuard::new(ADMIN_ROLES)")]
+    #[instrument(level = "debug", skip_all)]
+    async fn create_user(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        input: CreateUserInput,
+    ) -> async_graphql::Result<User> {
+        let storage = ctx.data::<AppStorage>()?;
+
+        // 入力チェック
+        let name = validate_user_name(&input.name)?;
+        let handle = validate_handle(&input.handle)?;
+        let email = input.email.as_deref().map(validate_email).transpose()?;
+        if storage.get_user_by_handle(&handle).await?.is_some() {
+            return Err(handle_taken(&handle));
+        }
+
+        let user = User {
+            id: ID::from(Uuid::new_v4().to_string()),
+            name,
+            handle,
+            avatar_url: input.avatar_url,
+            email,
+            email_on_comment: false,
+            bio: None,
+            website: None,
+            location: None,
+            role: input.role.unwrap_or(Role::Author),
+            password_hash: None,
+        };
+
+        storage.insert_user(user.clone()).await?;
+        Ok(user)
+    }
+
+    #[graphql(guard = "RoleGuard::new(ADMIN_ROLES)")]
+    #[instrument(level = "debug", skip_all)]
+    async fn update_user(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        input: UpdateUserInput,
+    ) -> async_graphql::Result<User> {
+        let storage = ctx.data::<AppStorage>()?;
+
+        let name = input.name.as_deref().map(validate_user_name).transpose()?;
+        let email = match input.email {
+            MaybeUndefined::Value(email) => MaybeUndefined::Value(validate_email(&email)?),
+            MaybeUndefined::Null => MaybeUndefined::Null,
+            MaybeUndefined::Undefined => MaybeUndefined::Undefined,
+        };
+        let profile = ProfileFields {
+            bio: input.bio.value().cloned(),
+            location: input.location.value().cloned(),
+        };
+        let profile = validate_profile_fields(profile)?;
+
+        let update = move |user: &mut User| -> async_graphql::Result<()> {
+            if let Some(name) = name {
+                user.name = name;
+            }
+            match input.avatar_url {
+                MaybeUndefined::Undefined => {}
+                MaybeUndefined::Null => user.avatar_url = None,
+                MaybeUndefined::Value(avatar_url) => user.avatar_url = Some(avatar_url),
+            }
+            match email {
+                MaybeUndefined::Undefined => {}
+                MaybeUndefined::Null => user.email = None,
+                MaybeUndefined::Value(email) => user.email = Some(email),
+            }
+            if !input.bio.is_undefined() {
+                user.bio = profile.bio.filter(|bio| !bio.is_empty());
+            }
+            match input.website {
+                MaybeUndefined::Undefined => {}
+                MaybeUndefined::Null => user.website = None,
+                MaybeUndefined::Value(website) => user.website = Some(website),
+            }
+            if !input.location.is_undefined() {
+                user.location = profile.location.filter(|location| !location.is_empty());
+            }
+            Ok(())
+        };
+        let user = storage.update_user(&input.id, Box::new(update)).await?;
+        user.ok_or_else(|| not_found("User"))
+    }
+
+    /// 画像をアップロードし、公開URLを返す（GraphQL multipart request）
+    /// URLはアバターや投稿のカバー画像にそのまま使える
+    #[instrument(level = "debug", skip_all)]
+    async fn upload_image(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        file: Upload,
+        purpose: ImagePurpose,
+    ) -> async_graphql::Result<UrlScalar> {
+        match purpose {
+            ImagePurpose::Avatar => {
+                current_user(ctx).await?;
+            }
+            ImagePurpose::PostImage => RoleGuard::new(AUTHOR_ROLES).check(ctx).await?,
+        }
+        let settings = ctx.data::<Settings>()?;
+        let upload = file
+            .value(ctx)
+            .map_err(|e| AppError::ValidationFailed(format!("Invalid upload: {}", e)))?;
+        store_image(upload, settings).await
+    }
+
+    /// ユーザーの権限を変更する
+    #[graphql(guard = "RoleGuard::new(ADMIN_ROLES)")]
+    #[instrument(level = "debug", skip_all)]
+    async fn set_user_role(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        id: ID,
+        role: Role,
+    ) -> async_graphql::Result<User> {
+        let storage = ctx.data::<AppStorage>()?;
+        let update = move |user: &mut User| -> async_graphql::Result<()> {
+            user.role = role;
+            Ok(())
+        };
+        let user = storage.update_user(&id, Box::new(update)).await?;
+        user.ok_or_else(|| not_found("User"))
+    }
+
+    /// ユーザーを削除する。
+    ///
+    /// デフォルトでは投稿（ゴミ箱を除く）が残っているユーザーは削除できず、
+    /// 該当する投稿IDを含むエラーを返す。
+    /// `cascade: true` を指定するとユーザーの投稿もまとめて削除する。
+    /// 存在しないIDの場合は `false` を返す。
+    #[graphql(guard = "RoleGuard::new(ADMIN_ROLES)")]
+    #[instrument(level = "debug", skip_all)]
+    async fn delete_user(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        id: ID,
+        #[graphql(default = false)] cascade: bool,
+    ) -> async_graphql::Result<bool> {
+        let storage = ctx.data::<AppStorage>()?;
+        let like_store = ctx.data::<LikeStore>()?;
+        let bookmark_store = ctx.data::<BookmarkStore>()?;
+        let reaction_store = ctx.data::<ReactionStore>()?;
+        let follow_store = ctx.data::<FollowStore>()?;
+        let api_key_store = ctx.data::<ApiKeyStore>()?;
+        let notification_store = ctx.data::<NotificationStore>()?;
+
+        if cascade {
+            // ユーザーだけが消えて投稿が残る、といった途中の状態を残さない
+            let Some(post_ids) = storage.delete_user_with_posts(&id).await? else {
+                return Ok(false);
+            };
+            remove_post_data(ctx, &post_ids.into_iter().collect())?;
+        } else {
+            if storage.get_user(&id).await?.is_none() {
+                return Ok(false);
+            }
+            // ゴミ箱の投稿は数えない
+            let blocking: Vec<ID> = storage
+                .list_all_posts()
+                .await?
+                .into_iter()
+                .filter(|p| p.author_id == id && !p.is_deleted())
+                .map(|p| p.id)
+                .collect();
+            if !blocking.is_empty() {
+                let ids: Vec<&str> = blocking.iter().map(|id| id.as_str()).collect();
+                let message = format!("User has posts: {}", ids.join(", "));
+                return Err(AppError::Conflict(message).into());
+            }
+            if storage.delete_user(&id).await?.is_none() {
+                return Ok(false);
+            }
+        }
+        like_store.lock_or_recover().retain(|(_, user_id)| user_id != &id);
+        bookmark_store.lock_or_recover().retain(|b| b.user_id != id);
+        reaction_store
+            .lock_or_recover()
+            .retain(|(_, user_id, _)| user_id != &id);
+        follow_store
+            .lock_or_recover()
+            .retain(|(follower, followee)| follower != &id && followee != &id);
+        api_key_store.lock_or_recover().retain(|k| k.user_id != id);
+        notification_store.lock_or_recover().retain(|n| n.recipient_id != id);
+        Ok(true)
+    }
+
+    #[graphql(guard = "RoleGuard::new(AUTHOR_ROLES)")]
+    #[instrument(level = "debug", skip_all)]
+    async fn update_post(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        input: UpdatePostInput,
+    ) -> async_graphql::Result<Post> {
+        let current_user = current_user(ctx).await?;
+        let settings = ctx.data::<Settings>()?;
+        let storage = ctx.data::<AppStorage>()?;
+        let fields = PostFields {
+            title: input.title,
+            body: input.body,
+            tags: input.tags.value().cloned(),
+            cover_image_alt: input.cover_image_alt.value().cloned(),
+            seo_description: input.seo_description.value().cloned(),
+        };
+        let fields = validate_post_fields(fields, settings.max_post_length)?;
+        let mentions = match &fields.body {
+            Some(body) => Some(resolve_mentions(storage.as_ref(), body).await?),
+            None => None,
+        };
+        let max_revisions = settings.max_revisions;
+        let update = move |post: &mut Post| -> async_graphql::Result<()> {
+            if post.is_deleted() {
+                return Err(not_found("Post"));
+            }
+            current_user.ensure_can_modify(post)?;
+
+            // 楽観的排他制御
+            if let Some(expected) = input.expected_updated_at {
+                if expected.0 != post.updated_at.0 {
+                    let updated_at = post.updated_at;
+                    let error: async_graphql::Error =
+                        AppError::Conflict("Post has been modified".into()).into();
+                    return Err(
+                        error.extend_with(|_, e| e.set("updatedAt", updated_at.to_value()))
+                    );
+                }
+            }
+
+            // 変更を始める前に確かめ、エラーのときに一部だけ更新された投稿を残さない
+            if input.scheduled_at.is_value() && post.is_published() {
+                return Err(AppError::Conflict("Post is already published".into()).into());
+            }
+
+            if fields.title.is_some() || fields.body.is_some() || !input.tags.is_undefined() {
+                post.save_revision(max_revisions);
+            }
+
+            // 指定されたフィールドのみ更新（published_atは変更しない）
+            if let Some(title) = fields.title {
+                post.title = title;
+            }
+            if let Some(body) = fields.body {
+                post.set_body(body);
+            }
+            if let Some(mentions) = mentions {
+                post.mentioned_user_ids = mentions;
+            }
+            match fields.tags {
+                Some(tags) => post.tags = tags,
+                None if input.tags.is_null() => post.tags.clear(),
+                None => {}
+            }
+            match input.cover_image_url {
+                MaybeUndefined::Undefined => {}
+                MaybeUndefined::Null => post.cover_image_url = None,
+                MaybeUndefined::Value(url) => post.cover_image_url = Some(url),
+            }
+            if !input.cover_image_alt.is_undefined() {
+                post.cover_image_alt = fields.cover_image_alt.filter(|alt| !alt.is_empty());
+            }
+            if !input.seo_description.is_undefined() {
+                post.seo_description = fields.seo_description.filter(|d| !d.is_empty());
+            }
+            match input.canonical_url {
+                MaybeUndefined::Undefined => {}
+                MaybeUndefined::Null => post.canonical_url = None,
+                MaybeUndefined::Value(url) => post.canonical_url = Some(url),
+            }
+            match input.scheduled_at {
+                MaybeUndefined::Undefined => {}
+                MaybeUndefined::Null => post.scheduled_at = None,
+                MaybeUndefined::Value(scheduled_at) => post.schedule(scheduled_at, Utc::now()),
+            }
+            post.updated_at = DateTimeScalar(Utc::now());
+            Ok(())
+        };
+        let post = storage.update_post(&input.id, Box::new(update)).await?;
+        let post = post.ok_or_else(|| not_found("Post"))?;
+        notify_post_mentions(ctx.data::<NotificationStore>()?, &post);
+        if post.is_visible(false) {
+            let event_bus = ctx.data::<EventBus>()?;
+            let _ = event_bus.send(BlogEvent::PostUpdated(post.clone()));
+        }
+        Ok(post)
+    }
+
+    /// 指定した履歴の内容に戻す（戻す前の内容も新しい履歴として残る）
+    #[graphql(guard = "RoleGuard::new(AUTHOR_ROLES)")]
+    #[instrument(level = "debug", skip_all)]
+    async fn revert_post(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        post_id: ID,
+        revision: i32,
+    ) -> async_graphql::Result<Post> {
+        let current_user = current_user(ctx).await?;
+        let settings = ctx.data::<Settings>()?;
+        let storage = ctx.data::<AppStorage>()?;
+        let max_revisions = settings.max_revisions;
+        // メンションは更新の外で解決する（戻す先の履歴がなければ更新の中でNOT_FOUNDにする）
+        let target_body = storage.get_post(&post_id).await?.and_then(|post| {
+            post.revisions.into_iter().find(|r| r.revision == revision).map(|r| r.body)
+        });
+        let mentions = match target_body {
+            Some(body) => resolve_mentions(storage.as_ref(), &body).await?,
+            None => Vec::new(),
+        };
+        let update = move |post: &mut Post| -> async_graphql::Result<()> {
+            if post.is_deleted() {
+                return Err(not_found("Post"));
+            }
+            current_user.ensure_can_modify(post)?;
+            let target = post
+                .revisions
+                .iter()
+                .find(|r| r.revision == revision)
+                .cloned()
+                .ok_or_else(|| AppError::NotFound("Revision not found".into()))?;
+
+            post.save_revision(max_revisions);
+            post.title = target.title;
+            post.set_body(target.body);
+            post.mentioned_user_ids = mentions;
+            post.tags = target.tags;
+            post.updated_at = DateTimeScalar(Utc::now());
+            Ok(())
+        };
+        let post = storage.update_post(&post_id, Box::new(update)).await?;
+        let post = post.ok_or_else(|| not_found("Post"))?;
+        notify_post_mentions(ctx.data::<NotificationStore>()?, &post);
+        if post.is_visible(false) {
+            let event_bus = ctx.data::<EventBus>()?;
+            let _ = event_bus.send(BlogEvent::PostUpdated(post.clone()));
+        }
+        Ok(post)
+    }
+
+    /// 下書きを公開する。公開済みの投稿はそのまま返す
+    #[graphql(guard = "RoleGuard::new(AUTHOR_ROLES)")]
+    #[instrument(level = "debug", skip_all)]
+    async fn publish_post(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        id: ID,
+    ) -> async_graphql::Result<Post> {
+        let current_user = current_user(ctx).await?;
+        let storage = ctx.data::<AppStorage>()?;
+        let mut published = false;
+        let update = |post: &mut Post| -> async_graphql::Result<()> {
+            if post.is_deleted() {
+                return Err(not_found("Post"));
+            }
+            current_user.ensure_can_modify(post)?;
+
+            if post.status == PostStatus::Draft {
+                post.status = PostStatus::Published;
+                post.published_at = DateTimeScalar(Utc::now());
+                post.scheduled_at = None;
+                post.updated_at = post.published_at;
+                published = true;
+            }
+            Ok(())
+        };
+        let post = storage.update_post(&id, Box::new(update)).await?;
+        let post = post.ok_or_else(|| not_found("Post"))?;
+        notify_post_mentions(ctx.data::<NotificationStore>()?, &post);
+        // 下書きから公開した場合は新しい投稿として配信する
+        if published {
+            let event_bus = ctx.data::<EventBus>()?;
+            let _ = event_bus.send(BlogEvent::PostCreated(post.clone()));
+        }
+        Ok(post)
+    }
+
+    /// 投稿をゴミ箱に移動する（restorePostで復元、purgePostで完全に削除）。
+    /// ゴミ箱にある場合は `false`、存在しない場合は `NOT_FOUND`
+    #[graphql(guard = "RoleGuard::new(AUTHOR_ROLES)")]
+    #[instrument(level = "debug", skip_all)]
+    async fn delete_post(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        id: ID,
+    ) -> async_graphql::Result<bool> {
+        let id = parse_id(id, "id")?;
+        let current_user = current_user(ctx).await?;
+        let storage = ctx.data::<AppStorage>()?;
+        let mut trashed = false;
+        let update = |post: &mut Post| -> async_graphql::Result<()> {
+            // ゴミ箱にある投稿は所有者に関わらずfalse
+            if post.is_deleted() {
+                return Ok(());
+            }
+            current_user.ensure_can_modify(post)?;
+            post.deleted_at = Some(DateTimeScalar(Utc::now()));
+            trashed = true;
+            Ok(())
+        };
+        let Some(post) = storage.update_post(&id, Box::new(update)).await? else {
+            return Err(not_found("Post"));
+        };
+        if trashed && post.is_published() {
+            let event_bus = ctx.data::<EventBus>()?;
+            let _ = event_bus.send(BlogEvent::PostDeleted(post));
+        }
+        Ok(trashed)
+    }
+
+    /// ゴミ箱の投稿を復元する。著者が削除済みの場合はエラー
+    #[graphql(guard = "RoleGuard::new(AUTHOR_ROLES)")]
+    #[instrument(level = "debug", skip_all)]
+    async fn restore_post(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        id: ID,
+    ) -> async_graphql::Result<Post> {
+        let current_user = current_user(ctx).await?;
+        let storage = ctx.data::<AppStorage>()?;
+        let not_in_trash = || AppError::NotFound("Post not found in trash".into());
+
+        let post = storage
+            .get_post(&id)
+            .await?
+            .filter(|p| p.is_deleted())
+            .ok_or_else(not_in_trash)?;
+        current_user.ensure_can_modify(&post)?;
+        if storage.get_user(&post.author_id).await?.is_none() {
+            return Err(AppError::Conflict(
+                "Cannot restore post: its author has been deleted".into(),
+            ).into());
+        }
+
+        let update = |post: &mut Post| -> async_graphql::Result<()> {
+            // 確認してから更新するまでの間に復元されていないか確認し直す
+            if !post.is_deleted() {
+                return Err(not_in_trash().into());
+            }
+            post.deleted_at = None;
+            post.updated_at = DateTimeScalar(Utc::now());
+            Ok(())
+        };
+        let post = storage.update_post(&id, Box::new(update)).await?;
+        let post = post.ok_or_else(not_in_trash)?;
+        if post.is_visible(false) {
+            let event_bus = ctx.data::<EventBus>()?;
+            let _ = event_bus.send(BlogEvent::PostUpdated(post.clone()));
+        }
+        Ok(post)
+    }
+
+    /// 投稿を完全に削除する
+    #[graphql(guard = "RoleGuard::new(ADMIN_ROLES)")]
+    #[instrument(level = "debug", skip_all)]
+    async fn purge_post(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        id: ID,
+    ) -> async_graphql::Result<bool> {
+        let storage = ctx.data::<AppStorage>()?;
+        let Some(post) = storage.delete_post(&id).await? else {
+            return Ok(false);
+        };
+
+        remove_post_data(ctx, &HashSet::from([id]))?;
+        // ゴミ箱にあった投稿は移動したときに配信済み
+        if post.is_visible(false) {
+            let event_bus = ctx.data::<EventBus>()?;
+            let _ = event_bus.send(BlogEvent::PostDeleted(post));
+        }
+        Ok(true)
+    }
+
+    /// 既存の投稿のタグを作成・更新時と同じ規則で正規化する（管理者のみ）。
+    /// ゴミ箱の投稿も対象で、タグが変わった投稿の数を返す
+    #[graphql(guard = "RoleGuard::new(ADMIN_ROLES)")]
+    #[instrument(level = "debug", skip_all)]
+    async fn normalize_tags(&self, ctx: &async_graphql::Context<'_>) -> async_graphql::Result<i32> {
+        let storage = ctx.data::<AppStorage>()?;
+        let mut changed = 0;
+        for post in storage.list_all_posts().await? {
+            let tags = normalize_tags(&post.tags);
+            if tags == post.tags {
+                continue;
+            }
+            // 表記の整理なので履歴やupdatedAtは変えない
+            let update = move |post: &mut Post| -> async_graphql::Result<()> {
+                post.tags = tags;
+                Ok(())
+            };
+            if storage.update_post(&post.id, Box::new(update)).await?.is_some() {
+                changed += 1;
+            }
+        }
+        Ok(changed)
+    }
+
+    #[graphql(guard = "RoleGuard::new(USER_ROLES)")]
+    #[instrument(level = "debug", skip_all)]
+    async fn add_comment(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        input: AddCommentInput,
+    ) -> async_graphql::Result<Comment> {
+        let settings = ctx.data::<Settings>()?;
+        let comment_store = ctx.data::<CommentStore>()?;
+        let author_id = current_user(ctx).await?.acting_as(input.author_id)?;
+
+        let body = validate_comment_body(&input.body, settings.max_comment_length)?;
+
+        // 投稿と著者の存在確認
+        let post = find_post_and_user(ctx, &input.post_id, &author_id).await?;
+        let storage = ctx.data::<AppStorage>()?;
+        let mentioned_user_ids = resolve_mentions(storage.as_ref(), &body).await?;
+
+        let comment = Comment {
+            id: ID::from(Uuid::new_v4().to_string()),
+            post_id: input.post_id,
+            parent_comment_id: input.parent_comment_id,
+            author_id,
+            body,
+            created_at: DateTimeScalar(Utc::now()),
+            hidden: false,
+            deleted: false,
+            mentioned_user_ids,
+        };
+
+        let parent_author_id = {
+            let mut comments = comment_store.lock_or_recover();
+            if let Some(parent_id) = &comment.parent_comment_id {
+                let parent = comments
+                    .iter()
+                    .find(|c| &c.id == parent_id)
+                    .ok_or_else(|| AppError::NotFound("Parent comment not found".into()))?;
+                if parent.post_id != comment.post_id {
+                    return Err(AppError::ValidationFailed(
+                        "Parent comment belongs to a different post".into(),
+                    ).into());
+                }
+                if comment_depth(&comments, &comment) > settings.max_comment_depth {
+                    return Err(AppError::ValidationFailed(format!(
+                        "Replies can be nested at most {} level(s) deep",
+                        settings.max_comment_depth
+                    )).into());
+                }
+            }
+            let parent_author_id = comment
+                .parent_comment_id
+                .as_ref()
+                .and_then(|parent_id| comments.iter().find(|c| &c.id == parent_id))
+                .map(|parent| parent.author_id.clone());
+            comments.push(comment.clone());
+            parent_author_id
+        };
+
+        let notification_store = ctx.data::<NotificationStore>()?;
+        notify_comment(notification_store, &comment, &post.author_id, parent_author_id.as_ref());
+        let mailer = ctx.data::<Mailer>()?;
+        notify_comment_by_email(mailer, storage, settings, &post, &comment).await;
+
+        let event_bus = ctx.data::<EventBus>()?;
+        let _ = event_bus.send(BlogEvent::CommentAdded(comment.clone()));
+        Ok(comment)
+    }
+
+    /// 閲覧数を1増やす。存在しない投稿の場合は何もせずfalseを返す
+    #[instrument(level = "debug", skip_all)]
+    async fn record_view(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        post_id: ID,
+    ) -> async_graphql::Result<bool> {
+        let view_store = ctx.data::<ViewStore>()?;
+        match view_store.get(&post_id) {
+            Some(count) => {
+                count.fetch_add(1, Ordering::Relaxed);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// いいねする（同じユーザーが何度いいねしても1回として数える）
+    #[graphql(guard = "RoleGuard::new(USER_ROLES)")]
+    #[instrument(level = "debug", skip_all)]
+    async fn like_post(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        post_id: ID,
+        user_id: Option<ID>,
+    ) -> async_graphql::Result<Post> {
+        let user_id = current_user(ctx).await?.acting_as(user_id)?;
+        let post = find_post_and_user(ctx, &post_id, &user_id).await?;
+        let like_store = ctx.data::<LikeStore>()?;
+        like_store.lock_or_recover().insert((post_id, user_id));
+        Ok(post)
+    }
+
+    #[graphql(guard = "RoleGuard::new(USER_ROLES)")]
+    #[instrument(level = "debug", skip_all)]
+    async fn unlike_post(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        post_id: ID,
+        user_id: Option<ID>,
+    ) -> async_graphql::Result<Post> {
+        let user_id = current_user(ctx).await?.acting_as(user_id)?;
+        let post = find_post_and_user(ctx, &post_id, &user_id).await?;
+        let like_store = ctx.data::<LikeStore>()?;
+        like_store.lock_or_recover().remove(&(post_id, user_id));
+        Ok(post)
+    }
+
+    /// ユーザーをフォローする。フォロー済みの場合は何もしない
+    #[graphql(guard = "RoleGuard::new(USER_ROLES)")]
+    #[instrument(level = "debug", skip_all)]
+    async fn follow_user(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        follower_id: Option<ID>,
+        followee_id: ID,
+    ) -> async_graphql::Result<User> {
+        let follower_id = current_user(ctx).await?.acting_as(follower_id)?;
+        if follower_id == followee_id {
+            return Err(AppError::ValidationFailed("Users cannot follow themselves".into()).into());
+        }
+        let storage = ctx.data::<AppStorage>()?;
+        let follow_store = ctx.data::<FollowStore>()?;
+
+        if storage.get_user(&follower_id).await?.is_none() {
+            return Err(AppError::NotFound("Follower not found".into()).into());
+        }
+        let followee = storage
+            .get_user(&followee_id)
+            .await?
+            .ok_or_else(|| not_found("User"))?;
+
+        follow_store
+            .lock_or_recover()
+            .insert((follower_id, followee_id));
+        Ok(followee)
+    }
+
+    #[graphql(guard = "RoleGuard::new(USER_ROLES)")]
+    #[instrument(level = "debug", skip_all)]
+    async fn unfollow_user(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        follower_id: Option<ID>,
+        followee_id: ID,
+    ) -> async_graphql::Result<bool> {
+        let follower_id = current_user(ctx).await?.acting_as(follower_id)?;
+        let follow_store = ctx.data::<FollowStore>()?;
+        Ok(follow_store
+            .lock_or_recover()
+            .remove(&(follower_id, followee_id)))
+    }
+
+    /// リアクションする（同じ種類のリアクションは1ユーザー1回まで）
+    #[graphql(guard = "RoleGuard::new(USER_ROLES)")]
+    #[instrument(level = "debug", skip_all)]
+    async fn react_to_post(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        post_id: ID,
+        user_id: Option<ID>,
+        reaction: Reaction,
+    ) -> async_graphql::Result<Post> {
+        let user_id = current_user(ctx).await?.acting_as(user_id)?;
+        let post = find_post_and_user(ctx, &post_id, &user_id).await?;
+        let reaction_store = ctx.data::<ReactionStore>()?;
+        reaction_store
+            .lock_or_recover()
+            .insert((post_id, user_id, reaction));
+        Ok(post)
+    }
+
+    #[graphql(guard = "RoleGuard::new(USER_ROLES)")]
+    #[instrument(level = "debug", skip_all)]
+    async fn remove_reaction(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        post_id: ID,
+        user_id: Option<ID>,
+        reaction: Reaction,
+    ) -> async_graphql::Result<Post> {
+        let user_id = current_user(ctx).await?.acting_as(user_id)?;
+        let post = find_post_and_user(ctx, &post_id, &user_id).await?;
+        let reaction_store = ctx.data::<ReactionStore>()?;
+        reaction_store
+            .lock_or_recover()
+            .remove(&(post_id, user_id, reaction));
+        Ok(post)
+    }
+
+    /// あとで読むに追加する。追加済みの場合は既存のブックマークを返す
+    #[graphql(guard = "RoleGuard::new(USER_ROLES)")]
+    #[instrument(level = "debug", skip_all)]
+    async fn bookmark_post(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        post_id: ID,
+        user_id: Option<ID>,
+    ) -> async_graphql::Result<Bookmark> {
+        let user_id = current_user(ctx).await?.acting_as(user_id)?;
+        let post = find_post_and_user(ctx, &post_id, &user_id).await?;
+        let bookmark_store = ctx.data::<BookmarkStore>()?;
+        let mut bookmarks = bookmark_store.lock_or_recover();
+        let bookmarked_at = match bookmarks
+            .iter()
+            .find(|b| b.post_id == post_id && b.user_id == user_id)
+        {
+            Some(existing) => existing.bookmarked_at,
+            None => {
+                let bookmarked_at = DateTimeScalar(Utc::now());
+                bookmarks.push(BookmarkEntry {
+                    post_id,
+                    user_id,
+                    bookmarked_at,
+                });
+                bookmarked_at
+            }
+        };
+        Ok(Bookmark {
+            post,
+            bookmarked_at,
+        })
+    }
+
+    #[graphql(guard = "RoleGuard::new(USER_ROLES)")]
+    #[instrument(level = "debug", skip_all)]
+    async fn unbookmark_post(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        post_id: ID,
+        user_id: Option<ID>,
+    ) -> async_graphql::Result<bool> {
+        let user_id = current_user(ctx).await?.acting_as(user_id)?;
+        let bookmark_store = ctx.data::<BookmarkStore>()?;
+        let mut bookmarks = bookmark_store.lock_or_recover();
+        let initial_len = bookmarks.len();
+        bookmarks.retain(|b| !(b.post_id == post_id && b.user_id == user_id));
+        Ok(bookmarks.len() < initial_len)
+    }
+
+    /// コメントを削除する（著者本人か管理者のみ）。返信がある場合は本文を "[deleted]" に置き換えて残す。
+    /// そうして残したコメントは、最後の返信が削除されたときに一緒に削除する
+    #[graphql(guard = "RoleGuard::new(USER_ROLES)")]
+    #[instrument(level = "debug", skip_all)]
+    async fn delete_comment(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        id: ID,
+    ) -> async_graphql::Result<bool> {
+        let current_user = current_user(ctx).await?;
+        let comment_store = ctx.data::<CommentStore>()?;
+        let mut comments = comment_store.lock_or_recover();
+        let has_replies = comments
+            .iter()
+            .any(|c| c.parent_comment_id.as_ref() == Some(&id));
+        let comment = comments
+            .iter_mut()
+            .find(|c| c.id == id && !c.deleted)
+            .ok_or_else(|| not_found("Comment"))?;
+        current_user.ensure_can_delete(comment)?;
+
+        if has_replies {
+            comment.body = "[deleted]".to_string();
+            comment.deleted = true;
+            comment.mentioned_user_ids.clear();
+        } else {
+            // 返信のなくなった削除済みの親をさかのぼって消す
+            let mut removed = HashSet::new();
+            let mut next = Some(id);
+            while let Some(id) = next.take() {
+                let Some(index) = comments.iter().position(|c| c.id == id) else {
+                    break;
+                };
+                let comment = comments.remove(index);
+                removed.insert(comment.id);
+                next = comment.parent_comment_id.filter(|parent_id| {
+                    let is_tombstone = comments.iter().any(|c| &c.id == parent_id && c.deleted);
+                    let has_replies =
+                        comments.iter().any(|c| c.parent_comment_id.as_ref() == Some(parent_id));
+                    is_tombstone && !has_replies
+                });
+            }
+            let notification_store = ctx.data::<NotificationStore>()?;
+            notification_store
+                .lock_or_recover()
+                .retain(|n| n.comment_id.as_ref().is_none_or(|id| !removed.contains(id)));
+        }
+        Ok(true)
+    }
+
+    /// コメントを非表示にする（管理者向け）
+    #[graphql(guard = "RoleGuard::new(ADMIN_ROLES)")]
+    #[instrument(level = "debug", skip_all)]
+    async fn hide_comment(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        id: ID,
+    ) -> async_graphql::Result<Comment> {
+        let comment_store = ctx.data::<CommentStore>()?;
+        let mut comments = comment_store.lock_or_recover();
+        let comment = comments
+            .iter_mut()
+            .find(|c| c.id == id)
+            .ok_or_else(|| not_found("Comment"))?;
+
+        comment.hidden = true;
+        Ok(comment.clone())
+    }
+
+    /// 通知を既読にする（受け取ったユーザー本人のみ）
+    #[instrument(level = "debug", skip_all)]
+    async fn mark_notification_read(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        id: ID,
+    ) -> async_graphql::Result<Notification> {
+        let notification_store = ctx.data::<NotificationStore>()?;
+        let mut notifications = notification_store.lock_or_recover();
+        let notification = notifications
+            .iter_mut()
+            .find(|n| n.id == id)
+            .ok_or_else(|| not_found("Notification"))?;
+        ensure_recipient(ctx, &notification.recipient_id)?;
+        notification.read = true;
+        Ok(notification.clone())
+    }
+
+    /// ユーザーの未読の通知を全て既読にし、既読にした件数を返す（本人のみ）
+    #[instrument(level = "debug", skip_all)]
+    async fn mark_all_read(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        user_id: ID,
+    ) -> async_graphql::Result<i32> {
+        ensure_recipient(ctx, &user_id)?;
+        let notification_store = ctx.data::<NotificationStore>()?;
+        let mut marked = 0;
+        for notification in notification_store.lock_or_recover().iter_mut() {
+            if notification.recipient_id == user_id && !notification.read {
+                notification.read = true;
+                marked += 1;
+            }
+        }
+        Ok(marked)
+    }
+
+    /// 通知の設定を変更する（本人のみ）
+    #[instrument(level = "debug", skip_all)]
+    async fn update_notification_settings(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        input: UpdateNotificationSettingsInput,
+    ) -> async_graphql::Result<NotificationSettings> {
+        ensure_recipient(ctx, &input.user_id)?;
+        let storage = ctx.data::<AppStorage>()?;
+        let email_on_comment = input.email_on_comment;
+        let update = move |user: &mut User| -> async_graphql::Result<()> {
+            if let Some(email_on_comment) = email_on_comment {
+                user.email_on_comment = email_on_comment;
+            }
+            Ok(())
+        };
+        let user = storage.update_user(&input.user_id, Box::new(update)).await?;
+        let user = user.ok_or_else(|| not_found("User"))?;
+        Ok(NotificationSettings::of(&user))
+    }
+
+    /// exportDataで書き出したJSONを読み込む（管理者のみ）。新しいバージョンの形式は読み込めない
+    #[graphql(guard = "RoleGuard::new(ADMIN_ROLES)")]
+    #[instrument(level = "debug", skip_all)]
+    async fn import_data(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        json: Json<serde_json::Value>,
+        mode: ImportMode,
+    ) -> async_graphql::Result<ImportResult> {
+        let document = backup::parse_document(json.0)?;
+        backup::import_data(ctx, document, mode).await
+    }
+}
@@ -0,0 +1,241 @@
+// ニュースレターの購読（ダブルオプトイン）
+// subscribeNewsletterは確認待ちとして保存して確認のメールを送り、リンクが開かれたら購読を確定する
+use actix_web::http::header;
+use actix_web::{web, HttpResponse, HttpResponseBuilder};
+use async_graphql::ID;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use lettre::message::Mailbox;
+use lettre::Address;
+use serde::Deserialize;
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::feed::escape_xml;
+use crate::mail::{Email, Mailer};
+use crate::models::{NewsletterSubscriber, NewsletterSubscriberStatus, NewsletterSubscribers};
+use crate::scalars::DateTimeScalar;
+use crate::settings::Settings;
+use crate::store::{LockExt, NewsletterStore};
+
+// 確認のリンクの有効期限。過ぎた確認待ちは削除する
+const CONFIRMATION_TTL: chrono::Duration = chrono::Duration::days(7);
+// 確認待ちのアドレスに確認のメールを送り直す最短の間隔（同じアドレスに何度も送らない）
+const RESEND_INTERVAL: chrono::Duration = chrono::Duration::minutes(10);
+// 確認のトークンの署名の対象に付ける接頭辞（同じ鍵の他の用途と区別する）
+const CONFIRMATION_PURPOSE: &str = "newsletter-confirm:";
+
+// 確認のトークン（`<購読者ID>.<署名>`）の署名に使う鍵
+// JWT_SECRETを使い、未指定ならプロセスごとのランダムな鍵（購読者もメモリ上にのみ保持する）
+#[derive(Clone)]
+pub(crate) struct NewsletterKey {
+    secret: Vec<u8>,
+}
+
+impl NewsletterKey {
+    pub(crate) fn from_env() -> Self {
+        let secret = std::env::var("JWT_SECRET").unwrap_or_else(|_| Uuid::new_v4().to_string());
+        NewsletterKey {
+            secret: secret.into_bytes(),
+        }
+    }
+
+    fn mac(&self, id: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret)
+            .expect("HMAC accepts keys of any length");
+        mac.update(CONFIRMATION_PURPOSE.as_bytes());
+        mac.update(id.as_bytes());
+        mac
+    }
+
+    fn confirmation_token(&self, id: &ID) -> String {
+        let signature = self.mac(id.as_str()).finalize().into_bytes();
+        format!("{}.{}", id.as_str(), URL_SAFE_NO_PAD.encode(signature))
+    }
+
+    // 署名が正しければ購読者のIDを返す
+    fn verify(&self, token: &str) -> Option<ID> {
+        let (id, signature) = token.split_once('.')?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        self.mac(id).verify_slice(&signature).ok()?;
+        Some(ID::from(id))
+    }
+}
+
+// 期限の切れた確認待ちを削除する
+fn remove_expired(subscribers: &mut Vec<NewsletterSubscriber>, now: DateTime<Utc>) {
+    subscribers.retain(|s| {
+        s.status == NewsletterSubscriberStatus::Confirmed
+            || now - s.confirmation_sent_at < CONFIRMATION_TTL
+    });
+}
+
+// 確認待ちとして保存し、確認のメールをキューに入れる
+// 購読済みかどうかを返さないよう、既に購読済みなら何もせず、確認待ちなら間隔を空けて送り直すだけにする
+pub(crate) fn subscribe(
+    store: &NewsletterStore,
+    key: &NewsletterKey,
+    mailer: &Mailer,
+    settings: &Settings,
+    address: Address,
+) {
+    let now = Utc::now();
+    let email = address.to_string();
+    let subscriber = {
+        let mut subscribers = store.lock_or_recover();
+        remove_expired(&mut subscribers, now);
+        // アドレスは大文字小文字を区別せずに比べる
+        match subscribers.iter_mut().find(|s| s.email.eq_ignore_ascii_case(&email)) {
+            Some(s) if s.status == NewsletterSubscriberStatus::Confirmed => return,
+            Some(s) if now - s.confirmation_sent_at < RESEND_INTERVAL => return,
+            Some(s) => {
+                s.confirmation_sent_at = now;
+                s.clone()
+            }
+            None => {
+                let subscriber = NewsletterSubscriber {
+                    id: ID::from(Uuid::new_v4().to_string()),
+                    email,
+                    status: NewsletterSubscriberStatus::Pending,
+                    created_at: DateTimeScalar(now),
+                    confirmed_at: None,
+                    confirmation_sent_at: now,
+                    unsubscribe_token: Uuid::new_v4().simple().to_string(),
+                };
+                subscribers.push(subscriber.clone());
+                subscriber
+            }
+        }
+    };
+    let to = Mailbox::new(None, address);
+    mailer.enqueue(confirmation_email(to, &subscriber, key, settings));
+}
+
+fn confirm_url(settings: &Settings, token: &str) -> String {
+    format!("{}/newsletter/confirm?token={}", settings.site_base_url, token)
+}
+
+fn unsubscribe_url(settings: &Settings, subscriber: &NewsletterSubscriber) -> String {
+    format!(
+        "{}/newsletter/unsubscribe?token={}",
+        settings.site_base_url, subscriber.unsubscribe_token
+    )
+}
+
+fn confirmation_email(
+    to: Mailbox,
+    subscriber: &NewsletterSubscriber,
+    key: &NewsletterKey,
+    settings: &Settings,
+) -> Email {
+    let confirm = confirm_url(settings, &key.confirmation_token(&subscriber.id));
+    let unsubscribe = unsubscribe_url(settings, subscriber);
+    let intro = format!("Please confirm your subscription to {}.", settings.site_title);
+    let note = format!(
+        "The link expires in {} days. If you did not subscribe, you can ignore this email.",
+        CONFIRMATION_TTL.num_days()
+    );
+    let text = format!(
+        "{}\n\n{}\n\n{}\n\n--\nUnsubscribe: {}\n",
+        intro, confirm, note, unsubscribe
+    );
+    let html = format!(
+        "<p>{}</p>\n<p><a href=\"{}\">Confirm subscription</a></p>\n<p>{}</p>\n\
+         <p><small><a href=\"{}\">Unsubscribe</a></small></p>\n",
+        escape_xml(&intro),
+        escape_xml(&confirm),
+        escape_xml(&note),
+        escape_xml(&unsubscribe)
+    );
+    Email {
+        to,
+        reply_to: None,
+        subject: format!("Confirm your subscription to {}", settings.site_title),
+        text,
+        html,
+    }
+}
+
+// 購読者の一覧（statusを指定するとその状態のみ）と状態ごとの数
+pub(crate) fn list_subscribers(
+    store: &NewsletterStore,
+    status: Option<NewsletterSubscriberStatus>,
+) -> NewsletterSubscribers {
+    let mut subscribers = store.lock_or_recover();
+    remove_expired(&mut subscribers, Utc::now());
+    let count = |status| subscribers.iter().filter(|s| s.status == status).count() as i32;
+    NewsletterSubscribers {
+        pending_count: count(NewsletterSubscriberStatus::Pending),
+        confirmed_count: count(NewsletterSubscriberStatus::Confirmed),
+        subscribers: subscribers
+            .iter()
+            .filter(|s| status.is_none_or(|status| s.status == status))
+            .cloned()
+            .collect(),
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct TokenQuery {
+    token: String,
+}
+
+// メールのリンクから開くページ（確認済みのリンクを開き直しても同じページを返す）
+pub(crate) async fn confirm_handler(
+    store: web::Data<NewsletterStore>,
+    key: web::Data<NewsletterKey>,
+    settings: web::Data<Settings>,
+    query: web::Query<TokenQuery>,
+) -> HttpResponse {
+    let Some(id) = key.verify(&query.token) else {
+        return page(HttpResponse::BadRequest(), &settings, "This confirmation link is invalid.");
+    };
+    let now = Utc::now();
+    let mut subscribers = store.lock_or_recover();
+    remove_expired(&mut subscribers, now);
+    let Some(subscriber) = subscribers.iter_mut().find(|s| s.id == id) else {
+        let message = "This confirmation link has expired. Please subscribe again.";
+        return page(HttpResponse::NotFound(), &settings, message);
+    };
+    if subscriber.status == NewsletterSubscriberStatus::Pending {
+        subscriber.status = NewsletterSubscriberStatus::Confirmed;
+        subscriber.confirmed_at = Some(DateTimeScalar(now));
+    }
+    let message = format!(
+        "Your subscription to {} is confirmed. <a href=\"{}\">Unsubscribe</a>",
+        escape_xml(&settings.site_title),
+        escape_xml(&unsubscribe_url(&settings, subscriber))
+    );
+    page(HttpResponse::Ok(), &settings, &message)
+}
+
+pub(crate) async fn unsubscribe_handler(
+    store: web::Data<NewsletterStore>,
+    settings: web::Data<Settings>,
+    query: web::Query<TokenQuery>,
+) -> HttpResponse {
+    let mut subscribers = store.lock_or_recover();
+    let initial_len = subscribers.len();
+    subscribers.retain(|s| s.unsubscribe_token != query.token);
+    if subscribers.len() == initial_len {
+        let message = "This link is invalid or you have already unsubscribed.";
+        return page(HttpResponse::NotFound(), &settings, message);
+    }
+    let message = format!("You have been unsubscribed from {}.", escape_xml(&settings.site_title));
+    page(HttpResponse::Ok(), &settings, &message)
+}
+
+// messageはエスケープ済みのHTML
+fn page(mut builder: HttpResponseBuilder, settings: &Settings, message: &str) -> HttpResponse {
+    builder
+        .content_type("text/html; charset=utf-8")
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .body(format!(
+            "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{}</title></head>\n\
+             <body><p>{}</p></body>\n</html>\n",
+            escape_xml(&settings.site_title),
+            message
+        ))
+}
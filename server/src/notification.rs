@@ -0,0 +1,120 @@
+// アプリ内の通知（コメント・返信・@メンション）
+use async_graphql::ID;
+use chrono::Utc;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::auth::Viewer;
+use crate::error::AppError;
+use crate::models::{Comment, Notification, NotificationKind, Post};
+use crate::scalars::DateTimeScalar;
+use crate::store::{AppStorage, LockExt, NotificationStore};
+
+// 通知は受け取ったユーザー本人だけが参照・既読にできる
+pub(crate) fn ensure_recipient(
+    ctx: &async_graphql::Context<'_>,
+    user_id: &ID,
+) -> async_graphql::Result<()> {
+    match ctx.data_opt::<Viewer>() {
+        Some(viewer) if &viewer.user_id == user_id => Ok(()),
+        Some(_) => Err(AppError::Forbidden("Not allowed to access notifications".into()).into()),
+        None => Err(AppError::Unauthenticated("Authentication required".into()).into()),
+    }
+}
+
+fn push(
+    notifications: &mut Vec<Notification>,
+    recipient_id: &ID,
+    actor_id: &ID,
+    kind: NotificationKind,
+    post_id: &ID,
+    comment_id: Option<&ID>,
+) {
+    notifications.push(Notification {
+        id: ID::from(Uuid::new_v4().to_string()),
+        recipient_id: recipient_id.clone(),
+        actor_id: actor_id.clone(),
+        kind,
+        post_id: post_id.clone(),
+        comment_id: comment_id.cloned(),
+        created_at: DateTimeScalar(Utc::now()),
+        read: false,
+    });
+}
+
+// コメントを受けた投稿の著者・返信先のコメントの著者・メンションしたユーザーに通知する
+// 1つのコメントでは1人に1件だけ（返信 > メンション > コメントの順に優先）で、書いた本人には送らない
+pub(crate) fn notify_comment(
+    store: &NotificationStore,
+    comment: &Comment,
+    post_author_id: &ID,
+    parent_author_id: Option<&ID>,
+) {
+    let mut recipients: Vec<(&ID, NotificationKind)> = Vec::new();
+    if let Some(parent_author_id) = parent_author_id {
+        recipients.push((parent_author_id, NotificationKind::Reply));
+    }
+    for user_id in &comment.mentioned_user_ids {
+        recipients.push((user_id, NotificationKind::Mention));
+    }
+    recipients.push((post_author_id, NotificationKind::Comment));
+
+    let mut notifications = store.lock_or_recover();
+    let mut notified = vec![&comment.author_id];
+    for (recipient_id, kind) in recipients {
+        if notified.contains(&recipient_id) {
+            continue;
+        }
+        notified.push(recipient_id);
+        let (actor_id, post_id) = (&comment.author_id, &comment.post_id);
+        push(&mut notifications, recipient_id, actor_id, kind, post_id, Some(&comment.id));
+    }
+}
+
+// 公開中の投稿の本文でメンションしたユーザーに通知する
+// 下書きの間は送らず、公開した時点で送る。同じ投稿で通知済みのユーザーには再び送らない
+pub(crate) fn notify_post_mentions(store: &NotificationStore, post: &Post) {
+    if !post.is_visible(false) {
+        return;
+    }
+    let mut notifications = store.lock_or_recover();
+    for user_id in &post.mentioned_user_ids {
+        let notified = notifications.iter().any(|n| {
+            n.kind == NotificationKind::Mention
+                && n.post_id == post.id
+                && n.comment_id.is_none()
+                && &n.recipient_id == user_id
+        });
+        if notified || user_id == &post.author_id {
+            continue;
+        }
+        let kind = NotificationKind::Mention;
+        push(&mut notifications, user_id, &post.author_id, kind, &post.id, None);
+    }
+}
+
+// ユーザーの通知（新しい順）。ゴミ箱にある投稿への通知は含めない（復元すると戻る）
+pub(crate) async fn user_notifications(
+    store: &NotificationStore,
+    storage: &AppStorage,
+    user_id: &ID,
+    unread_only: bool,
+) -> async_graphql::Result<Vec<Notification>> {
+    let mut notifications: Vec<Notification> = store
+        .lock_or_recover()
+        .iter()
+        .filter(|n| &n.recipient_id == user_id && !(unread_only && n.read))
+        .cloned()
+        .collect();
+    notifications.reverse();
+
+    let mut visible: HashMap<ID, bool> = HashMap::new();
+    for notification in &notifications {
+        if !visible.contains_key(&notification.post_id) {
+            let post = storage.get_post(&notification.post_id).await?;
+            visible.insert(notification.post_id.clone(), post.is_some_and(|p| !p.is_deleted()));
+        }
+    }
+    notifications.retain(|n| visible[&n.post_id]);
+    Ok(notifications)
+}
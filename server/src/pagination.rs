@@ -0,0 +1,81 @@
+use async_graphql::connection::CursorType;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+
+use crate::models::Post;
+
+// ページネーション
+pub(crate) const DEFAULT_PAGE_SIZE: i32 = 20;
+pub(crate) const MAX_PAGE_SIZE: i32 = 100;
+
+pub(crate) fn paginate<T>(items: impl IntoIterator<Item = T>, limit: i32, offset: i32) -> Vec<T> {
+    Page::new(limit, offset).apply(items)
+}
+
+// 取り出す範囲（limitがNoneなら残り全て）
+#[derive(Clone, Copy)]
+pub struct Page {
+    pub(crate) limit: Option<usize>,
+    pub(crate) offset: usize,
+}
+
+impl Page {
+    pub(crate) const ALL: Page = Page {
+        limit: None,
+        offset: 0,
+    };
+
+    pub(crate) fn new(limit: i32, offset: i32) -> Self {
+        Page {
+            limit: Some(limit.clamp(0, MAX_PAGE_SIZE) as usize),
+            offset: offset.max(0) as usize,
+        }
+    }
+
+    pub(crate) fn apply<T>(self, items: impl IntoIterator<Item = T>) -> Vec<T> {
+        let items = items.into_iter().skip(self.offset);
+        match self.limit {
+            Some(limit) => items.take(limit).collect(),
+            None => items.collect(),
+        }
+    }
+}
+
+// 投稿カーソル（公開日時とIDのbase64）
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct PostCursor {
+    published_at: DateTime<Utc>,
+    id: String,
+}
+
+impl PostCursor {
+    pub(crate) fn of(post: &Post) -> Self {
+        PostCursor {
+            published_at: post.published_at.0,
+            id: post.id.to_string(),
+        }
+    }
+}
+
+impl CursorType for PostCursor {
+    type Error = String;
+
+    fn decode_cursor(s: &str) -> Result<Self, Self::Error> {
+        let invalid = || format!("Invalid cursor: {}", s);
+        let bytes = URL_SAFE_NO_PAD.decode(s).map_err(|_| invalid())?;
+        let decoded = String::from_utf8(bytes).map_err(|_| invalid())?;
+        let (published_at, id) = decoded.split_once('|').ok_or_else(invalid)?;
+        let published_at = DateTime::parse_from_rfc3339(published_at)
+            .map_err(|_| invalid())?
+            .with_timezone(&Utc);
+        Ok(PostCursor {
+            published_at,
+            id: id.to_string(),
+        })
+    }
+
+    fn encode_cursor(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!("{}|{}", self.published_at.to_rfc3339(), self.id))
+    }
+}
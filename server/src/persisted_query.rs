@@ -0,0 +1,93 @@
+use async_graphql::{ErrorExtensionValues, ServerError};
+use lru::LruCache;
+use serde::Deserialize;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crate::auth::hash_token;
+use crate::store::LockExt;
+
+// Automatic Persisted Queries（ハッシュをキーにクエリ本文をキャッシュする）
+#[derive(Deserialize)]
+struct PersistedQuery {
+    version: i32,
+    #[serde(rename = "sha256Hash")]
+    sha256_hash: String,
+}
+
+// ヒット率をログに出す間隔（参照回数）
+const APQ_LOG_INTERVAL: u64 = 100;
+
+pub(crate) struct PersistedQueryCache {
+    queries: Mutex<LruCache<String, String>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl PersistedQueryCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        PersistedQueryCache {
+            queries: Mutex::new(LruCache::new(
+                NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN),
+            )),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    fn get(&self, hash: &str) -> Option<String> {
+        let query = self.queries.lock_or_recover().get(hash).cloned();
+        if query.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        if (hits + misses).is_multiple_of(APQ_LOG_INTERVAL) {
+            tracing::info!(
+                "APQ cache: {} hits, {} misses (hit rate {:.1}%)",
+                hits,
+                misses,
+                hits as f64 * 100.0 / (hits + misses) as f64
+            );
+        }
+        query
+    }
+
+    // persistedQuery拡張があればクエリ本文を解決する
+    pub(crate) fn resolve(&self, request: &mut async_graphql::Request) -> Result<(), ServerError> {
+        let Some(value) = request.extensions.remove("persistedQuery") else {
+            return Ok(());
+        };
+        let persisted: PersistedQuery = async_graphql::from_value(value)
+            .map_err(|_| ServerError::new("Invalid persistedQuery extension", None))?;
+        if persisted.version != 1 {
+            return Err(ServerError::new(
+                format!("Unsupported persistedQuery version: {}", persisted.version),
+                None,
+            ));
+        }
+
+        if request.query.is_empty() {
+            // 未登録ならクライアントがクエリ本文付きで再送する
+            request.query = self.get(&persisted.sha256_hash).ok_or_else(|| {
+                let mut error = ServerError::new("PersistedQueryNotFound", None);
+                let mut extensions = ErrorExtensionValues::default();
+                extensions.set("code", "PERSISTED_QUERY_NOT_FOUND");
+                error.extensions = Some(extensions);
+                error
+            })?;
+        } else {
+            if hash_token(&request.query) != persisted.sha256_hash {
+                return Err(ServerError::new("provided sha does not match query", None));
+            }
+            self.queries
+                .lock_or_recover()
+                .put(persisted.sha256_hash, request.query.clone());
+        }
+        Ok(())
+    }
+}
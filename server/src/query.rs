@@ -0,0 +1,521 @@
+use async_graphql::{Json, Object, ID};
+use async_graphql::connection::{self, Connection, Edge};
+use std::collections::{HashMap, HashSet};
+
+use crate::auth::{ADMIN_ROLES, AUTHOR_ROLES, RoleGuard, Viewer, current_user};
+use crate::backup::{self, ExportDocument};
+use crate::extensions::list_complexity;
+use crate::models::{
+    Bookmark, ContactMessage, NewsletterSubscriberStatus, NewsletterSubscribers, Notification, Post,
+    PostRevision, TagCount, User, Webhook,
+};
+use crate::newsletter;
+use crate::notification::{ensure_recipient, user_notifications};
+use crate::pagination::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE, Page, PostCursor, paginate};
+use crate::scalars::DateTimeScalar;
+use crate::search::{PostFilter, PostSort, SearchIndexStore, UserFilter, sort_posts};
+use crate::store::{
+    AppStorage, BookmarkEntry, BookmarkStore, ContactMessageStore, FollowStore, LikeStore, LockExt,
+    NewsletterStore, NotificationStore, ViewStore, WebhookStore, count_likes, view_count,
+};
+use crate::error::not_found;
+use crate::validation::{normalize_tags, parse_id};
+
+// GraphQL Query
+pub struct Query;
+
+/// ブログのクエリ
+///
+/// エラーには`extensions.code`に次のいずれかが入る。
+///
+/// - `NOT_FOUND`: 指定したリソースが存在しない（`extensions.entity` に `Post` / `User` など）
+/// - `VALIDATION_FAILED`: 入力値が不正
+/// - `INVALID_ID`: IDの形式が不正（UUIDでも整数でもない。`extensions.argument` に引数名）
+/// - `FORBIDDEN`: 権限がない
+/// - `UNAUTHENTICATED`: 認証が必要、またはトークンが無効
+/// - `CONFLICT`: 名前の重複や更新の競合など、現在の状態と矛盾する
+/// - `INTERNAL`: サーバー内部のエラー（詳細は返さない）
+/// - `QUERY_TOO_DEEP`: クエリの入れ子が上限より深い
+/// - `QUERY_TOO_COMPLEX`: クエリの複雑さが上限を超えた
+/// - `PERSISTED_QUERY_NOT_FOUND`: 登録されていないPersisted Query
+#[Object]
+impl Query {
+    /// 投稿一覧（デフォルトは新しい順）
+    /// `includeDrafts` は著者と管理者のみ指定でき、著者には自分の下書きだけを含める
+    #[allow(clippy::too_many_arguments)]
+    #[graphql(complexity = "list_complexity(limit, child_complexity)")]
+    async fn posts(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        tag: Option<String>,
+        published_after: Option<DateTimeScalar>,
+        published_before: Option<DateTimeScalar>,
+        #[graphql(default)] include_drafts: bool,
+        has_cover_image: Option<bool>,
+        #[graphql(default)] sort: PostSort,
+        #[graphql(default_with = "DEFAULT_PAGE_SIZE")] limit: i32,
+        #[graphql(default = 0)] offset: i32,
+    ) -> async_graphql::Result<Vec<Post>> {
+        let filter = PostFilter {
+            tag,
+            published_after,
+            published_before,
+            include_drafts,
+            has_cover_image,
+            ..PostFilter::default()
+        }
+        .validate()?;
+        let filter = scope_drafts(ctx, filter).await?;
+        let storage = ctx.data::<AppStorage>()?;
+        // ストレージは新しい順に返すので、デフォルトの並び順なら必要な分だけ取り出す
+        if sort == PostSort::PublishedAtDesc {
+            return storage.list_posts(&filter, Page::new(limit, offset)).await;
+        }
+        let mut matching = storage.list_posts(&filter, Page::ALL).await?;
+
+        let like_counts = if sort == PostSort::MostLiked {
+            let like_store = ctx.data::<LikeStore>()?;
+            count_likes(&like_store.lock_or_recover())
+        } else {
+            HashMap::new()
+        };
+        sort_posts(&mut matching, sort, &like_counts);
+        Ok(paginate(matching, limit, offset))
+    }
+
+    /// カーソルベースの投稿一覧（新しい順）
+    #[graphql(
+        complexity = "list_complexity(first.or(last).unwrap_or(MAX_PAGE_SIZE), child_complexity)"
+    )]
+    async fn posts_connection(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> async_graphql::Result<Connection<PostCursor, Post>> {
+        let storage = ctx.data::<AppStorage>()?;
+        // ストレージの並び順（公開日時とIDの降順）はカーソルの降順と同じ
+        let posts = storage.list_posts(&PostFilter::default(), Page::ALL).await?;
+
+        connection::query(
+            after,
+            before,
+            first,
+            last,
+            |after: Option<PostCursor>, before: Option<PostCursor>, first, last| async move {
+                // カーソルより古い（after）／新しい（before）投稿に絞り込む
+                let mut start = after
+                    .map(|after| posts.partition_point(|p| PostCursor::of(p) >= after))
+                    .unwrap_or(0);
+                let mut end = before
+                    .map(|before| posts.partition_point(|p| PostCursor::of(p) > before))
+                    .unwrap_or(posts.len())
+                    .max(start);
+
+                let max = MAX_PAGE_SIZE as usize;
+                match (first, last) {
+                    (None, None) => end = end.min(start + DEFAULT_PAGE_SIZE as usize),
+                    (first, last) => {
+                        if let Some(first) = first {
+                            end = end.min(start + first.min(max));
+                        }
+                        if let Some(last) = last {
+                            start = start.max(end.saturating_sub(last.min(max)));
+                        }
+                    }
+                }
+
+                let mut connection = Connection::new(start > 0, end < posts.len());
+                connection.edges.extend(
+                    posts[start..end]
+                        .iter()
+                        .map(|p| Edge::new(PostCursor::of(p), p.clone())),
+                );
+                Ok::<_, async_graphql::Error>(connection)
+            },
+        )
+        .await
+    }
+
+    /// タイトルと本文の全文検索（複数語はAND条件）
+    #[graphql(complexity = "list_complexity(limit, child_complexity)")]
+    async fn search_posts(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        query: String,
+        #[graphql(default_with = "DEFAULT_PAGE_SIZE")] limit: i32,
+        #[graphql(default = 0)] offset: i32,
+    ) -> async_graphql::Result<Vec<Post>> {
+        let storage = ctx.data::<AppStorage>()?;
+        let search_index = ctx.data::<SearchIndexStore>()?;
+        let posts = storage.list_posts(&PostFilter::default(), Page::ALL).await?;
+        let hits = search_index.search(&posts, &query);
+        Ok(paginate(hits, limit, offset))
+    }
+
+    async fn posts_count(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        tag: Option<String>,
+        published_after: Option<DateTimeScalar>,
+        published_before: Option<DateTimeScalar>,
+        #[graphql(default)] include_drafts: bool,
+        has_cover_image: Option<bool>,
+    ) -> async_graphql::Result<usize> {
+        let filter = PostFilter {
+            tag,
+            published_after,
+            published_before,
+            include_drafts,
+            has_cover_image,
+            ..PostFilter::default()
+        }
+        .validate()?;
+        let filter = scope_drafts(ctx, filter).await?;
+        let storage = ctx.data::<AppStorage>()?;
+        storage.count_posts(&filter).await
+    }
+
+    /// タグごとの投稿数（多い順、同数は名前順）
+    #[graphql(complexity = "list_complexity(limit.unwrap_or(MAX_PAGE_SIZE), child_complexity)")]
+    async fn tags(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        limit: Option<i32>,
+    ) -> async_graphql::Result<Vec<TagCount>> {
+        let storage = ctx.data::<AppStorage>()?;
+        let posts = storage.list_posts(&PostFilter::default(), Page::ALL).await?;
+
+        // 正規化前に保存されたタグも、正規化した表記でまとめて数える
+        let mut counts: HashMap<String, i32> = HashMap::new();
+        for post in &posts {
+            let tags: HashSet<String> = normalize_tags(&post.tags).into_iter().collect();
+            for tag in tags {
+                *counts.entry(tag).or_default() += 1;
+            }
+        }
+
+        let mut tags: Vec<TagCount> = counts
+            .into_iter()
+            .map(|(name, count)| TagCount { name, count })
+            .collect();
+        tags.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+        if let Some(limit) = limit {
+            tags.truncate(limit.max(0) as usize);
+        }
+        Ok(tags)
+    }
+
+    /// 下書きは `includeDrafts: true` の場合のみ返す（著者は自分の下書きのみ）
+    async fn post_by_slug(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        slug: String,
+        #[graphql(default)] include_drafts: bool,
+    ) -> async_graphql::Result<Option<Post>> {
+        let filter = PostFilter { include_drafts, ..PostFilter::default() };
+        let filter = scope_drafts(ctx, filter).await?;
+        let storage = ctx.data::<AppStorage>()?;
+        let post = storage.get_post_by_slug(&slug).await?;
+        Ok(post.filter(|p| filter.matches(p)))
+    }
+
+    async fn post_revision(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        post_id: ID,
+        revision: i32,
+    ) -> async_graphql::Result<Option<PostRevision>> {
+        let storage = ctx.data::<AppStorage>()?;
+        let post = storage.get_post(&post_id).await?;
+        Ok(post
+            .filter(|p| !p.is_deleted())
+            .and_then(|p| p.revisions.into_iter().find(|r| r.revision == revision)))
+    }
+
+    /// フォロー中のユーザーの投稿（新しい順）
+    #[graphql(complexity = "list_complexity(limit, child_complexity)")]
+    async fn feed(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        user_id: ID,
+        #[graphql(default_with = "DEFAULT_PAGE_SIZE")] limit: i32,
+        #[graphql(default = 0)] offset: i32,
+    ) -> async_graphql::Result<Vec<Post>> {
+        let follow_store = ctx.data::<FollowStore>()?;
+        let storage = ctx.data::<AppStorage>()?;
+
+        let followees: Vec<ID> = follow_store
+            .lock_or_recover()
+            .iter()
+            .filter(|(follower, _)| follower == &user_id)
+            .map(|(_, followee)| followee.clone())
+            .collect();
+        let filter = PostFilter {
+            author_ids: Some(followees),
+            ..PostFilter::default()
+        };
+        storage.list_posts(&filter, Page::new(limit, offset)).await
+    }
+
+    /// 閲覧数の多い投稿
+    #[graphql(complexity = "list_complexity(limit, child_complexity)")]
+    async fn popular_posts(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        #[graphql(default = 10)] limit: i32,
+    ) -> async_graphql::Result<Vec<Post>> {
+        let storage = ctx.data::<AppStorage>()?;
+        let view_store = ctx.data::<ViewStore>()?;
+        let mut posts: Vec<(u64, Post)> = storage
+            .list_posts(&PostFilter::default(), Page::ALL)
+            .await?
+            .into_iter()
+            .map(|p| (view_count(view_store, &p.id), p))
+            .collect();
+        posts.sort_by(|(a_views, a), (b_views, b)| {
+            b_views
+                .cmp(a_views)
+                .then_with(|| b.published_at.0.cmp(&a.published_at.0))
+        });
+        Ok(paginate(posts.into_iter().map(|(_, p)| p), limit, 0))
+    }
+
+    /// ユーザーへの通知（新しい順。本人のみ）。ゴミ箱にある投稿への通知は含まない
+    #[graphql(complexity = "list_complexity(limit, child_complexity)")]
+    async fn notifications(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        user_id: ID,
+        #[graphql(default)] unread_only: bool,
+        #[graphql(default_with = "DEFAULT_PAGE_SIZE")] limit: i32,
+        #[graphql(default = 0)] offset: i32,
+    ) -> async_graphql::Result<Vec<Notification>> {
+        ensure_recipient(ctx, &user_id)?;
+        let notification_store = ctx.data::<NotificationStore>()?;
+        let storage = ctx.data::<AppStorage>()?;
+        let notifications =
+            user_notifications(notification_store, storage, &user_id, unread_only).await?;
+        Ok(paginate(notifications, limit, offset))
+    }
+
+    /// あとで読む一覧（追加日時の新しい順）。非公開になった投稿は含まない
+    #[graphql(complexity = "list_complexity(DEFAULT_PAGE_SIZE, child_complexity)")]
+    async fn bookmarks(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        user_id: ID,
+    ) -> async_graphql::Result<Vec<Bookmark>> {
+        let bookmark_store = ctx.data::<BookmarkStore>()?;
+        let storage = ctx.data::<AppStorage>()?;
+
+        let mut entries: Vec<BookmarkEntry> = bookmark_store
+            .lock_or_recover()
+            .iter()
+            .filter(|b| b.user_id == user_id)
+            .cloned()
+            .collect();
+        entries.sort_by_key(|b| std::cmp::Reverse(b.bookmarked_at.0));
+
+        let mut bookmarks = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let post = storage.get_post(&entry.post_id).await?;
+            if let Some(post) = post.filter(|p| p.is_visible(false)) {
+                bookmarks.push(Bookmark {
+                    post,
+                    bookmarked_at: entry.bookmarked_at,
+                });
+            }
+        }
+        Ok(bookmarks)
+    }
+
+    /// ゴミ箱の投稿（削除日時の新しい順。著者と管理者のみで、著者には自分の投稿だけを返す）
+    #[graphql(
+        guard = "RoleGuard::new(AUTHOR_ROLES)",
+        complexity = "list_complexity(DEFAULT_PAGE_SIZE, child_complexity)"
+    )]
+    async fn trashed_posts(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> async_graphql::Result<Vec<Post>> {
+        let owner = current_user(ctx).await?.unpublished_posts_owner()?;
+        let storage = ctx.data::<AppStorage>()?;
+        let posts = storage.list_all_posts().await?;
+        let mut posts: Vec<Post> = posts
+            .into_iter()
+            .filter(|p| p.is_deleted() && owner.as_ref().is_none_or(|id| *id == p.author_id))
+            .collect();
+        posts.sort_by_key(|p| std::cmp::Reverse(p.deleted_at.map(|d| d.0)));
+        Ok(posts)
+    }
+
+    /// 公開待ちの予約投稿（公開予定日時の早い順。著者と管理者のみで、著者には自分の投稿だけを返す）
+    #[graphql(
+        guard = "RoleGuard::new(AUTHOR_ROLES)",
+        complexity = "list_complexity(DEFAULT_PAGE_SIZE, child_complexity)"
+    )]
+    async fn scheduled_posts(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> async_graphql::Result<Vec<Post>> {
+        let owner = current_user(ctx).await?.unpublished_posts_owner()?;
+        let storage = ctx.data::<AppStorage>()?;
+        let posts = storage.list_all_posts().await?;
+        let mut posts: Vec<Post> = posts
+            .into_iter()
+            .filter(|p| p.scheduled_at.is_some() && !p.is_deleted())
+            .filter(|p| owner.as_ref().is_none_or(|id| *id == p.author_id))
+            .collect();
+        posts.sort_by_key(|p| p.scheduled_at.map(|d| d.0));
+        Ok(posts)
+    }
+
+    /// 下書きは `includeDrafts: true` の場合のみ返す（著者は自分の下書きのみ）。見つからない場合は `NOT_FOUND`
+    async fn post(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        id: ID,
+        #[graphql(default)] include_drafts: bool,
+    ) -> async_graphql::Result<Option<Post>> {
+        let id = parse_id(id, "id")?;
+        let filter = PostFilter { include_drafts, ..PostFilter::default() };
+        let filter = scope_drafts(ctx, filter).await?;
+        let storage = ctx.data::<AppStorage>()?;
+        let post = storage.get_post(&id).await?;
+        match post.filter(|p| filter.matches(p)) {
+            Some(post) => Ok(Some(post)),
+            None => Err(not_found("Post")),
+        }
+    }
+
+    /// ユーザー一覧（名前の部分一致検索、大文字小文字を区別しない）
+    /// `includeBio` がtrueなら自己紹介が一致するユーザーも含める
+    #[graphql(complexity = "list_complexity(limit, child_complexity)")]
+    async fn users(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        search: Option<String>,
+        #[graphql(default)] include_bio: bool,
+        #[graphql(default_with = "DEFAULT_PAGE_SIZE")] limit: i32,
+        #[graphql(default = 0)] offset: i32,
+    ) -> async_graphql::Result<Vec<User>> {
+        let storage = ctx.data::<AppStorage>()?;
+        let filter = UserFilter { search, include_bio };
+        storage.list_users(&filter, Page::new(limit, offset)).await
+    }
+
+    async fn users_count(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        search: Option<String>,
+        #[graphql(default)] include_bio: bool,
+    ) -> async_graphql::Result<usize> {
+        let storage = ctx.data::<AppStorage>()?;
+        storage.count_users(&UserFilter { search, include_bio }).await
+    }
+
+    /// 見つからない場合は `NOT_FOUND`
+    async fn user(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        id: ID,
+    ) -> async_graphql::Result<Option<User>> {
+        let id = parse_id(id, "id")?;
+        let storage = ctx.data::<AppStorage>()?;
+        match storage.get_user(&id).await? {
+            Some(user) => Ok(Some(user)),
+            None => Err(not_found("User")),
+        }
+    }
+
+    /// ハンドルでユーザーを引く（大文字小文字を区別しない）。見つからない場合は `NOT_FOUND`
+    async fn user_by_handle(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        handle: String,
+    ) -> async_graphql::Result<Option<User>> {
+        let storage = ctx.data::<AppStorage>()?;
+        match storage.get_user_by_handle(&handle.trim().to_lowercase()).await? {
+            Some(user) => Ok(Some(user)),
+            None => Err(not_found("User")),
+        }
+    }
+
+    /// ログイン中のユーザー（未ログインならnull）
+    async fn me(&self, ctx: &async_graphql::Context<'_>) -> async_graphql::Result<Option<User>> {
+        let Some(viewer) = ctx.data_opt::<Viewer>() else {
+            return Ok(None);
+        };
+        let storage = ctx.data::<AppStorage>()?;
+        storage.get_user(&viewer.user_id).await
+    }
+
+    /// 登録したWebhook（管理者のみ）
+    #[graphql(guard = "RoleGuard::new(ADMIN_ROLES)")]
+    async fn webhooks(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> async_graphql::Result<Vec<Webhook>> {
+        let webhook_store = ctx.data::<WebhookStore>()?;
+        Ok(webhook_store.lock_or_recover().clone())
+    }
+
+    /// お問い合わせのメッセージ（新しい順。管理者のみ）
+    #[graphql(
+        guard = "RoleGuard::new(ADMIN_ROLES)",
+        complexity = "list_complexity(limit, child_complexity)"
+    )]
+    async fn contact_messages(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        #[graphql(default)] unread_only: bool,
+        #[graphql(default_with = "DEFAULT_PAGE_SIZE")] limit: i32,
+        #[graphql(default = 0)] offset: i32,
+    ) -> async_graphql::Result<Vec<ContactMessage>> {
+        let contact_store = ctx.data::<ContactMessageStore>()?;
+        let messages: Vec<ContactMessage> = contact_store
+            .lock_or_recover()
+            .iter()
+            .rev()
+            .filter(|m| !unread_only || !m.read)
+            .cloned()
+            .collect();
+        Ok(paginate(messages, limit, offset))
+    }
+
+    /// ニュースレターの購読者と、確認待ち・購読中の数（管理者のみ）
+    /// statusを指定するとその状態の購読者だけを返す（数は常に全体）
+    #[graphql(guard = "RoleGuard::new(ADMIN_ROLES)")]
+    async fn newsletter_subscribers(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        status: Option<NewsletterSubscriberStatus>,
+    ) -> async_graphql::Result<NewsletterSubscribers> {
+        let newsletter_store = ctx.data::<NewsletterStore>()?;
+        Ok(newsletter::list_subscribers(newsletter_store, status))
+    }
+
+    /// 全てのユーザー・投稿・タグ・コメントをバージョン付きのJSONで書き出す（管理者のみ）
+    #[graphql(guard = "RoleGuard::new(ADMIN_ROLES)")]
+    async fn export_data(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> async_graphql::Result<Json<ExportDocument>> {
+        Ok(Json(backup::export_data(ctx).await?))
+    }
+}
+
+// includeDraftsを指定できるのは著者と管理者だけ。著者には自分の下書きだけを含める
+async fn scope_drafts(
+    ctx: &async_graphql::Context<'_>,
+    filter: PostFilter,
+) -> async_graphql::Result<PostFilter> {
+    if !filter.include_drafts {
+        return Ok(filter);
+    }
+    let draft_author_id = current_user(ctx).await?.unpublished_posts_owner()?;
+    Ok(PostFilter { draft_author_id, ..filter })
+}
@@ -0,0 +1,199 @@
+use actix_web::HttpRequest;
+use async_graphql::parser::types::{
+    ExecutableDocument, OperationDefinition, OperationType, Selection, SelectionSet,
+};
+use std::collections::HashSet;
+use dashmap::DashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::settings::env_or;
+use crate::tasks::{wait_next, ShutdownReceiver};
+
+// レート制限（クライアントIPごとのトークンバケット）
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum OperationKind {
+    Query,
+    Mutation,
+    // 以下はTHROTTLED_FIELDSのフィールド（ミューテーションとは別に、より厳しく数える）
+    Newsletter,
+    Contact,
+}
+
+// メールを送る・スパムに使われやすいミューテーションのフィールドと、数えるバケット
+const THROTTLED_FIELDS: &[(&str, OperationKind)] = &[
+    ("subscribeNewsletter", OperationKind::Newsletter),
+    ("submitContactMessage", OperationKind::Contact),
+];
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+pub(crate) struct RateLimiter {
+    buckets: DashMap<(IpAddr, OperationKind), Bucket>,
+    query_limit: f64,
+    mutation_limit: f64,
+    window: Duration,
+    newsletter_limit: f64,
+    newsletter_window: Duration,
+    contact_limit: f64,
+    contact_window: Duration,
+    // trueならX-Forwarded-Forのクライアントアドレスを使う（リバースプロキシの背後で動かす場合）
+    trust_proxy: bool,
+}
+
+impl RateLimiter {
+    pub(crate) fn from_env() -> Self {
+        RateLimiter {
+            buckets: DashMap::new(),
+            query_limit: env_or("RATE_LIMIT_QUERIES", 300.0),
+            mutation_limit: env_or("RATE_LIMIT_MUTATIONS", 30.0),
+            window: Duration::from_secs(env_or("RATE_LIMIT_WINDOW_SECS", 60)),
+            newsletter_limit: env_or("RATE_LIMIT_NEWSLETTER", 5.0),
+            newsletter_window: Duration::from_secs(env_or(
+                "RATE_LIMIT_NEWSLETTER_WINDOW_SECS",
+                3600,
+            )),
+            contact_limit: env_or("RATE_LIMIT_CONTACT", 3.0),
+            contact_window: Duration::from_secs(env_or("RATE_LIMIT_CONTACT_WINDOW_SECS", 3600)),
+            trust_proxy: env_or("TRUST_PROXY", false),
+        }
+    }
+
+    pub(crate) fn client_ip(&self, req: &HttpRequest) -> Option<IpAddr> {
+        if self.trust_proxy {
+            let forwarded = req
+                .headers()
+                .get("X-Forwarded-For")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.split(',').next())
+                .and_then(|ip| ip.trim().parse().ok());
+            if forwarded.is_some() {
+                return forwarded;
+            }
+        }
+        req.peer_addr().map(|addr| addr.ip())
+    }
+
+    // トークンを1つ消費する。足りなければ次のトークンが貯まるまでの時間を返す
+    pub(crate) fn check(&self, ip: IpAddr, kind: OperationKind) -> Result<(), Duration> {
+        let (limit, window) = self.limit(kind);
+        let rate = limit / window.as_secs_f64();
+        let now = Instant::now();
+        let mut bucket = self.buckets.entry((ip, kind)).or_insert(Bucket {
+            tokens: limit,
+            updated_at: now,
+        });
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(limit);
+        bucket.updated_at = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+        }
+    }
+
+    fn limit(&self, kind: OperationKind) -> (f64, Duration) {
+        match kind {
+            OperationKind::Query => (self.query_limit, self.window),
+            OperationKind::Mutation => (self.mutation_limit, self.window),
+            OperationKind::Newsletter => (self.newsletter_limit, self.newsletter_window),
+            OperationKind::Contact => (self.contact_limit, self.contact_window),
+        }
+    }
+
+    // ウィンドウ以上使われていないバケットは満杯に戻っているので捨ててよい
+    fn sweep(&self) {
+        self.buckets.retain(|(_, kind), b| b.updated_at.elapsed() < self.limit(*kind).1);
+    }
+}
+
+pub(crate) async fn run_rate_limit_sweeper(
+    rate_limiter: Arc<RateLimiter>,
+    mut shutdown: ShutdownReceiver,
+) {
+    let mut interval = tokio::time::interval(rate_limiter.window);
+    while wait_next(&mut interval, &mut shutdown).await {
+        rate_limiter.sweep();
+    }
+}
+
+// リクエストで実行する操作（operationNameで選ぶ。指定がなければ全て）
+fn selected_operations<'a>(
+    document: &'a ExecutableDocument,
+    request: &'a async_graphql::Request,
+) -> impl Iterator<Item = &'a OperationDefinition> {
+    document.operations.iter().filter_map(|(name, op)| {
+        let selected = match (&request.operation_name, name) {
+            (Some(operation_name), Some(name)) => operation_name == name.as_str(),
+            _ => true,
+        };
+        selected.then_some(&op.node)
+    })
+}
+
+// 実行する操作がミューテーションか（構文エラーの場合はクエリ扱い）
+pub(crate) fn operation_kind(request: &async_graphql::Request) -> OperationKind {
+    let Ok(document) = async_graphql::parser::parse_query(&request.query) else {
+        return OperationKind::Query;
+    };
+    let is_mutation = selected_operations(&document, request)
+        .any(|op| op.ty == OperationType::Mutation);
+    if is_mutation {
+        OperationKind::Mutation
+    } else {
+        OperationKind::Query
+    }
+}
+
+// 実行するミューテーションに含まれるTHROTTLED_FIELDSのフィールドごとのバケット
+// （別名で並べた分も1つずつ数える）
+pub(crate) fn throttled_fields(request: &async_graphql::Request) -> Vec<OperationKind> {
+    let Ok(document) = async_graphql::parser::parse_query(&request.query) else {
+        return Vec::new();
+    };
+    // 同じフラグメントを何度展開しても、同じフィールドはまとめて1回だけ実行される
+    let mut visited = HashSet::new();
+    let mut kinds = Vec::new();
+    for op in selected_operations(&document, request) {
+        if op.ty == OperationType::Mutation {
+            collect_throttled(&document, &op.selection_set.node, &mut visited, &mut kinds);
+        }
+    }
+    kinds
+}
+
+fn collect_throttled<'a>(
+    document: &'a ExecutableDocument,
+    selection_set: &'a SelectionSet,
+    visited: &mut HashSet<&'a str>,
+    kinds: &mut Vec<OperationKind>,
+) {
+    for item in &selection_set.items {
+        match &item.node {
+            Selection::Field(field) => {
+                let name = field.node.name.node.as_str();
+                if let Some((_, kind)) = THROTTLED_FIELDS.iter().find(|(f, _)| *f == name) {
+                    kinds.push(*kind);
+                }
+            }
+            Selection::InlineFragment(fragment) => {
+                collect_throttled(document, &fragment.node.selection_set.node, visited, kinds);
+            }
+            Selection::FragmentSpread(spread) => {
+                let name = &spread.node.fragment_name.node;
+                if let Some(fragment) = document.fragments.get(name) {
+                    if visited.insert(name.as_str()) {
+                        let selection_set = &fragment.node.selection_set.node;
+                        collect_throttled(document, selection_set, visited, kinds);
+                    }
+                }
+            }
+        }
+    }
+}
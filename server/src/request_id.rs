@@ -0,0 +1,149 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{Error, HttpMessage, HttpRequest};
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextParseQuery, NextRequest,
+};
+use async_graphql::parser::types::ExecutableDocument;
+use async_graphql::{Response, ServerError, ServerResult, Variables};
+use std::sync::{Arc, OnceLock};
+use tracing::Span;
+use tracing_actix_web::{DefaultRootSpanBuilder, RootSpanBuilder};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::telemetry;
+
+pub(crate) const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+// クライアントから受け取るIDの最大長（ログに出すので長すぎるものや制御文字を含むものは使わない）
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+// リクエストごとのID（ログ・レスポンスヘッダー・GraphQLのエラーで共通）
+#[derive(Clone)]
+pub(crate) struct RequestId(pub(crate) String);
+
+impl RequestId {
+    fn generate() -> Self {
+        RequestId(uuid::Uuid::new_v4().to_string())
+    }
+
+    fn from_header(value: &HeaderValue) -> Option<Self> {
+        let value = value.to_str().ok()?;
+        let valid = !value.is_empty()
+            && value.len() <= MAX_REQUEST_ID_LEN
+            && value.bytes().all(|b| b.is_ascii_graphic());
+        valid.then(|| RequestId(value.to_string()))
+    }
+
+    // assign_request_idを通っていないリクエストでは新しく作る
+    pub(crate) fn of(req: &HttpRequest) -> Self {
+        req.extensions()
+            .get::<RequestId>()
+            .cloned()
+            .unwrap_or_else(RequestId::generate)
+    }
+
+    // エラーのextensions.requestIdに設定する
+    pub(crate) fn attach(&self, errors: &mut [ServerError]) {
+        for error in errors {
+            error
+                .extensions
+                .get_or_insert_with(Default::default)
+                .set("requestId", self.0.as_str());
+        }
+    }
+}
+
+// X-Request-Idを受け取るか新しく作り、リクエストに保存してレスポンスにも付ける
+// TracingLoggerより外側に置き、リクエストのspanにも同じIDを出す
+pub(crate) async fn assign_request_id(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(RequestId::from_header)
+        .unwrap_or_else(RequestId::generate);
+    req.extensions_mut().insert(request_id.clone());
+    let mut res = next.call(req).await?;
+    if let Ok(value) = HeaderValue::from_str(&request_id.0) {
+        res.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    Ok(res)
+}
+
+// リクエストのspan（request_idはtracing-actix-webが作るものではなくassign_request_idのもの）
+pub(crate) struct RequestIdRootSpan;
+
+impl RootSpanBuilder for RequestIdRootSpan {
+    fn on_request_start(request: &ServiceRequest) -> Span {
+        let request_id = request
+            .extensions()
+            .get::<RequestId>()
+            .map(|id| id.0.clone())
+            .unwrap_or_default();
+        let route = request.match_pattern().unwrap_or_default();
+        let span = tracing::info_span!(
+            "HTTP request",
+            otel.name = %format!("{} {}", request.method(), route),
+            otel.kind = "server",
+            http.method = %request.method(),
+            http.route = %route,
+            http.target = %request.uri().path_and_query().map(|p| p.as_str()).unwrap_or(""),
+            http.client_ip = %request.connection_info().realip_remote_addr().unwrap_or(""),
+            http.status_code = tracing::field::Empty,
+            otel.status_code = tracing::field::Empty,
+            request_id = %request_id,
+            exception.message = tracing::field::Empty,
+            exception.details = tracing::field::Empty,
+        );
+        // traceparentがあれば呼び出し元のトレースにつなげる
+        let _ = span.set_parent(telemetry::extract_context(request.headers()));
+        span
+    }
+
+    fn on_request_end<B: MessageBody>(span: Span, outcome: &Result<ServiceResponse<B>, Error>) {
+        DefaultRootSpanBuilder::on_request_end(span, outcome);
+    }
+}
+
+// リゾルバーなどで作ったエラーにもリクエストIDを付ける（バッチの要素は同じIDを共有する）
+pub(crate) struct GraphQLRequestId;
+
+impl ExtensionFactory for GraphQLRequestId {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(GraphQLRequestIdExtension { request_id: OnceLock::new() })
+    }
+}
+
+struct GraphQLRequestIdExtension {
+    // リクエストのデータはrequestの時点ではまだ参照できないので、parse_queryで受け取っておく
+    request_id: OnceLock<RequestId>,
+}
+
+#[async_trait::async_trait]
+impl Extension for GraphQLRequestIdExtension {
+    async fn request(&self, ctx: &ExtensionContext<'_>, next: NextRequest<'_>) -> Response {
+        let mut response = next.run(ctx).await;
+        if let Some(request_id) = self.request_id.get() {
+            request_id.attach(&mut response.errors);
+        }
+        response
+    }
+
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        if let Some(request_id) = ctx.data_opt::<RequestId>() {
+            let _ = self.request_id.set(request_id.clone());
+        }
+        next.run(ctx, query, variables).await
+    }
+}
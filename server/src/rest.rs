@@ -0,0 +1,123 @@
+use actix_web::error::InternalError;
+use actix_web::{web, HttpResponse};
+use async_graphql::{ErrorExtensionValues, Value, ID};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::error::{not_found, AppError};
+use crate::models::{Post, User};
+use crate::pagination::{Page, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+use crate::search::PostFilter;
+use crate::store::AppStorage;
+use crate::validation::parse_id;
+
+// 読み取り専用のREST API（GraphQLのpostsとpostとuserと同じストレージと条件を使う）
+// 認証はしないので、公開済みの投稿だけを返し、書き込みのルートは作らない
+pub(crate) fn configure_rest(cfg: &mut web::ServiceConfig) {
+    // クエリパラメーターが読めない場合もJSONでエラーを返す
+    let query_config = web::QueryConfig::default().error_handler(|error, _| {
+        let response = HttpResponse::BadRequest().json(json!({
+            "error": { "code": "VALIDATION_FAILED", "message": error.to_string() },
+        }));
+        InternalError::from_response(error, response).into()
+    });
+    cfg.service(
+        web::resource("/api/posts")
+            .app_data(query_config)
+            .route(web::get().to(list_posts_handler)),
+    )
+    .service(web::resource("/api/posts/{id}").route(web::get().to(post_handler)))
+    .service(web::resource("/api/users/{id}").route(web::get().to(user_handler)));
+}
+
+#[derive(Deserialize)]
+pub(crate) struct PostsParams {
+    limit: Option<i32>,
+    offset: Option<i32>,
+    tag: Option<String>,
+}
+
+async fn list_posts_handler(
+    storage: web::Data<AppStorage>,
+    params: web::Query<PostsParams>,
+) -> HttpResponse {
+    let params = params.into_inner();
+    let result = async {
+        let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        let offset = params.offset.unwrap_or(0);
+        if !(0..=MAX_PAGE_SIZE).contains(&limit) || offset < 0 {
+            return Err(AppError::ValidationFailed(format!(
+                "limit must be between 0 and {} and offset must not be negative",
+                MAX_PAGE_SIZE
+            ))
+            .into());
+        }
+        let filter = PostFilter { tag: params.tag, ..PostFilter::default() }.validate()?;
+        let posts = storage.list_posts(&filter, Page::new(limit, offset)).await?;
+        Ok(posts.iter().map(post_json).collect::<Vec<_>>())
+    };
+    json_response(result.await)
+}
+
+async fn post_handler(storage: web::Data<AppStorage>, id: web::Path<String>) -> HttpResponse {
+    let result = async {
+        let id = parse_id(ID::from(id.into_inner()), "id")?;
+        match storage.get_post(&id).await? {
+            Some(post) if post.is_visible(false) => Ok(post_json(&post)),
+            _ => Err(not_found("Post")),
+        }
+    };
+    json_response(result.await)
+}
+
+async fn user_handler(storage: web::Data<AppStorage>, id: web::Path<String>) -> HttpResponse {
+    let result = async {
+        let id = parse_id(ID::from(id.into_inner()), "id")?;
+        match storage.get_user(&id).await? {
+            Some(user) => Ok(user_json(&user)),
+            None => Err(not_found("User")),
+        }
+    };
+    json_response(result.await)
+}
+
+// 保存用の項目（パスワードのハッシュ、メールアドレス、語数のキャッシュ）は返さない
+fn post_json(post: &Post) -> serde_json::Value {
+    without_fields(post, &["text_stats"])
+}
+
+fn user_json(user: &User) -> serde_json::Value {
+    without_fields(user, &["password_hash", "email", "email_on_comment"])
+}
+
+fn without_fields(value: &impl Serialize, fields: &[&str]) -> serde_json::Value {
+    let mut value = serde_json::to_value(value).unwrap_or_default();
+    if let Some(object) = value.as_object_mut() {
+        for field in fields {
+            object.remove(*field);
+        }
+    }
+    value
+}
+
+// GraphQLのエラーのextensions.codeをHTTPのステータスにする
+fn json_response<T: Serialize>(result: async_graphql::Result<T>) -> HttpResponse {
+    let error = match result {
+        Ok(body) => return HttpResponse::Ok().json(body),
+        Err(error) => error,
+    };
+    let code = error_code(error.extensions.as_ref()).unwrap_or_else(|| "INTERNAL".to_string());
+    let mut response = match code.as_str() {
+        "NOT_FOUND" => HttpResponse::NotFound(),
+        "VALIDATION_FAILED" | "INVALID_ID" => HttpResponse::BadRequest(),
+        _ => HttpResponse::InternalServerError(),
+    };
+    response.json(json!({ "error": { "code": code, "message": error.message } }))
+}
+
+fn error_code(extensions: Option<&ErrorExtensionValues>) -> Option<String> {
+    match extensions?.get("code")? {
+        Value::String(code) => Some(code.clone()),
+        _ => None,
+    }
+}
@@ -0,0 +1,166 @@
+use async_graphql::{Enum, Scalar, ScalarType, Value};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use url::Url;
+
+use crate::error::AppError;
+
+// DateTimeスカラー型
+// 入力はRFC 3339（Tの代わりに空白も可）、日付のみ（UTCの0時）、UNIX時間の整数を受け付け、出力はUTCのRFC 3339
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct DateTimeScalar(pub DateTime<Utc>);
+
+// エラーメッセージに含める値の最大文字数
+const MAX_CHARS_IN_ERROR: usize = 100;
+
+// これ以上の整数はミリ秒とみなす（秒なら5138年）
+const MIN_UNIX_MILLIS: i64 = 100_000_000_000;
+
+const ACCEPTED_DATETIME_FORMATS: &str = "expected an RFC 3339 date-time \
+    (2024-06-01T09:00:00+09:00, a space instead of T is allowed), a date (2024-06-01, \
+    midnight UTC) or unix time as an integer (seconds, or milliseconds from 100000000000)";
+
+impl DateTimeScalar {
+    fn parse_str(s: &str) -> Option<DateTime<Utc>> {
+        if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+            return Some(dt.with_timezone(&Utc));
+        }
+        if let (Some(date), Some(" "), Some(time)) = (s.get(..10), s.get(10..11), s.get(11..)) {
+            let dt = DateTime::parse_from_rfc3339(&format!("{}T{}", date, time)).ok()?;
+            return Some(dt.with_timezone(&Utc));
+        }
+        let date = NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()?;
+        Some(date.and_time(NaiveTime::MIN).and_utc())
+    }
+
+    fn parse_unix(n: i64) -> Option<DateTime<Utc>> {
+        if n.unsigned_abs() >= MIN_UNIX_MILLIS as u64 {
+            DateTime::from_timestamp_millis(n)
+        } else {
+            DateTime::from_timestamp(n, 0)
+        }
+    }
+}
+
+// 日時のフィールドの出力形式（引数で指定する）
+#[derive(Enum, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum DateTimeFormat {
+    #[default]
+    #[graphql(name = "RFC3339")]
+    Rfc3339,
+    #[graphql(name = "RFC2822")]
+    Rfc2822,
+    // 日付のみ（2024-06-01）
+    Date,
+    // UNIX時間の秒（タイムゾーンに関係しない）
+    Unix,
+}
+
+impl DateTimeScalar {
+    // タイムゾーンはIANAの名前（Asia/Tokyoなど）。省略時はUTC
+    pub(crate) fn format(
+        &self,
+        timezone: Option<&str>,
+        format: DateTimeFormat,
+    ) -> async_graphql::Result<String> {
+        let tz = match timezone {
+            Some(name) => name.parse::<Tz>().map_err(|_| {
+                AppError::ValidationFailed(format!(
+                    "Unknown time zone \"{}\": use an IANA name such as Asia/Tokyo",
+                    truncate_for_error(name)
+                ))
+            })?,
+            None => Tz::UTC,
+        };
+        let dt = self.0.with_timezone(&tz);
+        Ok(match format {
+            DateTimeFormat::Rfc3339 => dt.to_rfc3339(),
+            DateTimeFormat::Rfc2822 => dt.to_rfc2822(),
+            DateTimeFormat::Date => dt.format("%Y-%m-%d").to_string(),
+            DateTimeFormat::Unix => dt.timestamp().to_string(),
+        })
+    }
+}
+
+#[Scalar]
+impl ScalarType for DateTimeScalar {
+    fn parse(value: Value) -> async_graphql::InputValueResult<Self> {
+        let dt = match &value {
+            Value::String(s) => DateTimeScalar::parse_str(s).ok_or_else(|| {
+                let shown = truncate_for_error(s);
+                format!("Invalid DateTime \"{}\": {}", shown, ACCEPTED_DATETIME_FORMATS)
+            }),
+            Value::Number(n) => match n.as_i64() {
+                Some(n) => DateTimeScalar::parse_unix(n)
+                    .ok_or_else(|| format!("DateTime out of range: unix time {}", n)),
+                None => Err(format!("Invalid DateTime {}: {}", n, ACCEPTED_DATETIME_FORMATS)),
+            },
+            _ => return Err(async_graphql::InputValueError::expected_type(value)),
+        };
+        dt.map(DateTimeScalar).map_err(async_graphql::InputValueError::custom)
+    }
+
+    fn to_value(&self) -> Value {
+        Value::String(self.0.to_rfc3339())
+    }
+}
+
+// URLスカラー型（スキーマでは Url）。アバターやカバー画像、Webサイトなどリンクのフィールドに使う
+// 絶対URLのhttp/httpsのみ受け付け、スキームとホストの小文字化とデフォルトのポートの削除をして保存する
+// 保存済みのデータは検証せずに読み込む
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct UrlScalar(pub String);
+
+impl UrlScalar {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for UrlScalar {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, String> {
+        let shown = truncate_for_error(value);
+        let url = Url::parse(value).map_err(|e| {
+            format!("Invalid URL \"{}\" ({}): expected an absolute http or https URL", shown, e)
+        })?;
+        match url.scheme() {
+            "http" | "https" => Ok(UrlScalar(url.to_string())),
+            scheme @ ("javascript" | "data") => Err(format!(
+                "{}: URLs are not allowed (\"{}\"): use an http or https URL",
+                scheme, shown
+            )),
+            scheme => Err(format!(
+                "Unsupported URL scheme \"{}\" in \"{}\": only http and https are allowed",
+                scheme, shown
+            )),
+        }
+    }
+}
+
+fn truncate_for_error(value: &str) -> String {
+    if value.chars().count() <= MAX_CHARS_IN_ERROR {
+        return value.to_string();
+    }
+    let truncated: String = value.chars().take(MAX_CHARS_IN_ERROR).collect();
+    format!("{}…", truncated)
+}
+
+#[Scalar(name = "Url")]
+impl ScalarType for UrlScalar {
+    fn parse(value: Value) -> async_graphql::InputValueResult<Self> {
+        if let Value::String(s) = &value {
+            s.parse().map_err(async_graphql::InputValueError::custom)
+        } else {
+            Err(async_graphql::InputValueError::expected_type(value))
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        Value::String(self.0.clone())
+    }
+}
@@ -0,0 +1,168 @@
+use async_graphql::{Enum, ID};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::error::AppError;
+use crate::models::{Post, User};
+use crate::scalars::DateTimeScalar;
+use crate::validation::normalize_tag;
+
+// 並び順
+#[derive(Enum, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum PostSort {
+    #[default]
+    PublishedAtDesc,
+    PublishedAtAsc,
+    TitleAsc,
+    TitleDesc,
+    MostLiked,
+}
+
+// MostLikedの場合のみlike_countsを参照する
+pub(crate) fn sort_posts(posts: &mut [Post], sort: PostSort, like_counts: &HashMap<ID, usize>) {
+    // 同値の場合はIDで並べて順序を安定させる
+    let by_published_at = |a: &Post, b: &Post| {
+        a.published_at.0.cmp(&b.published_at.0).then_with(|| a.id.cmp(&b.id))
+    };
+    let by_title = |a: &Post, b: &Post| {
+        a.title
+            .to_lowercase()
+            .cmp(&b.title.to_lowercase())
+            .then_with(|| a.title.cmp(&b.title))
+            .then_with(|| a.id.cmp(&b.id))
+    };
+    match sort {
+        PostSort::PublishedAtDesc => posts.sort_by(|a, b| by_published_at(b, a)),
+        PostSort::PublishedAtAsc => posts.sort_by(by_published_at),
+        PostSort::TitleAsc => posts.sort_by(by_title),
+        PostSort::TitleDesc => posts.sort_by(|a, b| by_title(b, a)),
+        PostSort::MostLiked => {
+            let likes = |p: &Post| like_counts.get(&p.id).copied().unwrap_or(0);
+            posts.sort_by(|a, b| likes(b).cmp(&likes(a)).then_with(|| by_published_at(b, a)))
+        }
+    }
+}
+
+// 投稿の絞り込み条件
+#[derive(Default)]
+pub struct PostFilter {
+    pub(crate) tag: Option<String>,
+    pub(crate) published_after: Option<DateTimeScalar>,
+    pub(crate) published_before: Option<DateTimeScalar>,
+    pub(crate) include_drafts: bool,
+    // include_draftsの場合に、下書きはこのユーザーのものに限る（他のユーザーの投稿は公開済みのみ）
+    pub(crate) draft_author_id: Option<ID>,
+    // 指定した場合はこれらのユーザーの投稿に限る
+    pub(crate) author_ids: Option<Vec<ID>>,
+    // trueならカバー画像のある投稿、falseならない投稿に限る
+    pub(crate) has_cover_image: Option<bool>,
+}
+
+impl PostFilter {
+    pub(crate) fn validate(self) -> async_graphql::Result<Self> {
+        if let (Some(after), Some(before)) = (self.published_after, self.published_before) {
+            if after.0 > before.0 {
+                return Err(AppError::ValidationFailed(
+                    "publishedAfter must not be later than publishedBefore".into(),
+                ).into());
+            }
+        }
+        Ok(PostFilter {
+            // タグは保存時と同じように正規化し、大文字小文字を区別しない
+            tag: self.tag.map(|t| normalize_tag(&t).to_lowercase()),
+            ..self
+        })
+    }
+
+    // 公開日時は下限を含み、上限を含まない
+    pub(crate) fn matches(&self, post: &Post) -> bool {
+        let published_at = post.published_at.0;
+        let include_drafts = self.include_drafts
+            && self.draft_author_id.as_ref().is_none_or(|id| *id == post.author_id);
+        post.is_visible(include_drafts)
+            && self
+                .tag
+                .as_deref()
+                .is_none_or(|tag| post.tags.iter().any(|t| t.trim().to_lowercase() == tag))
+            && self.published_after.is_none_or(|after| published_at >= after.0)
+            && self.published_before.is_none_or(|before| published_at < before.0)
+            && self
+                .author_ids
+                .as_ref()
+                .is_none_or(|author_ids| author_ids.contains(&post.author_id))
+            && self
+                .has_cover_image
+                .is_none_or(|has_cover| post.cover_image_url.is_some() == has_cover)
+    }
+}
+
+// 全文検索
+// 現状はメモリ上の線形スキャン。実際のインデックスに差し替える場合はSearchIndexを実装する
+pub(crate) trait SearchIndex: Send + Sync {
+    fn search(&self, posts: &[Post], query: &str) -> Vec<Post>;
+}
+
+pub(crate) type SearchIndexStore = Arc<dyn SearchIndex>;
+
+pub(crate) struct LinearScanIndex;
+
+impl SearchIndex for LinearScanIndex {
+    fn search(&self, posts: &[Post], query: &str) -> Vec<Post> {
+        let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        if terms.is_empty() {
+            return Vec::new();
+        }
+
+        // タイトルに一致した語の数でランク付けし、同順位は新しい順
+        let mut hits: Vec<(usize, &Post)> = posts
+            .iter()
+            .filter_map(|post| {
+                let title = post.title.to_lowercase();
+                let body = post.body.to_lowercase();
+                let mut title_hits = 0;
+                for term in &terms {
+                    if title.contains(term.as_str()) {
+                        title_hits += 1;
+                    } else if !body.contains(term.as_str()) {
+                        return None;
+                    }
+                }
+                Some((title_hits, post))
+            })
+            .collect();
+        hits.sort_by_key(|(title_hits, post)| {
+            std::cmp::Reverse((*title_hits, post.published_at.0))
+        });
+        hits.into_iter().map(|(_, post)| post.clone()).collect()
+    }
+}
+
+// ユーザーの絞り込み条件
+#[derive(Default)]
+pub struct UserFilter {
+    // 名前の部分一致（大文字小文字を区別しない）
+    pub(crate) search: Option<String>,
+    // trueなら自己紹介が一致するユーザーも含める
+    pub(crate) include_bio: bool,
+}
+
+impl UserFilter {
+    pub(crate) fn search(&self) -> Option<String> {
+        match self.search.as_deref().map(str::trim) {
+            None | Some("") => None,
+            Some(search) => Some(search.to_lowercase()),
+        }
+    }
+}
+
+// ユーザー検索
+pub(crate) fn user_matches(user: &User, filter: &UserFilter) -> bool {
+    match filter.search() {
+        None => true,
+        Some(search) => {
+            user.name.to_lowercase().contains(&search)
+                || filter.include_bio
+                    && user.bio.as_ref().is_some_and(|bio| bio.to_lowercase().contains(&search))
+        }
+    }
+}
@@ -0,0 +1,364 @@
+use async_graphql::ID;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use uuid::Uuid;
+
+use crate::auth::hash_password;
+use crate::markdown::{mentioned_handles, TextStats};
+use crate::models::{Post, PostStatus, Role, User};
+use crate::scalars::{DateTimeScalar, UrlScalar};
+use crate::store::{PostTable, UserTable};
+use crate::validation::{
+    derive_handle, is_well_formed_id, slugify, validate_email, validate_handle, validate_slug,
+    validate_user_name,
+};
+
+// 初期データ（DATA_FILEを使わない場合、ファイルがまだない場合、データベースが空の場合に使う）
+// SEED_FILEを指定した場合はそのファイル、指定しない場合は組み込みのデータを使う
+pub struct Seed {
+    users: Vec<User>,
+    posts: Vec<Post>,
+    // パスワードを省略したユーザーがいるか
+    uses_default_password: bool,
+}
+
+// builtin/loadでパスワードを省略したユーザーのパスワード（開発・テスト用）
+const DEV_PASSWORD: &str = "password";
+
+// SEED_FILEの形式（JSONのオブジェクト、またはTOMLの[[users]]と[[posts]]の配列）
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SeedFile {
+    #[serde(default)]
+    users: Vec<SeedUser>,
+    #[serde(default)]
+    posts: Vec<SeedPost>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SeedUser {
+    id: String,
+    name: String,
+    // 省略時は名前かIDから作る
+    handle: Option<String>,
+    avatar_url: Option<String>,
+    // Gravatarのアバターに使う
+    email: Option<String>,
+    // ADMIN / AUTHOR / READER（省略時はAUTHOR）
+    role: Option<String>,
+    // 省略時はSEED_USER_PASSWORD（未指定なら起動ごとにランダムに作る）
+    password: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SeedPost {
+    id: String,
+    title: String,
+    // 省略時はタイトルから生成する
+    slug: Option<String>,
+    author_id: String,
+    body: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    // 省略時は起動した日時
+    published_at: Option<SeedDateTime>,
+    #[serde(default)]
+    draft: bool,
+}
+
+// JSONでは文字列、TOMLでは日時リテラルでも書ける
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SeedDateTime {
+    Text(String),
+    Toml(toml::value::Datetime),
+}
+
+impl SeedDateTime {
+    fn parse(&self) -> Result<DateTime<Utc>, String> {
+        let text = match self {
+            SeedDateTime::Text(text) => text.clone(),
+            SeedDateTime::Toml(datetime) => datetime.to_string(),
+        };
+        DateTime::parse_from_rfc3339(&text)
+            .map(|dt| dt.with_timezone(&Utc))
+            .map_err(|_| format!("expected an RFC 3339 date-time with a time zone, got {}", text))
+    }
+}
+
+impl Seed {
+    // 組み込みの初期データ（開発・テスト用。パスワードは全員DEV_PASSWORD）
+    pub fn builtin() -> Self {
+        Seed::builtin_with_password(DEV_PASSWORD)
+    }
+
+    fn builtin_with_password(default_password: &str) -> Self {
+        let user = |id: &str, name: &str, avatar_url: Option<&str>, role: &str| SeedUser {
+            id: id.to_string(),
+            name: name.to_string(),
+            handle: None,
+            avatar_url: avatar_url.map(str::to_string),
+            email: None,
+            role: Some(role.to_string()),
+            password: None,
+        };
+        let file = SeedFile {
+            users: vec![
+                user("1", "髙橋慶祐", Some("https://example.com/avatar.png"), "ADMIN"),
+                user("2", "佐藤太郎", None, "AUTHOR"),
+                user("3", "鈴木花子", None, "AUTHOR"),
+                user("4", "伊藤次郎", None, "AUTHOR"),
+                user("5", "加藤三郎", None, "AUTHOR"),
+            ],
+            posts: vec![SeedPost {
+                id: "1".to_string(),
+                title: "はじめまして".to_string(),
+                slug: None,
+                author_id: "1".to_string(),
+                body: "これは最初の投稿です。".to_string(),
+                tags: vec!["はじめに".to_string(), "ブログ".to_string()],
+                published_at: None,
+                draft: false,
+            }],
+        };
+        match Seed::from_file(file, default_password) {
+            Ok(seed) => seed,
+            Err(errors) => panic!("invalid built-in seed: {}", errors.join(", ")),
+        }
+    }
+
+    // SEED_FILEを指定した場合はそのファイルを読み込む
+    // パスワードを省略したユーザーはSEED_USER_PASSWORD。未指定ならランダムに作り、ログに1度だけ出す
+    pub fn from_env() -> Result<Self, String> {
+        let password = std::env::var("SEED_USER_PASSWORD").ok();
+        let default_password = password
+            .clone()
+            .unwrap_or_else(|| Uuid::new_v4().simple().to_string());
+        let seed = match std::env::var("SEED_FILE") {
+            Ok(path) => Seed::load_with_password(Path::new(&path), &default_password)?,
+            Err(_) => Seed::builtin_with_password(&default_password),
+        };
+        if password.is_none() && seed.uses_default_password {
+            tracing::warn!(
+                password = %default_password,
+                "SEED_USER_PASSWORD is not set; seed users without a password get this generated \
+                 password when the storage is initialized"
+            );
+        }
+        Ok(seed)
+    }
+
+    // 拡張子が.tomlならTOML、それ以外はJSONとして読む
+    // 構文エラーは行と列、内容のエラーはフィールド（posts[0].author_idなど）ごとに返す
+    pub fn load(path: &Path) -> Result<Self, String> {
+        Seed::load_with_password(path, DEV_PASSWORD)
+    }
+
+    fn load_with_password(path: &Path, default_password: &str) -> Result<Self, String> {
+        let with_path = |e: String| format!("{}: {}", path.display(), e.trim_end());
+        let text = std::fs::read_to_string(path).map_err(|e| with_path(e.to_string()))?;
+        let file: SeedFile = if path.extension().is_some_and(|ext| ext == "toml") {
+            toml::from_str(&text).map_err(|e| with_path(e.to_string()))?
+        } else {
+            serde_json::from_str(&text).map_err(|e| with_path(e.to_string()))?
+        };
+        Seed::from_file(file, default_password)
+            .map_err(|errors| errors.into_iter().map(with_path).collect::<Vec<_>>().join("\n"))
+    }
+
+    pub fn user_count(&self) -> usize {
+        self.users.len()
+    }
+
+    pub fn post_count(&self) -> usize {
+        self.posts.len()
+    }
+
+    pub(crate) fn tables(&self) -> (UserTable, PostTable) {
+        let mut users = UserTable::default();
+        for user in &self.users {
+            users.insert(user.clone()).expect("duplicate seed user id");
+        }
+        let mut posts = PostTable::default();
+        for post in &self.posts {
+            posts.insert(post.clone()).expect("duplicate seed post id");
+        }
+        (users, posts)
+    }
+
+    // 全てのエラーをまとめて返す
+    fn from_file(file: SeedFile, default_password: &str) -> Result<Self, Vec<String>> {
+        let mut errors = Vec::new();
+
+        // ユーザー
+        // 同じパスワードは1回だけハッシュ化する
+        let uses_default_password = file.users.iter().any(|seed| seed.password.is_none());
+        let mut password_hashes: HashMap<String, String> = HashMap::new();
+        let mut user_ids = HashSet::new();
+        // 省略したハンドルは、指定されたハンドルと重ならないように作る
+        let mut handles: HashSet<String> = file
+            .users
+            .iter()
+            .filter_map(|seed| validate_handle(seed.handle.as_deref()?).ok())
+            .collect();
+        let mut explicit_handles = HashSet::new();
+        let mut users = Vec::new();
+        for (i, seed) in file.users.into_iter().enumerate() {
+            let field = |name: &str| format!("users[{}].{}", i, name);
+            let id = check(&mut errors, field("id"), check_id(&seed.id, "user", &mut user_ids));
+            let name = validate_user_name(&seed.name).map_err(|e| e.message);
+            let name = check(&mut errors, field("name"), name);
+            let handle = match seed.handle.as_deref() {
+                Some(handle) => validate_handle(handle).map_err(|e| e.message).and_then(|handle| {
+                    if explicit_handles.insert(handle.clone()) {
+                        Ok(handle)
+                    } else {
+                        Err(format!("handle \"{}\" is already used by another user", handle))
+                    }
+                }),
+                None => {
+                    let id = ID::from(seed.id.as_str());
+                    let handle = derive_handle(&seed.name, &id, |h| handles.contains(h));
+                    handles.insert(handle.clone());
+                    Ok(handle)
+                }
+            };
+            let handle = check(&mut errors, field("handle"), handle);
+            let avatar_url = seed.avatar_url.as_deref().map(str::parse::<UrlScalar>).transpose();
+            let avatar_url = check(&mut errors, field("avatar_url"), avatar_url);
+            let email = seed.email.as_deref().map(validate_email).transpose();
+            let email = check(&mut errors, field("email"), email.map_err(|e| e.message));
+            let role = match seed.role.as_deref() {
+                None | Some("AUTHOR") => Ok(Role::Author),
+                Some("ADMIN") => Ok(Role::Admin),
+                Some("READER") => Ok(Role::Reader),
+                Some(other) => Err(format!("expected ADMIN, AUTHOR or READER, got {}", other)),
+            };
+            let role = check(&mut errors, field("role"), role);
+            let (Some(()), Some(name), Some(handle), Some(avatar_url), Some(email), Some(role)) =
+                (id, name, handle, avatar_url, email, role)
+            else {
+                continue;
+            };
+
+            let password = seed.password.unwrap_or_else(|| default_password.to_string());
+            let password_hash = password_hashes
+                .entry(password)
+                .or_insert_with_key(|password| {
+                    hash_password(password).expect("failed to hash seed password")
+                })
+                .clone();
+            users.push(User {
+                id: ID::from(seed.id),
+                name,
+                handle,
+                avatar_url,
+                email,
+                email_on_comment: false,
+                bio: None,
+                website: None,
+                location: None,
+                role,
+                password_hash: Some(password_hash),
+            });
+        }
+
+        // 投稿
+        let handle_ids: HashMap<&str, &ID> =
+            users.iter().map(|user| (user.handle.as_str(), &user.id)).collect();
+        let seeded_at = Utc::now();
+        let mut post_ids = HashSet::new();
+        let mut slugs = HashSet::new();
+        let mut posts = Vec::new();
+        for (i, seed) in file.posts.into_iter().enumerate() {
+            let field = |name: &str| format!("posts[{}].{}", i, name);
+            let id = check(&mut errors, field("id"), check_id(&seed.id, "post", &mut post_ids));
+            // ユーザーの存在確認
+            let author = if user_ids.contains(&seed.author_id) {
+                Ok(())
+            } else {
+                Err(format!("user \"{}\" does not exist", seed.author_id))
+            };
+            let author = check(&mut errors, field("author_id"), author);
+            let post_id = ID::from(seed.id);
+            let slug = match seed.slug {
+                Some(slug) => validate_slug(&slug).map(|_| slug).map_err(|e| e.message),
+                None => Ok(slugify(&seed.title, &post_id)),
+            };
+            let slug = slug.and_then(|slug| {
+                if slugs.insert(slug.clone()) {
+                    Ok(slug)
+                } else {
+                    Err(format!("slug \"{}\" is already used by another post", slug))
+                }
+            });
+            let slug = check(&mut errors, field("slug"), slug);
+            let published_at = seed.published_at.as_ref().map(SeedDateTime::parse).transpose();
+            let published_at = check(&mut errors, field("published_at"), published_at);
+            let (Some(()), Some(()), Some(slug), Some(published_at)) =
+                (id, author, slug, published_at)
+            else {
+                continue;
+            };
+
+            let published_at = DateTimeScalar(published_at.unwrap_or(seeded_at));
+            let mentioned_user_ids = mentioned_handles(&seed.body)
+                .iter()
+                .filter_map(|handle| handle_ids.get(handle.as_str()).map(|&id| id.clone()))
+                .collect();
+            posts.push(Post {
+                id: post_id,
+                title: seed.title,
+                slug,
+                author_id: ID::from(seed.author_id),
+                text_stats: Some(TextStats::of(&seed.body)),
+                body: seed.body,
+                tags: seed.tags,
+                cover_image_url: None,
+                cover_image_alt: None,
+                seo_description: None,
+                canonical_url: None,
+                status: if seed.draft { PostStatus::Draft } else { PostStatus::Published },
+                published_at,
+                scheduled_at: None,
+                updated_at: published_at,
+                deleted_at: None,
+                revisions: Vec::new(),
+                mentioned_user_ids,
+            });
+        }
+
+        if errors.is_empty() {
+            Ok(Seed {
+                users,
+                posts,
+                uses_default_password,
+            })
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+// エラーならフィールド名を付けて記録する
+fn check<T>(errors: &mut Vec<String>, field: String, result: Result<T, String>) -> Option<T> {
+    result.map_err(|e| errors.push(format!("{}: {}", field, e))).ok()
+}
+
+// IDは空でなく、ファイル内で重複しないこと
+fn check_id(id: &str, kind: &str, ids: &mut HashSet<String>) -> Result<(), String> {
+    if id.trim().is_empty() {
+        return Err("must not be empty".to_string());
+    }
+    if !is_well_formed_id(id) {
+        return Err(format!("expected a UUID or an integer, got \"{}\"", id));
+    }
+    if !ids.insert(id.to_string()) {
+        return Err(format!("duplicate {} id \"{}\"", kind, id));
+    }
+    Ok(())
+}
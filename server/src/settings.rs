@@ -0,0 +1,152 @@
+use actix_web::cookie::SameSite;
+
+// 設定値（環境変数で上書き可能）
+#[derive(Clone)]
+pub(crate) struct Settings {
+    pub(crate) max_revisions: usize,
+    pub(crate) max_comment_length: usize,
+    // 投稿の本文の最大文字数
+    pub(crate) max_post_length: usize,
+    pub(crate) max_comment_depth: usize,
+    // 読了時間の目安に使う1分あたりに読む単語数とCJKの文字数
+    pub(crate) reading_words_per_minute: u32,
+    pub(crate) reading_chars_per_minute: u32,
+    pub(crate) min_password_length: usize,
+    pub(crate) refresh_token_expiry: chrono::Duration,
+    pub(crate) session_expiry: chrono::Duration,
+    pub(crate) session_cookie_same_site: SameSite,
+    pub(crate) max_query_depth: usize,
+    pub(crate) max_query_complexity: usize,
+    pub(crate) max_batch_size: usize,
+    // これより時間のかかったGraphQLの操作をWARNでログに出す
+    pub(crate) slow_query_threshold: std::time::Duration,
+    // フィードのリンクに使うサイトのURL（末尾の/なし）とタイトル
+    pub(crate) site_base_url: String,
+    pub(crate) site_title: String,
+    // フィードに載せる投稿数
+    pub(crate) feed_size: usize,
+    // サイトマップ1つに載せる投稿数（超えるとサイトマップインデックスにする）
+    pub(crate) sitemap_page_size: usize,
+    // アップロードした画像の保存先と、1ファイルの大きさの上限（バイト）
+    pub(crate) uploads_dir: std::path::PathBuf,
+    pub(crate) max_upload_size: usize,
+    // アバターを設定していないユーザーのGravatarに画像がない場合の画像（d=の値）
+    pub(crate) gravatar_default: String,
+    // Webhookの送信が失敗した場合の再試行の回数と、最初の再試行までの待ち時間（毎回2倍にする）
+    pub(crate) webhook_max_retries: u32,
+    pub(crate) webhook_retry_base: std::time::Duration,
+    // Webhookの1回の送信を待つ時間
+    pub(crate) webhook_timeout: std::time::Duration,
+    // メールでの通知に使うSMTPサーバー（SMTP_HOSTを指定しなければメールは送らない）
+    pub(crate) smtp: Option<SmtpSettings>,
+    // デバッグ用の情報をレスポンスに含める
+    pub(crate) debug: bool,
+}
+
+impl Settings {
+    pub(crate) fn from_env() -> Self {
+        Settings {
+            max_revisions: env_or("MAX_POST_REVISIONS", 50),
+            max_comment_length: env_or("MAX_COMMENT_LENGTH", 2000),
+            max_post_length: env_or("MAX_POST_LENGTH", 100_000),
+            max_comment_depth: env_or("MAX_COMMENT_DEPTH", 1),
+            reading_words_per_minute: env_or("READING_WORDS_PER_MINUTE", 200),
+            reading_chars_per_minute: env_or("READING_CHARS_PER_MINUTE", 500),
+            min_password_length: env_or("MIN_PASSWORD_LENGTH", 8),
+            refresh_token_expiry: chrono::Duration::seconds(env_or(
+                "REFRESH_TOKEN_EXPIRY_SECS",
+                30 * 24 * 60 * 60,
+            )),
+            session_expiry: chrono::Duration::seconds(env_or(
+                "SESSION_EXPIRY_SECS",
+                7 * 24 * 60 * 60,
+            )),
+            session_cookie_same_site: match std::env::var("SESSION_COOKIE_SAMESITE")
+                .unwrap_or_default()
+                .to_lowercase()
+                .as_str()
+            {
+                "strict" => SameSite::Strict,
+                "none" => SameSite::None,
+                _ => SameSite::Lax,
+            },
+            max_query_depth: env_or("MAX_QUERY_DEPTH", 10),
+            max_query_complexity: env_or("MAX_QUERY_COMPLEXITY", 2000),
+            max_batch_size: env_or("MAX_BATCH_SIZE", 10),
+            slow_query_threshold: std::time::Duration::from_millis(env_or("SLOW_QUERY_MS", 500)),
+            site_base_url: env_or("SITE_BASE_URL", "http://localhost:8000".to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            site_title: env_or("SITE_TITLE", "Blog".to_string()),
+            feed_size: env_or("FEED_SIZE", 20),
+            sitemap_page_size: env_or("SITEMAP_PAGE_SIZE", 50_000),
+            uploads_dir: env_or("UPLOADS_DIR", "uploads".into()),
+            max_upload_size: env_or("MAX_UPLOAD_SIZE", 5 * 1024 * 1024),
+            gravatar_default: env_or("GRAVATAR_DEFAULT", "identicon".to_string()),
+            webhook_max_retries: env_or("WEBHOOK_MAX_RETRIES", 3),
+            webhook_retry_base: std::time::Duration::from_millis(env_or(
+                "WEBHOOK_RETRY_BASE_MS",
+                1000,
+            )),
+            webhook_timeout: std::time::Duration::from_secs(env_or("WEBHOOK_TIMEOUT_SECS", 10)),
+            smtp: SmtpSettings::from_env(),
+            debug: env_or("GRAPHQL_DEBUG", false),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct SmtpSettings {
+    pub(crate) host: String,
+    pub(crate) port: u16,
+    pub(crate) tls: SmtpTls,
+    // SMTP_USERNAMEを指定した場合のみ認証する
+    pub(crate) credentials: Option<(String, String)>,
+    // 送信元（"Blog <blog@example.com>" の形式も可）。検証はbuild_app_stateで行う
+    pub(crate) from: String,
+}
+
+// SMTPサーバーとの接続の暗号化
+#[derive(Clone, Copy)]
+pub(crate) enum SmtpTls {
+    // 接続時からTLS（通常はポート465）
+    Tls,
+    // STARTTLSで暗号化する（通常はポート587）
+    StartTls,
+    // 暗号化しない（開発用のSMTPサーバー向け）
+    None,
+}
+
+impl SmtpSettings {
+    fn from_env() -> Option<Self> {
+        let host = std::env::var("SMTP_HOST").ok().filter(|host| !host.is_empty())?;
+        let tls = match std::env::var("SMTP_TLS").unwrap_or_default().to_lowercase().as_str() {
+            "tls" => SmtpTls::Tls,
+            "none" => SmtpTls::None,
+            _ => SmtpTls::StartTls,
+        };
+        let default_port = match tls {
+            SmtpTls::Tls => 465,
+            SmtpTls::StartTls => 587,
+            SmtpTls::None => 25,
+        };
+        let credentials = std::env::var("SMTP_USERNAME")
+            .ok()
+            .filter(|username| !username.is_empty())
+            .map(|username| (username, std::env::var("SMTP_PASSWORD").unwrap_or_default()));
+        Some(SmtpSettings {
+            host,
+            port: env_or("SMTP_PORT", default_port),
+            tls,
+            credentials,
+            from: env_or("SMTP_FROM", String::new()),
+        })
+    }
+}
+
+pub(crate) fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
@@ -0,0 +1,123 @@
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::feed::{atom_datetime, escape_xml};
+use crate::pagination::Page;
+use crate::search::PostFilter;
+use crate::settings::Settings;
+use crate::store::{AppStorage, LockExt, StoreRevision};
+
+// サイトマップ1つに載せられるURLの上限（仕様）
+pub(crate) const MAX_SITEMAP_URLS: usize = 50_000;
+
+#[derive(Deserialize)]
+pub(crate) struct SitemapQuery {
+    // 投稿が多くてサイトマップインデックスを返す場合の、各サイトマップの番号（1から）
+    page: Option<usize>,
+}
+
+// 生成したサイトマップ（ページ番号ごと）。データの版が変わったら全て作り直す
+#[derive(Clone, Default)]
+pub(crate) struct SitemapCache {
+    cached: Arc<Mutex<CachedSitemaps>>,
+}
+
+#[derive(Default)]
+struct CachedSitemaps {
+    revision: u64,
+    documents: HashMap<Option<usize>, Arc<str>>,
+}
+
+pub(crate) async fn sitemap_handler(
+    storage: web::Data<AppStorage>,
+    settings: web::Data<Settings>,
+    revision: web::Data<StoreRevision>,
+    cache: web::Data<SitemapCache>,
+    query: web::Query<SitemapQuery>,
+) -> HttpResponse {
+    // 生成前に版を読むので、生成中に変更があれば次のリクエストで作り直す
+    let current = revision.current();
+    let page = query.page;
+    let cached = {
+        let mut cached = cache.cached.lock_or_recover();
+        if cached.revision != current {
+            cached.revision = current;
+            cached.documents.clear();
+        }
+        cached.documents.get(&page).cloned()
+    };
+    let document = match cached {
+        Some(document) => document,
+        None => match render_sitemap(&storage, &settings, page).await {
+            Ok(Some(document)) => {
+                let document: Arc<str> = document.into();
+                let mut cached = cache.cached.lock_or_recover();
+                if cached.revision == current {
+                    cached.documents.insert(page, document.clone());
+                }
+                document
+            }
+            Ok(None) => return HttpResponse::NotFound().finish(),
+            Err(e) => {
+                tracing::error!(error = %e.message, "failed to generate sitemap");
+                return HttpResponse::InternalServerError().finish();
+            }
+        },
+    };
+    HttpResponse::Ok()
+        .content_type("application/xml; charset=utf-8")
+        .body(document.to_string())
+}
+
+// 公開済みの投稿がページの大きさ以下ならurlset、超えればsitemapindexを返す
+// 範囲外のページ番号ならNone
+async fn render_sitemap(
+    storage: &AppStorage,
+    settings: &Settings,
+    page: Option<usize>,
+) -> async_graphql::Result<Option<String>> {
+    let page_size = settings.sitemap_page_size.clamp(1, MAX_SITEMAP_URLS);
+    let filter = PostFilter::default();
+    let count = storage.count_posts(&filter).await?;
+    let pages = count.div_ceil(page_size).max(1);
+    let base = settings.site_base_url.as_str();
+    let page = match page {
+        None if pages > 1 => return Ok(Some(render_index(base, pages))),
+        None => 1,
+        Some(page) if (1..=pages).contains(&page) => page,
+        Some(_) => return Ok(None),
+    };
+
+    let range = Page {
+        limit: Some(page_size),
+        offset: (page - 1) * page_size,
+    };
+    let posts = storage.list_posts(&filter, range).await?;
+    let mut xml = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    xml.push_str("\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+    for post in &posts {
+        let loc = format!("{}/posts/{}", base, post.slug);
+        let lastmod = post.published_at.0.max(post.updated_at.0);
+        xml.push_str(&format!(
+            "<url><loc>{}</loc><lastmod>{}</lastmod>\
+             <changefreq>monthly</changefreq><priority>0.5</priority></url>\n",
+            escape_xml(&loc),
+            atom_datetime(lastmod)
+        ));
+    }
+    xml.push_str("</urlset>\n");
+    Ok(Some(xml))
+}
+
+fn render_index(base: &str, pages: usize) -> String {
+    let mut xml = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    xml.push_str("\n<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+    for page in 1..=pages {
+        let loc = format!("{}/sitemap.xml?page={}", base, page);
+        xml.push_str(&format!("<sitemap><loc>{}</loc></sitemap>\n", escape_xml(&loc)));
+    }
+    xml.push_str("</sitemapindex>\n");
+    xml
+}
@@ -0,0 +1,140 @@
+use async_graphql::{Response, ServerResult, Variables};
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextParseQuery, NextRequest,
+};
+use async_graphql::parser::types::{ExecutableDocument, OperationType};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use super::memory::{MemoryStorage, PostTable, UserTable};
+
+use crate::models::{Post, User};
+use crate::store::{LockExt, RwLockExt};
+use crate::validation::assign_missing_handles;
+
+// データファイル（DATA_FILEを指定した場合のみ、ユーザーと投稿を保存する）
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Snapshot {
+    users: Vec<User>,
+    posts: Vec<Post>,
+}
+
+impl Snapshot {
+    // ファイルがなければNone、読めないか壊れている場合はエラー
+    pub(crate) fn load(path: &Path) -> Result<Option<MemoryStorage>, String> {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.to_string()),
+        };
+        let mut snapshot: Snapshot =
+            serde_json::from_slice(&bytes).map_err(|e| e.to_string())?;
+        assign_missing_handles(&mut snapshot.users);
+        let mut users = UserTable::default();
+        for user in snapshot.users {
+            let id = user.id.clone();
+            users
+                .insert(user)
+                .map_err(|_| format!("duplicate user id or handle: {}", id.as_str()))?;
+        }
+        let mut posts = PostTable::default();
+        for post in snapshot.posts {
+            let id = post.id.clone();
+            posts
+                .insert(post)
+                .map_err(|_| format!("duplicate post id: {}", id.as_str()))?;
+        }
+        Ok(Some(MemoryStorage::new(users, posts)))
+    }
+}
+
+// メモリ上のストレージを使う場合のみ
+#[derive(Clone)]
+pub struct DataFile {
+    path: PathBuf,
+    storage: Arc<MemoryStorage>,
+    // 同時に書き込んで一時ファイルが混ざらないようにする
+    write_lock: Arc<Mutex<()>>,
+}
+
+impl DataFile {
+    pub(crate) fn new(path: PathBuf, storage: Arc<MemoryStorage>) -> Self {
+        DataFile {
+            path,
+            storage,
+            write_lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    // 一時ファイルに書いてからrenameするので、書き込み中に落ちても元のファイルは壊れない
+    fn save(&self) -> std::io::Result<()> {
+        let _write_lock = self.write_lock.lock_or_recover();
+        let snapshot = Snapshot {
+            users: self.storage.users.read_or_recover().iter().cloned().collect(),
+            posts: self.storage.posts.read_or_recover().iter().cloned().collect(),
+        };
+        let json = serde_json::to_vec_pretty(&snapshot)?;
+
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        let mut file = std::fs::File::create(&tmp_path)?;
+        file.write_all(&json)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp_path, &self.path)
+    }
+
+    // 保存に失敗してもリクエストは失敗させず、ログにだけ出す
+    pub(crate) async fn persist(&self) {
+        let data_file = self.clone();
+        let result = tokio::task::spawn_blocking(move || data_file.save())
+            .await
+            .unwrap_or_else(|e| Err(std::io::Error::other(e)));
+        if let Err(e) = result {
+            tracing::error!("failed to write {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+// ミューテーションを実行したらデータファイルに書き出す
+impl ExtensionFactory for DataFile {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(DataFileExtension {
+            data_file: self.clone(),
+            mutation: AtomicBool::new(false),
+        })
+    }
+}
+
+struct DataFileExtension {
+    data_file: DataFile,
+    mutation: AtomicBool,
+}
+
+#[async_trait::async_trait]
+impl Extension for DataFileExtension {
+    async fn request(&self, ctx: &ExtensionContext<'_>, next: NextRequest<'_>) -> Response {
+        let response = next.run(ctx).await;
+        if self.mutation.load(Ordering::Relaxed) {
+            self.data_file.persist().await;
+        }
+        response
+    }
+
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let document = next.run(ctx, query, variables).await?;
+        let mutation = document
+            .operations
+            .iter()
+            .any(|(_, op)| op.node.ty == OperationType::Mutation);
+        self.mutation.store(mutation, Ordering::Relaxed);
+        Ok(document)
+    }
+}
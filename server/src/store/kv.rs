@@ -0,0 +1,534 @@
+// 組み込みのキーバリューストア（STORAGE=redb:パス）
+// 1つの書き込みトランザクションで投稿と索引をまとめて書くので、途中で落ちても半端な投稿は残らない
+//
+// キー（値は全てJSON）
+//   user/<id>                          ユーザーと登録順の番号
+//   user_order/<番号（20桁）>          ユーザーID（登録順の一覧用）
+//   user_handle/<handle>               ユーザーID
+//   post/<id>                          投稿
+//   post_slug/<slug>                   投稿ID
+//   post_published/<公開日時>/<id>     投稿ID（新しい順の一覧用）
+//   meta/next_user_seq                 次に登録するユーザーの番号
+use async_graphql::ID;
+use serde::{Deserialize, Serialize};
+use redb::{
+    Database, ReadOnlyTable, ReadableDatabase, ReadableTable, ReadableTableMetadata, Table,
+    TableDefinition,
+};
+use serde::de::DeserializeOwned;
+use std::path::Path;
+use super::sqlite::encode_datetime;
+
+use crate::error::AppError;
+use crate::models::{Post, User};
+use crate::pagination::Page;
+use crate::search::{PostFilter, UserFilter, user_matches};
+use crate::seed::Seed;
+use crate::store::{PostUpdate, Storage, UserUpdate};
+use crate::validation::{assign_missing_handles, handle_taken};
+
+const TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("blog");
+
+const USER: &str = "user/";
+const USER_ORDER: &str = "user_order/";
+const USER_HANDLE: &str = "user_handle/";
+const POST: &str = "post/";
+const POST_SLUG: &str = "post_slug/";
+const POST_PUBLISHED: &str = "post_published/";
+const NEXT_USER_SEQ: &str = "meta/next_user_seq";
+
+// ディレクトリを指定した場合はその中のファイルを使う
+const DEFAULT_FILE_NAME: &str = "blog.redb";
+
+pub(crate) struct RedbStorage {
+    db: Database,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredUser {
+    seq: u64,
+    user: User,
+}
+
+impl RedbStorage {
+    // ファイルがなければ作成し、空なら初期データを入れる
+    // 空でなければ、以前のバージョンで保存したハンドルのないユーザーにハンドルを付ける
+    pub(crate) fn open(path: &Path, seed: &Seed) -> Result<Self, String> {
+        let path = if path.is_dir() {
+            path.join(DEFAULT_FILE_NAME)
+        } else {
+            path.to_path_buf()
+        };
+        let db = Database::create(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let storage = RedbStorage { db };
+        let seeded = storage
+            .write(|table| {
+                if !table.is_empty().map_err(kv_error)? {
+                    let mut users = Vec::new();
+                    visit_users(table, |user| users.push(user))?;
+                    for user in assign_missing_handles(&mut users) {
+                        let Some(mut stored) = get::<StoredUser>(table, &user_key(&user.id))?
+                        else {
+                            continue;
+                        };
+                        put(table, &user_handle_key(&user.handle), &user.id)?;
+                        stored.user = user;
+                        put(table, &user_key(&stored.user.id), &stored)?;
+                    }
+                    return Ok(false);
+                }
+                let (users, posts) = seed.tables();
+                for user in users.iter() {
+                    put_new_user(table, user)?;
+                }
+                for post in posts.iter() {
+                    put_post(table, None, post)?;
+                }
+                Ok(true)
+            })
+            .map_err(|e| e.message)?;
+        if seeded {
+            tracing::info!("Seeded {}", path.display());
+        }
+        Ok(storage)
+    }
+
+    fn read<T>(
+        &self,
+        f: impl FnOnce(&ReadOnlyTable<&'static str, &'static [u8]>) -> async_graphql::Result<T>,
+    ) -> async_graphql::Result<T> {
+        let txn = self.db.begin_read().map_err(kv_error)?;
+        let table = txn.open_table(TABLE).map_err(kv_error)?;
+        f(&table)
+    }
+
+    // fがエラーを返した場合は何も書き込まない。コミットしたらディスクに同期してから戻る
+    fn write<T>(
+        &self,
+        f: impl FnOnce(&mut Table<'_, &'static str, &'static [u8]>) -> async_graphql::Result<T>,
+    ) -> async_graphql::Result<T> {
+        let txn = self.db.begin_write().map_err(kv_error)?;
+        let result = {
+            let mut table = txn.open_table(TABLE).map_err(kv_error)?;
+            f(&mut table)
+        };
+        match result {
+            Ok(value) => {
+                txn.commit().map_err(kv_error)?;
+                Ok(value)
+            }
+            Err(e) => {
+                let _ = txn.abort();
+                Err(e)
+            }
+        }
+    }
+}
+
+// 詳細はサーバーのログにだけ出す
+fn kv_error(error: impl Into<redb::Error>) -> async_graphql::Error {
+    AppError::Internal(format!("storage error: {}", error.into())).into()
+}
+
+fn user_key(id: &ID) -> String {
+    format!("{}{}", USER, id.as_str())
+}
+
+fn user_order_key(seq: u64) -> String {
+    format!("{}{:020}", USER_ORDER, seq)
+}
+
+fn user_handle_key(handle: &str) -> String {
+    format!("{}{}", USER_HANDLE, handle)
+}
+
+fn post_key(id: &ID) -> String {
+    format!("{}{}", POST, id.as_str())
+}
+
+fn post_slug_key(slug: &str) -> String {
+    format!("{}{}", POST_SLUG, slug)
+}
+
+// 日時は固定桁なので、キーの順序が公開日時・IDの順序と一致する
+fn post_published_key(post: &Post) -> String {
+    format!(
+        "{}{}/{}",
+        POST_PUBLISHED,
+        encode_datetime(post.published_at),
+        post.id.as_str()
+    )
+}
+
+// プレフィックスで始まるキーの上限（プレフィックスは全て'/'で終わり、その次の文字は'0'）
+fn prefix_end(prefix: &str) -> String {
+    format!("{}0", prefix.trim_end_matches('/'))
+}
+
+fn get<T: DeserializeOwned>(
+    table: &impl ReadableTable<&'static str, &'static [u8]>,
+    key: &str,
+) -> async_graphql::Result<Option<T>> {
+    let Some(value) = table.get(key).map_err(kv_error)? else {
+        return Ok(None);
+    };
+    serde_json::from_slice(value.value())
+        .map(Some)
+        .map_err(|e| AppError::Internal(format!("corrupt record {}: {}", key, e)).into())
+}
+
+fn put<T: Serialize>(
+    table: &mut Table<'_, &'static str, &'static [u8]>,
+    key: &str,
+    value: &T,
+) -> async_graphql::Result<()> {
+    let bytes = serde_json::to_vec(value).map_err(|e| AppError::Internal(e.to_string()))?;
+    table.insert(key, bytes.as_slice()).map_err(kv_error)?;
+    Ok(())
+}
+
+fn remove(
+    table: &mut Table<'_, &'static str, &'static [u8]>,
+    key: &str,
+) -> async_graphql::Result<()> {
+    table.remove(key).map_err(kv_error)?;
+    Ok(())
+}
+
+// 索引の指す先がない場合はデータが壊れている
+fn get_indexed_post(
+    table: &impl ReadableTable<&'static str, &'static [u8]>,
+    id: &ID,
+) -> async_graphql::Result<Post> {
+    get(table, &post_key(id))?.ok_or_else(|| {
+        AppError::Internal(format!("index refers to missing post: {}", id.as_str())).into()
+    })
+}
+
+// ゴミ箱や予約中のものも含めた全ての投稿（新しい順）
+fn all_posts(
+    table: &impl ReadableTable<&'static str, &'static [u8]>,
+) -> async_graphql::Result<Vec<Post>> {
+    let end = prefix_end(POST_PUBLISHED);
+    let mut posts = Vec::new();
+    for entry in table.range(POST_PUBLISHED..end.as_str()).map_err(kv_error)?.rev() {
+        let (key, value) = entry.map_err(kv_error)?;
+        let id: ID = serde_json::from_slice(value.value()).map_err(|e| {
+            AppError::Internal(format!("corrupt record {}: {}", key.value(), e))
+        })?;
+        posts.push(get_indexed_post(table, &id)?);
+    }
+    Ok(posts)
+}
+
+fn remove_post(
+    table: &mut Table<'_, &'static str, &'static [u8]>,
+    post: &Post,
+) -> async_graphql::Result<()> {
+    remove(table, &post_key(&post.id))?;
+    remove(table, &post_slug_key(&post.slug))?;
+    remove(table, &post_published_key(post))
+}
+
+fn remove_user(
+    table: &mut Table<'_, &'static str, &'static [u8]>,
+    stored: &StoredUser,
+) -> async_graphql::Result<()> {
+    remove(table, &user_key(&stored.user.id))?;
+    remove(table, &user_order_key(stored.seq))?;
+    remove(table, &user_handle_key(&stored.user.handle))
+}
+
+// 公開日時の索引を新しい順にたどり、条件に合う投稿をvisitに渡す（falseを返したら終わる）
+fn visit_posts(
+    table: &impl ReadableTable<&'static str, &'static [u8]>,
+    filter: &PostFilter,
+    mut visit: impl FnMut(Post) -> bool,
+) -> async_graphql::Result<()> {
+    let start = match filter.published_after {
+        Some(after) => format!("{}{}", POST_PUBLISHED, encode_datetime(after)),
+        None => POST_PUBLISHED.to_string(),
+    };
+    let end = match filter.published_before {
+        Some(before) => format!("{}{}", POST_PUBLISHED, encode_datetime(before)),
+        None => prefix_end(POST_PUBLISHED),
+    };
+    if start >= end {
+        return Ok(());
+    }
+    for entry in table.range(start.as_str()..end.as_str()).map_err(kv_error)?.rev() {
+        let (key, value) = entry.map_err(kv_error)?;
+        let id: ID = serde_json::from_slice(value.value()).map_err(|e| {
+            AppError::Internal(format!("corrupt record {}: {}", key.value(), e))
+        })?;
+        let post = get_indexed_post(table, &id)?;
+        if filter.matches(&post) && !visit(post) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+// 登録順にユーザーをvisitに渡す
+fn visit_users(
+    table: &impl ReadableTable<&'static str, &'static [u8]>,
+    mut visit: impl FnMut(User),
+) -> async_graphql::Result<()> {
+    let end = prefix_end(USER_ORDER);
+    for entry in table.range(USER_ORDER..end.as_str()).map_err(kv_error)? {
+        let (key, value) = entry.map_err(kv_error)?;
+        let id: ID = serde_json::from_slice(value.value()).map_err(|e| {
+            AppError::Internal(format!("corrupt record {}: {}", key.value(), e))
+        })?;
+        let stored: StoredUser = get(table, &user_key(&id))?.ok_or_else(|| {
+            AppError::Internal(format!("index refers to missing user: {}", id.as_str()))
+        })?;
+        visit(stored.user);
+    }
+    Ok(())
+}
+
+// 投稿と索引を書き込む。oldは更新前の投稿（索引の古いキーを消す）
+fn put_post(
+    table: &mut Table<'_, &'static str, &'static [u8]>,
+    old: Option<&Post>,
+    post: &Post,
+) -> async_graphql::Result<()> {
+    if old.is_none_or(|old| old.slug != post.slug)
+        && get::<ID>(table, &post_slug_key(&post.slug))?.is_some()
+    {
+        return Err(AppError::Conflict(format!("Slug is already in use: {}", post.slug)).into());
+    }
+    if let Some(old) = old {
+        remove(table, &post_slug_key(&old.slug))?;
+        remove(table, &post_published_key(old))?;
+    }
+    put(table, &post_key(&post.id), post)?;
+    put(table, &post_slug_key(&post.slug), &post.id)?;
+    put(table, &post_published_key(post), &post.id)
+}
+
+fn put_new_user(
+    table: &mut Table<'_, &'static str, &'static [u8]>,
+    user: &User,
+) -> async_graphql::Result<()> {
+    if get::<StoredUser>(table, &user_key(&user.id))?.is_some() {
+        return Err(AppError::Conflict(format!("Duplicate user id: {}", user.id.as_str())).into());
+    }
+    if get::<ID>(table, &user_handle_key(&user.handle))?.is_some() {
+        return Err(handle_taken(&user.handle));
+    }
+    put(table, &user_handle_key(&user.handle), &user.id)?;
+    let seq: u64 = get(table, NEXT_USER_SEQ)?.unwrap_or(0);
+    put(table, NEXT_USER_SEQ, &(seq + 1))?;
+    put(table, &user_order_key(seq), &user.id)?;
+    let stored = StoredUser {
+        seq,
+        user: user.clone(),
+    };
+    put(table, &user_key(&user.id), &stored)
+}
+
+#[async_trait::async_trait]
+impl Storage for RedbStorage {
+    async fn list_posts(
+        &self,
+        filter: &PostFilter,
+        page: Page,
+    ) -> async_graphql::Result<Vec<Post>> {
+        self.read(|table| {
+            let mut skip = page.offset;
+            let mut posts = Vec::new();
+            visit_posts(table, filter, |post| {
+                if skip > 0 {
+                    skip -= 1;
+                } else {
+                    posts.push(post);
+                }
+                page.limit.is_none_or(|limit| posts.len() < limit)
+            })?;
+            Ok(posts)
+        })
+    }
+
+    async fn count_posts(&self, filter: &PostFilter) -> async_graphql::Result<usize> {
+        self.read(|table| {
+            let mut count = 0;
+            visit_posts(table, filter, |_| {
+                count += 1;
+                true
+            })?;
+            Ok(count)
+        })
+    }
+
+    async fn list_all_posts(&self) -> async_graphql::Result<Vec<Post>> {
+        self.read(all_posts)
+    }
+
+    async fn get_post(&self, id: &ID) -> async_graphql::Result<Option<Post>> {
+        self.read(|table| get(table, &post_key(id)))
+    }
+
+    async fn get_post_by_slug(&self, slug: &str) -> async_graphql::Result<Option<Post>> {
+        self.read(|table| match get::<ID>(table, &post_slug_key(slug))? {
+            Some(id) => get_indexed_post(table, &id).map(Some),
+            None => Ok(None),
+        })
+    }
+
+    async fn insert_post(&self, post: Post) -> async_graphql::Result<Post> {
+        self.write(|table| {
+            if get::<Post>(table, &post_key(&post.id))?.is_some() {
+                let message = format!("Duplicate post id: {}", post.id.as_str());
+                return Err(AppError::Conflict(message).into());
+            }
+            put_post(table, None, &post)
+        })?;
+        Ok(post)
+    }
+
+    async fn update_post(
+        &self,
+        id: &ID,
+        update: PostUpdate<'_>,
+    ) -> async_graphql::Result<Option<Post>> {
+        self.write(|table| {
+            let Some(old) = get::<Post>(table, &post_key(id))? else {
+                return Ok(None);
+            };
+            let mut post = old.clone();
+            update(&mut post)?;
+            put_post(table, Some(&old), &post)?;
+            Ok(Some(post))
+        })
+    }
+
+    async fn delete_post(&self, id: &ID) -> async_graphql::Result<Option<Post>> {
+        self.write(|table| {
+            let Some(post) = get::<Post>(table, &post_key(id))? else {
+                return Ok(None);
+            };
+            remove_post(table, &post)?;
+            Ok(Some(post))
+        })
+    }
+
+    async fn list_users(
+        &self,
+        filter: &UserFilter,
+        page: Page,
+    ) -> async_graphql::Result<Vec<User>> {
+        self.read(|table| {
+            let mut users = Vec::new();
+            visit_users(table, |user| {
+                if user_matches(&user, filter) {
+                    users.push(user);
+                }
+            })?;
+            Ok(page.apply(users))
+        })
+    }
+
+    async fn count_users(&self, filter: &UserFilter) -> async_graphql::Result<usize> {
+        self.read(|table| {
+            let mut count = 0;
+            visit_users(table, |user| {
+                if user_matches(&user, filter) {
+                    count += 1;
+                }
+            })?;
+            Ok(count)
+        })
+    }
+
+    async fn get_user(&self, id: &ID) -> async_graphql::Result<Option<User>> {
+        self.read(|table| {
+            let stored: Option<StoredUser> = get(table, &user_key(id))?;
+            Ok(stored.map(|stored| stored.user))
+        })
+    }
+
+    async fn get_users(&self, ids: &[ID]) -> async_graphql::Result<Vec<User>> {
+        self.read(|table| {
+            let mut users = Vec::new();
+            for id in ids {
+                if let Some(stored) = get::<StoredUser>(table, &user_key(id))? {
+                    users.push(stored.user);
+                }
+            }
+            Ok(users)
+        })
+    }
+
+    async fn get_users_by_name(&self, name: &str) -> async_graphql::Result<Vec<User>> {
+        self.read(|table| {
+            let mut users = Vec::new();
+            visit_users(table, |user| {
+                if user.name == name {
+                    users.push(user);
+                }
+            })?;
+            Ok(users)
+        })
+    }
+
+    async fn get_user_by_handle(&self, handle: &str) -> async_graphql::Result<Option<User>> {
+        self.read(|table| {
+            let Some(id) = get::<ID>(table, &user_handle_key(handle))? else {
+                return Ok(None);
+            };
+            Ok(get::<StoredUser>(table, &user_key(&id))?.map(|stored| stored.user))
+        })
+    }
+
+    async fn insert_user(&self, user: User) -> async_graphql::Result<()> {
+        self.write(|table| put_new_user(table, &user))
+    }
+
+    async fn update_user(
+        &self,
+        id: &ID,
+        update: UserUpdate<'_>,
+    ) -> async_graphql::Result<Option<User>> {
+        self.write(|table| {
+            let Some(mut stored) = get::<StoredUser>(table, &user_key(id))? else {
+                return Ok(None);
+            };
+            update(&mut stored.user)?;
+            put(table, &user_key(id), &stored)?;
+            Ok(Some(stored.user))
+        })
+    }
+
+    async fn delete_user(&self, id: &ID) -> async_graphql::Result<Option<User>> {
+        self.write(|table| {
+            let Some(stored) = get::<StoredUser>(table, &user_key(id))? else {
+                return Ok(None);
+            };
+            remove_user(table, &stored)?;
+            Ok(Some(stored.user))
+        })
+    }
+
+    async fn delete_user_with_posts(&self, id: &ID) -> async_graphql::Result<Option<Vec<ID>>> {
+        self.write(|table| {
+            let Some(stored) = get::<StoredUser>(table, &user_key(id))? else {
+                return Ok(None);
+            };
+            remove_user(table, &stored)?;
+            let posts: Vec<Post> =
+                all_posts(table)?.into_iter().filter(|p| p.author_id == *id).collect();
+            for post in &posts {
+                remove_post(table, post)?;
+            }
+            Ok(Some(posts.into_iter().map(|p| p.id).collect()))
+        })
+    }
+
+    // 読み取りのトランザクションは書き込み中でも待たずに始められる
+    async fn ping(&self) -> Result<(), String> {
+        let txn = self.db.begin_read().map_err(|e| e.to_string())?;
+        txn.open_table(TABLE).map(|_| ()).map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,300 @@
+use async_graphql::ID;
+use chrono::{DateTime, Utc};
+use indexmap::IndexMap;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{RwLock, TryLockError};
+use std::time::{Duration, Instant};
+
+use crate::error::AppError;
+use crate::models::{Post, User};
+use crate::pagination::Page;
+use crate::search::{PostFilter, UserFilter, user_matches};
+use crate::seed::Seed;
+use crate::store::{PostUpdate, RwLockExt, Storage, UserUpdate};
+
+// /readyzでロックの取得を試みる時間
+const LOCK_PROBE_TIMEOUT: Duration = Duration::from_millis(3);
+
+// メモリストア
+// ユーザーはIDで引けるようにし、一覧は登録順のまま返す
+#[derive(Default)]
+pub(crate) struct UserTable {
+    users: IndexMap<ID, User>,
+}
+
+impl UserTable {
+    // 同じIDかハンドルのユーザーがいる場合は追加しない
+    pub(crate) fn insert(&mut self, user: User) -> Result<(), AppError> {
+        if self.users.contains_key(&user.id) {
+            return Err(AppError::Conflict(format!("Duplicate user id: {}", user.id.as_str())));
+        }
+        if self.get_by_handle(&user.handle).is_some() {
+            return Err(AppError::Conflict(format!("Handle is already taken: {}", user.handle)));
+        }
+        self.users.insert(user.id.clone(), user);
+        Ok(())
+    }
+
+    fn get(&self, id: &ID) -> Option<&User> {
+        self.users.get(id)
+    }
+
+    fn get_mut(&mut self, id: &ID) -> Option<&mut User> {
+        self.users.get_mut(id)
+    }
+
+    fn get_by_handle(&self, handle: &str) -> Option<&User> {
+        self.users.values().find(|u| u.handle == handle)
+    }
+
+    fn remove(&mut self, id: &ID) -> Option<User> {
+        self.users.shift_remove(id)
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &User> {
+        self.users.values()
+    }
+}
+
+// 投稿はIDで引けるようにし、公開日時のインデックスで新しい順に並べる
+#[derive(Default)]
+pub(crate) struct PostTable {
+    posts: HashMap<ID, Post>,
+    // (公開日時, ID) の昇順。逆順にたどると新しい順になる
+    by_published_at: BTreeSet<(DateTime<Utc>, ID)>,
+}
+
+impl PostTable {
+    // 同じIDの投稿がある場合は追加しない
+    pub(crate) fn insert(&mut self, post: Post) -> Result<(), AppError> {
+        if self.posts.contains_key(&post.id) {
+            return Err(AppError::Conflict(format!("Duplicate post id: {}", post.id.as_str())));
+        }
+        self.by_published_at.insert((post.published_at.0, post.id.clone()));
+        self.posts.insert(post.id.clone(), post);
+        Ok(())
+    }
+
+    fn get(&self, id: &ID) -> Option<&Post> {
+        self.posts.get(id)
+    }
+
+    // 公開日時を変更した場合は、PostMutを手放したときにインデックスを付け直す
+    fn get_mut(&mut self, id: &ID) -> Option<PostMut<'_>> {
+        let post = self.posts.get_mut(id)?;
+        Some(PostMut {
+            key: (post.published_at.0, post.id.clone()),
+            post,
+            by_published_at: &mut self.by_published_at,
+        })
+    }
+
+    fn remove(&mut self, id: &ID) -> Option<Post> {
+        let post = self.posts.remove(id)?;
+        self.by_published_at.remove(&(post.published_at.0, post.id.clone()));
+        Some(post)
+    }
+
+    // 新しい順（公開日時が同じ場合はIDの降順）
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Post> {
+        self.by_published_at.iter().rev().map(|(_, id)| &self.posts[id])
+    }
+}
+
+struct PostMut<'a> {
+    post: &'a mut Post,
+    by_published_at: &'a mut BTreeSet<(DateTime<Utc>, ID)>,
+    key: (DateTime<Utc>, ID),
+}
+
+impl std::ops::Deref for PostMut<'_> {
+    type Target = Post;
+
+    fn deref(&self) -> &Post {
+        self.post
+    }
+}
+
+impl std::ops::DerefMut for PostMut<'_> {
+    fn deref_mut(&mut self) -> &mut Post {
+        self.post
+    }
+}
+
+impl Drop for PostMut<'_> {
+    fn drop(&mut self) {
+        if self.post.published_at.0 != self.key.0 {
+            self.by_published_at.remove(&self.key);
+            self.by_published_at.insert((self.post.published_at.0, self.post.id.clone()));
+        }
+    }
+}
+
+// 読み込みが大半なので、ユーザーと投稿は読み込み同士がブロックしないRwLockにする
+#[derive(Default)]
+pub struct MemoryStorage {
+    pub(crate) users: RwLock<UserTable>,
+    pub(crate) posts: RwLock<PostTable>,
+}
+
+impl MemoryStorage {
+    pub(crate) fn new(users: UserTable, posts: PostTable) -> Self {
+        MemoryStorage {
+            users: RwLock::new(users),
+            posts: RwLock::new(posts),
+        }
+    }
+
+    // 初期データ入りのストレージ（テストやDATA_FILEを指定しない場合に使う）
+    pub fn seeded(seed: &Seed) -> Self {
+        let (users, posts) = seed.tables();
+        MemoryStorage::new(users, posts)
+    }
+}
+
+#[async_trait::async_trait]
+impl Storage for MemoryStorage {
+    async fn list_posts(
+        &self,
+        filter: &PostFilter,
+        page: Page,
+    ) -> async_graphql::Result<Vec<Post>> {
+        let posts = self.posts.read_or_recover();
+        Ok(page.apply(posts.iter().filter(|p| filter.matches(p)).cloned()))
+    }
+
+    async fn count_posts(&self, filter: &PostFilter) -> async_graphql::Result<usize> {
+        let posts = self.posts.read_or_recover();
+        Ok(posts.iter().filter(|p| filter.matches(p)).count())
+    }
+
+    async fn list_all_posts(&self) -> async_graphql::Result<Vec<Post>> {
+        Ok(self.posts.read_or_recover().iter().cloned().collect())
+    }
+
+    async fn get_post(&self, id: &ID) -> async_graphql::Result<Option<Post>> {
+        Ok(self.posts.read_or_recover().get(id).cloned())
+    }
+
+    async fn get_post_by_slug(&self, slug: &str) -> async_graphql::Result<Option<Post>> {
+        let posts = self.posts.read_or_recover();
+        let post = posts.iter().find(|p| p.slug == slug).cloned();
+        Ok(post)
+    }
+
+    async fn insert_post(&self, post: Post) -> async_graphql::Result<Post> {
+        let mut posts = self.posts.write_or_recover();
+        if posts.iter().any(|p| p.slug == post.slug) {
+            return Err(AppError::Conflict(format!("Slug is already in use: {}", post.slug)).into());
+        }
+        posts.insert(post.clone())?;
+        Ok(post)
+    }
+
+    async fn update_post(
+        &self,
+        id: &ID,
+        update: PostUpdate<'_>,
+    ) -> async_graphql::Result<Option<Post>> {
+        let mut posts = self.posts.write_or_recover();
+        let Some(mut post) = posts.get_mut(id) else {
+            return Ok(None);
+        };
+        // 途中でエラーになっても元の投稿が変わらないよう、複製に適用してから置き換える
+        let mut updated = post.clone();
+        update(&mut updated)?;
+        *post = updated.clone();
+        Ok(Some(updated))
+    }
+
+    async fn delete_post(&self, id: &ID) -> async_graphql::Result<Option<Post>> {
+        Ok(self.posts.write_or_recover().remove(id))
+    }
+
+    async fn list_users(
+        &self,
+        filter: &UserFilter,
+        page: Page,
+    ) -> async_graphql::Result<Vec<User>> {
+        let users = self.users.read_or_recover();
+        Ok(page.apply(users.iter().filter(|u| user_matches(u, filter)).cloned()))
+    }
+
+    async fn count_users(&self, filter: &UserFilter) -> async_graphql::Result<usize> {
+        let users = self.users.read_or_recover();
+        Ok(users.iter().filter(|u| user_matches(u, filter)).count())
+    }
+
+    async fn get_user(&self, id: &ID) -> async_graphql::Result<Option<User>> {
+        Ok(self.users.read_or_recover().get(id).cloned())
+    }
+
+    async fn get_users(&self, ids: &[ID]) -> async_graphql::Result<Vec<User>> {
+        let users = self.users.read_or_recover();
+        Ok(ids.iter().filter_map(|id| users.get(id).cloned()).collect())
+    }
+
+    async fn get_users_by_name(&self, name: &str) -> async_graphql::Result<Vec<User>> {
+        let users = self.users.read_or_recover();
+        Ok(users.iter().filter(|u| u.name == name).cloned().collect())
+    }
+
+    async fn get_user_by_handle(&self, handle: &str) -> async_graphql::Result<Option<User>> {
+        Ok(self.users.read_or_recover().get_by_handle(handle).cloned())
+    }
+
+    async fn insert_user(&self, user: User) -> async_graphql::Result<()> {
+        Ok(self.users.write_or_recover().insert(user)?)
+    }
+
+    async fn update_user(
+        &self,
+        id: &ID,
+        update: UserUpdate<'_>,
+    ) -> async_graphql::Result<Option<User>> {
+        let mut users = self.users.write_or_recover();
+        let Some(user) = users.get_mut(id) else {
+            return Ok(None);
+        };
+        let mut updated = user.clone();
+        update(&mut updated)?;
+        *user = updated.clone();
+        Ok(Some(updated))
+    }
+
+    async fn delete_user(&self, id: &ID) -> async_graphql::Result<Option<User>> {
+        Ok(self.users.write_or_recover().remove(id))
+    }
+
+    // 両方のロックを持ったまま消すので、途中の状態は他から見えない
+    async fn delete_user_with_posts(&self, id: &ID) -> async_graphql::Result<Option<Vec<ID>>> {
+        let mut posts = self.posts.write_or_recover();
+        let mut users = self.users.write_or_recover();
+        if users.remove(id).is_none() {
+            return Ok(None);
+        }
+        let ids: Vec<ID> =
+            posts.iter().filter(|p| p.author_id == *id).map(|p| p.id.clone()).collect();
+        for post_id in &ids {
+            posts.remove(post_id);
+        }
+        Ok(Some(ids))
+    }
+
+    // 重いミューテーションが書き込みロックを持っていても、待ち続けずに失敗とする
+    async fn ping(&self) -> Result<(), String> {
+        let deadline = Instant::now() + LOCK_PROBE_TIMEOUT;
+        loop {
+            // 取得できたロックはすぐに解放する（poisonedは読み書きできるので問題ない）
+            let users_blocked = matches!(self.users.try_read(), Err(TryLockError::WouldBlock));
+            let posts_blocked = matches!(self.posts.try_read(), Err(TryLockError::WouldBlock));
+            if !users_blocked && !posts_blocked {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err("memory storage is locked by a long-running write".into());
+            }
+            tokio::task::yield_now().await;
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use async_graphql::ID;
+use dashmap::DashMap;
+use std::collections::{HashMap, HashSet};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::models::{
+    ApiKey, Comment, ContactMessage, NewsletterSubscriber, Notification, Post, Reaction, User,
+    Webhook,
+};
+use crate::pagination::Page;
+use crate::scalars::DateTimeScalar;
+use crate::search::{PostFilter, UserFilter};
+use crate::seed::Seed;
+
+mod data_file;
+mod kv;
+mod memory;
+mod revision;
+#[cfg(feature = "postgres")]
+mod postgres;
+mod sqlite;
+mod traced;
+
+pub use data_file::DataFile;
+use data_file::Snapshot;
+pub use memory::MemoryStorage;
+pub(crate) use memory::{PostTable, UserTable};
+pub(crate) use revision::StoreRevision;
+use sqlite::SqliteStorage;
+pub(crate) use traced::TracedStorage;
+
+// ストレージ
+// リゾルバーはAppStorageだけを参照し、保存先（メモリ・SQLite）の違いを意識しない
+type PostUpdate<'a> = Box<dyn FnOnce(&mut Post) -> async_graphql::Result<()> + Send + 'a>;
+type UserUpdate<'a> = Box<dyn FnOnce(&mut User) -> async_graphql::Result<()> + Send + 'a>;
+
+#[async_trait::async_trait]
+pub trait Storage: Send + Sync {
+    /// 条件に合う投稿（新しい順）
+    async fn list_posts(&self, filter: &PostFilter, page: Page)
+        -> async_graphql::Result<Vec<Post>>;
+    async fn count_posts(&self, filter: &PostFilter) -> async_graphql::Result<usize>;
+    /// ゴミ箱や予約中のものも含めた全ての投稿（新しい順）
+    async fn list_all_posts(&self) -> async_graphql::Result<Vec<Post>>;
+    async fn get_post(&self, id: &ID) -> async_graphql::Result<Option<Post>>;
+    async fn get_post_by_slug(&self, slug: &str) -> async_graphql::Result<Option<Post>>;
+    /// IDかスラッグが既存の投稿と重複する場合はCONFLICT。保存した投稿を返す
+    async fn insert_post(&self, post: Post) -> async_graphql::Result<Post>;
+    /// updateがエラーを返した場合は何も変更しない。投稿がなければNone
+    async fn update_post(&self, id: &ID, update: PostUpdate<'_>)
+        -> async_graphql::Result<Option<Post>>;
+    /// 完全に削除する。投稿がなければNone
+    async fn delete_post(&self, id: &ID) -> async_graphql::Result<Option<Post>>;
+
+    /// 名前（と自己紹介）で絞り込んだユーザー（登録順）
+    async fn list_users(&self, filter: &UserFilter, page: Page)
+        -> async_graphql::Result<Vec<User>>;
+    async fn count_users(&self, filter: &UserFilter) -> async_graphql::Result<usize>;
+    async fn get_user(&self, id: &ID) -> async_graphql::Result<Option<User>>;
+    /// 存在しないIDは結果に含めない
+    async fn get_users(&self, ids: &[ID]) -> async_graphql::Result<Vec<User>>;
+    async fn get_users_by_name(&self, name: &str) -> async_graphql::Result<Vec<User>>;
+    /// handleは小文字にそろえたもの
+    async fn get_user_by_handle(&self, handle: &str) -> async_graphql::Result<Option<User>>;
+    /// IDかハンドルが既存のユーザーと重複する場合はCONFLICT
+    async fn insert_user(&self, user: User) -> async_graphql::Result<()>;
+    /// updateがエラーを返した場合は何も変更しない。ユーザーがいなければNone
+    async fn update_user(&self, id: &ID, update: UserUpdate<'_>)
+        -> async_graphql::Result<Option<User>>;
+    async fn delete_user(&self, id: &ID) -> async_graphql::Result<Option<User>>;
+    /// ユーザーとその投稿（ゴミ箱を含む）を1つのトランザクションで削除し、投稿のIDを返す。
+    /// ユーザーがいなければ何も削除せずNone
+    async fn delete_user_with_posts(&self, id: &ID) -> async_graphql::Result<Option<Vec<ID>>>;
+
+    /// 読み書きできる状態か（/readyz用）。できない場合は理由を返す
+    async fn ping(&self) -> Result<(), String>;
+
+    /// 終了時に呼ぶ（接続を閉じるなど）
+    async fn close(&self) {}
+}
+
+pub type AppStorage = Arc<dyn Storage>;
+
+pub(crate) type CommentStore = Arc<Mutex<Vec<Comment>>>;
+// (投稿ID, ユーザーID) の組
+pub(crate) type LikeStore = Arc<Mutex<HashSet<(ID, ID)>>>;
+
+#[derive(Clone)]
+pub(crate) struct BookmarkEntry {
+    pub(crate) post_id: ID,
+    pub(crate) user_id: ID,
+    pub(crate) bookmarked_at: DateTimeScalar,
+}
+
+pub(crate) type BookmarkStore = Arc<Mutex<Vec<BookmarkEntry>>>;
+// (フォローする側, フォローされる側) の組
+// スキーマのデータは型で引くので、LikeStoreと同じ型にならないよう包む
+#[derive(Clone, Default)]
+pub(crate) struct FollowStore(Arc<Mutex<HashSet<(ID, ID)>>>);
+
+impl Deref for FollowStore {
+    type Target = Mutex<HashSet<(ID, ID)>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+// (投稿ID, ユーザーID, リアクション) の組
+pub(crate) type ReactionStore = Arc<Mutex<HashSet<(ID, ID, Reaction)>>>;
+pub(crate) type ApiKeyStore = Arc<Mutex<Vec<ApiKey>>>;
+// 通知（作成順）
+pub(crate) type NotificationStore = Arc<Mutex<Vec<Notification>>>;
+pub(crate) type WebhookStore = Arc<Mutex<Vec<Webhook>>>;
+pub(crate) type NewsletterStore = Arc<Mutex<Vec<NewsletterSubscriber>>>;
+// お問い合わせのメッセージ（受け付けた順）
+pub(crate) type ContactMessageStore = Arc<Mutex<Vec<ContactMessage>>>;
+// 閲覧数は投稿作成時にカウンターを用意し、ストレージを介さずに加算する
+pub(crate) type ViewStore = Arc<DashMap<ID, AtomicU64>>;
+
+// 処理中にパニックしてロックが汚染されても中身は壊れていないので、そのまま取り出して使う
+pub(crate) trait LockExt<T> {
+    fn lock_or_recover(&self) -> MutexGuard<'_, T>;
+}
+
+impl<T> LockExt<T> for Mutex<T> {
+    fn lock_or_recover(&self) -> MutexGuard<'_, T> {
+        self.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+trait RwLockExt<T> {
+    fn read_or_recover(&self) -> RwLockReadGuard<'_, T>;
+    fn write_or_recover(&self) -> RwLockWriteGuard<'_, T>;
+}
+
+impl<T> RwLockExt<T> for RwLock<T> {
+    fn read_or_recover(&self) -> RwLockReadGuard<'_, T> {
+        self.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write_or_recover(&self) -> RwLockWriteGuard<'_, T> {
+        self.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+pub(crate) fn view_count(views: &ViewStore, post_id: &ID) -> u64 {
+    views
+        .get(post_id)
+        .map(|count| count.load(Ordering::Relaxed))
+        .unwrap_or(0)
+}
+
+pub(crate) fn count_likes(likes: &HashSet<(ID, ID)>) -> HashMap<ID, usize> {
+    let mut counts = HashMap::new();
+    for (post_id, _) in likes {
+        *counts.entry(post_id.clone()).or_default() += 1;
+    }
+    counts
+}
+
+// 投稿を完全に削除したときに関連データも削除する
+pub(crate) fn remove_post_data(
+    ctx: &async_graphql::Context<'_>,
+    post_ids: &HashSet<ID>,
+) -> async_graphql::Result<()> {
+    let comment_store = ctx.data::<CommentStore>()?;
+    let like_store = ctx.data::<LikeStore>()?;
+    let bookmark_store = ctx.data::<BookmarkStore>()?;
+    let view_store = ctx.data::<ViewStore>()?;
+    let reaction_store = ctx.data::<ReactionStore>()?;
+    let notification_store = ctx.data::<NotificationStore>()?;
+    comment_store
+        .lock_or_recover()
+        .retain(|c| !post_ids.contains(&c.post_id));
+    like_store
+        .lock_or_recover()
+        .retain(|(post_id, _)| !post_ids.contains(post_id));
+    bookmark_store
+        .lock_or_recover()
+        .retain(|b| !post_ids.contains(&b.post_id));
+    view_store.retain(|post_id, _| !post_ids.contains(post_id));
+    reaction_store
+        .lock_or_recover()
+        .retain(|(post_id, _, _)| !post_ids.contains(post_id));
+    notification_store
+        .lock_or_recover()
+        .retain(|n| !post_ids.contains(&n.post_id));
+    Ok(())
+}
+
+// STORAGE・DATABASE_URLのスキームで保存先を選ぶ
+pub async fn open_database(url: &str, seed: &Seed) -> Result<AppStorage, String> {
+    if let Some(path) = url.strip_prefix("redb:") {
+        return Ok(Arc::new(kv::RedbStorage::open(Path::new(path), seed)?));
+    }
+    if url.starts_with("sqlite:") {
+        let storage = SqliteStorage::connect(url, seed).await.map_err(|e| e.to_string())?;
+        return Ok(Arc::new(storage));
+    }
+    let scheme = url.split(':').next().unwrap_or_default();
+    if scheme == "postgres" || scheme == "postgresql" {
+        #[cfg(feature = "postgres")]
+        {
+            let storage = postgres::PgStorage::connect(url, seed).await.map_err(|e| e.to_string())?;
+            return Ok(Arc::new(storage));
+        }
+        #[cfg(not(feature = "postgres"))]
+        return Err("PostgreSQL support is not enabled (build with --features postgres)".into());
+    }
+    Err(format!("unsupported database: {}", scheme))
+}
+
+// DATA_FILEを指定した場合は保存済みの内容から起動する
+pub fn open_memory_storage(seed: &Seed, force: bool) -> (AppStorage, Option<DataFile>) {
+    let data_file_path = std::env::var("DATA_FILE").ok().map(PathBuf::from);
+    let loaded = match &data_file_path {
+        Some(path) => match Snapshot::load(path) {
+            Ok(Some(storage)) => {
+                tracing::info!("Loaded data from {}", path.display());
+                Some(storage)
+            }
+            Ok(None) => {
+                tracing::info!(
+                    "{} does not exist yet; starting with the initial data",
+                    path.display()
+                );
+                None
+            }
+            Err(e) if force => {
+                tracing::warn!(
+                    "Failed to load {}: {}; starting with the initial data (--force), \
+                     the file will be overwritten",
+                    path.display(),
+                    e
+                );
+                None
+            }
+            Err(e) => {
+                tracing::error!(
+                    "Failed to load {}: {} (pass --force to start with the initial data anyway)",
+                    path.display(),
+                    e
+                );
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+    let storage = Arc::new(loaded.unwrap_or_else(|| MemoryStorage::seeded(seed)));
+    let data_file = data_file_path.map(|path| DataFile::new(path, storage.clone()));
+    (storage, data_file)
+}
@@ -0,0 +1,631 @@
+// PostgreSQL（DATABASE_URL=postgres://...、postgres featureを有効にしてビルドした場合のみ）
+// IDはUUID、日時はTIMESTAMPTZ、タグはtext[]で保存する
+use async_graphql::ID;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+use sqlx::{QueryBuilder, Row};
+use chrono::SubsecRound;
+use sqlx::postgres::{PgConnectOptions, PgConnection, PgPool, PgPoolOptions, PgRow, Postgres};
+use sqlx::Connection;
+use sqlx::types::Json;
+use std::collections::HashMap;
+use std::time::Duration;
+use super::sqlite::{db_error, decode_text_stats, parse_role, parse_status, role_name, status_name};
+
+use crate::error::AppError;
+use crate::models::{Post, PostStatus, User};
+use crate::pagination::Page;
+use crate::scalars::{DateTimeScalar, UrlScalar};
+use crate::search::{PostFilter, UserFilter};
+use crate::seed::Seed;
+use crate::settings::env_or;
+use crate::store::{PostUpdate, Storage, UserUpdate};
+use crate::validation::{assign_missing_handles, handle_taken};
+
+pub(crate) struct PgStorage {
+    pool: PgPool,
+}
+
+const PG_USER_COLUMNS: &str = "id, name, handle, avatar_url, email, email_on_comment, bio, \
+     website, location, role, password_hash";
+const PG_POST_COLUMNS: &str = "id, title, slug, author_id, body, tags, status, published_at, \
+     scheduled_at, updated_at, deleted_at, revisions, latin_words, cjk_chars, cover_image_url, \
+     cover_image_alt, seo_description, canonical_url, mentioned_user_ids";
+
+impl PgStorage {
+    // 接続できなければ起動時にエラーにする。マイグレーションを実行し、空なら初期データを入れる
+    pub(crate) async fn connect(url: &str, seed: &Seed) -> Result<Self, sqlx::Error> {
+        let options = url.parse::<PgConnectOptions>()?;
+        let timeout = Duration::from_secs(env_or("DATABASE_CONNECT_TIMEOUT_SECS", 5));
+        // プールのタイムアウトではなく、接続できない理由（拒否・認証失敗など）を表示する
+        tokio::time::timeout(timeout, PgConnection::connect_with(&options))
+            .await
+            .map_err(|_| sqlx::Error::PoolTimedOut)??
+            .close()
+            .await?;
+        let pool = PgPoolOptions::new()
+            .max_connections(env_or("DATABASE_POOL_SIZE", 10))
+            .acquire_timeout(timeout)
+            .connect_with(options)
+            .await?;
+        sqlx::migrate!("./migrations/postgres").run(&pool).await?;
+
+        let (count,): (i64,) = sqlx::query_as(
+            "SELECT (SELECT COUNT(*) FROM users) + (SELECT COUNT(*) FROM posts)",
+        )
+        .fetch_one(&pool)
+        .await?;
+        if count == 0 {
+            let (users, posts) = seed.tables();
+            // 初期データのIDはUUIDではないので振り直す
+            let ids: HashMap<ID, ID> = users
+                .iter()
+                .map(|user| (user.id.clone(), ID::from(Uuid::new_v4().to_string())))
+                .collect();
+            let mut tx = pool.begin().await?;
+            for user in users.iter() {
+                let user = User {
+                    id: ids[&user.id].clone(),
+                    ..user.clone()
+                };
+                pg_save_user(&mut tx, &user, false).await?;
+            }
+            for post in posts.iter() {
+                let post = Post {
+                    id: ID::from(Uuid::new_v4().to_string()),
+                    author_id: ids[&post.author_id].clone(),
+                    mentioned_user_ids: post
+                        .mentioned_user_ids
+                        .iter()
+                        .map(|id| ids[id].clone())
+                        .collect(),
+                    ..post.clone()
+                };
+                pg_save_post(&mut tx, &truncate_post(post), false).await?;
+            }
+            tx.commit().await?;
+        }
+
+        // 以前のバージョンで保存したユーザーにはハンドルがない
+        let (missing,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM users WHERE handle IS NULL")
+            .fetch_one(&pool)
+            .await?;
+        if missing > 0 {
+            let sql = format!("SELECT {} FROM users ORDER BY seq", PG_USER_COLUMNS);
+            let rows = sqlx::query(&sql).fetch_all(&pool).await?;
+            let mut users = rows.iter().map(pg_user_from_row).collect::<Result<Vec<_>, _>>()?;
+            let mut tx = pool.begin().await?;
+            for user in assign_missing_handles(&mut users) {
+                pg_save_user(&mut tx, &user, true).await?;
+            }
+            tx.commit().await?;
+        }
+
+        Ok(PgStorage { pool })
+    }
+}
+
+// UUIDとして読めないIDのデータは存在しない
+fn pg_uuid(id: &ID) -> Option<Uuid> {
+    Uuid::parse_str(id.as_str()).ok()
+}
+
+fn pg_id(id: &ID) -> Result<Uuid, sqlx::Error> {
+    pg_uuid(id).ok_or_else(|| sqlx::Error::Encode(format!("invalid uuid: {}", id.as_str()).into()))
+}
+
+// TIMESTAMPTZはマイクロ秒までなので、保存前に切り捨てて返す値と保存される値を揃える
+fn truncate_datetime(value: DateTimeScalar) -> DateTimeScalar {
+    DateTimeScalar(value.0.trunc_subsecs(6))
+}
+
+fn truncate_post(post: Post) -> Post {
+    Post {
+        published_at: truncate_datetime(post.published_at),
+        scheduled_at: post.scheduled_at.map(truncate_datetime),
+        updated_at: truncate_datetime(post.updated_at),
+        deleted_at: post.deleted_at.map(truncate_datetime),
+        ..post
+    }
+}
+
+fn pg_user_from_row(row: &PgRow) -> Result<User, sqlx::Error> {
+    Ok(User {
+        id: ID::from(row.try_get::<Uuid, _>("id")?.to_string()),
+        name: row.try_get("name")?,
+        handle: row.try_get::<Option<String>, _>("handle")?.unwrap_or_default(),
+        avatar_url: row.try_get::<Option<String>, _>("avatar_url")?.map(UrlScalar),
+        email: row.try_get("email")?,
+        email_on_comment: row.try_get("email_on_comment")?,
+        bio: row.try_get("bio")?,
+        website: row.try_get::<Option<String>, _>("website")?.map(UrlScalar),
+        location: row.try_get("location")?,
+        role: parse_role(row.try_get("role")?)?,
+        password_hash: row.try_get("password_hash")?,
+    })
+}
+
+fn pg_post_from_row(row: &PgRow) -> Result<Post, sqlx::Error> {
+    let scheduled_at: Option<DateTime<Utc>> = row.try_get("scheduled_at")?;
+    let deleted_at: Option<DateTime<Utc>> = row.try_get("deleted_at")?;
+    let Json(revisions) = row.try_get("revisions")?;
+    let mentioned_user_ids: Vec<Uuid> = row.try_get("mentioned_user_ids")?;
+    Ok(Post {
+        id: ID::from(row.try_get::<Uuid, _>("id")?.to_string()),
+        title: row.try_get("title")?,
+        slug: row.try_get("slug")?,
+        author_id: ID::from(row.try_get::<Uuid, _>("author_id")?.to_string()),
+        body: row.try_get("body")?,
+        tags: row.try_get("tags")?,
+        cover_image_url: row.try_get::<Option<String>, _>("cover_image_url")?.map(UrlScalar),
+        cover_image_alt: row.try_get("cover_image_alt")?,
+        seo_description: row.try_get("seo_description")?,
+        canonical_url: row.try_get::<Option<String>, _>("canonical_url")?.map(UrlScalar),
+        status: parse_status(row.try_get("status")?)?,
+        published_at: DateTimeScalar(row.try_get("published_at")?),
+        scheduled_at: scheduled_at.map(DateTimeScalar),
+        updated_at: DateTimeScalar(row.try_get("updated_at")?),
+        deleted_at: deleted_at.map(DateTimeScalar),
+        revisions,
+        text_stats: decode_text_stats(row.try_get("latin_words")?, row.try_get("cjk_chars")?),
+        mentioned_user_ids: mentioned_user_ids.iter().map(|id| ID::from(id.to_string())).collect(),
+    })
+}
+
+// replaceがfalseの場合、同じIDの行があれば一意制約の違反になる
+// （更新してもseqは変わらないので登録順は保たれる）
+async fn pg_save_user(
+    conn: &mut PgConnection,
+    user: &User,
+    replace: bool,
+) -> Result<(), sqlx::Error> {
+    let on_conflict = if replace {
+        " ON CONFLICT (id) DO UPDATE SET name = excluded.name, name_key = excluded.name_key, \
+         handle = excluded.handle, avatar_url = excluded.avatar_url, email = excluded.email, \
+         email_on_comment = excluded.email_on_comment, bio = excluded.bio, \
+         bio_key = excluded.bio_key, website = excluded.website, \
+         location = excluded.location, role = excluded.role, \
+         password_hash = excluded.password_hash"
+    } else {
+        ""
+    };
+    let sql = format!(
+        "INSERT INTO users (id, name, name_key, handle, avatar_url, email, email_on_comment, \
+         bio, bio_key, website, location, role, password_hash) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13){}",
+        on_conflict
+    );
+    sqlx::query(&sql)
+        .bind(pg_id(&user.id)?)
+        .bind(user.name.as_str())
+        .bind(user.name.to_lowercase())
+        .bind(user.handle.as_str())
+        .bind(user.avatar_url.as_ref().map(UrlScalar::as_str))
+        .bind(user.email.as_deref())
+        .bind(user.email_on_comment)
+        .bind(user.bio.as_deref())
+        .bind(user.bio.as_deref().map(str::to_lowercase))
+        .bind(user.website.as_ref().map(UrlScalar::as_str))
+        .bind(user.location.as_deref())
+        .bind(role_name(user.role))
+        .bind(user.password_hash.as_deref())
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+// replaceがfalseの場合、同じIDの行があれば一意制約の違反になる
+async fn pg_save_post(
+    conn: &mut PgConnection,
+    post: &Post,
+    replace: bool,
+) -> Result<(), sqlx::Error> {
+    let tag_keys: Vec<String> = post.tags.iter().map(|tag| tag.trim().to_lowercase()).collect();
+    let mentioned_user_ids: Vec<Uuid> =
+        post.mentioned_user_ids.iter().filter_map(pg_uuid).collect();
+    let on_conflict = if replace {
+        " ON CONFLICT (id) DO UPDATE SET title = excluded.title, slug = excluded.slug, \
+         author_id = excluded.author_id, body = excluded.body, tags = excluded.tags, \
+         tag_keys = excluded.tag_keys, status = excluded.status, \
+         published_at = excluded.published_at, scheduled_at = excluded.scheduled_at, \
+         updated_at = excluded.updated_at, deleted_at = excluded.deleted_at, \
+         revisions = excluded.revisions, latin_words = excluded.latin_words, \
+         cjk_chars = excluded.cjk_chars, cover_image_url = excluded.cover_image_url, \
+         cover_image_alt = excluded.cover_image_alt, \
+         seo_description = excluded.seo_description, canonical_url = excluded.canonical_url, \
+         mentioned_user_ids = excluded.mentioned_user_ids"
+    } else {
+        ""
+    };
+    let sql = format!(
+        "INSERT INTO posts (id, title, slug, author_id, body, tags, tag_keys, status, \
+         published_at, scheduled_at, updated_at, deleted_at, revisions, latin_words, cjk_chars, \
+         cover_image_url, cover_image_alt, seo_description, canonical_url, mentioned_user_ids) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, \
+         $18, $19, $20){}",
+        on_conflict
+    );
+    sqlx::query(&sql)
+        .bind(pg_id(&post.id)?)
+        .bind(post.title.as_str())
+        .bind(post.slug.as_str())
+        .bind(pg_id(&post.author_id)?)
+        .bind(post.body.as_str())
+        .bind(&post.tags)
+        .bind(tag_keys)
+        .bind(status_name(post.status))
+        .bind(post.published_at.0)
+        .bind(post.scheduled_at.map(|d| d.0))
+        .bind(post.updated_at.0)
+        .bind(post.deleted_at.map(|d| d.0))
+        .bind(Json(&post.revisions))
+        .bind(post.text_stats.map(|stats| stats.words as i32))
+        .bind(post.text_stats.map(|stats| stats.cjk_chars as i32))
+        .bind(post.cover_image_url.as_ref().map(UrlScalar::as_str))
+        .bind(post.cover_image_alt.as_deref())
+        .bind(post.seo_description.as_deref())
+        .bind(post.canonical_url.as_ref().map(UrlScalar::as_str))
+        .bind(mentioned_user_ids)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+// 一意制約の違反はCONFLICTにする
+fn pg_insert_error(error: sqlx::Error, post: Option<&Post>) -> async_graphql::Error {
+    let Some(db) = error.as_database_error().filter(|db| db.is_unique_violation()) else {
+        return db_error(error);
+    };
+    let message = match (post, db.constraint()) {
+        (Some(post), Some("posts_slug_key")) => format!("Slug is already in use: {}", post.slug),
+        (Some(post), _) => format!("Duplicate post id: {}", post.id.as_str()),
+        (None, _) => "Duplicate user id".to_string(),
+    };
+    AppError::Conflict(message).into()
+}
+
+// PostFilter::matchesと同じ条件のWHERE句
+fn push_pg_post_filter(query: &mut QueryBuilder<'_, Postgres>, filter: &PostFilter) {
+    query.push(" WHERE deleted_at IS NULL");
+    if !filter.include_drafts {
+        query
+            .push(" AND status = ")
+            .push_bind(status_name(PostStatus::Published));
+    } else if let Some(author_id) = &filter.draft_author_id {
+        query
+            .push(" AND (status = ")
+            .push_bind(status_name(PostStatus::Published))
+            .push(" OR author_id = ")
+            .push_bind(pg_uuid(author_id))
+            .push(")");
+    }
+    if let Some(tag) = &filter.tag {
+        query
+            .push(" AND tag_keys @> ARRAY[")
+            .push_bind(tag.clone())
+            .push("]::text[]");
+    }
+    if let Some(after) = filter.published_after {
+        query.push(" AND published_at >= ").push_bind(after.0);
+    }
+    if let Some(before) = filter.published_before {
+        query.push(" AND published_at < ").push_bind(before.0);
+    }
+    if let Some(author_ids) = &filter.author_ids {
+        let author_ids: Vec<Uuid> = author_ids.iter().filter_map(pg_uuid).collect();
+        query.push(" AND author_id = ANY(").push_bind(author_ids).push(")");
+    }
+    match filter.has_cover_image {
+        Some(true) => query.push(" AND cover_image_url IS NOT NULL"),
+        Some(false) => query.push(" AND cover_image_url IS NULL"),
+        None => query,
+    };
+}
+
+// user_matchesと同じ条件のWHERE句
+fn push_pg_user_search(query: &mut QueryBuilder<'_, Postgres>, filter: &UserFilter) {
+    let Some(search) = filter.search() else {
+        return;
+    };
+    query.push(" WHERE strpos(name_key, ").push_bind(search.clone()).push(") > 0");
+    if filter.include_bio {
+        query.push(" OR strpos(bio_key, ").push_bind(search).push(") > 0");
+    }
+}
+
+// LIMIT NULLは上限なしになる
+fn push_pg_page(query: &mut QueryBuilder<'_, Postgres>, page: Page) {
+    query
+        .push(" LIMIT ")
+        .push_bind(page.limit.map(|limit| limit as i64))
+        .push(" OFFSET ")
+        .push_bind(page.offset as i64);
+}
+
+#[async_trait::async_trait]
+impl Storage for PgStorage {
+    async fn list_posts(
+        &self,
+        filter: &PostFilter,
+        page: Page,
+    ) -> async_graphql::Result<Vec<Post>> {
+        let mut query =
+            QueryBuilder::<Postgres>::new(format!("SELECT {} FROM posts", PG_POST_COLUMNS));
+        push_pg_post_filter(&mut query, filter);
+        query.push(" ORDER BY published_at DESC, id DESC");
+        push_pg_page(&mut query, page);
+        let rows = query.build().fetch_all(&self.pool).await.map_err(db_error)?;
+        rows.iter()
+            .map(pg_post_from_row)
+            .collect::<Result<_, _>>()
+            .map_err(db_error)
+    }
+
+    async fn count_posts(&self, filter: &PostFilter) -> async_graphql::Result<usize> {
+        let mut query = QueryBuilder::<Postgres>::new("SELECT COUNT(*) FROM posts");
+        push_pg_post_filter(&mut query, filter);
+        let count: i64 = query
+            .build_query_scalar()
+            .fetch_one(&self.pool)
+            .await
+            .map_err(db_error)?;
+        Ok(count as usize)
+    }
+
+    async fn list_all_posts(&self) -> async_graphql::Result<Vec<Post>> {
+        let sql = format!(
+            "SELECT {} FROM posts ORDER BY published_at DESC, id DESC",
+            PG_POST_COLUMNS
+        );
+        let rows = sqlx::query(&sql)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+        rows.iter()
+            .map(pg_post_from_row)
+            .collect::<Result<_, _>>()
+            .map_err(db_error)
+    }
+
+    async fn get_post(&self, id: &ID) -> async_graphql::Result<Option<Post>> {
+        let Some(id) = pg_uuid(id) else {
+            return Ok(None);
+        };
+        let sql = format!("SELECT {} FROM posts WHERE id = $1", PG_POST_COLUMNS);
+        let row = sqlx::query(&sql)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+        row.as_ref().map(pg_post_from_row).transpose().map_err(db_error)
+    }
+
+    async fn get_post_by_slug(&self, slug: &str) -> async_graphql::Result<Option<Post>> {
+        let sql = format!("SELECT {} FROM posts WHERE slug = $1", PG_POST_COLUMNS);
+        let row = sqlx::query(&sql)
+            .bind(slug)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+        row.as_ref().map(pg_post_from_row).transpose().map_err(db_error)
+    }
+
+    // 重複はIDとスラッグの一意制約で検出する
+    async fn insert_post(&self, post: Post) -> async_graphql::Result<Post> {
+        let post = truncate_post(post);
+        let mut conn = self.pool.acquire().await.map_err(db_error)?;
+        pg_save_post(&mut conn, &post, false)
+            .await
+            .map_err(|e| pg_insert_error(e, Some(&post)))?;
+        Ok(post)
+    }
+
+    // 行ロック（FOR UPDATE）で他の書き込みを待たせる
+    async fn update_post(
+        &self,
+        id: &ID,
+        update: PostUpdate<'_>,
+    ) -> async_graphql::Result<Option<Post>> {
+        let Some(uuid) = pg_uuid(id) else {
+            return Ok(None);
+        };
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let sql = format!("SELECT {} FROM posts WHERE id = $1 FOR UPDATE", PG_POST_COLUMNS);
+        let row = sqlx::query(&sql)
+            .bind(uuid)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(db_error)?;
+        let Some(mut post) = row.as_ref().map(pg_post_from_row).transpose().map_err(db_error)?
+        else {
+            return Ok(None);
+        };
+        update(&mut post)?;
+        let post = truncate_post(post);
+        pg_save_post(&mut tx, &post, true)
+            .await
+            .map_err(|e| pg_insert_error(e, Some(&post)))?;
+        tx.commit().await.map_err(db_error)?;
+        Ok(Some(post))
+    }
+
+    async fn delete_post(&self, id: &ID) -> async_graphql::Result<Option<Post>> {
+        let Some(id) = pg_uuid(id) else {
+            return Ok(None);
+        };
+        let sql = format!("DELETE FROM posts WHERE id = $1 RETURNING {}", PG_POST_COLUMNS);
+        let row = sqlx::query(&sql)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+        row.as_ref().map(pg_post_from_row).transpose().map_err(db_error)
+    }
+
+    async fn list_users(
+        &self,
+        filter: &UserFilter,
+        page: Page,
+    ) -> async_graphql::Result<Vec<User>> {
+        let mut query =
+            QueryBuilder::<Postgres>::new(format!("SELECT {} FROM users", PG_USER_COLUMNS));
+        push_pg_user_search(&mut query, filter);
+        query.push(" ORDER BY seq");
+        push_pg_page(&mut query, page);
+        let rows = query.build().fetch_all(&self.pool).await.map_err(db_error)?;
+        rows.iter()
+            .map(pg_user_from_row)
+            .collect::<Result<_, _>>()
+            .map_err(db_error)
+    }
+
+    async fn count_users(&self, filter: &UserFilter) -> async_graphql::Result<usize> {
+        let mut query = QueryBuilder::<Postgres>::new("SELECT COUNT(*) FROM users");
+        push_pg_user_search(&mut query, filter);
+        let count: i64 = query
+            .build_query_scalar()
+            .fetch_one(&self.pool)
+            .await
+            .map_err(db_error)?;
+        Ok(count as usize)
+    }
+
+    async fn get_user(&self, id: &ID) -> async_graphql::Result<Option<User>> {
+        let Some(id) = pg_uuid(id) else {
+            return Ok(None);
+        };
+        let sql = format!("SELECT {} FROM users WHERE id = $1", PG_USER_COLUMNS);
+        let row = sqlx::query(&sql)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+        row.as_ref().map(pg_user_from_row).transpose().map_err(db_error)
+    }
+
+    async fn get_users(&self, ids: &[ID]) -> async_graphql::Result<Vec<User>> {
+        let ids: Vec<Uuid> = ids.iter().filter_map(pg_uuid).collect();
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let sql = format!("SELECT {} FROM users WHERE id = ANY($1)", PG_USER_COLUMNS);
+        let rows = sqlx::query(&sql)
+            .bind(ids)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+        rows.iter()
+            .map(pg_user_from_row)
+            .collect::<Result<_, _>>()
+            .map_err(db_error)
+    }
+
+    async fn get_users_by_name(&self, name: &str) -> async_graphql::Result<Vec<User>> {
+        let sql = format!(
+            "SELECT {} FROM users WHERE name = $1 ORDER BY seq",
+            PG_USER_COLUMNS
+        );
+        let rows = sqlx::query(&sql)
+            .bind(name)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+        rows.iter()
+            .map(pg_user_from_row)
+            .collect::<Result<_, _>>()
+            .map_err(db_error)
+    }
+
+    async fn get_user_by_handle(&self, handle: &str) -> async_graphql::Result<Option<User>> {
+        let sql = format!("SELECT {} FROM users WHERE handle = $1", PG_USER_COLUMNS);
+        let row = sqlx::query(&sql)
+            .bind(handle)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+        row.as_ref().map(pg_user_from_row).transpose().map_err(db_error)
+    }
+
+    async fn insert_user(&self, user: User) -> async_graphql::Result<()> {
+        let mut conn = self.pool.acquire().await.map_err(db_error)?;
+        pg_save_user(&mut conn, &user, false).await.map_err(|e| {
+            match e.as_database_error().and_then(|db| db.constraint()) {
+                Some("users_handle") => handle_taken(&user.handle),
+                _ => pg_insert_error(e, None),
+            }
+        })
+    }
+
+    async fn update_user(
+        &self,
+        id: &ID,
+        update: UserUpdate<'_>,
+    ) -> async_graphql::Result<Option<User>> {
+        let Some(uuid) = pg_uuid(id) else {
+            return Ok(None);
+        };
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let sql = format!("SELECT {} FROM users WHERE id = $1 FOR UPDATE", PG_USER_COLUMNS);
+        let row = sqlx::query(&sql)
+            .bind(uuid)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(db_error)?;
+        let Some(mut user) = row.as_ref().map(pg_user_from_row).transpose().map_err(db_error)?
+        else {
+            return Ok(None);
+        };
+        update(&mut user)?;
+        pg_save_user(&mut tx, &user, true).await.map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+        Ok(Some(user))
+    }
+
+    async fn delete_user(&self, id: &ID) -> async_graphql::Result<Option<User>> {
+        let Some(id) = pg_uuid(id) else {
+            return Ok(None);
+        };
+        let sql = format!("DELETE FROM users WHERE id = $1 RETURNING {}", PG_USER_COLUMNS);
+        let row = sqlx::query(&sql)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+        row.as_ref().map(pg_user_from_row).transpose().map_err(db_error)
+    }
+
+    async fn delete_user_with_posts(&self, id: &ID) -> async_graphql::Result<Option<Vec<ID>>> {
+        let Some(id) = pg_uuid(id) else {
+            return Ok(None);
+        };
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let deleted = sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+        if deleted.rows_affected() == 0 {
+            return Ok(None);
+        }
+        let sql = "DELETE FROM posts WHERE author_id = $1 RETURNING id";
+        let ids: Vec<Uuid> = sqlx::query_scalar(sql)
+            .bind(id)
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+        Ok(Some(ids.into_iter().map(|id| ID::from(id.to_string())).collect()))
+    }
+
+    async fn ping(&self) -> Result<(), String> {
+        sqlx::query("SELECT 1")
+            .execute(&self.pool)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    async fn close(&self) {
+        self.pool.close().await;
+    }
+}
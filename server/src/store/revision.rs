@@ -0,0 +1,63 @@
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextParseQuery, NextRequest,
+};
+use async_graphql::parser::types::{ExecutableDocument, OperationType};
+use async_graphql::{Response, ServerResult, Variables};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+// データの版（ミューテーションや予約投稿の公開のたびに増える）。GETのレスポンスのETagに使う
+#[derive(Clone, Default)]
+pub(crate) struct StoreRevision(Arc<AtomicU64>);
+
+impl StoreRevision {
+    pub(crate) fn current(&self) -> u64 {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn bump(&self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+// ミューテーションを実行したら版を進める（失敗した場合も途中まで変更しているかもしれない）
+impl ExtensionFactory for StoreRevision {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(StoreRevisionExtension {
+            revision: self.clone(),
+            mutation: AtomicBool::new(false),
+        })
+    }
+}
+
+struct StoreRevisionExtension {
+    revision: StoreRevision,
+    mutation: AtomicBool,
+}
+
+#[async_trait::async_trait]
+impl Extension for StoreRevisionExtension {
+    async fn request(&self, ctx: &ExtensionContext<'_>, next: NextRequest<'_>) -> Response {
+        let response = next.run(ctx).await;
+        if self.mutation.load(Ordering::Relaxed) {
+            self.revision.bump();
+        }
+        response
+    }
+
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let document = next.run(ctx, query, variables).await?;
+        let mutation = document
+            .operations
+            .iter()
+            .any(|(_, op)| op.node.ty == OperationType::Mutation);
+        self.mutation.store(mutation, Ordering::Relaxed);
+        Ok(document)
+    }
+}
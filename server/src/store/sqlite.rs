@@ -0,0 +1,615 @@
+use async_graphql::ID;
+use chrono::{DateTime, SecondsFormat, Utc};
+use sqlx::sqlite::{Sqlite, SqliteConnectOptions, SqliteConnection, SqlitePool, SqliteRow};
+use sqlx::{QueryBuilder, Row};
+
+use crate::error::AppError;
+use crate::markdown::TextStats;
+use crate::models::{Post, PostStatus, Role, User};
+use crate::pagination::Page;
+use crate::scalars::{DateTimeScalar, UrlScalar};
+use crate::search::{PostFilter, UserFilter};
+use crate::seed::Seed;
+use crate::store::{PostUpdate, Storage, UserUpdate};
+use crate::validation::{assign_missing_handles, handle_taken};
+
+// SQLite（DATABASE_URL=sqlite:...）
+// タグは並び順を保つため別テーブルに持ち、絞り込み用に正規化した名前も保存する
+pub(crate) struct SqliteStorage {
+    pool: SqlitePool,
+    // 読み込んでから書き込むまでの間に、他の書き込みが割り込まないようにする
+    write_lock: tokio::sync::Mutex<()>,
+}
+
+const SQLITE_USER_COLUMNS: &str = "id, name, handle, avatar_url, email, email_on_comment, bio, \
+     website, location, role, password_hash";
+const SQLITE_POST_COLUMNS: &str = "id, title, slug, author_id, body, status, published_at, \
+     scheduled_at, updated_at, deleted_at, revisions, latin_words, cjk_chars, \
+     cover_image_url, cover_image_alt, seo_description, canonical_url, mentioned_user_ids, \
+     (SELECT json_group_array(name ORDER BY position) FROM post_tags \
+      WHERE post_tags.post_id = posts.id) AS tags";
+
+impl SqliteStorage {
+    // マイグレーションを実行し、データベースが空なら初期データを入れる
+    pub(crate) async fn connect(url: &str, seed: &Seed) -> Result<Self, sqlx::Error> {
+        let options = url
+            .parse::<SqliteConnectOptions>()?
+            .create_if_missing(true)
+            .foreign_keys(true);
+        let pool = SqlitePool::connect_with(options).await?;
+        sqlx::migrate!("./migrations/sqlite").run(&pool).await?;
+
+        let (count,): (i64,) = sqlx::query_as(
+            "SELECT (SELECT COUNT(*) FROM users) + (SELECT COUNT(*) FROM posts)",
+        )
+        .fetch_one(&pool)
+        .await?;
+        if count == 0 {
+            let (users, posts) = seed.tables();
+            let mut tx = pool.begin().await?;
+            for user in users.iter() {
+                sqlite_save_user(&mut tx, user).await?;
+            }
+            for post in posts.iter() {
+                sqlite_save_post(&mut tx, post).await?;
+            }
+            tx.commit().await?;
+        }
+
+        // 以前のバージョンで保存したユーザーにはハンドルがない
+        let (missing,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM users WHERE handle IS NULL")
+            .fetch_one(&pool)
+            .await?;
+        if missing > 0 {
+            let sql = format!("SELECT {} FROM users ORDER BY rowid", SQLITE_USER_COLUMNS);
+            let rows = sqlx::query(&sql).fetch_all(&pool).await?;
+            let mut users = rows.iter().map(user_from_row).collect::<Result<Vec<_>, _>>()?;
+            let mut tx = pool.begin().await?;
+            for user in assign_missing_handles(&mut users) {
+                sqlite_save_user(&mut tx, &user).await?;
+            }
+            tx.commit().await?;
+        }
+
+        Ok(SqliteStorage {
+            pool,
+            write_lock: tokio::sync::Mutex::new(()),
+        })
+    }
+}
+
+// 詳細はサーバーのログにだけ出す
+pub(crate) fn db_error(error: sqlx::Error) -> async_graphql::Error {
+    AppError::Internal(format!("database error: {}", error)).into()
+}
+
+fn decode_error(message: String) -> sqlx::Error {
+    sqlx::Error::Decode(message.into())
+}
+
+// 日時はナノ秒まで固定桁のRFC 3339で保存し、文字列の大小と時刻の前後を一致させる
+pub(crate) fn encode_datetime(value: DateTimeScalar) -> String {
+    value.0.to_rfc3339_opts(SecondsFormat::Nanos, true)
+}
+
+fn decode_datetime(value: &str) -> Result<DateTimeScalar, sqlx::Error> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|dt| DateTimeScalar(dt.with_timezone(&Utc)))
+        .map_err(|e| decode_error(format!("invalid datetime {:?}: {}", value, e)))
+}
+
+// 語数を数える前に保存された投稿ではNULL
+pub(crate) fn decode_text_stats(words: Option<i32>, cjk_chars: Option<i32>) -> Option<TextStats> {
+    Some(TextStats {
+        words: u32::try_from(words?).ok()?,
+        cjk_chars: u32::try_from(cjk_chars?).ok()?,
+    })
+}
+
+pub(crate) fn role_name(role: Role) -> &'static str {
+    match role {
+        Role::Admin => "ADMIN",
+        Role::Author => "AUTHOR",
+        Role::Reader => "READER",
+    }
+}
+
+pub(crate) fn parse_role(name: &str) -> Result<Role, sqlx::Error> {
+    match name {
+        "ADMIN" => Ok(Role::Admin),
+        "AUTHOR" => Ok(Role::Author),
+        "READER" => Ok(Role::Reader),
+        _ => Err(decode_error(format!("unknown role: {}", name))),
+    }
+}
+
+pub(crate) fn status_name(status: PostStatus) -> &'static str {
+    match status {
+        PostStatus::Draft => "DRAFT",
+        PostStatus::Published => "PUBLISHED",
+    }
+}
+
+pub(crate) fn parse_status(name: &str) -> Result<PostStatus, sqlx::Error> {
+    match name {
+        "DRAFT" => Ok(PostStatus::Draft),
+        "PUBLISHED" => Ok(PostStatus::Published),
+        _ => Err(decode_error(format!("unknown post status: {}", name))),
+    }
+}
+
+fn user_from_row(row: &SqliteRow) -> Result<User, sqlx::Error> {
+    Ok(User {
+        id: ID::from(row.try_get::<String, _>("id")?),
+        name: row.try_get("name")?,
+        handle: row.try_get::<Option<String>, _>("handle")?.unwrap_or_default(),
+        avatar_url: row.try_get::<Option<String>, _>("avatar_url")?.map(UrlScalar),
+        email: row.try_get("email")?,
+        email_on_comment: row.try_get("email_on_comment")?,
+        bio: row.try_get("bio")?,
+        website: row.try_get::<Option<String>, _>("website")?.map(UrlScalar),
+        location: row.try_get("location")?,
+        role: parse_role(row.try_get("role")?)?,
+        password_hash: row.try_get("password_hash")?,
+    })
+}
+
+fn post_from_row(row: &SqliteRow) -> Result<Post, sqlx::Error> {
+    let tags: &str = row.try_get("tags")?;
+    let revisions: &str = row.try_get("revisions")?;
+    let mentioned_user_ids: &str = row.try_get("mentioned_user_ids")?;
+    let scheduled_at: Option<&str> = row.try_get("scheduled_at")?;
+    let deleted_at: Option<&str> = row.try_get("deleted_at")?;
+    Ok(Post {
+        id: ID::from(row.try_get::<String, _>("id")?),
+        title: row.try_get("title")?,
+        slug: row.try_get("slug")?,
+        author_id: ID::from(row.try_get::<String, _>("author_id")?),
+        body: row.try_get("body")?,
+        tags: serde_json::from_str(tags).map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
+        cover_image_url: row.try_get::<Option<String>, _>("cover_image_url")?.map(UrlScalar),
+        cover_image_alt: row.try_get("cover_image_alt")?,
+        seo_description: row.try_get("seo_description")?,
+        canonical_url: row.try_get::<Option<String>, _>("canonical_url")?.map(UrlScalar),
+        status: parse_status(row.try_get("status")?)?,
+        published_at: decode_datetime(row.try_get("published_at")?)?,
+        scheduled_at: scheduled_at.map(decode_datetime).transpose()?,
+        updated_at: decode_datetime(row.try_get("updated_at")?)?,
+        deleted_at: deleted_at.map(decode_datetime).transpose()?,
+        revisions: serde_json::from_str(revisions).map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
+        text_stats: decode_text_stats(row.try_get("latin_words")?, row.try_get("cjk_chars")?),
+        mentioned_user_ids: serde_json::from_str(mentioned_user_ids)
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
+    })
+}
+
+// 追加と更新を兼ねる（更新してもrowidは変わらないので登録順は保たれる）
+async fn sqlite_save_user(conn: &mut SqliteConnection, user: &User) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO users (id, name, name_key, handle, avatar_url, email, email_on_comment, \
+         bio, bio_key, website, location, role, password_hash) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) \
+         ON CONFLICT (id) DO UPDATE SET name = excluded.name, name_key = excluded.name_key, \
+         handle = excluded.handle, avatar_url = excluded.avatar_url, email = excluded.email, \
+         email_on_comment = excluded.email_on_comment, bio = excluded.bio, \
+         bio_key = excluded.bio_key, website = excluded.website, \
+         location = excluded.location, role = excluded.role, \
+         password_hash = excluded.password_hash",
+    )
+    .bind(user.id.as_str())
+    .bind(user.name.as_str())
+    .bind(user.name.to_lowercase())
+    .bind(user.handle.as_str())
+    .bind(user.avatar_url.as_ref().map(UrlScalar::as_str))
+    .bind(user.email.as_deref())
+    .bind(user.email_on_comment)
+    .bind(user.bio.as_deref())
+    .bind(user.bio.as_deref().map(str::to_lowercase))
+    .bind(user.website.as_ref().map(UrlScalar::as_str))
+    .bind(user.location.as_deref())
+    .bind(role_name(user.role))
+    .bind(user.password_hash.as_deref())
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+// 追加と更新を兼ねる。タグは全て入れ直す
+async fn sqlite_save_post(conn: &mut SqliteConnection, post: &Post) -> Result<(), sqlx::Error> {
+    let revisions =
+        serde_json::to_string(&post.revisions).map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+    let mentioned_user_ids = serde_json::to_string(&post.mentioned_user_ids)
+        .map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+    sqlx::query(
+        "INSERT INTO posts (id, title, slug, author_id, body, status, published_at, \
+         scheduled_at, updated_at, deleted_at, revisions, latin_words, cjk_chars, \
+         cover_image_url, cover_image_alt, seo_description, canonical_url, mentioned_user_ids) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) \
+         ON CONFLICT (id) DO UPDATE SET title = excluded.title, slug = excluded.slug, \
+         author_id = excluded.author_id, body = excluded.body, status = excluded.status, \
+         published_at = excluded.published_at, scheduled_at = excluded.scheduled_at, \
+         updated_at = excluded.updated_at, deleted_at = excluded.deleted_at, \
+         revisions = excluded.revisions, latin_words = excluded.latin_words, \
+         cjk_chars = excluded.cjk_chars, cover_image_url = excluded.cover_image_url, \
+         cover_image_alt = excluded.cover_image_alt, \
+         seo_description = excluded.seo_description, canonical_url = excluded.canonical_url, \
+         mentioned_user_ids = excluded.mentioned_user_ids",
+    )
+    .bind(post.id.as_str())
+    .bind(post.title.as_str())
+    .bind(post.slug.as_str())
+    .bind(post.author_id.as_str())
+    .bind(post.body.as_str())
+    .bind(status_name(post.status))
+    .bind(encode_datetime(post.published_at))
+    .bind(post.scheduled_at.map(encode_datetime))
+    .bind(encode_datetime(post.updated_at))
+    .bind(post.deleted_at.map(encode_datetime))
+    .bind(revisions)
+    .bind(post.text_stats.map(|stats| stats.words as i32))
+    .bind(post.text_stats.map(|stats| stats.cjk_chars as i32))
+    .bind(post.cover_image_url.as_ref().map(UrlScalar::as_str))
+    .bind(post.cover_image_alt.as_deref())
+    .bind(post.seo_description.as_deref())
+    .bind(post.canonical_url.as_ref().map(UrlScalar::as_str))
+    .bind(mentioned_user_ids)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query("DELETE FROM post_tags WHERE post_id = ?")
+        .bind(post.id.as_str())
+        .execute(&mut *conn)
+        .await?;
+    for (position, tag) in post.tags.iter().enumerate() {
+        sqlx::query(
+            "INSERT INTO post_tags (post_id, position, name, normalized) VALUES (?, ?, ?, ?)",
+        )
+        .bind(post.id.as_str())
+        .bind(position as i64)
+        .bind(tag.as_str())
+        .bind(tag.trim().to_lowercase())
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+// PostFilter::matchesと同じ条件のWHERE句
+fn push_post_filter(query: &mut QueryBuilder<'_, Sqlite>, filter: &PostFilter) {
+    query.push(" WHERE deleted_at IS NULL");
+    if !filter.include_drafts {
+        query
+            .push(" AND status = ")
+            .push_bind(status_name(PostStatus::Published));
+    } else if let Some(author_id) = &filter.draft_author_id {
+        query
+            .push(" AND (status = ")
+            .push_bind(status_name(PostStatus::Published))
+            .push(" OR author_id = ")
+            .push_bind(author_id.to_string())
+            .push(")");
+    }
+    if let Some(tag) = &filter.tag {
+        query
+            .push(" AND EXISTS (SELECT 1 FROM post_tags WHERE post_tags.post_id = posts.id")
+            .push(" AND normalized = ")
+            .push_bind(tag.clone())
+            .push(")");
+    }
+    if let Some(after) = filter.published_after {
+        query
+            .push(" AND published_at >= ")
+            .push_bind(encode_datetime(after));
+    }
+    if let Some(before) = filter.published_before {
+        query
+            .push(" AND published_at < ")
+            .push_bind(encode_datetime(before));
+    }
+    match filter.has_cover_image {
+        Some(true) => query.push(" AND cover_image_url IS NOT NULL"),
+        Some(false) => query.push(" AND cover_image_url IS NULL"),
+        None => query,
+    };
+    if let Some(author_ids) = &filter.author_ids {
+        if author_ids.is_empty() {
+            query.push(" AND 0");
+        } else {
+            query.push(" AND author_id IN (");
+            let mut ids = query.separated(", ");
+            for id in author_ids {
+                ids.push_bind(id.to_string());
+            }
+            ids.push_unseparated(")");
+        }
+    }
+}
+
+// user_matchesと同じ条件のWHERE句
+fn push_user_search(query: &mut QueryBuilder<'_, Sqlite>, filter: &UserFilter) {
+    let Some(search) = filter.search() else {
+        return;
+    };
+    query.push(" WHERE instr(name_key, ").push_bind(search.clone()).push(") > 0");
+    if filter.include_bio {
+        query.push(" OR instr(bio_key, ").push_bind(search).push(") > 0");
+    }
+}
+
+// LIMITに負の値を渡すと上限なしになる
+fn push_page(query: &mut QueryBuilder<'_, Sqlite>, page: Page) {
+    query
+        .push(" LIMIT ")
+        .push_bind(page.limit.map_or(-1, |limit| limit as i64))
+        .push(" OFFSET ")
+        .push_bind(page.offset as i64);
+}
+
+#[async_trait::async_trait]
+impl Storage for SqliteStorage {
+    async fn list_posts(
+        &self,
+        filter: &PostFilter,
+        page: Page,
+    ) -> async_graphql::Result<Vec<Post>> {
+        let mut query =
+            QueryBuilder::<Sqlite>::new(format!("SELECT {} FROM posts", SQLITE_POST_COLUMNS));
+        push_post_filter(&mut query, filter);
+        query.push(" ORDER BY published_at DESC, id DESC");
+        push_page(&mut query, page);
+        let rows = query.build().fetch_all(&self.pool).await.map_err(db_error)?;
+        rows.iter()
+            .map(post_from_row)
+            .collect::<Result<_, _>>()
+            .map_err(db_error)
+    }
+
+    async fn count_posts(&self, filter: &PostFilter) -> async_graphql::Result<usize> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) FROM posts");
+        push_post_filter(&mut query, filter);
+        let count: i64 = query
+            .build_query_scalar()
+            .fetch_one(&self.pool)
+            .await
+            .map_err(db_error)?;
+        Ok(count as usize)
+    }
+
+    async fn list_all_posts(&self) -> async_graphql::Result<Vec<Post>> {
+        let sql = format!(
+            "SELECT {} FROM posts ORDER BY published_at DESC, id DESC",
+            SQLITE_POST_COLUMNS
+        );
+        let rows = sqlx::query(&sql)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+        rows.iter()
+            .map(post_from_row)
+            .collect::<Result<_, _>>()
+            .map_err(db_error)
+    }
+
+    async fn get_post(&self, id: &ID) -> async_graphql::Result<Option<Post>> {
+        let sql = format!("SELECT {} FROM posts WHERE id = ?", SQLITE_POST_COLUMNS);
+        let row = sqlx::query(&sql)
+            .bind(id.as_str())
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+        row.as_ref().map(post_from_row).transpose().map_err(db_error)
+    }
+
+    async fn get_post_by_slug(&self, slug: &str) -> async_graphql::Result<Option<Post>> {
+        let sql = format!("SELECT {} FROM posts WHERE slug = ?", SQLITE_POST_COLUMNS);
+        let row = sqlx::query(&sql)
+            .bind(slug)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+        row.as_ref().map(post_from_row).transpose().map_err(db_error)
+    }
+
+    async fn insert_post(&self, post: Post) -> async_graphql::Result<Post> {
+        let _write_lock = self.write_lock.lock().await;
+        if self.get_post(&post.id).await?.is_some() {
+            let message = format!("Duplicate post id: {}", post.id.as_str());
+            return Err(AppError::Conflict(message).into());
+        }
+        if self.get_post_by_slug(&post.slug).await?.is_some() {
+            return Err(AppError::Conflict(format!("Slug is already in use: {}", post.slug)).into());
+        }
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        sqlite_save_post(&mut tx, &post).await.map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+        Ok(post)
+    }
+
+    async fn update_post(
+        &self,
+        id: &ID,
+        update: PostUpdate<'_>,
+    ) -> async_graphql::Result<Option<Post>> {
+        let _write_lock = self.write_lock.lock().await;
+        let Some(mut post) = self.get_post(id).await? else {
+            return Ok(None);
+        };
+        update(&mut post)?;
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        sqlite_save_post(&mut tx, &post).await.map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+        Ok(Some(post))
+    }
+
+    // タグは外部キーのON DELETE CASCADEで削除される
+    async fn delete_post(&self, id: &ID) -> async_graphql::Result<Option<Post>> {
+        let _write_lock = self.write_lock.lock().await;
+        let Some(post) = self.get_post(id).await? else {
+            return Ok(None);
+        };
+        sqlx::query("DELETE FROM posts WHERE id = ?")
+            .bind(id.as_str())
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+        Ok(Some(post))
+    }
+
+    async fn list_users(
+        &self,
+        filter: &UserFilter,
+        page: Page,
+    ) -> async_graphql::Result<Vec<User>> {
+        let mut query =
+            QueryBuilder::<Sqlite>::new(format!("SELECT {} FROM users", SQLITE_USER_COLUMNS));
+        push_user_search(&mut query, filter);
+        query.push(" ORDER BY rowid");
+        push_page(&mut query, page);
+        let rows = query.build().fetch_all(&self.pool).await.map_err(db_error)?;
+        rows.iter()
+            .map(user_from_row)
+            .collect::<Result<_, _>>()
+            .map_err(db_error)
+    }
+
+    async fn count_users(&self, filter: &UserFilter) -> async_graphql::Result<usize> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) FROM users");
+        push_user_search(&mut query, filter);
+        let count: i64 = query
+            .build_query_scalar()
+            .fetch_one(&self.pool)
+            .await
+            .map_err(db_error)?;
+        Ok(count as usize)
+    }
+
+    async fn get_user(&self, id: &ID) -> async_graphql::Result<Option<User>> {
+        let sql = format!("SELECT {} FROM users WHERE id = ?", SQLITE_USER_COLUMNS);
+        let row = sqlx::query(&sql)
+            .bind(id.as_str())
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+        row.as_ref().map(user_from_row).transpose().map_err(db_error)
+    }
+
+    async fn get_users(&self, ids: &[ID]) -> async_graphql::Result<Vec<User>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut query = QueryBuilder::<Sqlite>::new(format!(
+            "SELECT {} FROM users WHERE id IN (",
+            SQLITE_USER_COLUMNS
+        ));
+        let mut separated = query.separated(", ");
+        for id in ids {
+            separated.push_bind(id.to_string());
+        }
+        separated.push_unseparated(")");
+        let rows = query.build().fetch_all(&self.pool).await.map_err(db_error)?;
+        rows.iter()
+            .map(user_from_row)
+            .collect::<Result<_, _>>()
+            .map_err(db_error)
+    }
+
+    async fn get_users_by_name(&self, name: &str) -> async_graphql::Result<Vec<User>> {
+        let sql = format!(
+            "SELECT {} FROM users WHERE name = ? ORDER BY rowid",
+            SQLITE_USER_COLUMNS
+        );
+        let rows = sqlx::query(&sql)
+            .bind(name)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+        rows.iter()
+            .map(user_from_row)
+            .collect::<Result<_, _>>()
+            .map_err(db_error)
+    }
+
+    async fn get_user_by_handle(&self, handle: &str) -> async_graphql::Result<Option<User>> {
+        let sql = format!("SELECT {} FROM users WHERE handle = ?", SQLITE_USER_COLUMNS);
+        let row = sqlx::query(&sql)
+            .bind(handle)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+        row.as_ref().map(user_from_row).transpose().map_err(db_error)
+    }
+
+    async fn insert_user(&self, user: User) -> async_graphql::Result<()> {
+        let _write_lock = self.write_lock.lock().await;
+        if self.get_user(&user.id).await?.is_some() {
+            let message = format!("Duplicate user id: {}", user.id.as_str());
+            return Err(AppError::Conflict(message).into());
+        }
+        if self.get_user_by_handle(&user.handle).await?.is_some() {
+            return Err(handle_taken(&user.handle));
+        }
+        let mut conn = self.pool.acquire().await.map_err(db_error)?;
+        sqlite_save_user(&mut conn, &user).await.map_err(db_error)
+    }
+
+    async fn update_user(
+        &self,
+        id: &ID,
+        update: UserUpdate<'_>,
+    ) -> async_graphql::Result<Option<User>> {
+        let _write_lock = self.write_lock.lock().await;
+        let Some(mut user) = self.get_user(id).await? else {
+            return Ok(None);
+        };
+        update(&mut user)?;
+        let mut conn = self.pool.acquire().await.map_err(db_error)?;
+        sqlite_save_user(&mut conn, &user).await.map_err(db_error)?;
+        Ok(Some(user))
+    }
+
+    async fn delete_user(&self, id: &ID) -> async_graphql::Result<Option<User>> {
+        let _write_lock = self.write_lock.lock().await;
+        let Some(user) = self.get_user(id).await? else {
+            return Ok(None);
+        };
+        sqlx::query("DELETE FROM users WHERE id = ?")
+            .bind(id.as_str())
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+        Ok(Some(user))
+    }
+
+    async fn delete_user_with_posts(&self, id: &ID) -> async_graphql::Result<Option<Vec<ID>>> {
+        let _write_lock = self.write_lock.lock().await;
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let deleted = sqlx::query("DELETE FROM users WHERE id = ?")
+            .bind(id.as_str())
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+        if deleted.rows_affected() == 0 {
+            return Ok(None);
+        }
+        let sql = "DELETE FROM posts WHERE author_id = ? RETURNING id";
+        let ids: Vec<String> = sqlx::query_scalar(sql)
+            .bind(id.as_str())
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+        Ok(Some(ids.into_iter().map(ID::from).collect()))
+    }
+
+    async fn ping(&self) -> Result<(), String> {
+        sqlx::query("SELECT 1")
+            .execute(&self.pool)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    async fn close(&self) {
+        self.pool.close().await;
+    }
+}
@@ -0,0 +1,128 @@
+use async_graphql::ID;
+use tracing::instrument;
+
+use super::{AppStorage, PostUpdate, Storage, UserUpdate};
+use crate::models::{Post, User};
+use crate::pagination::Page;
+use crate::search::{PostFilter, UserFilter};
+
+// ストレージの操作ごとにspanを作る（OpenTelemetryでGraphQLの操作の子spanとして送る）
+// 保存先によらず同じ名前になるよう、各実装ではなくここでまとめて計測する
+pub(crate) struct TracedStorage(pub(crate) AppStorage);
+
+#[async_trait::async_trait]
+impl Storage for TracedStorage {
+    #[instrument(level = "debug", name = "storage.list_posts", skip_all)]
+    async fn list_posts(
+        &self,
+        filter: &PostFilter,
+        page: Page,
+    ) -> async_graphql::Result<Vec<Post>> {
+        self.0.list_posts(filter, page).await
+    }
+
+    #[instrument(level = "debug", name = "storage.count_posts", skip_all)]
+    async fn count_posts(&self, filter: &PostFilter) -> async_graphql::Result<usize> {
+        self.0.count_posts(filter).await
+    }
+
+    #[instrument(level = "debug", name = "storage.list_all_posts", skip_all)]
+    async fn list_all_posts(&self) -> async_graphql::Result<Vec<Post>> {
+        self.0.list_all_posts().await
+    }
+
+    #[instrument(level = "debug", name = "storage.get_post", skip_all)]
+    async fn get_post(&self, id: &ID) -> async_graphql::Result<Option<Post>> {
+        self.0.get_post(id).await
+    }
+
+    #[instrument(level = "debug", name = "storage.get_post_by_slug", skip_all)]
+    async fn get_post_by_slug(&self, slug: &str) -> async_graphql::Result<Option<Post>> {
+        self.0.get_post_by_slug(slug).await
+    }
+
+    #[instrument(level = "debug", name = "storage.insert_post", skip_all)]
+    async fn insert_post(&self, post: Post) -> async_graphql::Result<Post> {
+        self.0.insert_post(post).await
+    }
+
+    #[instrument(level = "debug", name = "storage.update_post", skip_all)]
+    async fn update_post(
+        &self,
+        id: &ID,
+        update: PostUpdate<'_>,
+    ) -> async_graphql::Result<Option<Post>> {
+        self.0.update_post(id, update).await
+    }
+
+    #[instrument(level = "debug", name = "storage.delete_post", skip_all)]
+    async fn delete_post(&self, id: &ID) -> async_graphql::Result<Option<Post>> {
+        self.0.delete_post(id).await
+    }
+
+    #[instrument(level = "debug", name = "storage.list_users", skip_all)]
+    async fn list_users(
+        &self,
+        filter: &UserFilter,
+        page: Page,
+    ) -> async_graphql::Result<Vec<User>> {
+        self.0.list_users(filter, page).await
+    }
+
+    #[instrument(level = "debug", name = "storage.count_users", skip_all)]
+    async fn count_users(&self, filter: &UserFilter) -> async_graphql::Result<usize> {
+        self.0.count_users(filter).await
+    }
+
+    #[instrument(level = "debug", name = "storage.get_user", skip_all)]
+    async fn get_user(&self, id: &ID) -> async_graphql::Result<Option<User>> {
+        self.0.get_user(id).await
+    }
+
+    #[instrument(level = "debug", name = "storage.get_users", skip_all)]
+    async fn get_users(&self, ids: &[ID]) -> async_graphql::Result<Vec<User>> {
+        self.0.get_users(ids).await
+    }
+
+    #[instrument(level = "debug", name = "storage.get_users_by_name", skip_all)]
+    async fn get_users_by_name(&self, name: &str) -> async_graphql::Result<Vec<User>> {
+        self.0.get_users_by_name(name).await
+    }
+
+    #[instrument(level = "debug", name = "storage.get_user_by_handle", skip_all)]
+    async fn get_user_by_handle(&self, handle: &str) -> async_graphql::Result<Option<User>> {
+        self.0.get_user_by_handle(handle).await
+    }
+
+    #[instrument(level = "debug", name = "storage.insert_user", skip_all)]
+    async fn insert_user(&self, user: User) -> async_graphql::Result<()> {
+        self.0.insert_user(user).await
+    }
+
+    #[instrument(level = "debug", name = "storage.update_user", skip_all)]
+    async fn update_user(
+        &self,
+        id: &ID,
+        update: UserUpdate<'_>,
+    ) -> async_graphql::Result<Option<User>> {
+        self.0.update_user(id, update).await
+    }
+
+    #[instrument(level = "debug", name = "storage.delete_user", skip_all)]
+    async fn delete_user(&self, id: &ID) -> async_graphql::Result<Option<User>> {
+        self.0.delete_user(id).await
+    }
+
+    #[instrument(level = "debug", name = "storage.delete_user_with_posts", skip_all)]
+    async fn delete_user_with_posts(&self, id: &ID) -> async_graphql::Result<Option<Vec<ID>>> {
+        self.0.delete_user_with_posts(id).await
+    }
+
+    async fn ping(&self) -> Result<(), String> {
+        self.0.ping().await
+    }
+
+    async fn close(&self) {
+        self.0.close().await
+    }
+}
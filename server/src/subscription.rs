@@ -0,0 +1,76 @@
+use async_graphql::{Subscription, ID};
+use futures_util::{Stream, StreamExt};
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+
+use crate::error::not_found;
+use crate::models::{Comment, Post};
+use crate::store::AppStorage;
+
+// GraphQL Subscription
+pub struct Subscription;
+
+#[Subscription]
+impl Subscription {
+    /// 公開された新しい投稿
+    async fn post_created(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> async_graphql::Result<impl Stream<Item = Post>> {
+        let events = ctx.data::<EventBus>()?.subscribe();
+        // 受信が遅れて取りこぼしたイベントは読み飛ばす
+        Ok(BroadcastStream::new(events).filter_map(|event| async move {
+            match event {
+                Ok(BlogEvent::PostCreated(post)) => Some(post),
+                _ => None,
+            }
+        }))
+    }
+
+    /// 指定した投稿に追加されたコメント
+    async fn comment_added(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        post_id: ID,
+    ) -> async_graphql::Result<impl Stream<Item = Comment>> {
+        let storage = ctx.data::<AppStorage>()?;
+        if !storage
+            .get_post(&post_id)
+            .await?
+            .is_some_and(|p| p.is_visible(false))
+        {
+            return Err(not_found("Post"));
+        }
+
+        let events = ctx.data::<EventBus>()?.subscribe();
+        Ok(BroadcastStream::new(events).filter_map(move |event| {
+            let post_id = post_id.clone();
+            async move {
+                match event {
+                    Ok(BlogEvent::CommentAdded(comment))
+                        if comment.post_id == post_id && !comment.hidden =>
+                    {
+                        Some(comment)
+                    }
+                    _ => None,
+                }
+            }
+        }))
+    }
+}
+
+// サブスクリプションとWebhookに配信するイベント
+#[derive(Clone)]
+pub(crate) enum BlogEvent {
+    // 公開された（下書きを公開した場合を含む）
+    PostCreated(Post),
+    // 公開中の投稿を更新した
+    PostUpdated(Post),
+    // 公開中の投稿をゴミ箱に移動したか完全に削除した
+    PostDeleted(Post),
+    CommentAdded(Comment),
+}
+
+pub(crate) type EventBus = broadcast::Sender<BlogEvent>;
+
+pub(crate) const EVENT_BUS_CAPACITY: usize = 256;
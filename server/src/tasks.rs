@@ -0,0 +1,96 @@
+use async_graphql::ID;
+use chrono::Utc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::Interval;
+
+use crate::auth::{RefreshTokenStore, SessionStore};
+use crate::models::Post;
+use crate::scalars::DateTimeScalar;
+use crate::notification::notify_post_mentions;
+use crate::store::{AppStorage, DataFile, LockExt, NotificationStore, StoreRevision};
+use crate::subscription::{BlogEvent, EventBus};
+
+// 終了の合図（BackgroundTasks::shutdownで送る）
+pub(crate) type ShutdownReceiver = watch::Receiver<bool>;
+
+// 次の実行まで待つ。終了の合図があった場合（送信側がなくなった場合も）はfalse
+pub(crate) async fn wait_next(interval: &mut Interval, shutdown: &mut ShutdownReceiver) -> bool {
+    tokio::select! {
+        _ = interval.tick() => true,
+        _ = shutdown.changed() => false,
+    }
+}
+
+// 予約投稿の公開タスク
+const SCHEDULER_INTERVAL: Duration = Duration::from_secs(10);
+
+pub(crate) async fn run_scheduler(
+    storage: AppStorage,
+    notification_store: NotificationStore,
+    event_bus: EventBus,
+    data_file: Option<DataFile>,
+    revision: StoreRevision,
+    mut shutdown: ShutdownReceiver,
+) {
+    let mut interval = tokio::time::interval(SCHEDULER_INTERVAL);
+    while wait_next(&mut interval, &mut shutdown).await {
+        let now = Utc::now();
+        // 内部エラーの詳細はasync_graphql::Errorへの変換時にログに出ている
+        let Ok(posts) = storage.list_all_posts().await else {
+            continue;
+        };
+        let due: Vec<ID> = posts
+            .into_iter()
+            .filter(|p| !p.is_deleted() && p.scheduled_at.is_some_and(|d| d.0 <= now))
+            .map(|p| p.id)
+            .collect();
+        for id in &due {
+            // 確認してから更新するまでの間に予約が取り消されていれば何もしない
+            let mut published = false;
+            let update = |post: &mut Post| -> async_graphql::Result<()> {
+                if let Some(scheduled_at) = post.scheduled_at.filter(|_| !post.is_deleted()) {
+                    post.schedule(scheduled_at, now);
+                    post.updated_at = DateTimeScalar(now);
+                    published = post.is_published();
+                }
+                Ok(())
+            };
+            let Ok(Some(post)) = storage.update_post(id, Box::new(update)).await else {
+                continue;
+            };
+            // 公開されたら本文でメンションしたユーザーに通知し、購読者とWebhookにも知らせる
+            if published {
+                notify_post_mentions(&notification_store, &post);
+                let _ = event_bus.send(BlogEvent::PostCreated(post));
+            }
+        }
+
+        if !due.is_empty() {
+            revision.bump();
+        }
+        if let Some(data_file) = data_file.as_ref().filter(|_| !due.is_empty()) {
+            data_file.persist().await;
+        }
+    }
+}
+
+// 期限切れのリフレッシュトークンとセッションを削除するタスク
+const AUTH_SWEEP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+pub(crate) async fn run_auth_sweeper(
+    refresh_token_store: RefreshTokenStore,
+    session_store: SessionStore,
+    mut shutdown: ShutdownReceiver,
+) {
+    let mut interval = tokio::time::interval(AUTH_SWEEP_INTERVAL);
+    while wait_next(&mut interval, &mut shutdown).await {
+        let now = Utc::now();
+        refresh_token_store
+            .lock_or_recover()
+            .retain(|_, t| t.expires_at > now);
+        session_store
+            .lock_or_recover()
+            .retain(|_, s| s.expires_at > now);
+    }
+}
@@ -0,0 +1,132 @@
+use actix_web::http::header::HeaderMap;
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextExecute, NextRequest,
+};
+use async_graphql::Response;
+use opentelemetry::propagation::Extractor;
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::SpanExporter;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider};
+use opentelemetry_sdk::Resource;
+use std::sync::{Arc, OnceLock};
+use tracing::{Instrument, Span};
+
+// OTLPでspanを送る（OTEL_EXPORTER_OTLP_ENDPOINTかOTEL_EXPORTER_OTLP_TRACES_ENDPOINTを指定した場合だけ）
+// 送り先のほか、ヘッダーやタイムアウト、OTEL_SERVICE_NAMEなども標準の環境変数で指定する
+pub struct Telemetry {
+    provider: Option<SdkTracerProvider>,
+}
+
+impl Telemetry {
+    pub(crate) fn disabled() -> Self {
+        Telemetry { provider: None }
+    }
+
+    pub(crate) fn from_env() -> Result<Self, String> {
+        let configured = ["OTEL_EXPORTER_OTLP_ENDPOINT", "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT"]
+            .into_iter()
+            .any(|name| std::env::var(name).is_ok_and(|value| !value.is_empty()));
+        if !configured {
+            return Ok(Telemetry::disabled());
+        }
+
+        let exporter = SpanExporter::builder()
+            .with_http()
+            .build()
+            .map_err(|e| format!("Failed to create the OTLP exporter: {}", e))?;
+        let mut resource = Resource::builder();
+        if std::env::var_os("OTEL_SERVICE_NAME").is_none() {
+            resource = resource.with_service_name(env!("CARGO_PKG_NAME"));
+        }
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(resource.build())
+            .build();
+        // 受け取ったtraceparentを親にして、フロントエンドのゲートウェイのトレースにつなげる
+        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+        Ok(Telemetry {
+            provider: Some(provider),
+        })
+    }
+
+    pub(crate) fn tracer(&self) -> Option<SdkTracer> {
+        self.provider
+            .as_ref()
+            .map(|provider| provider.tracer(env!("CARGO_PKG_NAME")))
+    }
+
+    // 終了時に送っていないspanを送り切る
+    pub async fn shutdown(self) {
+        let Some(provider) = self.provider else {
+            return;
+        };
+        let result = tokio::task::spawn_blocking(move || provider.shutdown()).await;
+        if let Ok(Err(e)) = result {
+            tracing::warn!("Failed to flush spans: {}", e);
+        }
+    }
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+// traceparentなどのヘッダーからトレースの文脈を取り出す（OTLPを使わない場合は空）
+pub(crate) fn extract_context(headers: &HeaderMap) -> opentelemetry::Context {
+    opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(headers))
+    })
+}
+
+// GraphQLの操作ごとのspan（span名は操作名）。ストレージの操作はこの子spanになる
+pub(crate) struct GraphQLTracing;
+
+impl ExtensionFactory for GraphQLTracing {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(GraphQLTracingExtension { span: OnceLock::new() })
+    }
+}
+
+struct GraphQLTracingExtension {
+    span: OnceLock<Span>,
+}
+
+#[async_trait::async_trait]
+impl Extension for GraphQLTracingExtension {
+    async fn request(&self, ctx: &ExtensionContext<'_>, next: NextRequest<'_>) -> Response {
+        // 操作名は実行するまでわからないので、executeで設定する
+        let span = tracing::debug_span!(
+            "graphql.operation",
+            otel.name = "invalid",
+            graphql.operation.name = tracing::field::Empty,
+            graphql.errors = tracing::field::Empty,
+        );
+        let _ = self.span.set(span.clone());
+        let response = next.run(ctx).instrument(span.clone()).await;
+        span.record("graphql.errors", response.errors.len());
+        response
+    }
+
+    async fn execute(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        operation_name: Option<&str>,
+        next: NextExecute<'_>,
+    ) -> Response {
+        if let Some(span) = self.span.get() {
+            let name = operation_name.unwrap_or("anonymous");
+            span.record("otel.name", name);
+            span.record("graphql.operation.name", name);
+        }
+        next.run(ctx, operation_name).await
+    }
+}
@@ -0,0 +1,168 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
+use rustls::crypto::ring;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+// HTTPS（TLS_CERT_PATH / TLS_KEY_PATH）の証明書
+// ハンドシェイクのたびにその時点の証明書を返すので、入れ替えても接続中のリクエストは切れない
+#[derive(Debug)]
+pub struct TlsCertificates {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+impl TlsCertificates {
+    // ファイルがない・読めない・期限切れなどは起動時にわかるようにエラーにする
+    pub fn load(cert_path: &Path, key_path: &Path) -> Result<Arc<Self>, String> {
+        let certified = load_certified_key(cert_path, key_path)?;
+        Ok(Arc::new(TlsCertificates {
+            cert_path: cert_path.to_path_buf(),
+            key_path: key_path.to_path_buf(),
+            current: RwLock::new(Arc::new(certified)),
+        }))
+    }
+
+    // 読み込みに失敗した場合は今の証明書を使い続ける
+    pub fn reload(&self) -> Result<(), String> {
+        let certified = load_certified_key(&self.cert_path, &self.key_path)?;
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(certified);
+        Ok(())
+    }
+
+    pub fn server_config(self: &Arc<Self>) -> rustls::ServerConfig {
+        rustls::ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .expect("the ring provider supports the default protocol versions")
+            .with_no_client_auth()
+            .with_cert_resolver(self.clone())
+    }
+}
+
+impl ResolvesServerCert for TlsCertificates {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().unwrap_or_else(|e| e.into_inner()).clone())
+    }
+}
+
+fn load_certified_key(cert_path: &Path, key_path: &Path) -> Result<CertifiedKey, String> {
+    let certs = read_certificates(cert_path)?;
+    let key = PrivateKeyDer::from_pem_file(key_path).map_err(|e| {
+        format!("Failed to read TLS_KEY_PATH {}: {}", key_path.display(), pem_error(e))
+    })?;
+    let signing_key = ring::sign::any_supported_type(&key).map_err(|e| {
+        format!("Unsupported private key in {}: {}", key_path.display(), e)
+    })?;
+    let certified = CertifiedKey::new(certs, signing_key);
+    certified.keys_match().map_err(|_| {
+        format!(
+            "The private key in {} does not match the certificate in {}",
+            key_path.display(),
+            cert_path.display()
+        )
+    })?;
+    Ok(certified)
+}
+
+// 証明書チェーン。先頭（サーバーの証明書）の有効期間を確認する
+fn read_certificates(path: &Path) -> Result<Vec<CertificateDer<'static>>, String> {
+    let read_error = |e| format!("Failed to read TLS_CERT_PATH {}: {}", path.display(), e);
+    let certs = CertificateDer::pem_file_iter(path)
+        .map_err(|e| read_error(pem_error(e)))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| read_error(pem_error(e)))?;
+    let Some(leaf) = certs.first() else {
+        return Err(read_error("no certificates found".to_string()));
+    };
+    let (_, parsed) = x509_parser::parse_x509_certificate(leaf)
+        .map_err(|e| format!("Invalid certificate in {}: {}", path.display(), e))?;
+    let validity = parsed.validity();
+    let now = Utc::now().timestamp();
+    let format_time = |timestamp: i64| {
+        DateTime::from_timestamp(timestamp, 0).map_or(timestamp.to_string(), |t| t.to_rfc3339())
+    };
+    if validity.not_after.timestamp() < now {
+        return Err(format!(
+            "The certificate in {} expired at {}",
+            path.display(),
+            format_time(validity.not_after.timestamp())
+        ));
+    }
+    if validity.not_before.timestamp() > now {
+        return Err(format!(
+            "The certificate in {} is not valid until {}",
+            path.display(),
+            format_time(validity.not_before.timestamp())
+        ));
+    }
+    Ok(certs)
+}
+
+// ファイルがない場合などはio::Errorの内容をそのまま見せる
+fn pem_error(error: rustls::pki_types::pem::Error) -> String {
+    match error {
+        rustls::pki_types::pem::Error::Io(e) => e.to_string(),
+        rustls::pki_types::pem::Error::NoItemsFound => "no PEM data found".to_string(),
+        e => format!("{:?}", e),
+    }
+}
+
+// SIGHUPを受け取ったら証明書を読み直す
+#[cfg(unix)]
+pub fn spawn_certificate_reloader(certificates: Arc<TlsCertificates>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(e) => {
+            tracing::warn!(
+                "Failed to listen for SIGHUP; TLS certificates will not be reloaded: {}",
+                e
+            );
+            return;
+        }
+    };
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            match certificates.reload() {
+                Ok(()) => tracing::info!("Reloaded TLS certificates"),
+                Err(e) => {
+                    tracing::error!(
+                        "Failed to reload TLS certificates (keeping the old ones): {}",
+                        e
+                    )
+                }
+            }
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub fn spawn_certificate_reloader(_certificates: Arc<TlsCertificates>) {}
+
+// HTTPで来たリクエストを同じホストのHTTPSに転送する（メソッドと本文を保つため308）
+pub fn configure_https_redirect(cfg: &mut web::ServiceConfig, https_port: u16) {
+    cfg.default_service(web::to(move |req: HttpRequest| async move {
+        let connection_info = req.connection_info();
+        let host = connection_info.host();
+        // IPv6アドレス（[::1]:80）の:はポートの区切りではない
+        let hostname = match host.rsplit_once(':') {
+            Some((name, port)) if !port.contains(']') => name,
+            _ => host,
+        };
+        let authority = if https_port == 443 {
+            hostname.to_string()
+        } else {
+            format!("{}:{}", hostname, https_port)
+        };
+        let path = req.uri().path_and_query().map_or("/", |p| p.as_str());
+        HttpResponse::PermanentRedirect()
+            .insert_header(("Location", format!("https://{}{}", authority, path)))
+            .finish()
+    }));
+}
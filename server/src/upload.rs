@@ -0,0 +1,324 @@
+use actix_files::NamedFile;
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::{mime, web, HttpRequest, HttpResponse};
+use async_graphql::{Enum, UploadValue};
+use image::imageops::FilterType;
+use image::ImageReader;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::http::if_none_match;
+use crate::scalars::UrlScalar;
+use crate::settings::Settings;
+
+// 配信する画像のCache-Control（1年）
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+
+// 縮小する場合の幅と高さの上限
+pub(crate) const MAX_RESIZE_DIMENSION: u32 = 2048;
+
+/// アップロードする画像の用途
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ImagePurpose {
+    /// ユーザーのアバター（ログイン中のユーザーなら誰でもアップロードできる）
+    Avatar,
+    /// 投稿のカバー画像や本文中の画像（著者と管理者のみ）
+    PostImage,
+}
+
+// 受け付ける画像の形式
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ImageFormat {
+    Png,
+    Jpeg,
+    Webp,
+}
+
+impl ImageFormat {
+    // パラメーター（; charset=...など）は無視する
+    fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime = content_type.split(';').next().unwrap_or_default().trim();
+        match mime.to_ascii_lowercase().as_str() {
+            "image/png" => Some(ImageFormat::Png),
+            "image/jpeg" => Some(ImageFormat::Jpeg),
+            "image/webp" => Some(ImageFormat::Webp),
+            _ => None,
+        }
+    }
+
+    fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "png" => Some(ImageFormat::Png),
+            "jpg" => Some(ImageFormat::Jpeg),
+            "webp" => Some(ImageFormat::Webp),
+            _ => None,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Webp => "webp",
+        }
+    }
+
+    fn image_format(self) -> image::ImageFormat {
+        match self {
+            ImageFormat::Png => image::ImageFormat::Png,
+            ImageFormat::Jpeg => image::ImageFormat::Jpeg,
+            ImageFormat::Webp => image::ImageFormat::WebP,
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "image/png",
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Webp => "image/webp",
+        }
+    }
+
+    // ファイルの先頭のバイト列（マジックナンバー）が形式と一致するか
+    fn matches(self, content: &[u8]) -> bool {
+        match self {
+            ImageFormat::Png => content.starts_with(b"\x89PNG\r\n\x1a\n"),
+            ImageFormat::Jpeg => content.starts_with(b"\xff\xd8\xff"),
+            ImageFormat::Webp => {
+                content.len() >= 12 && content.starts_with(b"RIFF") && &content[8..12] == b"WEBP"
+            }
+        }
+    }
+}
+
+// 検証してUPLOADS_DIRに内容のハッシュ（SHA-256）のファイル名で保存し、公開URLを返す
+// 同じ内容のファイルは1つにまとまる
+pub(crate) async fn store_image(
+    upload: UploadValue,
+    settings: &Settings,
+) -> async_graphql::Result<UrlScalar> {
+    let content_type = upload.content_type.clone().unwrap_or_default();
+    let format = ImageFormat::from_content_type(&content_type).ok_or_else(|| {
+        AppError::ValidationFailed(format!(
+            "Unsupported image type \"{}\": expected image/png, image/jpeg or image/webp",
+            content_type
+        ))
+    })?;
+    let max_size = settings.max_upload_size;
+    let too_large = || {
+        AppError::ValidationFailed(format!("Image must be at most {} bytes", max_size))
+    };
+    let size = upload.size().map_err(|e| AppError::Internal(e.to_string()))?;
+    if size > max_size as u64 {
+        return Err(too_large().into());
+    }
+
+    // 一時ファイルの読み込みとハッシュの計算はブロッキングスレッドで行う
+    let (content, hash) = tokio::task::spawn_blocking(move || {
+        let mut content = Vec::new();
+        upload.into_read().take(max_size as u64 + 1).read_to_end(&mut content)?;
+        let hash = format!("{:x}", Sha256::digest(&content));
+        Ok::<_, std::io::Error>((content, hash))
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))?
+    .map_err(|e| AppError::Internal(format!("Failed to read upload: {}", e)))?;
+    if content.len() > max_size {
+        return Err(too_large().into());
+    }
+    if !format.matches(&content) {
+        return Err(AppError::ValidationFailed(format!(
+            "File content does not match the content type {}",
+            format.content_type()
+        ))
+        .into());
+    }
+
+    let filename = format!("{}.{}", hash, format.extension());
+    let path = settings.uploads_dir.join(&filename);
+    if !tokio::fs::try_exists(&path).await.unwrap_or(false) {
+        write_upload(&settings.uploads_dir, &filename, &content).await.map_err(|e| {
+            AppError::Internal(format!("Failed to save upload {}: {}", path.display(), e))
+        })?;
+    }
+
+    let url = format!("{}/uploads/{}", settings.site_base_url, filename);
+    url.parse::<UrlScalar>().map_err(|e| AppError::Internal(e).into())
+}
+
+// アップロードした画像のURLなら縮小の指定（?w=&h=）を付ける。外部のURLはそのまま返す
+pub(crate) fn resized_url(
+    url: &UrlScalar,
+    settings: &Settings,
+    width: u32,
+    height: u32,
+) -> UrlScalar {
+    let prefix = format!("{}/uploads/", settings.site_base_url);
+    let path = url.as_str().strip_prefix(&prefix).unwrap_or_default();
+    if uploaded_file(path).is_none() {
+        return url.clone();
+    }
+    UrlScalar(format!("{}?w={}&h={}", url.as_str(), width, height))
+}
+
+// 書き込み途中のファイルを配信しないよう、一時ファイルに書いてから名前を変える
+async fn write_upload(dir: &Path, filename: &str, content: &[u8]) -> std::io::Result<()> {
+    tokio::fs::create_dir_all(dir).await?;
+    let temporary = dir.join(format!(".{}.{}", filename, Uuid::new_v4()));
+    tokio::fs::write(&temporary, content).await?;
+    tokio::fs::rename(&temporary, dir.join(filename)).await
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ResizeQuery {
+    // 幅と高さの上限（ピクセル）。縦横比は変えず、元の大きさとMAX_RESIZE_DIMENSIONを超えない
+    w: Option<u32>,
+    h: Option<u32>,
+}
+
+impl ResizeQuery {
+    // 縮小する場合の幅と高さの上限（0や未指定はMAX_RESIZE_DIMENSIONとして扱う）
+    fn bounds(&self) -> Option<(u32, u32)> {
+        let clamp = |n: Option<u32>| match n {
+            Some(n) if n > 0 => n.min(MAX_RESIZE_DIMENSION),
+            _ => MAX_RESIZE_DIMENSION,
+        };
+        match (self.w, self.h) {
+            (None, None) => None,
+            (w, h) => Some((clamp(w), clamp(h))),
+        }
+    }
+}
+
+// アップロードした画像を配信する（Rangeリクエストにも対応する）
+// ファイル名は内容のハッシュで内容が変わらないので、長期間キャッシュさせ、ハッシュをETagにする
+// ?w=と?h=を指定すると縮小した画像を返す。縮小した画像は元の画像の隣に保存して使い回す
+pub(crate) async fn uploads_handler(
+    settings: web::Data<Settings>,
+    filename: web::Path<String>,
+    query: web::Query<ResizeQuery>,
+    http_req: HttpRequest,
+) -> HttpResponse {
+    let Some((hash, format)) = uploaded_file(&filename) else {
+        return HttpResponse::NotFound().finish();
+    };
+    let bounds = query.bounds();
+    let etag = match bounds {
+        Some((width, height)) => format!("\"{}-{}x{}\"", hash, width, height),
+        None => format!("\"{}\"", hash),
+    };
+    if if_none_match(&http_req, &etag) {
+        return HttpResponse::NotModified()
+            .insert_header((header::ETAG, etag))
+            .insert_header((header::CACHE_CONTROL, IMMUTABLE))
+            .finish();
+    }
+    let mut path = settings.uploads_dir.join(filename.as_str());
+    if let Some(bounds) = bounds {
+        if let Some(resized) = resized_variant(&settings.uploads_dir, hash, format, bounds).await {
+            path = resized;
+        }
+    }
+    let file = match NamedFile::open_async(&path).await {
+        Ok(file) => file,
+        // パスはログにだけ出し、レスポンスには含めない
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return HttpResponse::NotFound().finish();
+        }
+        Err(e) => {
+            tracing::error!(error = %e, path = %path.display(), "failed to open upload");
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let mime = format.content_type().parse().unwrap_or(mime::APPLICATION_OCTET_STREAM);
+    let mut response = file
+        .set_content_type(mime)
+        .disable_content_disposition()
+        .use_etag(false)
+        .into_response(&http_req);
+    let headers = response.headers_mut();
+    if let Ok(etag) = HeaderValue::from_str(&etag) {
+        headers.insert(header::ETAG, etag);
+    }
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static(IMMUTABLE));
+    headers.insert(header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    response
+}
+
+// 縮小した画像のパス（{ハッシュ}-{幅}x{高さ}.{拡張子}）。なければ作って保存する
+// 元の画像が上限に収まる場合や、読めない・縮小できない場合はNone（元の画像を返す）
+async fn resized_variant(
+    dir: &Path,
+    hash: &str,
+    format: ImageFormat,
+    (width, height): (u32, u32),
+) -> Option<PathBuf> {
+    let original = dir.join(format!("{}.{}", hash, format.extension()));
+    let variant = format!("{}-{}x{}.{}", hash, width, height, format.extension());
+    let path = dir.join(&variant);
+    if tokio::fs::try_exists(&path).await.unwrap_or(false) {
+        return Some(path);
+    }
+    if !tokio::fs::try_exists(&original).await.unwrap_or(false) {
+        return None;
+    }
+    let source = original.clone();
+    let resized =
+        tokio::task::spawn_blocking(move || resize_image(&source, format, width, height)).await;
+    let resized = resized
+        .map_err(|e| e.to_string())
+        .and_then(|resized| resized.map_err(|e| e.to_string()));
+    let content = match resized {
+        Ok(Some(content)) => content,
+        Ok(None) => return None,
+        Err(e) => {
+            tracing::warn!(error = %e, path = %original.display(), "failed to resize upload");
+            return None;
+        }
+    };
+    match write_upload(dir, &variant, &content).await {
+        Ok(()) => Some(path),
+        Err(e) => {
+            tracing::warn!(error = %e, path = %path.display(), "failed to save resized upload");
+            None
+        }
+    }
+}
+
+// 縦横比を変えずに幅と高さの上限に収める。既に収まっていればNone（拡大はしない）
+fn resize_image(
+    path: &Path,
+    format: ImageFormat,
+    width: u32,
+    height: u32,
+) -> image::ImageResult<Option<Vec<u8>>> {
+    let open = || -> image::ImageResult<ImageReader<_>> {
+        let mut reader = ImageReader::open(path)?;
+        reader.set_format(format.image_format());
+        Ok(reader)
+    };
+    // 先にヘッダーだけを読んで、縮小が必要か確かめる
+    let (original_width, original_height) = open()?.into_dimensions()?;
+    if original_width <= width && original_height <= height {
+        return Ok(None);
+    }
+    let resized = open()?.decode()?.resize(width, height, FilterType::Lanczos3);
+    let mut content = Cursor::new(Vec::new());
+    resized.write_to(&mut content, format.image_format())?;
+    Ok(Some(content.into_inner()))
+}
+
+// store_imageが付けた形式のファイル名（64桁の16進数と拡張子）だけを受け付ける
+// ディレクトリの外や一時ファイルは読ませない
+fn uploaded_file(filename: &str) -> Option<(&str, ImageFormat)> {
+    let (hash, extension) = filename.split_once('.')?;
+    let is_hash = hash.len() == 64 && hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'));
+    if !is_hash {
+        return None;
+    }
+    Some((hash, ImageFormat::from_extension(extension)?))
+}
@@ -0,0 +1,403 @@
+use async_graphql::{ErrorExtensions, Name, Value, ID};
+use indexmap::IndexMap;
+use std::collections::HashSet;
+use unicode_normalization::UnicodeNormalization;
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::models::User;
+use crate::store::Storage;
+
+// 入力チェック
+
+// IDの形式（作成したものはUUID、初期データは整数）
+const MAX_INTEGER_ID_LENGTH: usize = 18;
+
+// メールアドレスの長さの上限（RFC 5321）
+const MAX_EMAIL_LENGTH: usize = 254;
+
+const MIN_HANDLE_LENGTH: usize = 3;
+const MAX_HANDLE_LENGTH: usize = 30;
+
+pub(crate) fn is_well_formed_id(id: &str) -> bool {
+    let integer = !id.is_empty()
+        && id.len() <= MAX_INTEGER_ID_LENGTH
+        && id.bytes().all(|b| b.is_ascii_digit());
+    integer || Uuid::try_parse(id).is_ok()
+}
+
+// 引数のIDを検査する。UUIDは小文字のハイフン区切りにそろえる
+pub(crate) fn parse_id(id: ID, argument: &'static str) -> async_graphql::Result<ID> {
+    if let Ok(uuid) = Uuid::try_parse(&id) {
+        return Ok(ID::from(uuid.to_string()));
+    }
+    if is_well_formed_id(&id) {
+        return Ok(id);
+    }
+    let message =
+        format!("Invalid id for argument \"{}\": expected a UUID or an integer", argument);
+    let error: async_graphql::Error = AppError::InvalidId(message).into();
+    Err(error.extend_with(|_, e| e.set("argument", argument)))
+}
+pub(crate) fn validate_user_name(name: &str) -> async_graphql::Result<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::ValidationFailed("Name must not be empty".into()).into());
+    }
+    Ok(name.to_string())
+}
+
+// メールアドレス（前後の空白を除く）。形式は@の前後に文字があることだけを確かめる
+pub(crate) fn validate_email(email: &str) -> async_graphql::Result<String> {
+    let email = email.trim();
+    let valid = match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && !domain.is_empty()
+                && !domain.contains('@')
+                && !email.chars().any(char::is_whitespace)
+                && email.len() <= MAX_EMAIL_LENGTH
+        }
+        None => false,
+    };
+    if !valid {
+        return Err(AppError::ValidationFailed(format!("Invalid email address: {}", email)).into());
+    }
+    Ok(email.to_string())
+}
+
+// ハンドル（URLや@メンションに使う名前）。前後の空白を除いて小文字にそろえるので、
+// 大文字小文字だけが違うハンドルは同じものになる
+pub(crate) fn validate_handle(handle: &str) -> async_graphql::Result<String> {
+    let handle = handle.trim().to_lowercase();
+    let valid = (MIN_HANDLE_LENGTH..=MAX_HANDLE_LENGTH).contains(&handle.len())
+        && handle.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_');
+    if !valid {
+        return Err(AppError::ValidationFailed(format!(
+            "Handle must be {} to {} lowercase letters, digits or underscores: {}",
+            MIN_HANDLE_LENGTH, MAX_HANDLE_LENGTH, handle
+        ))
+        .into());
+    }
+    Ok(handle)
+}
+
+pub(crate) fn handle_taken(handle: &str) -> async_graphql::Error {
+    AppError::Conflict(format!("Handle is already taken: {}", handle)).into()
+}
+
+// 名前の英数字からハンドルを作る（空白や記号は_にする）。3文字に満たない場合は user_<ID>
+// takenに含まれる場合は _2, _3 ... を付ける
+pub(crate) fn derive_handle(name: &str, id: &ID, taken: impl Fn(&str) -> bool) -> String {
+    let mut base = String::new();
+    for c in name.to_lowercase().chars() {
+        if c.is_ascii_alphanumeric() {
+            base.push(c);
+        } else if (c.is_whitespace() || c.is_ascii_punctuation()) && !base.ends_with('_') {
+            base.push('_');
+        }
+    }
+    let mut base = base.trim_matches('_').to_string();
+    if base.len() < MIN_HANDLE_LENGTH {
+        let id: String = id.chars().filter(char::is_ascii_alphanumeric).collect();
+        base = format!("user_{}", id.to_lowercase());
+    }
+    base.truncate(MAX_HANDLE_LENGTH);
+    let mut handle = base.clone();
+    let mut n = 1;
+    while taken(&handle) {
+        n += 1;
+        let suffix = format!("_{}", n);
+        handle = format!("{}{}", &base[..base.len().min(MAX_HANDLE_LENGTH - suffix.len())], suffix);
+    }
+    handle
+}
+
+// ハンドルのないユーザー（以前のバージョンで保存したデータ）にハンドルを付け、付けたユーザーを返す
+pub(crate) fn assign_missing_handles(users: &mut [User]) -> Vec<User> {
+    let mut taken: HashSet<String> = users
+        .iter()
+        .filter(|user| !user.handle.is_empty())
+        .map(|user| user.handle.clone())
+        .collect();
+    let mut assigned = Vec::new();
+    for user in users.iter_mut().filter(|user| user.handle.is_empty()) {
+        user.handle = derive_handle(&user.name, &user.id, |handle| taken.contains(handle));
+        taken.insert(user.handle.clone());
+        assigned.push(user.clone());
+    }
+    assigned
+}
+
+pub(crate) fn validate_comment_body(
+    body: &str,
+    max_length: usize,
+) -> async_graphql::Result<String> {
+    let body = body.trim();
+    if body.is_empty() {
+        return Err(AppError::ValidationFailed("Comment must not be empty".into()).into());
+    }
+    if body.chars().count() > max_length {
+        return Err(AppError::ValidationFailed(format!(
+            "Comment must be at most {} characters",
+            max_length
+        )).into());
+    }
+    Ok(body.to_string())
+}
+
+pub(crate) fn validate_password(
+    password: &str,
+    name: &str,
+    min_length: usize,
+) -> async_graphql::Result<()> {
+    if password.chars().count() < min_length {
+        return Err(AppError::ValidationFailed(format!(
+            "Password must be at least {} characters",
+            min_length
+        )).into());
+    }
+    if password == name {
+        let message = "Password must not be the same as the name";
+        return Err(AppError::ValidationFailed(message.into()).into());
+    }
+    Ok(())
+}
+
+pub(crate) const MAX_POST_TITLE_LENGTH: usize = 200;
+pub(crate) const MAX_POST_TAGS: usize = 10;
+pub(crate) const MAX_TAG_LENGTH: usize = 50;
+pub(crate) const MAX_COVER_IMAGE_ALT_LENGTH: usize = 300;
+pub(crate) const MAX_SEO_DESCRIPTION_LENGTH: usize = 160;
+
+pub(crate) const MAX_BIO_LENGTH: usize = 500;
+pub(crate) const MAX_LOCATION_LENGTH: usize = 100;
+
+pub(crate) const MAX_CONTACT_NAME_LENGTH: usize = 100;
+pub(crate) const MIN_CONTACT_MESSAGE_LENGTH: usize = 10;
+pub(crate) const MAX_CONTACT_MESSAGE_LENGTH: usize = 5000;
+
+// お問い合わせの入力（前後の空白を除く）
+pub(crate) struct ContactFields {
+    pub(crate) name: String,
+    pub(crate) email: String,
+    pub(crate) message: String,
+}
+
+// 違反の扱いはvalidate_post_fieldsと同じ。メールアドレスの形式はvalidate_emailで確かめる
+pub(crate) fn validate_contact_fields(
+    fields: ContactFields,
+) -> async_graphql::Result<ContactFields> {
+    let mut violations = Vec::new();
+    let name = fields.name.trim().to_string();
+    if name.is_empty() {
+        violations.push(violation("name", None, "required", None));
+    } else if name.chars().count() > MAX_CONTACT_NAME_LENGTH {
+        violations.push(violation("name", None, "maxLength", Some(MAX_CONTACT_NAME_LENGTH)));
+    }
+    let email = validate_email(&fields.email);
+    if email.is_err() {
+        violations.push(violation("email", None, "email", None));
+    }
+    let message = fields.message.trim().to_string();
+    let length = message.chars().count();
+    if length < MIN_CONTACT_MESSAGE_LENGTH {
+        let limit = Some(MIN_CONTACT_MESSAGE_LENGTH);
+        violations.push(violation("message", None, "minLength", limit));
+    } else if length > MAX_CONTACT_MESSAGE_LENGTH {
+        let limit = Some(MAX_CONTACT_MESSAGE_LENGTH);
+        violations.push(violation("message", None, "maxLength", limit));
+    }
+    match email {
+        Ok(email) if violations.is_empty() => Ok(ContactFields { name, email, message }),
+        _ => {
+            let error: async_graphql::Error =
+                AppError::ValidationFailed("Invalid contact message".into()).into();
+            Err(error.extend_with(|_, e| e.set("validation", Value::List(violations))))
+        }
+    }
+}
+
+// プロフィールの入力（指定されたフィールドだけ）
+// 前後の空白を除く（空になった場合は未設定にする）
+pub(crate) struct ProfileFields {
+    pub(crate) bio: Option<String>,
+    pub(crate) location: Option<String>,
+}
+
+// 違反の扱いはvalidate_post_fieldsと同じ
+pub(crate) fn validate_profile_fields(
+    fields: ProfileFields,
+) -> async_graphql::Result<ProfileFields> {
+    let mut violations = Vec::new();
+    let bio = fields.bio.map(|bio| bio.trim().to_string());
+    if bio.as_ref().is_some_and(|bio| bio.chars().count() > MAX_BIO_LENGTH) {
+        violations.push(violation("bio", None, "maxLength", Some(MAX_BIO_LENGTH)));
+    }
+    let location = fields.location.map(|location| location.trim().to_string());
+    if location
+        .as_ref()
+        .is_some_and(|location| location.chars().count() > MAX_LOCATION_LENGTH)
+    {
+        violations.push(violation("location", None, "maxLength", Some(MAX_LOCATION_LENGTH)));
+    }
+    if !violations.is_empty() {
+        let error: async_graphql::Error =
+            AppError::ValidationFailed("Invalid profile input".into()).into();
+        return Err(error.extend_with(|_, e| e.set("validation", Value::List(violations))));
+    }
+    Ok(ProfileFields { bio, location })
+}
+
+// 投稿の入力（更新の場合は指定されたフィールドだけ）
+pub(crate) struct PostFields {
+    pub(crate) title: Option<String>,
+    pub(crate) body: Option<String>,
+    pub(crate) tags: Option<Vec<String>>,
+    // 前後の空白を除く（空になった場合は代替テキストなし）
+    pub(crate) cover_image_alt: Option<String>,
+    pub(crate) seo_description: Option<String>,
+}
+
+// 違反はまとめて1つのエラーにし、フィールドと規則をextensions.validationに入れる
+// 文字数はバイト数ではなく文字数で数える。タイトルは前後の空白を除き、タグは正規化して保存する
+pub(crate) fn validate_post_fields(
+    fields: PostFields,
+    max_body_length: usize,
+) -> async_graphql::Result<PostFields> {
+    let mut violations = Vec::new();
+    let title = fields.title.map(|title| title.trim().to_string());
+    if let Some(title) = &title {
+        if title.is_empty() {
+            violations.push(violation("title", None, "required", None));
+        } else if title.chars().count() > MAX_POST_TITLE_LENGTH {
+            violations.push(violation("title", None, "maxLength", Some(MAX_POST_TITLE_LENGTH)));
+        }
+    }
+    if let Some(body) = &fields.body {
+        if body.trim().is_empty() {
+            violations.push(violation("body", None, "required", None));
+        } else if body.chars().count() > max_body_length {
+            violations.push(violation("body", None, "maxLength", Some(max_body_length)));
+        }
+    }
+    // 長さの違反は送られた位置で示し、個数は空のものと重複を除いて数える
+    let tags = fields.tags.map(|tags| {
+        tags.iter().map(|tag| normalize_tag(tag)).collect::<Vec<_>>()
+    });
+    if let Some(tags) = &tags {
+        for (index, tag) in tags.iter().enumerate() {
+            if tag.chars().count() > MAX_TAG_LENGTH {
+                violations.push(violation("tags", Some(index), "maxLength", Some(MAX_TAG_LENGTH)));
+            }
+        }
+    }
+    let tags = tags.map(dedup_tags);
+    if tags.as_ref().is_some_and(|tags| tags.len() > MAX_POST_TAGS) {
+        violations.push(violation("tags", None, "maxItems", Some(MAX_POST_TAGS)));
+    }
+    let cover_image_alt = fields.cover_image_alt.map(|alt| alt.trim().to_string());
+    if cover_image_alt
+        .as_ref()
+        .is_some_and(|alt| alt.chars().count() > MAX_COVER_IMAGE_ALT_LENGTH)
+    {
+        let limit = Some(MAX_COVER_IMAGE_ALT_LENGTH);
+        violations.push(violation("coverImageAlt", None, "maxLength", limit));
+    }
+    let seo_description = fields.seo_description.map(|description| description.trim().to_string());
+    if seo_description
+        .as_ref()
+        .is_some_and(|description| description.chars().count() > MAX_SEO_DESCRIPTION_LENGTH)
+    {
+        let limit = Some(MAX_SEO_DESCRIPTION_LENGTH);
+        violations.push(violation("seoDescription", None, "maxLength", limit));
+    }
+    if !violations.is_empty() {
+        let error: async_graphql::Error =
+            AppError::ValidationFailed("Invalid post input".into()).into();
+        return Err(error.extend_with(|_, e| e.set("validation", Value::List(violations))));
+    }
+    Ok(PostFields { title, body: fields.body, tags, cover_image_alt, seo_description })
+}
+
+// タグの正規化（NFKCで全角英数字や全角スペースを半角にし、連続する空白を1つにまとめ、
+// ASCIIの英字を小文字にする）
+pub(crate) fn normalize_tag(tag: &str) -> String {
+    let tag: String = tag.nfkc().collect();
+    tag.split_whitespace().collect::<Vec<_>>().join(" ").to_ascii_lowercase()
+}
+
+// 正規化して空のものを除き、重複は最初のものだけ残す
+pub(crate) fn normalize_tags(tags: &[String]) -> Vec<String> {
+    dedup_tags(tags.iter().map(|tag| normalize_tag(tag)).collect())
+}
+
+fn dedup_tags(tags: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    tags.into_iter()
+        .filter(|tag| !tag.is_empty() && seen.insert(tag.clone()))
+        .collect()
+}
+
+// indexはタグの何番目か（0始まり）、limitは上限の値
+fn violation(field: &str, index: Option<usize>, rule: &str, limit: Option<usize>) -> Value {
+    let mut entry = IndexMap::new();
+    entry.insert(Name::new("field"), Value::from(field));
+    if let Some(index) = index {
+        entry.insert(Name::new("index"), Value::from(index as i32));
+    }
+    entry.insert(Name::new("rule"), Value::from(rule));
+    if let Some(limit) = limit {
+        entry.insert(Name::new("limit"), Value::from(limit as i32));
+    }
+    Value::Object(entry)
+}
+
+// スラッグ
+// 英数字以外はハイフンにまとめる。日本語など英数字が残らない場合は投稿IDから生成する
+pub(crate) fn slugify(title: &str, id: &ID) -> String {
+    let mut slug = String::new();
+    for c in title.to_lowercase().chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        let id: String = id.chars().filter(char::is_ascii_alphanumeric).take(8).collect();
+        format!("post-{}", id)
+    } else {
+        slug.to_string()
+    }
+}
+
+pub(crate) fn validate_slug(slug: &str) -> async_graphql::Result<()> {
+    let valid = slug.split('-').all(|part| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+    });
+    if !valid {
+        return Err(AppError::ValidationFailed(
+            "Slug must consist of lowercase letters, digits and single hyphens".into(),
+        ).into());
+    }
+    Ok(())
+}
+
+// 重複する場合は -2, -3 ... を付ける
+pub(crate) async fn unique_slug(
+    storage: &dyn Storage,
+    base: &str,
+) -> async_graphql::Result<String> {
+    let mut slug = base.to_string();
+    let mut n = 1;
+    while storage.get_post_by_slug(&slug).await?.is_some() {
+        n += 1;
+        slug = format!("{}-{}", base, n);
+    }
+    Ok(slug)
+}
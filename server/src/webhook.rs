@@ -0,0 +1,219 @@
+// 投稿とコメントのイベントを登録したWebhookにPOSTする
+// 送信はミューテーションとは別のタスクで行い、失敗してもミューテーションの結果は変えない
+use async_graphql::ID;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use uuid::Uuid;
+
+use crate::models::{Comment, Post, WebhookDelivery, WebhookEvent};
+use crate::scalars::DateTimeScalar;
+use crate::settings::Settings;
+use crate::store::{LockExt, WebhookStore};
+use crate::subscription::BlogEvent;
+use crate::tasks::ShutdownReceiver;
+
+const SIGNATURE_HEADER: &str = "X-Blog-Signature";
+const EVENT_HEADER: &str = "X-Blog-Event";
+const DELIVERY_HEADER: &str = "X-Blog-Delivery";
+
+fn event_name(event: WebhookEvent) -> &'static str {
+    match event {
+        WebhookEvent::PostCreated => "POST_CREATED",
+        WebhookEvent::PostUpdated => "POST_UPDATED",
+        WebhookEvent::PostDeleted => "POST_DELETED",
+        WebhookEvent::CommentAdded => "COMMENT_ADDED",
+    }
+}
+
+fn post_payload(post: &Post, settings: &Settings) -> Value {
+    json!({
+        "id": post.id.as_str(),
+        "title": post.title,
+        "slug": post.slug,
+        "url": format!("{}/posts/{}", settings.site_base_url, post.slug),
+        "authorId": post.author_id.as_str(),
+        "status": if post.is_published() { "PUBLISHED" } else { "DRAFT" },
+        "tags": post.tags,
+        "publishedAt": post.published_at.0.to_rfc3339(),
+        "updatedAt": post.updated_at.0.to_rfc3339(),
+    })
+}
+
+fn comment_payload(comment: &Comment) -> Value {
+    json!({
+        "id": comment.id.as_str(),
+        "postId": comment.post_id.as_str(),
+        "parentCommentId": comment.parent_comment_id.as_ref().map(|id| id.as_str()),
+        "authorId": comment.author_id.as_str(),
+        "body": comment.body,
+        "createdAt": comment.created_at.0.to_rfc3339(),
+    })
+}
+
+// 送信するイベントの種類と本文。非表示のコメントは送らない
+fn payload(
+    event: &BlogEvent,
+    delivery_id: &str,
+    settings: &Settings,
+) -> Option<(WebhookEvent, Value)> {
+    let post = |kind, post: &Post| (kind, "post", post_payload(post, settings));
+    let (kind, key, data) = match event {
+        BlogEvent::PostCreated(p) => post(WebhookEvent::PostCreated, p),
+        BlogEvent::PostUpdated(p) => post(WebhookEvent::PostUpdated, p),
+        BlogEvent::PostDeleted(p) => post(WebhookEvent::PostDeleted, p),
+        BlogEvent::CommentAdded(comment) if !comment.hidden => {
+            (WebhookEvent::CommentAdded, "comment", comment_payload(comment))
+        }
+        BlogEvent::CommentAdded(_) => return None,
+    };
+    let mut payload = json!({
+        "id": delivery_id,
+        "event": event_name(kind),
+        "createdAt": Utc::now().to_rfc3339(),
+    });
+    payload[key] = data;
+    Some((kind, payload))
+}
+
+// 本文のHMAC-SHA256（16進数）。受信側は同じ鍵で計算して比べる
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(body);
+    format!("sha256={:x}", mac.finalize().into_bytes())
+}
+
+// イベントバスを購読し、イベントの種類を登録したWebhookごとに送信のタスクを起動する
+pub(crate) async fn run_webhook_dispatcher(
+    store: WebhookStore,
+    settings: Arc<Settings>,
+    mut events: broadcast::Receiver<BlogEvent>,
+    mut shutdown: ShutdownReceiver,
+) {
+    let client = match reqwest::Client::builder().timeout(settings.webhook_timeout).build() {
+        Ok(client) => client,
+        Err(e) => {
+            tracing::error!(error = %e, "failed to build the webhook HTTP client");
+            return;
+        }
+    };
+    loop {
+        let event = tokio::select! {
+            event = events.recv() => event,
+            _ = shutdown.changed() => return,
+        };
+        let event = match event {
+            Ok(event) => event,
+            Err(RecvError::Lagged(skipped)) => {
+                tracing::warn!(skipped, "webhook dispatcher lagged behind; events were dropped");
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+
+        let delivery_id = Uuid::new_v4().to_string();
+        let Some((kind, payload)) = payload(&event, &delivery_id, &settings) else {
+            continue;
+        };
+        let targets: Vec<(ID, String, String)> = store
+            .lock_or_recover()
+            .iter()
+            .filter(|webhook| webhook.events.contains(&kind))
+            .map(|webhook| (webhook.id.clone(), webhook.url.0.clone(), webhook.secret.clone()))
+            .collect();
+        if targets.is_empty() {
+            continue;
+        }
+        let body = payload.to_string().into_bytes();
+        for (webhook_id, url, secret) in targets {
+            let delivery = Delivery {
+                client: client.clone(),
+                webhook_id,
+                url,
+                signature: sign(&secret, &body),
+                event: kind,
+                delivery_id: delivery_id.clone(),
+                body: body.clone(),
+            };
+            let max_retries = settings.webhook_max_retries;
+            tokio::spawn(delivery.run(store.clone(), max_retries, settings.webhook_retry_base));
+        }
+    }
+}
+
+// 1つのWebhookへの1つのイベントの送信（再試行でも同じ本文とX-Blog-Deliveryを送る）
+struct Delivery {
+    client: reqwest::Client,
+    webhook_id: ID,
+    url: String,
+    signature: String,
+    event: WebhookEvent,
+    delivery_id: String,
+    body: Vec<u8>,
+}
+
+impl Delivery {
+    // 2xx以外のレスポンスと接続の失敗は、待ち時間を2倍にしながらmax_retries回まで再試行する
+    async fn run(self, store: WebhookStore, max_retries: u32, retry_base: Duration) {
+        let mut attempts = 0;
+        let (status_code, error) = loop {
+            attempts += 1;
+            let (status_code, error) = self.send().await;
+            if error.is_none() || attempts > max_retries {
+                break (status_code, error);
+            }
+            let backoff = 2u32.saturating_pow(attempts - 1);
+            tokio::time::sleep(retry_base.saturating_mul(backoff)).await;
+        };
+        if let Some(error) = &error {
+            tracing::warn!(
+                webhook_id = %self.webhook_id.as_str(),
+                event = event_name(self.event),
+                attempts,
+                error = %error,
+                "webhook delivery failed"
+            );
+        }
+
+        // 送信中に削除されたWebhookには記録しない
+        let mut webhooks = store.lock_or_recover();
+        if let Some(webhook) = webhooks.iter_mut().find(|w| w.id == self.webhook_id) {
+            webhook.last_delivery = Some(WebhookDelivery {
+                event: self.event,
+                succeeded: error.is_none(),
+                status_code,
+                error,
+                attempts: attempts as i32,
+                delivered_at: DateTimeScalar(Utc::now()),
+            });
+        }
+    }
+
+    async fn send(&self) -> (Option<i32>, Option<String>) {
+        let response = self
+            .client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, event_name(self.event))
+            .header(DELIVERY_HEADER, &self.delivery_id)
+            .header(SIGNATURE_HEADER, &self.signature)
+            .body(self.body.clone())
+            .send()
+            .await;
+        match response {
+            Ok(response) if response.status().is_success() => {
+                (Some(response.status().as_u16().into()), None)
+            }
+            Ok(response) => {
+                let status = response.status();
+                (Some(status.as_u16().into()), Some(format!("Unexpected status {}", status)))
+            }
+            Err(e) => (None, Some(e.to_string())),
+        }
+    }
+}
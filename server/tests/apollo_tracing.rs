@@ -0,0 +1,42 @@
+// GRAPHQL_TRACING（レスポンスのextensions.tracing）
+// 環境変数を書き換えるので、このファイルのテストは1つにまとめて順番に確かめる
+use actix_web::{test, App};
+use blog_server::{build_app_state, configure_app, MemoryStorage, Seed};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+mod common;
+use common::graphql_request;
+
+const QUERY: &str = "{ posts(limit: 10) { title author { name } } }";
+
+async fn execute() -> async_graphql::Result<Value> {
+    let state = build_app_state(Arc::new(MemoryStorage::seeded(&Seed::builtin())), None).await?;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = graphql_request(None, QUERY, json!({})).to_request();
+    Ok(test::call_and_read_body_json(&app, req).await)
+}
+
+#[actix_web::test]
+async fn tracing_is_included_only_when_enabled() {
+    let body = execute().await.unwrap();
+    assert!(body["errors"].is_null(), "{}", body);
+    assert!(body["extensions"]["tracing"].is_null(), "{}", body);
+
+    std::env::set_var("GRAPHQL_TRACING", "1");
+    let body = execute().await.unwrap();
+    let resolvers = body["extensions"]["tracing"]["execution"]["resolvers"]
+        .as_array()
+        .expect("tracing should include resolvers");
+    let author = resolvers
+        .iter()
+        .find(|r| r["path"] == json!(["posts", "0", "author"]))
+        .expect("author resolver should be traced");
+    assert!(author["startOffset"].is_i64());
+    assert!(author["duration"].is_i64());
+
+    // イントロスペクションを無効にした設定とは組み合わせられない
+    std::env::set_var("GRAPHQL_INTROSPECTION", "off");
+    let error = execute().await.unwrap_err();
+    assert!(error.message.contains("GRAPHQL_TRACING"), "{}", error.message);
+}
@@ -0,0 +1,104 @@
+// User.avatarUrl の優先順位（設定したアバター > メールアドレスのGravatar > IDから作るアイコン）
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+// md5("foo@example.com")
+const EMAIL_HASH: &str = "b48def645758b95537d4424c84d1a9ff";
+
+const USER: &str = r#"
+    query User($id: ID!) {
+        user(id: $id) { avatarUrl small: avatarUrl(size: 64) emailHash }
+    }
+"#;
+
+const UPDATE: &str = r#"
+    mutation Update($input: UpdateUserInput!) { updateUser(input: $input) { id } }
+"#;
+
+#[actix_web::test]
+async fn avatar_url_prefers_explicit_then_gravatar_then_placeholder() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let token = token(&test::call_and_read_body_json(&app, req).await);
+    let user = |id: &str| graphql_request(None, USER, json!({ "id": id })).to_request();
+
+    // メールアドレスは前後の空白を除いて小文字にしてからハッシュにする
+    let create = r#"
+        mutation {
+            createUser(input: { name: "メール", handle: "mail", email: " Foo@Example.COM " }) { id }
+        }
+    "#;
+    let req = graphql_request(Some(&token), create, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let id = body["data"]["createUser"]["id"].as_str().unwrap().to_string();
+    let body: Value = test::call_and_read_body_json(&app, user(&id)).await;
+    let gravatar = format!("https://www.gravatar.com/avatar/{}?d=identicon", EMAIL_HASH);
+    assert_eq!(body["data"]["user"]["avatarUrl"], gravatar.as_str(), "{}", body);
+    assert_eq!(body["data"]["user"]["small"], format!("{}&s=64", gravatar));
+    assert_eq!(body["data"]["user"]["emailHash"], EMAIL_HASH);
+
+    // 設定したアバターが最優先
+    let input = json!({ "id": id, "avatarUrl": "https://example.com/me.png" });
+    let req = graphql_request(Some(&token), UPDATE, json!({ "input": input })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let body: Value = test::call_and_read_body_json(&app, user(&id)).await;
+    assert_eq!(body["data"]["user"]["avatarUrl"], "https://example.com/me.png");
+    assert_eq!(body["data"]["user"]["small"], "https://example.com/me.png");
+
+    // アバターとメールアドレスを消すとIDから作るアイコン（毎回同じURL）
+    let input = json!({ "id": id, "avatarUrl": null, "email": null });
+    let req = graphql_request(Some(&token), UPDATE, json!({ "input": input })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let first: Value = test::call_and_read_body_json(&app, user(&id)).await;
+    let placeholder = first["data"]["user"]["avatarUrl"].as_str().unwrap().to_string();
+    assert!(placeholder.starts_with("https://www.gravatar.com/avatar/"), "{}", placeholder);
+    assert!(placeholder.ends_with("?d=identicon&f=y"), "{}", placeholder);
+    assert!(!placeholder.contains(EMAIL_HASH));
+    assert!(first["data"]["user"]["emailHash"].is_null());
+    let second: Value = test::call_and_read_body_json(&app, user(&id)).await;
+    assert_eq!(second["data"]["user"]["avatarUrl"], placeholder.as_str());
+
+    // 初期データのアバターのないユーザー（IDのMD5）
+    let body: Value = test::call_and_read_body_json(&app, user("2")).await;
+    assert_eq!(
+        body["data"]["user"]["small"],
+        "https://www.gravatar.com/avatar/c81e728d9d4c2f636f067f89cc14862c?d=identicon&f=y&s=64"
+    );
+}
+
+#[actix_web::test]
+async fn email_is_validated_and_never_exposed() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let token = token(&test::call_and_read_body_json(&app, req).await);
+
+    for email in ["not-an-email", "a@", "a b@example.com"] {
+        let input = json!({ "id": "2", "email": email });
+        let req = graphql_request(Some(&token), UPDATE, json!({ "input": input })).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["errors"][0]["extensions"]["code"], "VALIDATION_FAILED", "{}", email);
+    }
+
+    let input = json!({ "id": "2", "email": "foo@example.com" });
+    let req = graphql_request(Some(&token), UPDATE, json!({ "input": input })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+
+    // スキーマにもREST APIにもメールアドレスそのものは出さない
+    let req = graphql_request(None, r#"{ user(id: "2") { email } }"#, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(!body["errors"].is_null(), "{}", body);
+    let req = test::TestRequest::get().uri("/api/users/2").to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["name"], "佐藤太郎");
+    assert!(body.get("email").is_none(), "{}", body);
+}
@@ -0,0 +1,192 @@
+// exportData / importData
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+const EXPORT: &str = "{ exportData }";
+
+const IMPORT: &str = r#"
+    mutation Import($json: JSON!, $mode: ImportMode!) {
+        importData(json: $json, mode: $mode) {
+            users { created updated skipped }
+            posts { created updated skipped }
+            comments { created updated skipped }
+        }
+    }
+"#;
+
+fn counts(created: i32, updated: i32, skipped: i32) -> Value {
+    json!({ "created": created, "updated": updated, "skipped": skipped })
+}
+
+#[actix_web::test]
+async fn export_wipe_import_round_trip() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let login: Value = test::call_and_read_body_json(&app, req).await;
+    let token = token(&login);
+    let admin_id = login["data"]["login"]["user"]["id"].clone();
+
+    // 初期データに下書きとコメントを足しておく
+    let create = r#"
+        mutation Create($authorId: ID!) {
+            createPost(input: {
+                title: "下書き", body: "本文", tags: ["メモ"], authorId: $authorId, draft: true
+            }) { id }
+        }
+    "#;
+    let req = graphql_request(Some(&token), create, json!({ "authorId": admin_id })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "unexpected errors: {}", body["errors"]);
+    let comment = r#"
+        mutation Comment($authorId: ID!) {
+            addComment(input: { postId: "1", authorId: $authorId, body: "コメント" }) { id }
+        }
+    "#;
+    let req = graphql_request(Some(&token), comment, json!({ "authorId": admin_id })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "unexpected errors: {}", body["errors"]);
+
+    let req = graphql_request(Some(&token), EXPORT, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let exported = body["data"]["exportData"].clone();
+    assert_eq!(exported["version"], 8);
+    assert_eq!(exported["users"].as_array().unwrap().len(), 5);
+    assert_eq!(exported["posts"].as_array().unwrap().len(), 2);
+    assert_eq!(exported["tags"], json!(["はじめに", "ブログ", "メモ"]));
+    assert_eq!(exported["comments"].as_array().unwrap().len(), 1);
+
+    // 管理者だけを残して全て消す（バージョン1の形式にはハンドルがない）
+    let mut admin = exported["users"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|user| user["id"] == admin_id)
+        .unwrap()
+        .clone();
+    let handle = admin.as_object_mut().unwrap().remove("handle").unwrap();
+    let wipe = json!({
+        "version": 1,
+        "exported_at": exported["exported_at"],
+        "users": [admin],
+        "posts": [],
+    });
+    let variables = json!({ "json": wipe, "mode": "REPLACE" });
+    let req = graphql_request(Some(&token), IMPORT, variables).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "unexpected errors: {}", body["errors"]);
+    let count = "{ postsCount(includeDrafts: true) }";
+    let req = graphql_request(Some(&token), count, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["postsCount"], 0);
+    let query = r#"query Admin($id: ID!) { user(id: $id) { handle } }"#;
+    let req = graphql_request(None, query, json!({ "id": admin_id })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["user"]["handle"], handle);
+
+    let variables = json!({ "json": exported, "mode": "REPLACE" });
+    let req = graphql_request(Some(&token), IMPORT, variables).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "unexpected errors: {}", body["errors"]);
+    let result = &body["data"]["importData"];
+    assert_eq!(result["users"], counts(5, 0, 0));
+    assert_eq!(result["posts"], counts(2, 0, 0));
+    assert_eq!(result["comments"], counts(1, 0, 0));
+
+    let req = graphql_request(Some(&token), EXPORT, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let mut reexported = body["data"]["exportData"].clone();
+    reexported["exported_at"] = exported["exported_at"].clone();
+    assert_eq!(reexported, exported);
+}
+
+#[actix_web::test]
+async fn merge_keeps_newer_posts() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let token = token(&test::call_and_read_body_json(&app, req).await);
+
+    let req = graphql_request(Some(&token), EXPORT, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let mut document = body["data"]["exportData"].clone();
+
+    // 初期データの投稿は新しくし、古い日時のコピーと新しい投稿を足す
+    let seed_post = document["posts"][0].clone();
+    let mut newer = seed_post.clone();
+    newer["title"] = json!("更新後");
+    newer["updated_at"] = json!("2999-01-01T00:00:00Z");
+    let mut added = seed_post.clone();
+    added["id"] = json!("0b7c2d4e-6f80-4a1b-9c3d-5e6f7a8b9c0d");
+    added["slug"] = json!("imported");
+    document["posts"] = json!([newer, added]);
+    let variables = json!({ "json": document, "mode": "MERGE" });
+    let req = graphql_request(Some(&token), IMPORT, variables).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "unexpected errors: {}", body["errors"]);
+    let result = &body["data"]["importData"];
+    assert_eq!(result["users"], counts(0, 0, 5));
+    assert_eq!(result["posts"], counts(1, 1, 0));
+
+    // 同じ内容をもう一度マージしても変わらない
+    let mut older = seed_post.clone();
+    older["title"] = json!("古い内容");
+    document["posts"] = json!([older]);
+    let variables = json!({ "json": document, "mode": "MERGE" });
+    let req = graphql_request(Some(&token), IMPORT, variables).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["importData"]["posts"], counts(0, 0, 1));
+
+    // UUIDは大文字でも同じ投稿を指す
+    let query = r#"
+        query Imported($id: ID!) { post(id: "1") { title } imported: post(id: $id) { title } }
+    "#;
+    let variables = json!({ "id": "0B7C2D4E-6F80-4A1B-9C3D-5E6F7A8B9C0D" });
+    let req = graphql_request(None, query, variables).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["post"]["title"], "更新後");
+    assert_eq!(body["data"]["imported"]["title"], "はじめまして");
+}
+
+#[actix_web::test]
+async fn rejects_newer_document_version() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let token = token(&test::call_and_read_body_json(&app, req).await);
+
+    let document = json!({
+        "version": 9,
+        "exported_at": "2030-01-01T00:00:00Z",
+        "users": [],
+        "posts": [],
+        "categories": [],
+    });
+    let variables = json!({ "json": document, "mode": "REPLACE" });
+    let req = graphql_request(Some(&token), IMPORT, variables).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let error = &body["errors"][0];
+    assert_eq!(error["extensions"]["code"], "VALIDATION_FAILED");
+    assert!(error["message"].as_str().unwrap().contains("newer than this server supports"));
+
+    // 何も消えていない
+    let req = graphql_request(None, "{ postsCount }", json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["postsCount"], 1);
+}
+
+#[actix_web::test]
+async fn export_requires_admin() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("佐藤太郎").to_request();
+    let token = token(&test::call_and_read_body_json(&app, req).await);
+
+    let req = graphql_request(Some(&token), EXPORT, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["errors"][0]["extensions"]["code"], "FORBIDDEN");
+}
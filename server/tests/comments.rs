@@ -0,0 +1,239 @@
+// コメントの返信と削除（返信が残るコメントは "[deleted]" として残し、返信がなくなれば消す）
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request, login_request, token, SpanCounter};
+use tracing_subscriber::layer::SubscriberExt;
+
+const ADD: &str = r#"
+    mutation Add($postId: ID!, $parentId: ID, $body: String!) {
+        addComment(input: { postId: $postId, parentCommentId: $parentId, body: $body }) { id }
+    }
+"#;
+
+const COMMENTS: &str = r#"
+    query {
+        post(id: "1") {
+            comments { body deleted replyCount replies { body } }
+        }
+    }
+"#;
+
+fn error_code(body: &Value) -> &str {
+    body["errors"][0]["extensions"]["code"].as_str().unwrap_or_default()
+}
+
+#[actix_web::test]
+async fn deleted_parents_stay_until_their_replies_are_gone() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let admin = token(&test::call_and_read_body_json(&app, req).await);
+    let call = |query: &str, variables: Value| {
+        graphql_request(Some(&admin), query, variables).to_request()
+    };
+    let add = |parent_id: Option<&str>, body: &str| {
+        call(ADD, json!({ "postId": "1", "parentId": parent_id, "body": body }))
+    };
+    let delete = |id: &str| {
+        call("mutation Delete($id: ID!) { deleteComment(id: $id) }", json!({ "id": id }))
+    };
+
+    let body: Value = test::call_and_read_body_json(&app, add(None, "親")).await;
+    let parent = body["data"]["addComment"]["id"].as_str().unwrap().to_string();
+    let mut replies = Vec::new();
+    for text in ["返信1", "返信2"] {
+        let body: Value = test::call_and_read_body_json(&app, add(Some(&parent), text)).await;
+        replies.push(body["data"]["addComment"]["id"].as_str().unwrap().to_string());
+    }
+
+    // デフォルトでは返信への返信はできない
+    let body: Value = test::call_and_read_body_json(&app, add(Some(&replies[0]), "孫")).await;
+    assert_eq!(error_code(&body), "VALIDATION_FAILED", "{}", body);
+    assert_eq!(body["errors"][0]["message"], "Replies can be nested at most 1 level(s) deep");
+    // 別の投稿のコメントには返信できない
+    let create = r#"mutation { createPost(input: { title: "別の投稿", body: "本文" }) { id } }"#;
+    let body: Value = test::call_and_read_body_json(&app, call(create, json!({}))).await;
+    let other_post = body["data"]["createPost"]["id"].as_str().unwrap();
+    let req = call(ADD, json!({ "postId": other_post, "parentId": parent, "body": "返信" }));
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(error_code(&body), "VALIDATION_FAILED", "{}", body);
+
+    let body: Value = test::call_and_read_body_json(&app, delete(&parent)).await;
+    assert_eq!(body["data"]["deleteComment"], true, "{}", body);
+    let body: Value = test::call_and_read_body_json(&app, call(COMMENTS, json!({}))).await;
+    assert_eq!(
+        body["data"]["post"]["comments"],
+        json!([{
+            "body": "[deleted]",
+            "deleted": true,
+            "replyCount": 2,
+            "replies": [{ "body": "返信1" }, { "body": "返信2" }],
+        }])
+    );
+    let body: Value = test::call_and_read_body_json(&app, delete(&parent)).await;
+    assert_eq!(error_code(&body), "NOT_FOUND", "{}", body);
+
+    // 最後の返信を消すと削除済みの親も消える
+    let body: Value = test::call_and_read_body_json(&app, delete(&replies[0])).await;
+    assert_eq!(body["data"]["deleteComment"], true, "{}", body);
+    let body: Value = test::call_and_read_body_json(&app, call(COMMENTS, json!({}))).await;
+    assert_eq!(body["data"]["post"]["comments"][0]["replyCount"], 1, "{}", body);
+    let body: Value = test::call_and_read_body_json(&app, delete(&replies[1])).await;
+    assert_eq!(body["data"]["deleteComment"], true, "{}", body);
+    let body: Value = test::call_and_read_body_json(&app, call(COMMENTS, json!({}))).await;
+    assert_eq!(body["data"]["post"]["comments"], json!([]), "{}", body);
+
+    // 削除していない親は返信がなくなっても残る
+    let body: Value = test::call_and_read_body_json(&app, add(None, "残る親")).await;
+    let parent = body["data"]["addComment"]["id"].as_str().unwrap().to_string();
+    let body: Value = test::call_and_read_body_json(&app, add(Some(&parent), "返信")).await;
+    let reply = body["data"]["addComment"]["id"].as_str().unwrap().to_string();
+    let body: Value = test::call_and_read_body_json(&app, delete(&reply)).await;
+    assert_eq!(body["data"]["deleteComment"], true, "{}", body);
+    let body: Value = test::call_and_read_body_json(&app, call(COMMENTS, json!({}))).await;
+    assert_eq!(
+        body["data"]["post"]["comments"],
+        json!([{ "body": "残る親", "deleted": false, "replyCount": 0, "replies": [] }])
+    );
+}
+
+#[actix_web::test]
+async fn comment_counts_for_a_page_are_loaded_in_one_pass() {
+    let spans = SpanCounter::default();
+    let subscriber = tracing_subscriber::registry().with(spans.clone());
+    let _guard = tracing::subscriber::set_default(subscriber);
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let admin = token(&test::call_and_read_body_json(&app, req).await);
+    let call = |query: &str, variables: Value| {
+        graphql_request(Some(&admin), query, variables).to_request()
+    };
+
+    // 初期データと合わせて10件。i件目にはi件のコメントがあり、1件は非表示
+    let create = r#"mutation { createPost(input: { title: "投稿", body: "本文" }) { id } }"#;
+    for i in 1..10 {
+        let body: Value = test::call_and_read_body_json(&app, call(create, json!({}))).await;
+        let post_id = body["data"]["createPost"]["id"].as_str().unwrap().to_string();
+        for _ in 0..i {
+            let req = call(ADD, json!({ "postId": post_id, "body": "コメント" }));
+            let body: Value = test::call_and_read_body_json(&app, req).await;
+            assert!(body["errors"].is_null(), "{}", body);
+        }
+        if i == 9 {
+            let req = call(ADD, json!({ "postId": post_id, "body": "非表示にするコメント" }));
+            let body: Value = test::call_and_read_body_json(&app, req).await;
+            let hide = "mutation Hide($id: ID!) { hideComment(id: $id) { id } }";
+            let req = call(hide, json!({ "id": body["data"]["addComment"]["id"] }));
+            let body: Value = test::call_and_read_body_json(&app, req).await;
+            assert!(body["errors"].is_null(), "{}", body);
+        }
+    }
+
+    spans.reset();
+    let query = "{ posts(limit: 10) { commentCount all: commentCount(includeHidden: true) } }";
+    let body: Value = test::call_and_read_body_json(&app, call(query, json!({}))).await;
+    let posts = body["data"]["posts"].as_array().unwrap_or_else(|| panic!("{}", body));
+    let counts: Vec<(i64, i64)> = posts
+        .iter()
+        .map(|p| (p["commentCount"].as_i64().unwrap(), p["all"].as_i64().unwrap()))
+        .collect();
+    let mut expected: Vec<(i64, i64)> = (0..10).rev().map(|i| (i, i)).collect();
+    expected[0].1 += 1;
+    assert_eq!(counts, expected);
+    // 投稿ごとではなく、ページ全体で1回だけ数える
+    assert_eq!(spans.count("loader.comment_counts"), 1);
+}
+
+#[actix_web::test]
+async fn only_admins_can_include_hidden_comments() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let mut tokens = Vec::new();
+    for name in ["髙橋慶祐", "佐藤太郎"] {
+        let req = login_request(name).to_request();
+        tokens.push(token(&test::call_and_read_body_json(&app, req).await));
+    }
+    let admin = &tokens[0];
+    let add = |parent_id: Option<&str>, body: &str| {
+        let variables = json!({ "postId": "1", "parentId": parent_id, "body": body });
+        graphql_request(Some(admin), ADD, variables).to_request()
+    };
+    let hide = |id: &str| {
+        let query = "mutation Hide($id: ID!) { hideComment(id: $id) { id } }";
+        graphql_request(Some(admin), query, json!({ "id": id })).to_request()
+    };
+
+    // 親には非表示の返信、投稿には非表示のコメント
+    let body: Value = test::call_and_read_body_json(&app, add(None, "親")).await;
+    let parent = body["data"]["addComment"]["id"].as_str().unwrap().to_string();
+    test::call_service(&app, add(Some(&parent), "返信")).await;
+    for (parent_id, text) in [(Some(parent.as_str()), "非表示の返信"), (None, "非表示のコメント")] {
+        let body: Value = test::call_and_read_body_json(&app, add(parent_id, text)).await;
+        let id = body["data"]["addComment"]["id"].as_str().unwrap().to_string();
+        let body: Value = test::call_and_read_body_json(&app, hide(&id)).await;
+        assert!(body["errors"].is_null(), "{}", body);
+    }
+
+    // 未ログインは認証が必要、管理者以外は権限がない
+    let fields = [
+        "comments(includeHidden: true) { body }",
+        "commentCount(includeHidden: true)",
+        "comments { replies(includeHidden: true) { body } }",
+        "comments { replyCount(includeHidden: true) }",
+    ];
+    for field in fields {
+        let query = format!(r#"{{ post(id: "1") {{ {} }} }}"#, field);
+        let req = graphql_request(None, &query, json!({})).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(error_code(&body), "UNAUTHENTICATED", "{}: {}", field, body);
+        let req = graphql_request(Some(&tokens[1]), &query, json!({})).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(error_code(&body), "FORBIDDEN", "{}: {}", field, body);
+    }
+
+    let query = r#"
+        query Comments($includeHidden: Boolean!) {
+            post(id: "1") {
+                commentCount(includeHidden: $includeHidden)
+                comments(includeHidden: $includeHidden) {
+                    body
+                    replyCount(includeHidden: $includeHidden)
+                    replies(includeHidden: $includeHidden) { body }
+                }
+            }
+        }
+    "#;
+    let req = graphql_request(None, query, json!({ "includeHidden": false })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(
+        body["data"]["post"],
+        json!({
+            "commentCount": 2,
+            "comments": [{ "body": "親", "replyCount": 1, "replies": [{ "body": "返信" }] }],
+        }),
+        "{}",
+        body
+    );
+    let req = graphql_request(Some(admin), query, json!({ "includeHidden": true })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(
+        body["data"]["post"],
+        json!({
+            "commentCount": 4,
+            "comments": [
+                {
+                    "body": "親",
+                    "replyCount": 2,
+                    "replies": [{ "body": "返信" }, { "body": "非表示の返信" }],
+                },
+                { "body": "非表示のコメント", "replyCount": 0, "replies": [] },
+            ],
+        }),
+        "{}",
+        body
+    );
+}
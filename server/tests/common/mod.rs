@@ -0,0 +1,186 @@
+// 結合テストの共通処理（テストファイルごとに使わない関数がある）
+#![allow(dead_code)]
+use actix_web::test;
+use blog_server::{build_app_state, AppState, MemoryStorage, Seed};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+// 初期データ入りのメモリストレージで組み立てる（テストごとに独立）
+pub async fn app_state() -> AppState {
+    build_app_state(Arc::new(MemoryStorage::seeded(&Seed::builtin())), None)
+        .await
+        .expect("failed to build app state")
+}
+
+pub fn graphql_request(token: Option<&str>, query: &str, variables: Value) -> test::TestRequest {
+    let mut req = test::TestRequest::post()
+        .uri("/api/graphql")
+        .set_json(json!({ "query": query, "variables": variables }));
+    if let Some(token) = token {
+        req = req.insert_header(("Authorization", format!("Bearer {}", token)));
+    }
+    req
+}
+
+// 初期ユーザーのパスワードはSEED_USER_PASSWORDのデフォルト
+pub fn login_request(name: &str) -> test::TestRequest {
+    let query = r#"
+        mutation Login($name: String!, $password: String!) {
+            login(name: $name, password: $password) { token user { id } }
+        }
+    "#;
+    graphql_request(None, query, json!({ "name": name, "password": "password" }))
+}
+
+pub fn token(login: &Value) -> String {
+    login["data"]["login"]["token"].as_str().expect("login failed").to_owned()
+}
+
+// 実際に待ち受けるサーバーにJSONを送る（サーバーが応答せずに切った場合はエラー）
+pub async fn post_json(addr: SocketAddr, token: Option<&str>, body: Value) -> io::Result<Value> {
+    let body = body.to_string();
+    let authorization = token.map(|t| format!("Authorization: Bearer {}\r\n", t));
+    let head = format!(
+        "POST /api/graphql HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\n\
+         {}Content-Length: {}\r\nConnection: close\r\n\r\n",
+        authorization.unwrap_or_default(),
+        body.len()
+    );
+    let mut stream = TcpStream::connect(addr).await?;
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    let (_, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "no response"))?;
+    Ok(serde_json::from_str(body)?)
+}
+
+// コマンドに決まった応答を返し、接続ごとに受け取った内容を記録するSMTPサーバー
+pub async fn serve_smtp(listener: TcpListener, received: Arc<Mutex<Vec<String>>>) {
+    while let Ok((stream, _)) = listener.accept().await {
+        let received = received.clone();
+        tokio::spawn(async move {
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            writer.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+            let mut transcript = String::new();
+            let mut in_data = false;
+            while let Ok(Some(line)) = lines.next_line().await {
+                transcript.push_str(&line);
+                transcript.push('\n');
+                let command = line.get(..4).unwrap_or_default().to_ascii_uppercase();
+                let reply: &[u8] = match command.as_str() {
+                    _ if in_data && line != "." => continue,
+                    _ if in_data => {
+                        in_data = false;
+                        b"250 OK\r\n"
+                    }
+                    "EHLO" | "HELO" => b"250 localhost\r\n",
+                    "DATA" => {
+                        in_data = true;
+                        b"354 End data with <CR><LF>.<CR><LF>\r\n"
+                    }
+                    "QUIT" => b"221 Bye\r\n",
+                    _ => b"250 OK\r\n",
+                };
+                writer.write_all(reply).await.unwrap();
+                if command == "QUIT" {
+                    break;
+                }
+            }
+            received.lock().unwrap().push(transcript);
+        });
+    }
+}
+
+// quoted-printableの本文を戻す（ASCIIのみ）
+pub fn decode_quoted_printable(text: &str) -> String {
+    let text = text.replace("=\n", "");
+    let mut decoded = String::new();
+    let mut rest = text.as_str();
+    while let Some(i) = rest.find('=') {
+        decoded.push_str(&rest[..i]);
+        let byte = rest.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match byte {
+            Some(byte) => {
+                decoded.push(byte as char);
+                rest = &rest[i + 3..];
+            }
+            None => {
+                decoded.push('=');
+                rest = &rest[i + 1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+// 作られたspanを名前ごとに数える（ストレージやローダーの呼び出し回数の確認用）
+#[derive(Clone, Default)]
+pub struct SpanCounter(Arc<Mutex<HashMap<&'static str, usize>>>);
+
+impl SpanCounter {
+    pub fn count(&self, name: &str) -> usize {
+        self.0.lock().unwrap().get(name).copied().unwrap_or(0)
+    }
+
+    pub fn reset(&self) {
+        self.0.lock().unwrap().clear();
+    }
+}
+
+impl<S: Subscriber> Layer<S> for SpanCounter {
+    fn on_new_span(&self, attrs: &Attributes<'_>, _: &Id, _: Context<'_, S>) {
+        *self.0.lock().unwrap().entry(attrs.metadata().name()).or_default() += 1;
+    }
+}
+
+// 出力されたイベントのフィールドを記録するレイヤー（メッセージはmessageに入る）
+#[derive(Clone, Default)]
+pub struct LogCapture(Arc<Mutex<Vec<HashMap<&'static str, String>>>>);
+
+impl LogCapture {
+    // メッセージが一致するイベントのフィールド（levelを含む）
+    pub fn events(&self, message: &str) -> Vec<HashMap<&'static str, String>> {
+        let events = self.0.lock().unwrap();
+        events
+            .iter()
+            .filter(|fields| fields.get("message").is_some_and(|m| m == message))
+            .cloned()
+            .collect()
+    }
+}
+
+impl<S: Subscriber> Layer<S> for LogCapture {
+    fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+        let mut fields = EventFields(HashMap::new());
+        fields.0.insert("level", event.metadata().level().to_string());
+        event.record(&mut fields);
+        self.0.lock().unwrap().push(fields.0);
+    }
+}
+
+struct EventFields(HashMap<&'static str, String>);
+
+impl Visit for EventFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(field.name(), format!("{:?}", value));
+    }
+}
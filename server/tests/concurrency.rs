@@ -0,0 +1,63 @@
+// 同時に届く読み込みと書き込み（読み込み同士で待たず、デッドロックしないこと）
+use actix_web::{test, App};
+use blog_server::configure_app;
+use futures_util::future::join_all;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+const READERS: usize = 200;
+const WRITERS: usize = 20;
+
+const READ: &str = r#"
+    { posts(limit: 100) { title author { name } commentCount } user(id: "2") { name } }
+"#;
+
+#[actix_web::test]
+async fn parallel_reads_and_writes_complete() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let admin = token(&test::call_and_read_body_json(&app, req).await);
+
+    let read = |_| async {
+        let req = graphql_request(None, READ, json!({})).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert!(body["errors"].is_null(), "{}", body);
+    };
+    let write = |i: usize| {
+        let admin = admin.clone();
+        let app = &app;
+        async move {
+            let create = r#"
+                mutation Create($title: String!) {
+                    createPost(input: { title: $title, body: "本文", authorId: "2" }) { id }
+                }
+            "#;
+            let variables = json!({ "title": format!("同時に作る投稿{}", i) });
+            let req = graphql_request(Some(&admin), create, variables).to_request();
+            let body: Value = test::call_and_read_body_json(app, req).await;
+            assert!(body["errors"].is_null(), "{}", body);
+            let update = r#"mutation { updateUser(input: { id: "2", bio: "更新中" }) { id } }"#;
+            let req = graphql_request(Some(&admin), update, json!({})).to_request();
+            let body: Value = test::call_and_read_body_json(app, req).await;
+            assert!(body["errors"].is_null(), "{}", body);
+        }
+    };
+
+    // 書き込みを読み込みの間に混ぜて、全て同時に走らせる
+    let requests = async {
+        let reads = join_all((0..READERS).map(read));
+        let writes = join_all((0..WRITERS).map(write));
+        futures_util::join!(reads, writes)
+    };
+    tokio::time::timeout(Duration::from_secs(30), requests)
+        .await
+        .expect("requests should not deadlock");
+
+    let req = graphql_request(None, "{ postsCount }", json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["postsCount"], 1 + WRITERS, "{}", body);
+}
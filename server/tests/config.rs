@@ -0,0 +1,45 @@
+// 起動時の設定（環境変数より同じ名前のフラグを優先し、不正な値は設定の名前を添えて拒否する）
+// 環境変数を書き換えるので、このファイルのテストは1つにまとめる
+use blog_server::ServerConfig;
+use clap::Parser;
+use std::net::{IpAddr, Ipv4Addr};
+
+#[test]
+fn flags_override_environment_variables() {
+    std::env::set_var("BIND_ADDR", "0.0.0.0");
+    std::env::set_var("PORT", "9000");
+    std::env::set_var("GRAPHQL_PATH", "/graphql");
+    std::env::set_var("CORS_ALLOWED_ORIGINS", "https://a.example,https://b.example/");
+
+    // フラグを指定しなかった設定は環境変数の値
+    let config = ServerConfig::try_parse_from(["blog-server", "--port", "9100"]).unwrap();
+    assert_eq!(config.port, 9100);
+    assert_eq!(config.bind_addr, IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    assert_eq!(config.graphql_path, "/graphql");
+    assert_eq!(config.cors_allowed_origins, ["https://a.example", "https://b.example"]);
+
+    let args = [
+        "blog-server",
+        "--graphql-path",
+        "/api/v2",
+        "--cors-allowed-origins",
+        "https://c.example",
+    ];
+    let config = ServerConfig::try_parse_from(args).unwrap();
+    assert_eq!(config.port, 9000);
+    assert_eq!(config.graphql_path, "/api/v2");
+    assert_eq!(config.cors_allowed_origins, ["https://c.example"]);
+
+    std::env::set_var("PORT", "eighty");
+    let error = ServerConfig::try_parse_from(["blog-server"]).err().unwrap().to_string();
+    assert!(error.contains("'eighty'") && error.contains("--port"), "{}", error);
+    // 環境変数の値が不正でもフラグで上書きすれば起動できる
+    assert!(ServerConfig::try_parse_from(["blog-server", "--port", "80"]).is_ok());
+
+    for name in ["BIND_ADDR", "PORT", "GRAPHQL_PATH", "CORS_ALLOWED_ORIGINS"] {
+        std::env::remove_var(name);
+    }
+    let config = ServerConfig::try_parse_from(["blog-server"]).unwrap();
+    assert_eq!((config.bind_addr, config.port), (IpAddr::V4(Ipv4Addr::LOCALHOST), 8000));
+    assert_eq!(config.graphql_path, "/api/graphql");
+}
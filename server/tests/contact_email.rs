@@ -0,0 +1,79 @@
+// お問い合わせのメールでの転送（メールアドレスを登録した管理者に、返信先を送り主にして送る）
+// 環境変数を書き換えるので、このファイルのテストは1つにまとめる
+use actix_web::{test, App};
+use blog_server::{configure_app, spawn_background_tasks};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+mod common;
+use common::{app_state, decode_quoted_printable, graphql_request, login_request, serve_smtp, token};
+
+#[actix_web::test]
+async fn forwards_contact_messages_to_admins() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let received = Arc::new(Mutex::new(Vec::new()));
+    tokio::spawn(serve_smtp(listener, received.clone()));
+
+    let vars = [
+        ("SMTP_HOST", "127.0.0.1".to_string()),
+        ("SMTP_PORT", port.to_string()),
+        ("SMTP_TLS", "none".to_string()),
+        ("SMTP_FROM", "blog@example.com".to_string()),
+    ];
+    for (name, value) in &vars {
+        std::env::set_var(name, value);
+    }
+    let state = app_state().await;
+    for (name, _) in &vars {
+        std::env::remove_var(name);
+    }
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let background_tasks = spawn_background_tasks(&state);
+
+    // 管理者（1）と著者（2）の両方にメールアドレスを登録する
+    let req = login_request("髙橋慶祐").to_request();
+    let admin = token(&test::call_and_read_body_json(&app, req).await);
+    let update = r#"
+        mutation Update($id: ID!, $email: String!) {
+            updateUser(input: { id: $id, email: $email }) { id }
+        }
+    "#;
+    for (id, email) in [("1", "admin@example.com"), ("2", "author@example.com")] {
+        let variables = json!({ "id": id, "email": email });
+        let req = graphql_request(Some(&admin), update, variables).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert!(body["errors"].is_null(), "{}", body);
+    }
+
+    let submit = r#"
+        mutation {
+            submitContactMessage(
+                name: "Alice", email: "alice@example.com", message: "Hello <admin> & thanks"
+            )
+        }
+    "#;
+    let req = graphql_request(None, submit, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["submitContactMessage"], true, "{}", body);
+
+    for _ in 0..200 {
+        if !received.lock().unwrap().is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(25)).await;
+    }
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let received = received.lock().unwrap().clone();
+    assert_eq!(received.len(), 1, "{:?}", received);
+    let mail = &decode_quoted_printable(&received[0]);
+    assert!(mail.contains("RCPT TO:<admin@example.com>"), "{}", mail);
+    assert!(mail.contains("Reply-To: Alice <alice@example.com>"), "{}", mail);
+    assert!(mail.contains("Subject: Contact message from Alice"), "{}", mail);
+    assert!(mail.contains("\nHello <admin> & thanks\n"), "{}", mail);
+    assert!(mail.contains("Hello &lt;admin&gt; &amp; thanks</blockquote>"), "{}", mail);
+
+    background_tasks.shutdown().await;
+}
@@ -0,0 +1,134 @@
+// お問い合わせ（submitContactMessage / contactMessages / markContactRead とレート制限）
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+const SUBMIT: &str = r#"
+    mutation Submit($name: String!, $email: String!, $message: String!, $website: String) {
+        submitContactMessage(name: $name, email: $email, message: $message, website: $website)
+    }
+"#;
+
+const MESSAGES: &str = r#"
+    query Messages($unreadOnly: Boolean! = false) {
+        contactMessages(unreadOnly: $unreadOnly) { id name email message read createdAt }
+    }
+"#;
+
+fn submit(name: &str, email: &str, message: &str, website: Option<&str>) -> test::TestRequest {
+    let variables = json!({ "name": name, "email": email, "message": message, "website": website });
+    graphql_request(None, SUBMIT, variables)
+}
+
+fn error_code(body: &Value) -> &str {
+    body["errors"][0]["extensions"]["code"].as_str().unwrap_or_default()
+}
+
+#[actix_web::test]
+async fn only_admins_can_read_contact_messages() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let admin = token(&test::call_and_read_body_json(&app, req).await);
+    let req = login_request("佐藤太郎").to_request();
+    let author = token(&test::call_and_read_body_json(&app, req).await);
+    let messages = |token: Option<&str>, unread_only: bool| {
+        graphql_request(token, MESSAGES, json!({ "unreadOnly": unread_only })).to_request()
+    };
+
+    // 違反はまとめてextensions.validationに入れる
+    let req = submit(" ", "not an email", "短い", None).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(error_code(&body), "VALIDATION_FAILED", "{}", body);
+    let violations = &body["errors"][0]["extensions"]["validation"];
+    assert_eq!(
+        *violations,
+        json!([
+            { "field": "name", "rule": "required" },
+            { "field": "email", "rule": "email" },
+            { "field": "message", "rule": "minLength", "limit": 10 },
+        ])
+    );
+    let req = submit("Alice", "alice@example.com", &"あ".repeat(5001), None).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let violations = &body["errors"][0]["extensions"]["validation"];
+    assert_eq!(*violations, json!([{ "field": "message", "rule": "maxLength", "limit": 5000 }]));
+
+    // 隠しフィールドに値が入っていれば、成功を返すが保存しない
+    let req = submit("Bot", "bot@example.com", "Buy cheap things now!", Some("http://spam"));
+    let body: Value = test::call_and_read_body_json(&app, req.to_request()).await;
+    assert_eq!(body["data"]["submitContactMessage"], true, "{}", body);
+
+    let submissions = [("Alice", "最初のお問い合わせです。"), ("Bob", "  二件目のお問い合わせです。  ")];
+    for (name, message) in submissions {
+        let req = submit(name, " visitor@example.com ", message, Some("")).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["data"]["submitContactMessage"], true, "{}", body);
+    }
+
+    // 管理者以外は参照も既読にもできない
+    let body: Value = test::call_and_read_body_json(&app, messages(Some(&author), false)).await;
+    assert_eq!(error_code(&body), "FORBIDDEN", "{}", body);
+    let body: Value = test::call_and_read_body_json(&app, messages(None, false)).await;
+    assert_eq!(error_code(&body), "UNAUTHENTICATED", "{}", body);
+
+    // 新しい順。前後の空白は除く
+    let body: Value = test::call_and_read_body_json(&app, messages(Some(&admin), false)).await;
+    let list = body["data"]["contactMessages"].as_array().unwrap();
+    let names: Vec<&str> = list.iter().map(|m| m["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["Bob", "Alice"], "{}", body);
+    assert_eq!(list[0]["email"], "visitor@example.com");
+    assert_eq!(list[0]["message"], "二件目のお問い合わせです。");
+    assert_eq!(list[0]["read"], false);
+    let alice_id = list[1]["id"].as_str().unwrap().to_string();
+
+    let mark = r#"mutation Mark($id: ID!) { markContactRead(id: $id) { id read } }"#;
+    let req = graphql_request(Some(&author), mark, json!({ "id": alice_id })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(error_code(&body), "FORBIDDEN", "{}", body);
+    let req = graphql_request(Some(&admin), mark, json!({ "id": alice_id })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["markContactRead"]["read"], true, "{}", body);
+    let req = graphql_request(Some(&admin), mark, json!({ "id": "0" })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(error_code(&body), "NOT_FOUND", "{}", body);
+
+    let body: Value = test::call_and_read_body_json(&app, messages(Some(&admin), true)).await;
+    let list = body["data"]["contactMessages"].as_array().unwrap();
+    assert_eq!(list.len(), 1, "{}", body);
+    assert_eq!(list[0]["name"], "Bob");
+}
+
+#[actix_web::test]
+async fn throttles_contact_messages_per_client_ip() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let first: SocketAddr = "203.0.113.1:4000".parse().unwrap();
+    let second: SocketAddr = "203.0.113.2:4000".parse().unwrap();
+    let send = |peer: SocketAddr| {
+        submit("Alice", "alice@example.com", "お問い合わせの本文です。", None).peer_addr(peer)
+    };
+
+    // デフォルトは1時間に3件。隠しフィールドで捨てたものも数える
+    let bot = submit("Bot", "bot@example.com", "Buy cheap things now!", Some("x"));
+    let res = test::call_service(&app, bot.peer_addr(first).to_request()).await;
+    assert_eq!(res.status(), 200);
+    for _ in 0..2 {
+        let res = test::call_service(&app, send(first).to_request()).await;
+        assert_eq!(res.status(), 200);
+    }
+    let res = test::call_service(&app, send(first).to_request()).await;
+    assert_eq!(res.status(), 429);
+    assert!(res.headers().contains_key("Retry-After"));
+
+    // 他のクライアントや他のミューテーションは制限しない
+    let res = test::call_service(&app, send(second).to_request()).await;
+    assert_eq!(res.status(), 200);
+    let req = login_request("髙橋慶祐").peer_addr(first).to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 200);
+}
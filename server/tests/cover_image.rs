@@ -0,0 +1,115 @@
+// 投稿のカバー画像（coverImageUrl / coverImageAlt）と hasCoverImage での絞り込み
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+const CREATE: &str = r#"
+    mutation Create($url: Url, $alt: String) {
+        createPost(input: {
+            title: "カバー画像", body: "本文", authorId: "1", coverImageUrl: $url, coverImageAlt: $alt
+        }) { id coverImageUrl coverImageAlt }
+    }
+"#;
+
+const UPDATE: &str = r#"
+    mutation Update($input: UpdatePostInput!) {
+        updatePost(input: $input) { coverImageUrl coverImageAlt }
+    }
+"#;
+
+#[actix_web::test]
+async fn sets_updates_and_clears_cover_image() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let token = token(&test::call_and_read_body_json(&app, req).await);
+
+    let variables = json!({ "url": "https://example.com/cover.png", "alt": "  海の写真 " });
+    let req = graphql_request(Some(&token), CREATE, variables).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let post = &body["data"]["createPost"];
+    assert_eq!(post["coverImageUrl"], "https://example.com/cover.png");
+    assert_eq!(post["coverImageAlt"], "海の写真");
+    let id = post["id"].clone();
+
+    // 指定しなければ変更しない
+    let input = json!({ "id": id, "title": "タイトルだけ変更" });
+    let req = graphql_request(Some(&token), UPDATE, json!({ "input": input })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(
+        body["data"]["updatePost"],
+        json!({ "coverImageUrl": "https://example.com/cover.png", "coverImageAlt": "海の写真" })
+    );
+
+    // nullで削除する
+    let input = json!({ "id": id, "coverImageUrl": null, "coverImageAlt": null });
+    let req = graphql_request(Some(&token), UPDATE, json!({ "input": input })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(
+        body["data"]["updatePost"],
+        json!({ "coverImageUrl": null, "coverImageAlt": null })
+    );
+}
+
+#[actix_web::test]
+async fn rejects_invalid_cover_image() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let token = token(&test::call_and_read_body_json(&app, req).await);
+
+    let variables = json!({ "url": "javascript:alert(1)" });
+    let req = graphql_request(Some(&token), CREATE, variables).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["data"].is_null(), "{}", body);
+    assert!(body["errors"][0]["message"].as_str().unwrap().contains("javascript:"), "{}", body);
+
+    let variables = json!({ "url": "https://example.com/a.png", "alt": "長".repeat(301) });
+    let req = graphql_request(Some(&token), CREATE, variables).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let error = &body["errors"][0];
+    assert_eq!(error["extensions"]["code"], "VALIDATION_FAILED");
+    assert_eq!(
+        error["extensions"]["validation"],
+        json!([{ "field": "coverImageAlt", "rule": "maxLength", "limit": 300 }])
+    );
+}
+
+#[actix_web::test]
+async fn filters_posts_by_cover_image() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let token = token(&test::call_and_read_body_json(&app, req).await);
+
+    let variables = json!({ "url": "https://example.com/cover.png" });
+    let req = graphql_request(Some(&token), CREATE, variables).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let id = body["data"]["createPost"]["id"].clone();
+
+    let query = r#"
+        query Posts($has: Boolean) {
+            posts(hasCoverImage: $has) { id }
+            postsCount(hasCoverImage: $has)
+        }
+    "#;
+    for (has, expected) in [(json!(true), vec![id.clone()]), (json!(false), vec![json!("1")])] {
+        let req = graphql_request(None, query, json!({ "has": has })).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        let ids: Vec<Value> = body["data"]["posts"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|post| post["id"].clone())
+            .collect();
+        assert_eq!(ids, expected, "{}", has);
+        assert_eq!(body["data"]["postsCount"], 1);
+    }
+    let req = graphql_request(None, query, json!({ "has": null })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["postsCount"], 2);
+}
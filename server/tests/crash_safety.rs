@@ -0,0 +1,153 @@
+// redbのストレージの強制終了（createPostの途中でkill -9しても、書きかけの投稿を残さない）
+use actix_web::{test, App};
+use blog_server::{build_app_state, configure_app, open_database, Seed};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::net::{SocketAddr, TcpListener};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+mod common;
+use common::{graphql_request, login_request, post_json, token};
+
+const WRITERS: usize = 8;
+const ACKNOWLEDGED_BEFORE_KILL: usize = 100;
+
+const LOGIN: &str = r#"
+    mutation { login(name: "髙橋慶祐", password: "password") { token user { id } } }
+"#;
+
+const CREATE: &str = r#"
+    mutation Create($title: String!, $body: String!) {
+        createPost(input: { title: $title, body: $body, tags: ["強制終了", "テスト"] }) { id }
+    }
+"#;
+
+const PAGE: &str = r#"
+    query Page($offset: Int!) {
+        posts(includeDrafts: true, limit: 100, offset: $offset) { id title body tags slug }
+        postsCount(includeDrafts: true)
+    }
+"#;
+
+// サーバーが起動して応答を返すまで待ち、ログインする
+async fn wait_for_login(addr: SocketAddr) -> String {
+    let started = Instant::now();
+    loop {
+        match post_json(addr, None, json!({ "query": LOGIN })).await {
+            Ok(body) => return token(&body),
+            Err(e) if started.elapsed() > Duration::from_secs(30) => {
+                panic!("server did not start: {}", e)
+            }
+            Err(_) => tokio::time::sleep(Duration::from_millis(50)).await,
+        }
+    }
+}
+
+// 接続を拒否されるまで投稿を作り続け、作成を確認できた投稿のIDを記録する
+async fn create_posts(
+    addr: SocketAddr,
+    admin: String,
+    writer: usize,
+    acknowledged: Arc<Mutex<Vec<String>>>,
+) {
+    for i in 0.. {
+        let variables = json!({
+            "title": format!("投稿{}-{}", writer, i),
+            "body": format!("本文{}-{}", writer, i),
+        });
+        let request = json!({ "query": CREATE, "variables": variables });
+        let Ok(body) = post_json(addr, Some(&admin), request).await else {
+            return;
+        };
+        if let Some(id) = body["data"]["createPost"]["id"].as_str() {
+            acknowledged.lock().unwrap().push(id.to_string());
+        }
+    }
+}
+
+#[actix_web::test]
+async fn killing_the_server_during_create_post_leaves_no_half_written_posts() {
+    let path = std::env::temp_dir().join(format!("blog-crash-{}.redb", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let url = format!("redb:{}", path.display());
+    let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let mut server = Command::new(env!("CARGO_BIN_EXE_blog-server"))
+        .env_remove("DATABASE_URL")
+        .env("STORAGE", &url)
+        .env("BIND_ADDR", "127.0.0.1")
+        .env("PORT", addr.port().to_string())
+        .env("SEED_USER_PASSWORD", "password")
+        .env("RATE_LIMIT_MUTATIONS", "1000000")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let admin = wait_for_login(addr).await;
+
+    // 書き込みが続いている最中にSIGKILLで止める
+    let acknowledged = Arc::new(Mutex::new(Vec::new()));
+    let writers: Vec<_> = (0..WRITERS)
+        .map(|writer| {
+            let acknowledged = acknowledged.clone();
+            tokio::spawn(create_posts(addr, admin.clone(), writer, acknowledged))
+        })
+        .collect();
+    let started = Instant::now();
+    while acknowledged.lock().unwrap().len() < ACKNOWLEDGED_BEFORE_KILL {
+        assert!(started.elapsed() < Duration::from_secs(30), "posts were not created");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    server.kill().unwrap();
+    server.wait().unwrap();
+    for writer in writers {
+        writer.await.unwrap();
+    }
+    let acknowledged: HashSet<String> = acknowledged.lock().unwrap().drain(..).collect();
+
+    // 開き直せて、一覧・件数・スラッグの索引が投稿そのものと食い違わないこと
+    let storage = open_database(&url, &Seed::builtin()).await.unwrap();
+    let state = build_app_state(storage, None).await.unwrap();
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let admin = token(&test::call_and_read_body_json(&app, req).await);
+    let mut posts = Vec::new();
+    let mut count = None;
+    loop {
+        let variables = json!({ "offset": posts.len() });
+        let req = graphql_request(Some(&admin), PAGE, variables).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert!(body["errors"].is_null(), "{}", body);
+        count = count.or(body["data"]["postsCount"].as_u64());
+        let page = body["data"]["posts"].as_array().unwrap();
+        if page.is_empty() {
+            break;
+        }
+        posts.extend(page.iter().cloned());
+    }
+    assert_eq!(count, Some(posts.len() as u64));
+
+    let mut ids = HashSet::new();
+    for post in &posts {
+        let id = post["id"].as_str().unwrap();
+        assert!(ids.insert(id.to_string()), "duplicate post {}", id);
+        if post["title"] == "はじめまして" {
+            continue;
+        }
+        let title = post["title"].as_str().unwrap();
+        assert_eq!(post["body"], title.replace("投稿", "本文"), "{}", post);
+        assert_eq!(post["tags"], json!(["強制終了", "テスト"]), "{}", post);
+        let query = r#"
+            query BySlug($slug: String!) { postBySlug(slug: $slug, includeDrafts: true) { id } }
+        "#;
+        let variables = json!({ "slug": post["slug"] });
+        let req = graphql_request(Some(&admin), query, variables).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["data"]["postBySlug"]["id"], id, "{}", body);
+    }
+    // 作成を返した投稿は全て残っている
+    assert!(acknowledged.is_subset(&ids), "lost {:?}", acknowledged.difference(&ids));
+    state.close().await;
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,65 @@
+// 投稿一覧の著者をまとめて引くDataLoader（ページ全体でストレージへの問い合わせは1回）
+use actix_web::{test, App};
+use blog_server::{build_app_state, configure_app, MemoryStorage, Seed, Storage};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing_subscriber::layer::SubscriberExt;
+
+mod common;
+use common::{graphql_request, login_request, token, SpanCounter};
+
+#[actix_web::test]
+async fn authors_of_a_page_are_loaded_in_one_storage_call() {
+    let spans = SpanCounter::default();
+    let subscriber = tracing_subscriber::registry().with(spans.clone());
+    let _guard = tracing::subscriber::set_default(subscriber);
+    let storage = Arc::new(MemoryStorage::seeded(&Seed::builtin()));
+    let state = build_app_state(storage.clone(), None).await.unwrap();
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let admin = token(&test::call_and_read_body_json(&app, req).await);
+    let call = |query: &str, variables: Value| {
+        graphql_request(Some(&admin), query, variables).to_request()
+    };
+
+    // 著者の異なる50件。最後の1人は投稿を残したままストレージから消し、存在しないIDを混ぜる
+    let create_user = r#"
+        mutation Create($name: String!, $handle: String!) {
+            createUser(input: { name: $name, handle: $handle }) { id }
+        }
+    "#;
+    let create_post = r#"
+        mutation Create($authorId: ID!) {
+            createPost(input: { title: "投稿", body: "本文", authorId: $authorId }) { id }
+        }
+    "#;
+    let mut names = Vec::new();
+    let mut last_author = String::new();
+    for i in 0..50 {
+        let name = format!("著者{}", i);
+        let variables = json!({ "name": name, "handle": format!("author_{}", i) });
+        let body: Value = test::call_and_read_body_json(&app, call(create_user, variables)).await;
+        last_author = body["data"]["createUser"]["id"].as_str().unwrap().to_string();
+        let variables = json!({ "authorId": last_author });
+        let body: Value = test::call_and_read_body_json(&app, call(create_post, variables)).await;
+        assert!(body["errors"].is_null(), "{}", body);
+        names.push(name);
+    }
+    storage.delete_user(&last_author.into()).await.unwrap();
+    *names.last_mut().unwrap() = "退会したユーザー".to_string();
+
+    spans.reset();
+    let query = "{ posts(limit: 50) { author { name } } }";
+    let body: Value = test::call_and_read_body_json(&app, call(query, json!({}))).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let authors: Vec<&str> = body["data"]["posts"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|post| post["author"]["name"].as_str().unwrap())
+        .collect();
+    let expected: Vec<&str> = names.iter().rev().map(String::as_str).collect();
+    assert_eq!(authors, expected);
+    assert_eq!(spans.count("storage.get_users"), 1);
+    assert_eq!(spans.count("storage.get_user"), 0);
+}
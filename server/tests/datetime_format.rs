@@ -0,0 +1,55 @@
+// publishedAtのtimezone・format引数
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+#[actix_web::test]
+async fn published_at_is_rendered_in_the_requested_zone_and_format() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let login: Value = test::call_and_read_body_json(&app, req).await;
+    let token = token(&login);
+
+    // 2024-06-01T15:30:00Z（日本時間では翌日）に公開予定の下書き
+    let create = r#"
+        mutation {
+            createPost(input: {
+                title: "日時", body: "本文", authorId: "1", scheduledAt: "2024-06-01T15:30:00Z"
+            }) {
+                id
+                publishedAt
+                utc: publishedAt(timezone: "UTC")
+                jst: publishedAt(timezone: "Asia/Tokyo")
+                rfc2822: publishedAt(timezone: "Asia/Tokyo", format: RFC2822)
+                date: publishedAt(timezone: "Asia/Tokyo", format: DATE)
+                unix: publishedAt(timezone: "America/New_York", format: UNIX)
+            }
+        }
+    "#;
+    let req = graphql_request(Some(&token), create, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let post = &body["data"]["createPost"];
+    // 引数なしは従来どおりUTCのRFC 3339
+    assert_eq!(post["publishedAt"], "2024-06-01T15:30:00+00:00");
+    assert_eq!(post["utc"], "2024-06-01T15:30:00+00:00");
+    assert_eq!(post["jst"], "2024-06-02T00:30:00+09:00");
+    assert_eq!(post["rfc2822"], "Sun, 2 Jun 2024 00:30:00 +0900");
+    assert_eq!(post["date"], "2024-06-02");
+    assert_eq!(post["unix"], "1717255800");
+
+    let query = r#"
+        query Post($id: ID!) {
+            post(id: $id) { id title publishedAt(timezone: "Asia/Toky0") }
+        }
+    "#;
+    let req = graphql_request(Some(&token), query, json!({ "id": post["id"] })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["errors"][0]["extensions"]["code"], "VALIDATION_FAILED");
+    assert_eq!(body["errors"][0]["path"], json!(["post", "publishedAt"]));
+    assert!(body["errors"][0]["message"].as_str().unwrap().contains("Asia/Toky0"));
+}
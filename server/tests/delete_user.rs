@@ -0,0 +1,201 @@
+// ユーザーの削除（投稿が残っていればCONFLICT、cascadeなら投稿ごと削除）
+use actix_web::{test, App};
+use blog_server::{build_app_state, configure_app, open_database, AppStorage, MemoryStorage, Seed};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+fn error_code(body: &Value) -> &str {
+    body["errors"][0]["extensions"]["code"].as_str().unwrap_or_default()
+}
+
+// 保存先ごとにトランザクションの実装が違うので、全て同じ手順で確かめる
+async fn deletes_user_with_posts(storage: AppStorage) {
+    let state = build_app_state(storage.clone(), None).await.unwrap();
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let admin = token(&test::call_and_read_body_json(&app, req).await);
+    let call = |query: &str, variables: Value| {
+        graphql_request(Some(&admin), query, variables).to_request()
+    };
+
+    let create = r#"
+        mutation Create($title: String!) {
+            createPost(input: { title: $title, body: "本文", authorId: "2" }) { id }
+        }
+    "#;
+    let mut ids = Vec::new();
+    for title in ["残る投稿", "ゴミ箱の投稿"] {
+        let req = call(create, json!({ "title": title }));
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        ids.push(body["data"]["createPost"]["id"].as_str().unwrap().to_string());
+    }
+    let trash = r#"mutation Trash($id: ID!) { deletePost(id: $id) }"#;
+    let req = call(trash, json!({ "id": ids[1] }));
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["deletePost"], true, "{}", body);
+
+    // ゴミ箱の投稿は数えない
+    let delete = r#"
+        mutation Delete($cascade: Boolean!) { deleteUser(id: "2", cascade: $cascade) }
+    "#;
+    let req = call(delete, json!({ "cascade": false }));
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(error_code(&body), "CONFLICT", "{}", body);
+    assert_eq!(body["errors"][0]["message"], format!("User has posts: {}", ids[0]));
+    assert!(storage.get_user(&"2".into()).await.unwrap().is_some());
+
+    let req = call(delete, json!({ "cascade": true }));
+
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["deleteUser"], true, "{}", body);
+    assert!(storage.get_user(&"2".into()).await.unwrap().is_none());
+    for id in &ids {
+        assert!(storage.get_post(&id.as_str().into()).await.unwrap().is_none());
+    }
+    assert_eq!(storage.list_all_posts().await.unwrap().len(), 1);
+    // 他のユーザーのスラッグなどの索引は残る
+    let query = r#"query { post(id: "1") { author { id } } }"#;
+    let req = call(query, json!({}));
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["post"]["author"]["id"], "1", "{}", body);
+
+    let req = call(delete, json!({ "cascade": true }));
+
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["deleteUser"], false, "{}", body);
+}
+
+#[actix_web::test]
+async fn deletes_user_with_posts_in_memory() {
+    deletes_user_with_posts(Arc::new(MemoryStorage::seeded(&Seed::builtin()))).await;
+}
+
+#[actix_web::test]
+async fn deletes_user_with_posts_in_sqlite() {
+    let path = std::env::temp_dir().join(format!("blog-delete-user-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let url = format!("sqlite:{}", path.display());
+    deletes_user_with_posts(open_database(&url, &Seed::builtin()).await.unwrap()).await;
+    let _ = std::fs::remove_file(&path);
+}
+
+#[actix_web::test]
+async fn deletes_user_with_posts_in_redb() {
+    let path = std::env::temp_dir().join(format!("blog-delete-user-{}.redb", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let url = format!("redb:{}", path.display());
+    deletes_user_with_posts(open_database(&url, &Seed::builtin()).await.unwrap()).await;
+    let _ = std::fs::remove_file(&path);
+}
+
+#[actix_web::test]
+async fn deletes_users_with_zero_one_or_many_posts() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let admin = token(&test::call_and_read_body_json(&app, req).await);
+    let call = |query: &str, variables: Value| {
+        graphql_request(Some(&admin), query, variables).to_request()
+    };
+    let create = r#"
+        mutation Create($authorId: ID!) {
+            createPost(input: { title: "投稿", body: "本文", authorId: $authorId }) { id }
+        }
+    "#;
+    let delete = r#"
+        mutation Delete($id: ID!, $cascade: Boolean!) { deleteUser(id: $id, cascade: $cascade) }
+    "#;
+
+    // 3は投稿なし、4は1件、5は3件
+    let mut posts: Vec<Vec<String>> = vec![Vec::new(); 3];
+    for (author, count) in [("4", 1), ("5", 3)] {
+        for _ in 0..count {
+            let req = call(create, json!({ "authorId": author }));
+            let body: Value = test::call_and_read_body_json(&app, req).await;
+            let id = body["data"]["createPost"]["id"].as_str().unwrap().to_string();
+            posts[author.parse::<usize>().unwrap() - 3].push(id);
+        }
+    }
+
+    let req = call(delete, json!({ "id": "3", "cascade": false }));
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["deleteUser"], true, "{}", body);
+    for (author, ids) in [("4", &posts[1]), ("5", &posts[2])] {
+        let req = call(delete, json!({ "id": author, "cascade": false }));
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(error_code(&body), "CONFLICT", "{}", body);
+        let message = body["errors"][0]["message"].as_str().unwrap();
+        let mut listed: Vec<&str> =
+            message.strip_prefix("User has posts: ").unwrap().split(", ").collect();
+        listed.sort();
+        let mut expected: Vec<&str> = ids.iter().map(String::as_str).collect();
+        expected.sort();
+        assert_eq!(listed, expected);
+
+        let req = call(delete, json!({ "id": author, "cascade": true }));
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["data"]["deleteUser"], true, "{}", body);
+        for id in ids {
+            let query = r#"query Post($id: ID!) { post(id: $id) { id } }"#;
+            let req = call(query, json!({ "id": id }));
+            let body: Value = test::call_and_read_body_json(&app, req).await;
+            assert!(body["data"]["post"].is_null(), "{}", body);
+        }
+    }
+
+    // 存在しないIDはエラーではなくfalse
+    let req = call(delete, json!({ "id": "3", "cascade": false }));
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["deleteUser"], false, "{}", body);
+}
+
+#[actix_web::test]
+async fn deleted_authors_are_shown_as_a_placeholder() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let admin = token(&test::call_and_read_body_json(&app, req).await);
+    let req = login_request("佐藤太郎").to_request();
+    let sato = token(&test::call_and_read_body_json(&app, req).await);
+    let call = |token: &str, query: &str| {
+        graphql_request(Some(token), query, json!({})).to_request()
+    };
+
+    let create = r#"mutation { createPost(input: { title: "ゴミ箱へ", body: "本文" }) { id } }"#;
+    let body: Value = test::call_and_read_body_json(&app, call(&sato, create)).await;
+    let post_id = body["data"]["createPost"]["id"].as_str().unwrap().to_string();
+    let trash = format!(r#"mutation {{ deletePost(id: "{}") }}"#, post_id);
+    let body: Value = test::call_and_read_body_json(&app, call(&sato, &trash)).await;
+    assert_eq!(body["data"]["deletePost"], true, "{}", body);
+    let comment = r#"mutation { addComment(input: { postId: "1", body: "コメント" }) { id } }"#;
+    let body: Value = test::call_and_read_body_json(&app, call(&sato, comment)).await;
+    assert!(body["errors"].is_null(), "{}", body);
+
+    // 著者は参照するたびに引くので、名前の変更は既存の投稿にも反映される
+    let rename = r#"mutation { updateUser(input: { id: "2", name: "佐藤次郎" }) { id } }"#;
+    let body: Value = test::call_and_read_body_json(&app, call(&admin, rename)).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let authors = r#"
+        query {
+            trashedPosts { author { id name } }
+            post(id: "1") { comments { author { id name } } }
+        }
+    "#;
+    let body: Value = test::call_and_read_body_json(&app, call(&admin, authors)).await;
+    let renamed = json!({ "id": "2", "name": "佐藤次郎" });
+    assert_eq!(body["data"]["trashedPosts"][0]["author"], renamed, "{}", body);
+    assert_eq!(body["data"]["post"]["comments"][0]["author"], renamed, "{}", body);
+
+    // ゴミ箱の投稿だけなら削除でき、残った投稿やコメントの著者は「退会したユーザー」になる
+    let delete = r#"mutation { deleteUser(id: "2") }"#;
+    let body: Value = test::call_and_read_body_json(&app, call(&admin, delete)).await;
+    assert_eq!(body["data"]["deleteUser"], true, "{}", body);
+    let body: Value = test::call_and_read_body_json(&app, call(&admin, authors)).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let placeholder = json!({ "id": "2", "name": "退会したユーザー" });
+    assert_eq!(body["data"]["trashedPosts"][0]["author"], placeholder, "{}", body);
+    assert_eq!(body["data"]["post"]["comments"][0]["author"], placeholder, "{}", body);
+}
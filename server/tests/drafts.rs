@@ -0,0 +1,137 @@
+// 公開前の投稿（includeDrafts・trashedPosts・scheduledPostsは著者と管理者のみで、著者は自分の投稿だけ）
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+const CREATE: &str = r#"
+    mutation Create($title: String!, $draft: Boolean, $at: DateTimeScalar) {
+        createPost(input: { title: $title, body: "本文", draft: $draft, scheduledAt: $at }) {
+            id slug
+        }
+    }
+"#;
+
+const UNPUBLISHED: &str = r#"
+    query Unpublished($id: ID!, $slug: String!) {
+        posts(includeDrafts: true, limit: 100) { title }
+        postsCount(includeDrafts: true)
+        post(id: $id, includeDrafts: true) { title }
+        postBySlug(slug: $slug, includeDrafts: true) { title }
+        trashedPosts { title }
+        scheduledPosts { title }
+    }
+"#;
+
+fn error_code(body: &Value) -> &str {
+    body["errors"][0]["extensions"]["code"].as_str().unwrap_or_default()
+}
+
+fn titles(posts: &Value) -> Vec<&str> {
+    let posts = posts.as_array().unwrap();
+    posts.iter().map(|post| post["title"].as_str().unwrap()).collect()
+}
+
+#[actix_web::test]
+async fn only_authors_and_admins_see_unpublished_posts() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let mut tokens = Vec::new();
+    for name in ["髙橋慶祐", "佐藤太郎", "鈴木花子"] {
+        let req = login_request(name).to_request();
+        tokens.push(token(&test::call_and_read_body_json(&app, req).await));
+    }
+    let register = r#"
+        mutation {
+            register(name: "読者", handle: "reader", password: "correct horse") { id }
+            login(name: "読者", password: "correct horse") { token }
+        }
+    "#;
+    let req = graphql_request(None, register, json!({})).to_request();
+    let reader = token(&test::call_and_read_body_json(&app, req).await);
+
+    // 佐藤さんと鈴木さんがそれぞれ下書き・予約投稿・ゴミ箱の投稿を持つ
+    let mut drafts = Vec::new();
+    for (token, name) in [(&tokens[1], "佐藤"), (&tokens[2], "鈴木")] {
+        let posts = [
+            (format!("{}の下書き", name), json!(true), json!(null)),
+            (format!("{}の予約", name), json!(null), json!("2099-01-01T00:00:00Z")),
+            (format!("{}のゴミ箱", name), json!(null), json!(null)),
+        ];
+        let mut created = Vec::new();
+        for (title, draft, at) in posts {
+            let variables = json!({ "title": title, "draft": draft, "at": at });
+            let req = graphql_request(Some(token), CREATE, variables).to_request();
+            let body: Value = test::call_and_read_body_json(&app, req).await;
+            assert!(body["errors"].is_null(), "{}", body);
+            created.push(body["data"]["createPost"].clone());
+        }
+        let trash = format!(r#"mutation {{ deletePost(id: {}) }}"#, created[2]["id"]);
+        let req = graphql_request(Some(token), &trash, json!({})).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["data"]["deletePost"], true, "{}", body);
+        drafts.push(created.swap_remove(0));
+    }
+    let req = graphql_request(None, "{ postsCount }", json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let published = body["data"]["postsCount"].as_u64().unwrap();
+
+    // 未ログインは認証が必要、読者は権限がない
+    let fields = [
+        "posts(includeDrafts: true) { title }",
+        "postsCount(includeDrafts: true)",
+        "post(id: \"1\", includeDrafts: true) { title }",
+        "postBySlug(slug: \"hajimemashite\", includeDrafts: true) { title }",
+        "trashedPosts { title }",
+        "scheduledPosts { title }",
+    ];
+    for field in fields {
+        let query = format!("{{ {} }}", field);
+        let req = graphql_request(None, &query, json!({})).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(error_code(&body), "UNAUTHENTICATED", "{}: {}", field, body);
+        let req = graphql_request(Some(&reader), &query, json!({})).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(error_code(&body), "FORBIDDEN", "{}: {}", field, body);
+    }
+    // includeDraftsを付けなければ、下書きは誰にも見つからない
+    let query = format!(r#"{{ post(id: {}) {{ title }} }}"#, drafts[0]["id"]);
+    let req = graphql_request(None, &query, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(error_code(&body), "NOT_FOUND", "{}", body);
+
+    // 著者には自分の下書き・予約・ゴミ箱の投稿だけを返す
+    let unpublished = |token: &str, draft: &Value| {
+        let variables = json!({ "id": draft["id"], "slug": draft["slug"] });
+        graphql_request(Some(token), UNPUBLISHED, variables).to_request()
+    };
+    let req = unpublished(&tokens[1], &drafts[0]);
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let posts = titles(&body["data"]["posts"]);
+    assert!(posts.contains(&"佐藤の下書き") && posts.contains(&"佐藤の予約"), "{}", body);
+    assert!(!posts.iter().any(|title| title.starts_with("鈴木")), "{}", body);
+    assert_eq!(body["data"]["postsCount"], published + 2);
+    assert_eq!(body["data"]["post"]["title"], "佐藤の下書き");
+    assert_eq!(body["data"]["postBySlug"]["title"], "佐藤の下書き");
+    assert_eq!(titles(&body["data"]["trashedPosts"]), ["佐藤のゴミ箱"]);
+    assert_eq!(titles(&body["data"]["scheduledPosts"]), ["佐藤の予約"]);
+    let req = unpublished(&tokens[1], &drafts[1]);
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(error_code(&body), "NOT_FOUND", "{}", body);
+    assert!(body["data"]["postBySlug"].is_null(), "{}", body);
+
+    // 管理者には全員分を返す
+    let req = unpublished(&tokens[0], &drafts[1]);
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    assert_eq!(body["data"]["postsCount"], published + 4);
+    assert_eq!(body["data"]["post"]["title"], "鈴木の下書き");
+    assert_eq!(body["data"]["postBySlug"]["title"], "鈴木の下書き");
+    let mut trashed = titles(&body["data"]["trashedPosts"]);
+    trashed.sort();
+    assert_eq!(trashed, ["佐藤のゴミ箱", "鈴木のゴミ箱"]);
+    assert_eq!(body["data"]["scheduledPosts"].as_array().unwrap().len(), 2, "{}", body);
+}
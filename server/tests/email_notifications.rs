@@ -0,0 +1,135 @@
+// コメントのメールでの通知（notificationSettings / updateNotificationSettings とSMTPでの送信）
+// 環境変数を書き換えるので、このファイルのテストは1つにまとめる
+use actix_web::{test, App};
+use blog_server::{configure_app, spawn_background_tasks};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+mod common;
+use common::{app_state, decode_quoted_printable, graphql_request, login_request, serve_smtp, token};
+
+const SETTINGS: &str = r#"
+    query Settings($id: ID!) { user(id: $id) { notificationSettings { emailOnComment } } }
+"#;
+
+const UPDATE_SETTINGS: &str = r#"
+    mutation Update($userId: ID!, $enabled: Boolean) {
+        updateNotificationSettings(input: { userId: $userId, emailOnComment: $enabled }) {
+            emailOnComment
+        }
+    }
+"#;
+
+const ADD_COMMENT: &str = r#"
+    mutation Add($postId: ID!, $authorId: ID!, $body: String!) {
+        addComment(input: { postId: $postId, authorId: $authorId, body: $body }) { id }
+    }
+"#;
+
+#[actix_web::test]
+async fn emails_post_authors_who_opted_in() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let received = Arc::new(Mutex::new(Vec::new()));
+    tokio::spawn(serve_smtp(listener, received.clone()));
+
+    let vars = [
+        ("SMTP_HOST", "127.0.0.1".to_string()),
+        ("SMTP_PORT", port.to_string()),
+        ("SMTP_TLS", "none".to_string()),
+        ("SMTP_FROM", "Blog <blog@example.com>".to_string()),
+    ];
+    for (name, value) in &vars {
+        std::env::set_var(name, value);
+    }
+    let state = app_state().await;
+    for (name, _) in &vars {
+        std::env::remove_var(name);
+    }
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let background_tasks = spawn_background_tasks(&state);
+
+    let req = login_request("髙橋慶祐").to_request();
+    let admin = token(&test::call_and_read_body_json(&app, req).await);
+    let req = login_request("佐藤太郎").to_request();
+    let sato = token(&test::call_and_read_body_json(&app, req).await);
+    let call = |token: &str, query: &str, variables: Value| {
+        graphql_request(Some(token), query, variables).to_request()
+    };
+
+    // 通知の設定は本人だけが参照・変更でき、デフォルトでは送らない
+    let req = call(&admin, SETTINGS, json!({ "id": "1" }));
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["user"]["notificationSettings"]["emailOnComment"], false, "{}", body);
+    let req = call(&sato, SETTINGS, json!({ "id": "1" }));
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["errors"][0]["extensions"]["code"], "FORBIDDEN", "{}", body);
+    let variables = json!({ "userId": "1", "enabled": true });
+    let req = call(&sato, UPDATE_SETTINGS, variables.clone());
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["errors"][0]["extensions"]["code"], "FORBIDDEN", "{}", body);
+
+    let create = r#"
+        mutation { createPost(input: { title: "Hello SMTP", body: "Body", authorId: "1" }) { id } }
+    "#;
+    let body: Value = test::call_and_read_body_json(&app, call(&admin, create, json!({}))).await;
+    let post_id = body["data"]["createPost"]["id"].as_str().unwrap().to_string();
+    let register = r#"
+        mutation { register(name: "Alice", handle: "alice", password: "password123") { id } }
+    "#;
+    let body: Value = test::call_and_read_body_json(&app, call(&admin, register, json!({}))).await;
+    let alice_id = body["data"]["register"]["id"].as_str().unwrap().to_string();
+    let add = |author_id: &str, body: &str| {
+        let variables = json!({ "postId": post_id, "authorId": author_id, "body": body });
+        // 管理者は他のユーザーとしてコメントできる
+        graphql_request(Some(&admin), ADD_COMMENT, variables).to_request()
+    };
+
+    // 無効のままならコメントされても送らない
+    let body: Value = test::call_and_read_body_json(&app, add(&alice_id, "Before")).await;
+    assert!(body["errors"].is_null(), "{}", body);
+
+    let update = r#"
+        mutation { updateUser(input: { id: "1", email: "owner@example.com" }) { id } }
+    "#;
+    test::call_service(&app, call(&admin, update, json!({}))).await;
+    let req = call(&admin, UPDATE_SETTINGS, variables);
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["updateNotificationSettings"]["emailOnComment"], true, "{}", body);
+    // 省略した設定は変えない
+    let req = call(&admin, UPDATE_SETTINGS, json!({ "userId": "1" }));
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["updateNotificationSettings"]["emailOnComment"], true, "{}", body);
+
+    // 自分のコメントでは送らない
+    let body: Value = test::call_and_read_body_json(&app, add("1", "Self")).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let req = add(&alice_id, "Nice post & thanks");
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+
+    for _ in 0..200 {
+        if !received.lock().unwrap().is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(25)).await;
+    }
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let received = received.lock().unwrap().clone();
+    assert_eq!(received.len(), 1, "{:?}", received);
+    let mail = &decode_quoted_printable(&received[0]);
+    assert!(mail.contains("MAIL FROM:<blog@example.com>"), "{}", mail);
+    assert!(mail.contains("RCPT TO:<owner@example.com>"), "{}", mail);
+    assert!(mail.contains("Subject: New comment on \"Hello SMTP\""), "{}", mail);
+    assert!(mail.contains("multipart/alternative"), "{}", mail);
+    assert!(mail.contains("Alice commented on \"Hello SMTP\":"), "{}", mail);
+    assert!(mail.contains("\nNice post & thanks\n"), "{}", mail);
+    assert!(mail.contains("http://localhost:8000/posts/hello-smtp"), "{}", mail);
+    let link = r#"<a href="http://localhost:8000/posts/hello-smtp">Hello SMTP</a>"#;
+    assert!(mail.contains(link), "{}", mail);
+    assert!(mail.contains("<blockquote>Nice post &amp; thanks</blockquote>"), "{}", mail);
+
+    background_tasks.shutdown().await;
+}
@@ -0,0 +1,88 @@
+// Post.excerpt（記法を除いた本文の抜粋）
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+// 本文から投稿を作り、lengthsの長さ（Noneは省略時）の抜粋を取得するリクエスト
+fn create_request(token: &str, body: &str, lengths: &[Option<i32>]) -> test::TestRequest {
+    let fields: Vec<String> = lengths
+        .iter()
+        .enumerate()
+        .map(|(i, length)| match length {
+            Some(length) => format!("e{}: excerpt(length: {})", i, length),
+            None => format!("e{}: excerpt", i),
+        })
+        .collect();
+    let create = format!(
+        r#"mutation Create($body: String!) {{
+            createPost(input: {{ title: "抜粋", body: $body, authorId: "1" }}) {{ {} }}
+        }}"#,
+        fields.join(" ")
+    );
+    graphql_request(Some(token), &create, json!({ "body": body }))
+}
+
+fn excerpts(response: &Value) -> Vec<&str> {
+    assert!(response["errors"].is_null(), "{}", response);
+    let post = response["data"]["createPost"].as_object().unwrap();
+    post.values().map(|v| v.as_str().unwrap()).collect()
+}
+
+#[actix_web::test]
+async fn excerpts_japanese_english_and_mixed_text() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let token = token(&test::call_and_read_body_json(&app, req).await);
+
+    // 日本語は文字単位で切る（コードポイントの途中では切らない）
+    let japanese = "# 見出し\n\n吾輩は**猫**である。名前はまだ無い。";
+    let req = create_request(&token, japanese, &[Some(8), Some(100)]).to_request();
+    let response: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(excerpts(&response), ["見出し 吾輩は猫…", "見出し 吾輩は猫である。名前はまだ無い。"]);
+
+    // 英語は単語の途中で切らない
+    let english = "The *quick* brown fox jumps over the [lazy](https://example.com) dog.";
+    let req = create_request(&token, english, &[Some(12), Some(15), Some(100)]).to_request();
+    let response: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(
+        excerpts(&response),
+        ["The quick…", "The quick brown…", "The quick brown fox jumps over the lazy dog."]
+    );
+
+    let mixed = "Rustで`async fn`を書く。\n\n- 項目1\n- 項目2";
+    let req = create_request(&token, mixed, &[Some(13), None]).to_request();
+    let response: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(excerpts(&response), ["Rustでasync fn…", "Rustでasync fnを書く。 項目1 項目2"]);
+
+    // 区切りのない長い英単語は文字数で切る
+    let long_word = "a".repeat(50);
+    let req = create_request(&token, &long_word, &[Some(30)]).to_request();
+    let response: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(excerpts(&response), [format!("{}…", "a".repeat(30))]);
+}
+
+#[actix_web::test]
+async fn more_marker_and_length_clamping() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let token = token(&test::call_and_read_body_json(&app, req).await);
+
+    // <!--more-->より前は長さに関係なく全体を返す
+    let body = "ここまでが**抜粋**です。\n\n<!--more-->\n\n続きの本文";
+    let req = create_request(&token, body, &[Some(3)]).to_request();
+    let response: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(excerpts(&response), ["ここまでが抜粋です。"]);
+
+    let body = "あ".repeat(2000);
+    let lengths = [Some(0), Some(-5), Some(i32::MAX), None];
+    let req = create_request(&token, &body, &lengths).to_request();
+    let response: Value = test::call_and_read_body_json(&app, req).await;
+    let counts: Vec<usize> = excerpts(&response).iter().map(|e| e.chars().count()).collect();
+    assert_eq!(counts, [2, 2, 1001, 201]);
+    assert!(excerpts(&response)[0].ends_with('…'));
+}
@@ -0,0 +1,134 @@
+// RSS（/feed.rss）、Atom（/feed.atom）、JSON Feed（/feed.json）のフィード
+use actix_web::http::header;
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+const CREATE: &str = r#"
+    mutation Create($title: String!, $draft: Boolean) {
+        createPost(input: {
+            title: $title, body: "**本文** & <続き>", tags: ["Rust"], authorId: "1", draft: $draft
+        }) { id }
+    }
+"#;
+
+#[actix_web::test]
+async fn feeds_list_published_posts() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let token = token(&test::call_and_read_body_json(&app, req).await);
+
+    for (title, draft) in [("公開した<投稿>", false), ("下書き", true)] {
+        let variables = json!({ "title": title, "draft": draft });
+        let req = graphql_request(Some(&token), CREATE, variables).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert!(body["errors"].is_null(), "{}", body);
+    }
+
+    let req = test::TestRequest::get().uri("/feed.rss").to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 200);
+    let content_type = res.headers().get(header::CONTENT_TYPE).unwrap();
+    assert_eq!(content_type, "application/rss+xml; charset=utf-8");
+    assert!(res.headers().contains_key(header::LAST_MODIFIED));
+    let rss = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    assert!(rss.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>"), "{}", rss);
+    assert!(rss.contains("<title>公開した&lt;投稿&gt;</title>"), "{}", rss);
+    assert!(rss.contains("<link>http://localhost:8000/posts/post-"), "{}", rss);
+    assert!(rss.contains("<dc:creator>髙橋慶祐</dc:creator>"), "{}", rss);
+    assert!(rss.contains("<category>rust</category>"), "{}", rss);
+    assert!(rss.contains("<description>本文 &amp; &lt;続き&gt;</description>"), "{}", rss);
+    assert!(rss.contains("<content:encoded>&lt;p&gt;&lt;strong&gt;本文"), "{}", rss);
+    // 初期データの投稿も載り、下書きは載らない
+    assert_eq!(rss.matches("<item>").count(), 2);
+    assert!(!rss.contains("下書き"), "{}", rss);
+
+    let req = test::TestRequest::get().uri("/feed.atom").to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 200);
+    let content_type = res.headers().get(header::CONTENT_TYPE).unwrap();
+    assert_eq!(content_type, "application/atom+xml; charset=utf-8");
+    let atom = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    assert!(atom.contains("<feed xmlns=\"http://www.w3.org/2005/Atom\">"), "{}", atom);
+    assert!(atom.contains("<author><name>髙橋慶祐</name>"), "{}", atom);
+    assert!(atom.contains("<category term=\"rust\"/>"), "{}", atom);
+    assert!(atom.contains("<content type=\"html\">&lt;p&gt;"), "{}", atom);
+    assert_eq!(atom.matches("<entry>").count(), 2);
+    assert!(!atom.contains("下書き"), "{}", atom);
+}
+
+#[actix_web::test]
+async fn feed_supports_conditional_get() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+
+    let req = test::TestRequest::get().uri("/feed.atom").to_request();
+    let res = test::call_service(&app, req).await;
+    let last_modified = res.headers().get(header::LAST_MODIFIED).unwrap().clone();
+
+    let req = test::TestRequest::get()
+        .uri("/feed.atom")
+        .insert_header((header::IF_MODIFIED_SINCE, last_modified.clone()))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 304);
+    assert!(test::read_body(res).await.is_empty());
+
+    // それより前の日時なら本文を返す
+    let req = test::TestRequest::get()
+        .uri("/feed.rss")
+        .insert_header((header::IF_MODIFIED_SINCE, "Mon, 01 Jan 2024 00:00:00 GMT"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 200);
+}
+
+#[actix_web::test]
+async fn json_feed_filters_by_tag() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let token = token(&test::call_and_read_body_json(&app, req).await);
+    for (title, draft) in [("Rustの投稿", false), ("Rustの下書き", true)] {
+        let variables = json!({ "title": title, "draft": draft });
+        let req = graphql_request(Some(&token), CREATE, variables).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert!(body["errors"].is_null(), "{}", body);
+    }
+
+    let req = test::TestRequest::get().uri("/feed.json?tag=RUST").to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 200);
+    let content_type = res.headers().get(header::CONTENT_TYPE).unwrap();
+    assert_eq!(content_type, "application/feed+json");
+    let feed: Value = test::read_body_json(res).await;
+    assert_eq!(feed["version"], "https://jsonfeed.org/version/1.1");
+    assert_eq!(feed["feed_url"], "http://localhost:8000/feed.json?tag=RUST");
+    let items = feed["items"].as_array().unwrap();
+    assert_eq!(items.len(), 1);
+    let item = &items[0];
+    assert_eq!(item["title"], "Rustの投稿");
+    assert!(item["url"].as_str().unwrap().starts_with("http://localhost:8000/posts/"));
+    assert_eq!(item["id"], item["url"]);
+    assert!(item["content_html"].as_str().unwrap().starts_with("<p><strong>本文</strong>"));
+    assert!(item["date_published"].as_str().unwrap().ends_with('Z'), "{}", item);
+    assert_eq!(
+        item["authors"],
+        json!([{ "name": "髙橋慶祐", "avatar": "https://example.com/avatar.png" }])
+    );
+    assert_eq!(item["tags"], json!(["rust"]));
+
+    // タグを指定しなければ全ての公開済みの投稿、知らないタグなら空
+    let req = test::TestRequest::get().uri("/feed.json").to_request();
+    let feed: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(feed["items"].as_array().unwrap().len(), 2);
+    let req = test::TestRequest::get().uri("/feed.json?tag=unknown").to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 200);
+    let feed: Value = test::read_body_json(res).await;
+    assert_eq!(feed["items"], json!([]));
+}
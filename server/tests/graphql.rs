@@ -0,0 +1,384 @@
+// /api/graphql にリクエストを送って、スキーマとハンドラーを通した結果を確認する
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+const POST_TITLES: &str = "{ posts(limit: 100) { id title } }";
+
+fn titles(body: &Value) -> Vec<&str> {
+    body["data"]["posts"]
+        .as_array()
+        .expect("posts should be a list")
+        .iter()
+        .map(|post| post["title"].as_str().unwrap())
+        .collect()
+}
+
+#[actix_web::test]
+async fn posts_returns_seeded_posts() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+
+    let req = graphql_request(None, POST_TITLES, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+
+    assert!(body["errors"].is_null(), "unexpected errors: {}", body["errors"]);
+    assert!(!titles(&body).is_empty());
+}
+
+#[actix_web::test]
+async fn create_post_requires_authentication() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+
+    let query = r#"
+        mutation {
+            createPost(input: { title: "テスト", body: "本文", authorId: "1" }) { id }
+        }
+    "#;
+    let req = graphql_request(None, query, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+
+    assert_eq!(body["errors"][0]["extensions"]["code"], "UNAUTHENTICATED");
+}
+
+#[actix_web::test]
+async fn create_and_delete_post() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+
+    let req = login_request("髙橋慶祐").to_request();
+    let login: Value = test::call_and_read_body_json(&app, req).await;
+    let token = token(&login);
+    let author_id = login["data"]["login"]["user"]["id"].clone();
+
+    let create = r#"
+        mutation Create($input: CreatePostInput!) {
+            createPost(input: $input) { id title tags author { id } }
+        }
+    "#;
+    let variables = json!({
+        "input": {
+            "title": "結合テスト",
+            "body": "本文",
+            "tags": ["テスト"],
+            "authorId": author_id,
+        }
+    });
+    let req = graphql_request(Some(&token), create, variables).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "unexpected errors: {}", body["errors"]);
+    let post = &body["data"]["createPost"];
+    assert_eq!(post["title"], "結合テスト");
+    assert_eq!(post["tags"], json!(["テスト"]));
+    assert_eq!(post["author"]["id"], author_id);
+    let post_id = post["id"].clone();
+
+    let req = graphql_request(None, POST_TITLES, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(titles(&body).contains(&"結合テスト"));
+
+    let delete = "mutation Delete($id: ID!) { deletePost(id: $id) }";
+    let req = graphql_request(Some(&token), delete, json!({ "id": post_id })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["deletePost"], true);
+
+    let req = graphql_request(None, POST_TITLES, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(!titles(&body).contains(&"結合テスト"));
+}
+
+fn error_code(body: &Value) -> &str {
+    body["errors"][0]["extensions"]["code"].as_str().unwrap_or_default()
+}
+
+#[actix_web::test]
+async fn created_user_can_author_posts() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let admin = token(&test::call_and_read_body_json(&app, req).await);
+    let create_user = r#"
+        mutation Create($name: String!, $avatarUrl: Url) {
+            createUser(input: { name: $name, handle: "new_author", avatarUrl: $avatarUrl }) {
+                id name avatarUrl
+            }
+        }
+    "#;
+    let call = |variables: Value| graphql_request(Some(&admin), create_user, variables);
+
+    // 空白だけの名前とhttp(s)以外のURLは受け付けない
+    let req = call(json!({ "name": "  " })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(error_code(&body), "VALIDATION_FAILED", "{}", body);
+    let req = call(json!({ "name": "新しい著者", "avatarUrl": "ftp://example.com/a.png" }));
+    let body: Value = test::call_and_read_body_json(&app, req.to_request()).await;
+    assert!(body["data"].is_null(), "{}", body);
+
+    let req = call(json!({ "name": " 新しい著者 ", "avatarUrl": "https://example.com/a.png" }));
+    let body: Value = test::call_and_read_body_json(&app, req.to_request()).await;
+    let user = &body["data"]["createUser"];
+    assert_eq!(user["name"], "新しい著者", "{}", body);
+    assert_eq!(user["avatarUrl"], "https://example.com/a.png");
+    let user_id = user["id"].as_str().unwrap();
+
+    let create_post = r#"
+        mutation Create($authorId: ID!) {
+            createPost(input: { title: "初投稿", body: "本文", authorId: $authorId }) {
+                author { id name }
+            }
+        }
+    "#;
+    let req = graphql_request(Some(&admin), create_post, json!({ "authorId": user_id }));
+    let body: Value = test::call_and_read_body_json(&app, req.to_request()).await;
+    let author = &body["data"]["createPost"]["author"];
+    assert_eq!(*author, json!({ "id": user_id, "name": "新しい著者" }), "{}", body);
+}
+
+#[actix_web::test]
+async fn create_post_takes_the_author_from_the_viewer() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let admin = token(&test::call_and_read_body_json(&app, req).await);
+    let req = login_request("佐藤太郎").to_request();
+    let sato = token(&test::call_and_read_body_json(&app, req).await);
+    let create = |token: &str, author_id: Value| {
+        let query = r#"
+            mutation Create($authorId: ID) {
+                createPost(input: { title: "著者", body: "本文", authorId: $authorId }) {
+                    author { id }
+                }
+            }
+        "#;
+        graphql_request(Some(token), query, json!({ "authorId": author_id })).to_request()
+    };
+
+    for author_id in [json!(null), json!("2")] {
+        let body: Value = test::call_and_read_body_json(&app, create(&sato, author_id)).await;
+        assert_eq!(body["data"]["createPost"]["author"]["id"], "2", "{}", body);
+    }
+    // 他のユーザーの投稿として作成できるのは管理者だけ
+    let body: Value = test::call_and_read_body_json(&app, create(&sato, json!("3"))).await;
+    assert_eq!(error_code(&body), "FORBIDDEN", "{}", body);
+    let body: Value = test::call_and_read_body_json(&app, create(&admin, json!("3"))).await;
+    assert_eq!(body["data"]["createPost"]["author"]["id"], "3", "{}", body);
+    let body: Value = test::call_and_read_body_json(&app, create(&admin, json!(null))).await;
+    assert_eq!(body["data"]["createPost"]["author"]["id"], "1", "{}", body);
+}
+
+#[actix_web::test]
+async fn only_the_author_or_an_admin_can_modify_a_post() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let mut tokens = Vec::new();
+    for name in ["髙橋慶祐", "佐藤太郎", "鈴木花子"] {
+        let req = login_request(name).to_request();
+        tokens.push(token(&test::call_and_read_body_json(&app, req).await));
+    }
+    let (admin, owner, other) = (&tokens[0], &tokens[1], &tokens[2]);
+    let create = r#"mutation { createPost(input: { title: "佐藤の投稿", body: "本文" }) { id } }"#;
+    let mut post_ids = Vec::new();
+    for _ in 0..2 {
+        let req = graphql_request(Some(owner), create, json!({})).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        post_ids.push(body["data"]["createPost"]["id"].as_str().unwrap().to_string());
+    }
+    let update = |token: Option<&str>, id: &str, title: &str| {
+        let query = r#"
+            mutation Update($id: ID!, $title: String!) {
+                updatePost(input: { id: $id, title: $title }) { title }
+            }
+        "#;
+        graphql_request(token, query, json!({ "id": id, "title": title })).to_request()
+    };
+    let delete = |token: Option<&str>, id: &str| {
+        let query = "mutation Delete($id: ID!) { deletePost(id: $id) }";
+        graphql_request(token, query, json!({ "id": id })).to_request()
+    };
+
+    // 未ログインと他の著者
+    let body: Value = test::call_and_read_body_json(&app, update(None, &post_ids[0], "x")).await;
+    assert_eq!(error_code(&body), "UNAUTHENTICATED", "{}", body);
+    let body: Value = test::call_and_read_body_json(&app, delete(None, &post_ids[0])).await;
+    assert_eq!(error_code(&body), "UNAUTHENTICATED", "{}", body);
+    let body: Value =
+        test::call_and_read_body_json(&app, update(Some(other), &post_ids[0], "x")).await;
+    assert_eq!(error_code(&body), "FORBIDDEN", "{}", body);
+    let body: Value = test::call_and_read_body_json(&app, delete(Some(other), &post_ids[0])).await;
+    assert_eq!(error_code(&body), "FORBIDDEN", "{}", body);
+
+    // 著者本人と管理者
+    let body: Value =
+        test::call_and_read_body_json(&app, update(Some(owner), &post_ids[0], "本人が更新")).await;
+    assert_eq!(body["data"]["updatePost"]["title"], "本人が更新", "{}", body);
+    let body: Value =
+        test::call_and_read_body_json(&app, update(Some(admin), &post_ids[0], "管理者が更新")).await;
+    assert_eq!(body["data"]["updatePost"]["title"], "管理者が更新", "{}", body);
+    let body: Value = test::call_and_read_body_json(&app, delete(Some(owner), &post_ids[0])).await;
+    assert_eq!(body["data"]["deletePost"], true, "{}", body);
+    let body: Value = test::call_and_read_body_json(&app, delete(Some(admin), &post_ids[1])).await;
+    assert_eq!(body["data"]["deletePost"], true, "{}", body);
+
+    // ゴミ箱にある自分の投稿を削除してもFORBIDDENではなくfalse
+    let body: Value = test::call_and_read_body_json(&app, delete(Some(owner), &post_ids[0])).await;
+    assert_eq!(body["data"]["deletePost"], false, "{}", body);
+    assert!(body["errors"].is_null(), "{}", body);
+}
+
+#[actix_web::test]
+async fn rejected_update_leaves_the_post_unchanged() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let admin = token(&test::call_and_read_body_json(&app, req).await);
+    let query = r#"
+        query {
+            post(id: "1") { title body tags updatedAt }
+            postRevision(postId: "1", revision: 1) { title }
+        }
+    "#;
+    let req = graphql_request(None, query, json!({})).to_request();
+    let before: Value = test::call_and_read_body_json(&app, req).await;
+
+    // 公開済みの投稿は予約できない。他のフィールドも含めて何も変えない
+    let update = r#"
+        mutation {
+            updatePost(input: {
+                id: "1", title: "変更", body: "変更", tags: ["変更"],
+                scheduledAt: "2099-01-01T00:00:00Z"
+            }) { id }
+        }
+    "#;
+    let req = graphql_request(Some(&admin), update, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(error_code(&body), "CONFLICT", "{}", body);
+    assert_eq!(body["errors"][0]["message"], "Post is already published");
+
+    let req = graphql_request(None, query, json!({})).to_request();
+    let after: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(after, before);
+    assert!(after["data"]["postRevision"].is_null(), "{}", after);
+}
+
+#[actix_web::test]
+async fn delete_post_rejects_malformed_and_unknown_ids() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+
+    let req = login_request("髙橋慶祐").to_request();
+    let token = token(&test::call_and_read_body_json(&app, req).await);
+
+    let delete = r#"mutation { deletePost(id: "no-such-post") }"#;
+    let req = graphql_request(Some(&token), delete, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["errors"][0]["extensions"]["code"], "INVALID_ID");
+    assert_eq!(body["errors"][0]["extensions"]["argument"], "id");
+
+    let delete = r#"mutation { deletePost(id: "7f1c6a52-3a7e-4c55-9d0e-2b8f6a4c1e90") }"#;
+    let req = graphql_request(Some(&token), delete, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["errors"][0]["extensions"]["code"], "NOT_FOUND");
+    assert_eq!(body["errors"][0]["extensions"]["entity"], "Post");
+}
+
+#[actix_web::test]
+async fn lookups_distinguish_malformed_and_missing_ids() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+
+    let query = r#"{ post(id: "definitely-not-a-uuid") { id } }"#;
+    let req = graphql_request(None, query, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["data"]["post"].is_null());
+    assert_eq!(body["errors"][0]["extensions"]["code"], "INVALID_ID");
+
+    let query = r#"{ post(id: "999") { id } user(id: "999") { id } seeded: user(id: "1") { id } }"#;
+    let req = graphql_request(None, query, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["seeded"]["id"], "1");
+    let entities: Vec<&Value> = body["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| {
+            assert_eq!(e["extensions"]["code"], "NOT_FOUND");
+            &e["extensions"]["entity"]
+        })
+        .collect();
+    assert_eq!(entities, [&json!("Post"), &json!("User")]);
+
+    let login = login_request("髙橋慶祐").to_request();
+    let token = token(&test::call_and_read_body_json(&app, login).await);
+    let create = r#"
+        mutation Create($authorId: ID!) {
+            createPost(input: { title: "著者", body: "本文", authorId: $authorId }) { id }
+        }
+    "#;
+    let req = graphql_request(Some(&token), create, json!({ "authorId": "nobody" })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["errors"][0]["extensions"]["code"], "INVALID_ID");
+    assert_eq!(body["errors"][0]["extensions"]["argument"], "input.authorId");
+
+    let req = graphql_request(Some(&token), create, json!({ "authorId": "42" })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["errors"][0]["extensions"]["code"], "NOT_FOUND");
+    assert_eq!(body["errors"][0]["extensions"]["entity"], "User");
+}
+
+fn get_request(query: &str, operation_name: Option<&str>) -> test::TestRequest {
+    let mut params = url::form_urlencoded::Serializer::new(String::new());
+    params.append_pair("query", query);
+    if let Some(operation_name) = operation_name {
+        params.append_pair("operationName", operation_name);
+    }
+    test::TestRequest::get().uri(&format!("/api/graphql?{}", params.finish()))
+}
+
+#[actix_web::test]
+async fn get_accepts_queries() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+
+    let req = get_request(POST_TITLES, None).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(titles(&body), vec!["はじめまして"]);
+}
+
+#[actix_web::test]
+async fn get_rejects_mutations() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+
+    let delete = r#"mutation { deletePost(id: "1") }"#;
+    let res = test::call_service(&app, get_request(delete, None).to_request()).await;
+    assert_eq!(res.status(), 405);
+    assert_eq!(res.headers().get("Allow").unwrap(), "POST");
+
+    let req = graphql_request(None, POST_TITLES, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(titles(&body), vec!["はじめまして"]);
+}
+
+#[actix_web::test]
+async fn get_judges_the_selected_operation() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let document = r#"
+        query Titles { posts(limit: 100) { id title } }
+        mutation Delete { deletePost(id: "1") }
+    "#;
+
+    let req = get_request(document, Some("Titles")).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(titles(&body), vec!["はじめまして"]);
+
+    let req = get_request(document, Some("Delete")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 405);
+    // operationNameがなければどちらを実行するか決まらないので、ミューテーションを含むものは拒否する
+    let req = get_request(document, None).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 405);
+}
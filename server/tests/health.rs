@@ -0,0 +1,29 @@
+// /healthz と /readyz
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::Value;
+
+mod common;
+use common::app_state;
+
+#[actix_web::test]
+async fn healthz_reports_version() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = test::TestRequest::get().uri("/healthz").to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["status"], "ok");
+    assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+    assert!(body["uptime_secs"].is_u64());
+}
+
+#[actix_web::test]
+async fn readyz_checks_storage() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = test::TestRequest::get().uri("/readyz").to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 200);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["status"], "ready");
+}
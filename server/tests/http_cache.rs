@@ -0,0 +1,70 @@
+// GETのクエリのETagとIf-None-Match（304）
+use actix_web::http::header;
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+const POSTS_COUNT: &str = "/api/graphql?query=%7B%20postsCount%20%7D";
+
+fn get(if_none_match: Option<&str>) -> test::TestRequest {
+    let mut req = test::TestRequest::get().uri(POSTS_COUNT);
+    if let Some(etag) = if_none_match {
+        req = req.insert_header((header::IF_NONE_MATCH, etag));
+    }
+    req
+}
+
+#[actix_web::test]
+async fn etag_changes_after_mutation() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let login: Value = test::call_and_read_body_json(&app, req).await;
+    let token = token(&login);
+
+    let res = test::call_service(&app, get(None).to_request()).await;
+    assert_eq!(res.status(), 200);
+    let etag = res.headers().get(header::ETAG).unwrap().to_str().unwrap().to_string();
+    assert!(etag.starts_with("W/\""), "{}", etag);
+    let cache_control = res.headers().get(header::CACHE_CONTROL).unwrap();
+    assert_eq!(cache_control, "private, max-age=0, must-revalidate");
+
+    let res = test::call_service(&app, get(Some(&etag)).to_request()).await;
+    assert_eq!(res.status(), 304);
+    assert!(test::read_body(res).await.is_empty());
+
+    let create = r#"
+        mutation { createPost(input: { title: "キャッシュ", body: "本文", authorId: "1" }) { id } }
+    "#;
+    let req = graphql_request(Some(&token), create, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+
+    let res = test::call_service(&app, get(Some(&etag)).to_request()).await;
+    assert_eq!(res.status(), 200);
+    let new_etag = res.headers().get(header::ETAG).unwrap().to_str().unwrap().to_string();
+    assert_ne!(new_etag, etag);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["data"]["postsCount"], 2);
+}
+
+#[actix_web::test]
+async fn post_and_mutations_are_not_cached() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+
+    let req = graphql_request(None, "{ postsCount }", json!({})).to_request();
+    let res = test::call_service(&app, req).await;
+    assert!(res.headers().get(header::ETAG).is_none());
+
+    let req = test::TestRequest::get()
+        .uri("/api/graphql?query=mutation%20%7B%20logout%20%7D")
+        .insert_header((header::IF_NONE_MATCH, "*"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 405);
+    assert!(res.headers().get(header::ETAG).is_none());
+}
@@ -0,0 +1,101 @@
+// /uploads/{hash}.{ext}?w=&h=（縮小した画像）と User.avatarUrl(size:)
+// 環境変数を書き換えるので、このファイルのテストは1つにまとめる
+use actix_web::{test, App};
+use blog_server::configure_app;
+use image::{ImageFormat, RgbImage};
+use serde_json::{json, Value};
+use std::io::Cursor;
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+fn png(width: u32, height: u32) -> Vec<u8> {
+    let mut content = Cursor::new(Vec::new());
+    RgbImage::from_pixel(width, height, image::Rgb([200, 100, 50]))
+        .write_to(&mut content, ImageFormat::Png)
+        .unwrap();
+    content.into_inner()
+}
+
+#[actix_web::test]
+async fn resizes_uploads_and_caches_variants() {
+    let uploads_dir = std::env::temp_dir().join(format!("blog-resize-{}", std::process::id()));
+    std::fs::create_dir_all(&uploads_dir).unwrap();
+    let hash = "a".repeat(64);
+    let original = png(100, 50);
+    std::fs::write(uploads_dir.join(format!("{}.png", hash)), &original).unwrap();
+    std::env::set_var("UPLOADS_DIR", &uploads_dir);
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let uri = |query: &str| format!("/uploads/{}.png{}", hash, query);
+
+    // 縦横比を保って縮小し、元の画像の隣に保存する
+    let req = test::TestRequest::get().uri(&uri("?w=20")).to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers().get("etag").unwrap(), &format!("\"{}-20x2048\"", hash));
+    assert_eq!(res.headers().get("content-type").unwrap(), "image/png");
+    let resized = image::load_from_memory(&test::read_body(res).await).unwrap();
+    assert_eq!((resized.width(), resized.height()), (20, 10));
+    let variant = uploads_dir.join(format!("{}-20x2048.png", hash));
+    assert!(variant.exists());
+
+    // 2回目は保存したものを返す
+    std::fs::write(&variant, b"cached").unwrap();
+    let req = test::TestRequest::get().uri(&uri("?w=20")).to_request();
+    assert_eq!(test::call_and_read_body(&app, req).await, "cached".as_bytes());
+
+    // 高さだけの指定と、両方を指定した場合は小さい方に合わせる
+    for (query, expected) in [("?h=10", (20, 10)), ("?w=40&h=40", (40, 20))] {
+        let req = test::TestRequest::get().uri(&uri(query)).to_request();
+        let resized = image::load_from_memory(&test::call_and_read_body(&app, req).await).unwrap();
+        assert_eq!((resized.width(), resized.height()), expected, "{}", query);
+    }
+
+    // 拡大はせず元の画像を返す（上限は2048）
+    for query in ["?w=500", "?w=5000&h=5000"] {
+        let req = test::TestRequest::get().uri(&uri(query)).to_request();
+        assert_eq!(test::call_and_read_body(&app, req).await, original, "{}", query);
+    }
+    assert!(!uploads_dir.join(format!("{}-2048x2048.png", hash)).exists());
+
+    // 読めない画像は元のファイルをそのまま返す
+    let broken = "b".repeat(64);
+    let content = b"\x89PNG\r\n\x1a\nbroken".to_vec();
+    std::fs::write(uploads_dir.join(format!("{}.png", broken)), &content).unwrap();
+    let req = test::TestRequest::get().uri(&format!("/uploads/{}.png?w=10", broken)).to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(test::read_body(res).await, content);
+
+    // アップロードした画像のアバターにだけ大きさの指定を付ける
+    let req = login_request("髙橋慶祐").to_request();
+    let admin = token(&test::call_and_read_body_json(&app, req).await);
+    let avatar = format!("http://localhost:8000{}", uri(""));
+    let update = r#"
+        mutation Update($url: Url!) { updateUser(input: { id: "2", avatarUrl: $url }) { id } }
+    "#;
+    let req = graphql_request(Some(&admin), update, json!({ "url": avatar })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let query = r#"{
+        uploaded: user(id: "2") { avatarUrl small: avatarUrl(size: 48) }
+        external: user(id: "1") { small: avatarUrl(size: 48) }
+        none: user(id: "3") { small: avatarUrl(size: 48) }
+    }"#;
+    let req = graphql_request(None, query, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    assert_eq!(body["data"]["uploaded"]["avatarUrl"], avatar.as_str());
+    assert_eq!(body["data"]["uploaded"]["small"], format!("{}?w=48&h=48", avatar));
+    assert_eq!(body["data"]["external"]["small"], "https://example.com/avatar.png");
+    let placeholder = body["data"]["none"]["small"].as_str().unwrap();
+    assert!(placeholder.ends_with("?d=identicon&f=y&s=48"), "{}", placeholder);
+
+    let query = r#"{ user(id: "2") { avatarUrl(size: 0) } }"#;
+    let req = graphql_request(None, query, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["errors"][0]["extensions"]["code"], "VALIDATION_FAILED", "{}", body);
+
+    let _ = std::fs::remove_dir_all(&uploads_dir);
+}
@@ -0,0 +1,199 @@
+// いいね・リアクション・ブックマーク・フォロー・コメント（操作するのはログイン中のユーザー）
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+fn error_code(body: &Value) -> &str {
+    body["errors"][0]["extensions"]["code"].as_str().unwrap_or_default()
+}
+
+const MUTATIONS: [&str; 9] = [
+    r#"mutation { addComment(input: { postId: "1", body: "コメント" }) { id } }"#,
+    r#"mutation { likePost(postId: "1") { id } }"#,
+    r#"mutation { unlikePost(postId: "1") { id } }"#,
+    r#"mutation { reactToPost(postId: "1", reaction: HEART) { id } }"#,
+    r#"mutation { removeReaction(postId: "1", reaction: HEART) { id } }"#,
+    r#"mutation { bookmarkPost(postId: "1") { bookmarkedAt } }"#,
+    r#"mutation { unbookmarkPost(postId: "1") }"#,
+    r#"mutation { followUser(followeeId: "1") { id } }"#,
+    r#"mutation { unfollowUser(followeeId: "1") }"#,
+];
+
+#[actix_web::test]
+async fn likes_and_follows_are_counted_separately() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("佐藤太郎").to_request();
+    let sato = token(&test::call_and_read_body_json(&app, req).await);
+    let counts = r#"
+        query {
+            post(id: "1") { likeCount }
+            a: user(id: "1") { followerCount followingCount }
+            b: user(id: "2") { followerCount followingCount }
+        }
+    "#;
+
+    // どちらもIDの組なので、同じ場所に入ると投稿1へのいいねがユーザー1のフォローに見える
+    let like = r#"mutation { likePost(postId: "1") { id } }"#;
+    let req = graphql_request(Some(&sato), like, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let req = graphql_request(None, counts, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(
+        body["data"],
+        json!({
+            "post": { "likeCount": 1 },
+            "a": { "followerCount": 0, "followingCount": 0 },
+            "b": { "followerCount": 0, "followingCount": 0 },
+        })
+    );
+
+    let follow = r#"mutation { followUser(followeeId: "1") { id } }"#;
+    let req = graphql_request(Some(&sato), follow, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let req = graphql_request(None, counts, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(
+        body["data"],
+        json!({
+            "post": { "likeCount": 1 },
+            "a": { "followerCount": 1, "followingCount": 0 },
+            "b": { "followerCount": 0, "followingCount": 1 },
+        })
+    );
+}
+
+#[actix_web::test]
+async fn interactions_require_authentication() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    for mutation in MUTATIONS {
+        let req = graphql_request(None, mutation, json!({})).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(error_code(&body), "UNAUTHENTICATED", "{}: {}", mutation, body);
+    }
+
+    // READERもできる
+    let register = r#"
+        mutation { register(name: "Reader", handle: "reader", password: "password123") { id } }
+    "#;
+    let req = graphql_request(None, register, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let login = r#"mutation { login(name: "Reader", password: "password123") { token } }"#;
+    let req = graphql_request(None, login, json!({})).to_request();
+    let reader = token(&test::call_and_read_body_json(&app, req).await);
+    for mutation in MUTATIONS {
+        let req = graphql_request(Some(&reader), mutation, json!({})).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert!(body["errors"].is_null(), "{}: {}", mutation, body);
+    }
+}
+
+#[actix_web::test]
+async fn only_admins_can_act_as_other_users() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let admin = token(&test::call_and_read_body_json(&app, req).await);
+    let req = login_request("佐藤太郎").to_request();
+    let sato = token(&test::call_and_read_body_json(&app, req).await);
+    let call = |token: &str, query: &str, variables: Value| {
+        graphql_request(Some(token), query, variables).to_request()
+    };
+    let likes = r#"
+        query {
+            post(id: "1") {
+                likeCount
+                sato: likedByViewer(viewerId: "2")
+                suzuki: likedByViewer(viewerId: "3")
+            }
+        }
+    "#;
+
+    // 未指定ならログイン中のユーザー。自分のIDは指定してもよい
+    let like = r#"mutation Like($userId: ID) { likePost(postId: "1", userId: $userId) { id } }"#;
+    for user_id in [json!(null), json!("2")] {
+        let req = call(&sato, like, json!({ "userId": user_id }));
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert!(body["errors"].is_null(), "{}", body);
+    }
+    // 他のユーザーとしては操作できない
+    let req = call(&sato, like, json!({ "userId": "3" }));
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(error_code(&body), "FORBIDDEN", "{}", body);
+    let body: Value = test::call_and_read_body_json(&app, call(&sato, likes, json!({}))).await;
+    let post = &body["data"]["post"];
+    assert_eq!(*post, json!({ "likeCount": 1, "sato": true, "suzuki": false }), "{}", body);
+
+    let follow = r#"
+        mutation Follow($followerId: ID) {
+            followUser(followerId: $followerId, followeeId: "1") { id }
+        }
+    "#;
+    let req = call(&sato, follow, json!({ "followerId": "3" }));
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(error_code(&body), "FORBIDDEN", "{}", body);
+    let comment = r#"
+        mutation Comment($authorId: ID) {
+            addComment(input: { postId: "1", authorId: $authorId, body: "代理のコメント" }) {
+                author { id }
+            }
+        }
+    "#;
+    let req = call(&sato, comment, json!({ "authorId": "3" }));
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(error_code(&body), "FORBIDDEN", "{}", body);
+    let req = call(&sato, comment, json!({ "authorId": null }));
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["addComment"]["author"]["id"], "2", "{}", body);
+
+    // 管理者は他のユーザーとして操作できる
+    let req = call(&admin, like, json!({ "userId": "3" }));
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let req = call(&admin, comment, json!({ "authorId": "3" }));
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["addComment"]["author"]["id"], "3", "{}", body);
+    let body: Value = test::call_and_read_body_json(&app, call(&sato, likes, json!({}))).await;
+    let post = &body["data"]["post"];
+    assert_eq!(*post, json!({ "likeCount": 2, "sato": true, "suzuki": true }), "{}", body);
+}
+
+#[actix_web::test]
+async fn comments_can_be_deleted_by_their_author_or_an_admin() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let mut tokens = Vec::new();
+    for name in ["髙橋慶祐", "佐藤太郎", "鈴木花子"] {
+        let req = login_request(name).to_request();
+        tokens.push(token(&test::call_and_read_body_json(&app, req).await));
+    }
+    let add = r#"mutation { addComment(input: { postId: "1", body: "消すコメント" }) { id } }"#;
+    let mut ids = Vec::new();
+    for _ in 0..2 {
+        let req = graphql_request(Some(&tokens[1]), add, json!({})).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        ids.push(body["data"]["addComment"]["id"].as_str().unwrap().to_string());
+    }
+    let delete = |token: Option<&str>, id: &str| {
+        let query = r#"mutation Delete($id: ID!) { deleteComment(id: $id) }"#;
+        graphql_request(token, query, json!({ "id": id })).to_request()
+    };
+
+    let body: Value = test::call_and_read_body_json(&app, delete(None, &ids[0])).await;
+    assert_eq!(error_code(&body), "UNAUTHENTICATED", "{}", body);
+    let body: Value = test::call_and_read_body_json(&app, delete(Some(&tokens[2]), &ids[0])).await;
+    assert_eq!(error_code(&body), "FORBIDDEN", "{}", body);
+    let body: Value = test::call_and_read_body_json(&app, delete(Some(&tokens[1]), &ids[0])).await;
+    assert_eq!(body["data"]["deleteComment"], true, "{}", body);
+    let body: Value = test::call_and_read_body_json(&app, delete(Some(&tokens[0]), &ids[1])).await;
+    assert_eq!(body["data"]["deleteComment"], true, "{}", body);
+    let body: Value = test::call_and_read_body_json(&app, delete(Some(&tokens[1]), &ids[1])).await;
+    assert_eq!(error_code(&body), "NOT_FOUND", "{}", body);
+}
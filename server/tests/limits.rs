@@ -0,0 +1,142 @@
+// MAX_BODY_SIZE（413）とEXECUTION_TIMEOUT_SECS（TIMEOUTのエラー）
+use actix_web::{test, App};
+use async_graphql::ID;
+use blog_server::{
+    build_app_state, configure_app, AppStorage, MemoryStorage, Page, Post, PostFilter, Seed,
+    Storage, User, UserFilter,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+
+mod common;
+use common::{app_state, graphql_request};
+
+type PostUpdate<'a> = Box<dyn FnOnce(&mut Post) -> async_graphql::Result<()> + Send + 'a>;
+type UserUpdate<'a> = Box<dyn FnOnce(&mut User) -> async_graphql::Result<()> + Send + 'a>;
+
+// 投稿の一覧だけ遅いストレージ
+struct SlowStorage(AppStorage);
+
+const LIST_POSTS_DELAY: Duration = Duration::from_millis(500);
+
+#[async_trait::async_trait]
+impl Storage for SlowStorage {
+    async fn list_posts(
+        &self,
+        filter: &PostFilter,
+        page: Page,
+    ) -> async_graphql::Result<Vec<Post>> {
+        tokio::time::sleep(LIST_POSTS_DELAY).await;
+        self.0.list_posts(filter, page).await
+    }
+    async fn count_posts(&self, filter: &PostFilter) -> async_graphql::Result<usize> {
+        self.0.count_posts(filter).await
+    }
+    async fn list_all_posts(&self) -> async_graphql::Result<Vec<Post>> {
+        self.0.list_all_posts().await
+    }
+    async fn get_post(&self, id: &ID) -> async_graphql::Result<Option<Post>> {
+        self.0.get_post(id).await
+    }
+    async fn get_post_by_slug(&self, slug: &str) -> async_graphql::Result<Option<Post>> {
+        self.0.get_post_by_slug(slug).await
+    }
+    async fn insert_post(&self, post: Post) -> async_graphql::Result<Post> {
+        self.0.insert_post(post).await
+    }
+    async fn update_post(
+        &self,
+        id: &ID,
+        update: PostUpdate<'_>,
+    ) -> async_graphql::Result<Option<Post>> {
+        self.0.update_post(id, update).await
+    }
+    async fn delete_post(&self, id: &ID) -> async_graphql::Result<Option<Post>> {
+        self.0.delete_post(id).await
+    }
+    async fn list_users(
+        &self,
+        filter: &UserFilter,
+        page: Page,
+    ) -> async_graphql::Result<Vec<User>> {
+        self.0.list_users(filter, page).await
+    }
+    async fn count_users(&self, filter: &UserFilter) -> async_graphql::Result<usize> {
+        self.0.count_users(filter).await
+    }
+    async fn get_user(&self, id: &ID) -> async_graphql::Result<Option<User>> {
+        self.0.get_user(id).await
+    }
+    async fn get_users(&self, ids: &[ID]) -> async_graphql::Result<Vec<User>> {
+        self.0.get_users(ids).await
+    }
+    async fn get_users_by_name(&self, name: &str) -> async_graphql::Result<Vec<User>> {
+        self.0.get_users_by_name(name).await
+    }
+    async fn get_user_by_handle(&self, handle: &str) -> async_graphql::Result<Option<User>> {
+        self.0.get_user_by_handle(handle).await
+    }
+    async fn insert_user(&self, user: User) -> async_graphql::Result<()> {
+        self.0.insert_user(user).await
+    }
+    async fn update_user(
+        &self,
+        id: &ID,
+        update: UserUpdate<'_>,
+    ) -> async_graphql::Result<Option<User>> {
+        self.0.update_user(id, update).await
+    }
+    async fn delete_user(&self, id: &ID) -> async_graphql::Result<Option<User>> {
+        self.0.delete_user(id).await
+    }
+    async fn delete_user_with_posts(&self, id: &ID) -> async_graphql::Result<Option<Vec<ID>>> {
+        self.0.delete_user_with_posts(id).await
+    }
+    async fn ping(&self) -> Result<(), String> {
+        self.0.ping().await
+    }
+}
+
+#[actix_web::test]
+async fn rejects_large_bodies() {
+    let state = app_state().await.with_request_limits(1024, Duration::from_secs(30));
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+
+    let padding = " ".repeat(2048);
+    let query = format!("{{ postsCount {} }}", padding);
+    let req = graphql_request(None, &query, json!({})).to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 413);
+
+    let req = test::TestRequest::post()
+        .uri("/api/graphql")
+        .set_json(json!([{ "query": query }]))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 413);
+
+    let req = graphql_request(None, "{ postsCount }", json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["postsCount"], 1);
+}
+
+#[actix_web::test]
+async fn aborts_slow_operations() {
+    let storage: AppStorage = Arc::new(MemoryStorage::seeded(&Seed::builtin()));
+    let state = build_app_state(Arc::new(SlowStorage(storage)), None)
+        .await
+        .unwrap()
+        .with_request_limits(1024 * 1024, Duration::from_millis(100));
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+
+    let req = graphql_request(None, "{ posts(limit: 10) { id } }", json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["data"].is_null(), "{}", body);
+    assert_eq!(body["errors"][0]["extensions"]["code"], "TIMEOUT");
+
+    // 遅くない操作は影響を受けない
+    let req = graphql_request(None, "{ postsCount }", json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["postsCount"], 1);
+}
@@ -0,0 +1,62 @@
+// GraphQLの操作のログ（操作名・変数・エラー数。パスワードなどの変数は値を伏せる）
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::json;
+use tracing_subscriber::layer::SubscriberExt;
+
+mod common;
+use common::{app_state, graphql_request, LogCapture};
+
+#[actix_web::test]
+async fn operations_are_logged_with_secrets_redacted() {
+    let logs = LogCapture::default();
+    let subscriber = tracing_subscriber::registry().with(logs.clone());
+    let _guard = tracing::subscriber::set_default(subscriber);
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+
+    let req = graphql_request(None, "query Home { postsCount }", json!({})).to_request();
+    test::call_service(&app, req).await;
+    let login = r#"
+        mutation Login($name: String!, $password: String!) {
+            login(name: $name, password: $password) { token }
+        }
+    "#;
+    let variables = json!({ "name": "髙橋慶祐", "password": "hunter2" });
+    let req = graphql_request(None, login, variables).to_request();
+    test::call_service(&app, req).await;
+    // 入れ子のオブジェクトやリストの中も名前で判断する（大文字小文字は区別しない）
+    let variables = json!({
+        "input": { "title": "題名", "apiKey": "k-123", "items": [{ "refreshToken": "t-456" }] },
+    });
+    let req = graphql_request(None, "{ postsCount }", variables).to_request();
+    test::call_service(&app, req).await;
+
+    let succeeded = logs.events("graphql operation");
+    assert_eq!(succeeded.len(), 2);
+    assert_eq!(succeeded[0]["level"], "INFO");
+    assert_eq!(succeeded[0]["operation"], "Home");
+    assert_eq!(succeeded[0]["errors"], "0");
+    assert!(succeeded[0].contains_key("duration_ms"));
+    assert_eq!(succeeded[1]["operation"], "anonymous");
+    assert_eq!(
+        succeeded[1]["variables"],
+        json!({
+            "input": {
+                "title": "題名",
+                "apiKey": "[REDACTED]",
+                "items": [{ "refreshToken": "[REDACTED]" }],
+            },
+        })
+        .to_string()
+    );
+
+    let failed = logs.events("graphql operation failed");
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0]["level"], "WARN");
+    assert_eq!(failed[0]["operation"], "Login");
+    assert_eq!(failed[0]["errors"], "1");
+    let variables = &failed[0]["variables"];
+    assert_eq!(variables, &json!({ "name": "髙橋慶祐", "password": "[REDACTED]" }).to_string());
+    assert!(!failed[0].values().any(|value| value.contains("hunter2")));
+}
@@ -0,0 +1,127 @@
+// Post.bodyHtml（Markdownの描画とサニタイズ）と Post.tableOfContents
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+const BODY: &str = r#"# 見出し
+
+| 言語 | 種類 |
+| --- | :---: |
+| Rust | **コンパイル** |
+
+```rust
+fn main() {}
+```
+
+<script>alert(1)</script>
+<div onclick="alert(1)">生のHTML</div>
+
+[外部リンク](https://example.com) [危険](javascript:alert(1)) ~~取り消し~~
+"#;
+
+#[actix_web::test]
+async fn body_html_renders_sanitized_markdown() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let login: Value = test::call_and_read_body_json(&app, req).await;
+    let token = token(&login);
+
+    let create = r#"
+        mutation Create($body: String!) {
+            createPost(input: { title: "Markdown", body: $body, authorId: "1" }) { id bodyHtml }
+        }
+    "#;
+    let req = graphql_request(Some(&token), create, json!({ "body": BODY })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let post = &body["data"]["createPost"];
+    let html = post["bodyHtml"].as_str().unwrap();
+
+    assert!(html.contains("<h1 id=\"見出し\">見出し</h1>"), "{}", html);
+    assert!(html.contains("<td><strong>コンパイル</strong></td>"), "{}", html);
+    assert!(html.contains("<pre><code class=\"language-rust\">fn main() {}"), "{}", html);
+    assert!(html.contains("<del>取り消し</del>"), "{}", html);
+    let link = r#"<a href="https://example.com" rel="noopener noreferrer">外部リンク</a>"#;
+    assert!(html.contains(link), "{}", html);
+    // 生のHTML、script、イベントハンドラー、javascript:のリンクは残らない
+    for removed in ["<script", "alert", "onclick", "<div", "javascript:"] {
+        assert!(!html.contains(removed), "{} in {}", removed, html);
+    }
+
+    // 本文を更新すると描画し直す
+    let update = r#"
+        mutation Update($id: ID!) {
+            updatePost(input: { id: $id, body: "更新後の<b>本文</b>" }) { bodyHtml }
+        }
+    "#;
+    let req = graphql_request(Some(&token), update, json!({ "id": post["id"] })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["updatePost"]["bodyHtml"], "<p>更新後の本文</p>\n");
+}
+
+const TOC_BODY: &str = r#"# Getting Started
+
+本文
+
+## Install `cargo`
+
+### Getting Started
+
+#### 設定ファイル
+
+##### 深すぎる見出し
+
+## Getting Started
+"#;
+
+#[actix_web::test]
+async fn table_of_contents_matches_heading_ids() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let token = token(&test::call_and_read_body_json(&app, req).await);
+
+    let create = r#"
+        mutation Create($body: String!) {
+            createPost(input: { title: "目次", body: $body, authorId: "1" }) {
+                bodyHtml
+                tableOfContents { level text anchor }
+            }
+        }
+    "#;
+    let req = graphql_request(Some(&token), create, json!({ "body": TOC_BODY })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let post = &body["data"]["createPost"];
+    // 同じテキストの見出しには -1, -2 を付け、#####以下は目次に含めない
+    assert_eq!(
+        post["tableOfContents"],
+        json!([
+            { "level": 1, "text": "Getting Started", "anchor": "getting-started" },
+            { "level": 2, "text": "Install cargo", "anchor": "install-cargo" },
+            { "level": 3, "text": "Getting Started", "anchor": "getting-started-1" },
+            { "level": 4, "text": "設定ファイル", "anchor": "設定ファイル" },
+            { "level": 2, "text": "Getting Started", "anchor": "getting-started-2" },
+        ])
+    );
+    let html = post["bodyHtml"].as_str().unwrap();
+    for heading in [
+        r#"<h1 id="getting-started">Getting Started</h1>"#,
+        r#"<h2 id="install-cargo">Install <code>cargo</code></h2>"#,
+        r#"<h3 id="getting-started-1">Getting Started</h3>"#,
+        r#"<h4 id="設定ファイル">設定ファイル</h4>"#,
+        r#"<h5 id="深すぎる見出し">深すぎる見出し</h5>"#,
+        r#"<h2 id="getting-started-2">Getting Started</h2>"#,
+    ] {
+        assert!(html.contains(heading), "{} in {}", heading, html);
+    }
+
+    let query = "{ post(id: \"1\") { tableOfContents { anchor } } }";
+    let req = graphql_request(None, query, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["post"]["tableOfContents"], json!([]));
+}
@@ -0,0 +1,125 @@
+// 投稿とコメントの@メンション（mentionsとbodyHtmlのリンク）
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+const BODY: &str = r#"@User_2 さんと @user_3 さん、@nobody さんへ。@user_2 さんは2回目
+
+メールアドレス foo@user_4 や [@user_4](https://example.com) と `@user_4` は含めない
+
+```
+@user_5
+```
+"#;
+
+#[actix_web::test]
+async fn resolves_mentions_in_posts() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let token = token(&test::call_and_read_body_json(&app, req).await);
+
+    let create = r#"
+        mutation Create($body: String!) {
+            createPost(input: { title: "メンション", body: $body, authorId: "1" }) {
+                id bodyHtml mentions { id handle }
+            }
+        }
+    "#;
+    let req = graphql_request(Some(&token), create, json!({ "body": BODY })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let post = &body["data"]["createPost"];
+    let id = post["id"].as_str().unwrap().to_string();
+    assert_eq!(
+        post["mentions"],
+        json!([{ "id": "2", "handle": "user_2" }, { "id": "3", "handle": "user_3" }])
+    );
+
+    // 存在するユーザーへの@だけをプロフィールへのリンクにする（書いたとおりの表記で）
+    let html = post["bodyHtml"].as_str().unwrap();
+    let link = |handle: &str, text: &str| {
+        format!(
+            r#"<a href="http://localhost:8000/users/{}" rel="noopener noreferrer">{}</a>"#,
+            handle, text
+        )
+    };
+    let first = format!("<p>{} さんと {} さん、", link("user_2", "@User_2"), link("user_3", "@user_3"));
+    assert!(html.starts_with(&first), "{}", html);
+    let second = format!("@nobody さんへ。{} さんは2回目", link("user_2", "@user_2"));
+    assert!(html.contains(&second), "{}", html);
+    assert!(html.contains("foo@user_4 や "), "{}", html);
+    assert!(html.contains("<code>@user_4</code>"), "{}", html);
+    assert!(html.contains("<pre><code>@user_5\n</code></pre>"), "{}", html);
+
+    // フィードの本文も同じHTML
+    let req = test::TestRequest::get().uri("/feed.json").to_request();
+    let feed: Value = test::call_and_read_body_json(&app, req).await;
+    let item = feed["items"].as_array().unwrap().iter().find(|item| item["title"] == "メンション");
+    assert_eq!(item.unwrap()["content_html"], html);
+
+    // 本文を変えると解決し直す
+    let update = r#"
+        mutation Update($id: ID!) {
+            updatePost(input: { id: $id, body: "**@user_4** だけ" }) { bodyHtml mentions { id } }
+        }
+    "#;
+    let req = graphql_request(Some(&token), update, json!({ "id": id })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let post = &body["data"]["updatePost"];
+    assert_eq!(post["mentions"], json!([{ "id": "4" }]));
+    let expected = format!("<p><strong>{}</strong> だけ</p>\n", link("user_4", "@user_4"));
+    assert_eq!(post["bodyHtml"], expected.as_str());
+
+    // 履歴に戻すとメンションも戻る
+    let revert = r#"
+        mutation Revert($id: ID!) { revertPost(postId: $id, revision: 1) { mentions { id } } }
+    "#;
+    let req = graphql_request(Some(&token), revert, json!({ "id": id })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["revertPost"]["mentions"], json!([{ "id": "2" }, { "id": "3" }]));
+
+    // 削除されたユーザーは一覧から除き、リンクもテキストに戻す
+    let delete = r#"mutation { deleteUser(id: "3") }"#;
+    let req = graphql_request(Some(&token), delete, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let query = r#"query Post($id: ID!) { post(id: $id) { bodyHtml mentions { id } } }"#;
+    let req = graphql_request(None, query, json!({ "id": id })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let post = &body["data"]["post"];
+    assert_eq!(post["mentions"], json!([{ "id": "2" }]), "{}", body);
+    let html = post["bodyHtml"].as_str().unwrap();
+    assert!(html.contains(" さんと @user_3 さん、"), "{}", html);
+}
+
+#[actix_web::test]
+async fn resolves_mentions_in_comments() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("佐藤太郎").to_request();
+    let sato = token(&test::call_and_read_body_json(&app, req).await);
+
+    let add = r#"
+        mutation Add($body: String!) {
+            addComment(input: { postId: "1", body: $body }) { mentions { handle } }
+        }
+    "#;
+    let variables = json!({ "body": "@user_1 @USER_5 @user_1 @nobody さん" });
+    let req = graphql_request(Some(&sato), add, variables).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    assert_eq!(
+        body["data"]["addComment"]["mentions"],
+        json!([{ "handle": "user_1" }, { "handle": "user_5" }])
+    );
+
+    let variables = json!({ "body": "```\n@user_1\n```" });
+    let req = graphql_request(Some(&sato), add, variables).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["addComment"]["mentions"], json!([]), "{}", body);
+}
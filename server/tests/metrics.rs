@@ -0,0 +1,39 @@
+// /metrics（Prometheusのテキスト形式）
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::json;
+
+mod common;
+use common::{app_state, graphql_request};
+
+#[actix_web::test]
+async fn counts_requests_and_operations() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = graphql_request(None, "query Home { postsCount }", json!({})).to_request();
+    test::call_service(&app, req).await;
+    let req = graphql_request(None, "{ missingField }", json!({})).to_request();
+    test::call_service(&app, req).await;
+
+    let req = test::TestRequest::get().uri("/metrics").to_request();
+    let body = test::call_and_read_body(&app, req).await;
+    let text = String::from_utf8(body.to_vec()).unwrap();
+    let graphql_requests = r#"http_requests_total{route="/api/graphql",status="200"} 2"#;
+    assert!(text.contains(graphql_requests), "{}", text);
+    assert!(text.contains(r#"graphql_operations_total{errors="false",operation="Home"} 1"#));
+    assert!(text.contains(r#"graphql_operations_total{errors="true",operation="invalid"} 1"#));
+    // SLOW_QUERY_MS（デフォルト500ms）より速い操作は数えない
+    assert!(text.contains("graphql_slow_operations_total 0"));
+    assert!(text.contains("blog_posts 1"));
+    assert!(text.contains("blog_users 5"));
+    assert!(text.contains("process_uptime_seconds"));
+}
+
+#[actix_web::test]
+async fn metrics_route_can_be_moved_off_the_public_app() {
+    let state = app_state().await.without_metrics_route();
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = test::TestRequest::get().uri("/metrics").to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 404);
+}
@@ -0,0 +1,188 @@
+// ニュースレターの購読（subscribeNewsletter / 確認・購読をやめるリンク / newsletterSubscribers）
+// 環境変数を書き換えるので、このファイルのテストは1つにまとめる
+use actix_web::{test, App};
+use blog_server::{configure_app, spawn_background_tasks};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+mod common;
+use common::{app_state, decode_quoted_printable, graphql_request, login_request, serve_smtp, token};
+
+const SUBSCRIBE: &str = r#"
+    mutation Subscribe($email: String!) { subscribeNewsletter(email: $email) }
+"#;
+
+const SUBSCRIBERS: &str = r#"
+    query Subscribers($status: NewsletterSubscriberStatus) {
+        newsletterSubscribers(status: $status) {
+            pendingCount
+            confirmedCount
+            subscribers { email status confirmedAt }
+        }
+    }
+"#;
+
+// メールの本文からリンクのパスとクエリを取り出す
+fn link(mail: &str, path: &str) -> String {
+    let prefix = format!("http://localhost:8000{}?token=", path);
+    let start = mail.find(&prefix).unwrap_or_else(|| panic!("no {} link in {}", path, mail));
+    let link = &mail[start + "http://localhost:8000".len()..];
+    let end = link.find(|c: char| c.is_whitespace() || c == '"').unwrap_or(link.len());
+    link[..end].to_string()
+}
+
+#[actix_web::test]
+async fn subscribes_with_double_opt_in() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let received = Arc::new(Mutex::new(Vec::new()));
+    tokio::spawn(serve_smtp(listener, received.clone()));
+
+    let vars = [
+        ("SMTP_HOST", "127.0.0.1".to_string()),
+        ("SMTP_PORT", port.to_string()),
+        ("SMTP_TLS", "none".to_string()),
+        ("SMTP_FROM", "blog@example.com".to_string()),
+        ("RATE_LIMIT_NEWSLETTER", "3".to_string()),
+    ];
+    for (name, value) in &vars {
+        std::env::set_var(name, value);
+    }
+    let state = app_state().await;
+    for (name, _) in &vars {
+        std::env::remove_var(name);
+    }
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let background_tasks = spawn_background_tasks(&state);
+
+    let req = login_request("髙橋慶祐").to_request();
+    let admin = token(&test::call_and_read_body_json(&app, req).await);
+    let req = login_request("佐藤太郎").to_request();
+    let author = token(&test::call_and_read_body_json(&app, req).await);
+    let subscribe = |email: &str| {
+        graphql_request(None, SUBSCRIBE, json!({ "email": email })).to_request()
+    };
+    let subscribers = |token: &str, status: Value| {
+        graphql_request(Some(token), SUBSCRIBERS, json!({ "status": status })).to_request()
+    };
+    let wait_for_emails = |count: usize| {
+        let received = received.clone();
+        async move {
+            for _ in 0..200 {
+                if received.lock().unwrap().len() >= count {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(25)).await;
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            let received = received.lock().unwrap().clone();
+            assert_eq!(received.len(), count, "{:?}", received);
+            decode_quoted_printable(&received[count - 1])
+        }
+    };
+
+    let body: Value = test::call_and_read_body_json(&app, subscribe("not an email")).await;
+    assert_eq!(body["errors"][0]["extensions"]["code"], "VALIDATION_FAILED", "{}", body);
+
+    // 確認待ちとして保存し、確認のリンクと購読をやめるリンクを送る
+    let body: Value = test::call_and_read_body_json(&app, subscribe("reader@example.com")).await;
+    assert_eq!(body["data"]["subscribeNewsletter"], true, "{}", body);
+    let mail = wait_for_emails(1).await;
+    assert!(mail.contains("RCPT TO:<reader@example.com>"), "{}", mail);
+    assert!(mail.contains("Subject: Confirm your subscription to"), "{}", mail);
+    let confirm = link(&mail, "/newsletter/confirm");
+    let unsubscribe = link(&mail, "/newsletter/unsubscribe");
+
+    let body: Value = test::call_and_read_body_json(&app, subscribers(&admin, json!(null))).await;
+    let list = &body["data"]["newsletterSubscribers"];
+    assert_eq!(list["pendingCount"], 1, "{}", body);
+    assert_eq!(list["confirmedCount"], 0);
+    let subscriber = &list["subscribers"][0];
+    assert_eq!(list["subscribers"].as_array().unwrap().len(), 1, "{}", body);
+    assert_eq!(subscriber["email"], "reader@example.com");
+    assert_eq!(subscriber["status"], "PENDING");
+    assert!(subscriber["confirmedAt"].is_null());
+    let body: Value = test::call_and_read_body_json(&app, subscribers(&author, json!(null))).await;
+    assert_eq!(body["errors"][0]["extensions"]["code"], "FORBIDDEN", "{}", body);
+
+    // 確認待ちのアドレスでも同じ結果を返し、すぐには送り直さない
+    let body: Value = test::call_and_read_body_json(&app, subscribe("Reader@Example.com")).await;
+    assert_eq!(body["data"]["subscribeNewsletter"], true, "{}", body);
+
+    // 署名の一致しないトークンは受け付けない
+    let tampered = confirm.replacen("token=", "token=0", 1);
+    let res = test::call_service(&app, test::TestRequest::get().uri(&tampered).to_request()).await;
+    assert_eq!(res.status(), 400);
+    // 確認済みのリンクを開き直しても同じページを返す
+    for _ in 0..2 {
+        let req = test::TestRequest::get().uri(&confirm).to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), 200);
+        let page = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+        assert!(page.contains("is confirmed"), "{}", page);
+    }
+    let body: Value = test::call_and_read_body_json(&app, subscribers(&admin, json!(null))).await;
+    let list = &body["data"]["newsletterSubscribers"];
+    assert_eq!((&list["pendingCount"], &list["confirmedCount"]), (&json!(0), &json!(1)));
+    assert_eq!(list["subscribers"][0]["status"], "CONFIRMED", "{}", body);
+    assert!(list["subscribers"][0]["confirmedAt"].is_string());
+
+    // 購読済みのアドレスでも同じ結果を返し、メールは送らない
+    let body: Value = test::call_and_read_body_json(&app, subscribe("reader@example.com")).await;
+    assert_eq!(body["data"]["subscribeNewsletter"], true, "{}", body);
+    wait_for_emails(1).await;
+
+    // クライアントIPごとに、別名で並べたフィールドも1つずつ数える
+    let peer: SocketAddr = "203.0.113.1:4000".parse().unwrap();
+    let twice = r#"
+        mutation {
+            a: subscribeNewsletter(email: "a@example.com")
+            b: subscribeNewsletter(email: "b@example.com")
+        }
+    "#;
+    let req = graphql_request(None, twice, json!({})).peer_addr(peer).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let variables = json!({ "email": "c@example.com" });
+    let req = graphql_request(None, SUBSCRIBE, variables).peer_addr(peer).to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 200);
+    let variables = json!({ "email": "d@example.com" });
+    let req = graphql_request(None, SUBSCRIBE, variables).peer_addr(peer).to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 429);
+    assert!(res.headers().contains_key("Retry-After"));
+    // 他のミューテーションは制限しない
+    let req = login_request("髙橋慶祐").peer_addr(peer).to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 200);
+    wait_for_emails(4).await;
+
+    let req = subscribers(&admin, json!("PENDING"));
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let list = &body["data"]["newsletterSubscribers"];
+    assert_eq!((&list["pendingCount"], &list["confirmedCount"]), (&json!(3), &json!(1)));
+    assert_eq!(list["subscribers"].as_array().unwrap().len(), 3, "{}", body);
+
+    // 購読をやめるリンクは1度だけ使える
+    let req = test::TestRequest::get().uri(&unsubscribe).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+    let req = test::TestRequest::get().uri(&unsubscribe).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 404);
+    let body: Value = test::call_and_read_body_json(&app, subscribers(&admin, json!(null))).await;
+    assert_eq!(body["data"]["newsletterSubscribers"]["confirmedCount"], 0, "{}", body);
+    // 削除した購読者の確認のリンクは使えない
+    let req = test::TestRequest::get().uri(&confirm).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 404);
+
+    background_tasks.shutdown().await;
+
+    // SMTPを設定していなければ受け付けない
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let body: Value = test::call_and_read_body_json(&app, subscribe("reader@example.com")).await;
+    assert_eq!(body["errors"][0]["extensions"]["code"], "FORBIDDEN", "{}", body);
+}
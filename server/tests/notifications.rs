@@ -0,0 +1,187 @@
+// 通知（notifications / unreadNotificationCount / markNotificationRead / markAllRead）
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+const NOTIFICATIONS: &str = r#"
+    query Notifications($userId: ID!, $unreadOnly: Boolean! = false) {
+        notifications(userId: $userId, unreadOnly: $unreadOnly) {
+            id kind read actor { id } recipient { id } post { id } comment { id body }
+        }
+        user(id: $userId) { unreadNotificationCount }
+    }
+"#;
+
+const ADD_COMMENT: &str = r#"
+    mutation Add($body: String!, $parent: ID) {
+        addComment(input: { postId: "1", body: $body, parentCommentId: $parent }) { id }
+    }
+"#;
+
+fn error_code(body: &Value) -> &str {
+    body["errors"][0]["extensions"]["code"].as_str().unwrap_or_default()
+}
+
+#[actix_web::test]
+async fn comments_replies_and_mentions_notify_other_users() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let mut tokens = Vec::new();
+    for name in ["髙橋慶祐", "佐藤太郎", "鈴木花子"] {
+        let req = login_request(name).to_request();
+        tokens.push(token(&test::call_and_read_body_json(&app, req).await));
+    }
+    let list = |token: &str, user_id: &str, unread_only: bool| {
+        let variables = json!({ "userId": user_id, "unreadOnly": unread_only });
+        graphql_request(Some(token), NOTIFICATIONS, variables).to_request()
+    };
+    let add = |token: &str, body: &str, parent: Option<&str>| {
+        let variables = json!({ "body": body, "parent": parent });
+        graphql_request(Some(token), ADD_COMMENT, variables).to_request()
+    };
+
+    // 投稿の著者にはコメント、メンションしたユーザーにはメンションの通知
+    let comment = add(&tokens[1], "@user_3 見て", None);
+    let body: Value = test::call_and_read_body_json(&app, comment).await;
+    let comment_id = body["data"]["addComment"]["id"].as_str().unwrap().to_string();
+    // 返信先の著者には（メンションしていても）返信の通知を1件だけ。自分の投稿へのコメントは通知しない
+    let reply = add(&tokens[0], "@user_2 ありがとう", Some(&comment_id));
+    let body: Value = test::call_and_read_body_json(&app, reply).await;
+    let reply_id = body["data"]["addComment"]["id"].as_str().unwrap().to_string();
+
+    let body: Value = test::call_and_read_body_json(&app, list(&tokens[0], "1", false)).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let items = body["data"]["notifications"].as_array().unwrap();
+    assert_eq!(items.len(), 1, "{}", body);
+    assert_eq!(items[0]["kind"], "COMMENT");
+    assert_eq!(items[0]["actor"]["id"], "2");
+    assert_eq!(items[0]["recipient"]["id"], "1");
+    assert_eq!(items[0]["post"]["id"], "1");
+    assert_eq!(items[0]["comment"], json!({ "id": comment_id, "body": "@user_3 見て" }));
+    assert_eq!(items[0]["read"], false);
+    assert_eq!(body["data"]["user"]["unreadNotificationCount"], 1);
+
+    let body: Value = test::call_and_read_body_json(&app, list(&tokens[1], "2", false)).await;
+    let items = body["data"]["notifications"].as_array().unwrap();
+    assert_eq!(items.len(), 1, "{}", body);
+    assert_eq!(items[0]["kind"], "REPLY");
+    assert_eq!(items[0]["comment"]["id"], reply_id.as_str());
+
+    let body: Value = test::call_and_read_body_json(&app, list(&tokens[2], "3", false)).await;
+    assert_eq!(body["data"]["notifications"][0]["kind"], "MENTION", "{}", body);
+
+    // 本人以外は参照も既読にもできない
+    let body: Value = test::call_and_read_body_json(&app, list(&tokens[1], "1", false)).await;
+    assert_eq!(error_code(&body), "FORBIDDEN", "{}", body);
+    let req = graphql_request(None, NOTIFICATIONS, json!({ "userId": "1" })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(error_code(&body), "UNAUTHENTICATED", "{}", body);
+
+    let body: Value = test::call_and_read_body_json(&app, list(&tokens[0], "1", false)).await;
+    let id = body["data"]["notifications"][0]["id"].as_str().unwrap().to_string();
+    let mark = r#"mutation Mark($id: ID!) { markNotificationRead(id: $id) { id read } }"#;
+    let req = graphql_request(Some(&tokens[1]), mark, json!({ "id": id })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(error_code(&body), "FORBIDDEN", "{}", body);
+    let req = graphql_request(Some(&tokens[0]), mark, json!({ "id": id })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["markNotificationRead"]["read"], true, "{}", body);
+    let req = graphql_request(Some(&tokens[0]), mark, json!({ "id": "0" })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(error_code(&body), "NOT_FOUND", "{}", body);
+
+    let body: Value = test::call_and_read_body_json(&app, list(&tokens[0], "1", true)).await;
+    assert_eq!(body["data"]["notifications"], json!([]), "{}", body);
+    assert_eq!(body["data"]["user"]["unreadNotificationCount"], 0);
+    let body: Value = test::call_and_read_body_json(&app, list(&tokens[0], "1", false)).await;
+    assert_eq!(body["data"]["notifications"][0]["read"], true, "{}", body);
+
+    // 全て既読にする（既読にした件数を返す）
+    let mark_all = r#"mutation MarkAll($userId: ID!) { markAllRead(userId: $userId) }"#;
+    let req = graphql_request(Some(&tokens[2]), mark_all, json!({ "userId": "3" })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["markAllRead"], 1, "{}", body);
+    let req = graphql_request(Some(&tokens[2]), mark_all, json!({ "userId": "3" })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["markAllRead"], 0, "{}", body);
+
+    // 削除したコメントの通知は消える
+    let delete = r#"mutation Delete($id: ID!) { deleteComment(id: $id) }"#;
+    let req = graphql_request(Some(&tokens[0]), delete, json!({ "id": reply_id })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let body: Value = test::call_and_read_body_json(&app, list(&tokens[1], "2", false)).await;
+    assert_eq!(body["data"]["notifications"], json!([]), "{}", body);
+}
+
+#[actix_web::test]
+async fn post_mentions_notify_once_published() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let admin = token(&test::call_and_read_body_json(&app, req).await);
+    let req = login_request("佐藤太郎").to_request();
+    let sato = token(&test::call_and_read_body_json(&app, req).await);
+    let call = |query: &str, variables: Value| {
+        graphql_request(Some(&admin), query, variables).to_request()
+    };
+    let kinds = |body: &Value| -> Vec<String> {
+        let list = body["data"]["notifications"].as_array().unwrap();
+        list.iter().map(|n| n["kind"].as_str().unwrap().to_string()).collect()
+    };
+    let sato_notifications = || {
+        graphql_request(Some(&sato), NOTIFICATIONS, json!({ "userId": "2" })).to_request()
+    };
+
+    // 下書きの間は通知しない
+    let create = r#"
+        mutation {
+            createPost(
+                input: { title: "下書き", body: "@user_2 と @user_1", authorId: "1", draft: true }
+            ) {
+                id
+            }
+        }
+    "#;
+    let body: Value = test::call_and_read_body_json(&app, call(create, json!({}))).await;
+    let id = body["data"]["createPost"]["id"].as_str().unwrap().to_string();
+    let body: Value = test::call_and_read_body_json(&app, sato_notifications()).await;
+    assert!(kinds(&body).is_empty(), "{}", body);
+
+    // 公開すると通知し、本文を書き換えても同じユーザーには再び通知しない（著者本人にも送らない）
+    let publish = r#"mutation Publish($id: ID!) { publishPost(id: $id) { id } }"#;
+    let body: Value = test::call_and_read_body_json(&app, call(publish, json!({ "id": id }))).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let update = r#"
+        mutation Update($id: ID!) { updatePost(input: { id: $id, body: "@user_2 さん再び" }) { id } }
+    "#;
+    let body: Value = test::call_and_read_body_json(&app, call(update, json!({ "id": id }))).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let body: Value = test::call_and_read_body_json(&app, sato_notifications()).await;
+    assert_eq!(kinds(&body), ["MENTION"], "{}", body);
+    assert!(body["data"]["notifications"][0]["comment"].is_null());
+    assert_eq!(body["data"]["notifications"][0]["post"]["id"], id.as_str());
+    let query = r#"{ notifications(userId: "1") { id } }"#;
+    let body: Value = test::call_and_read_body_json(&app, call(query, json!({}))).await;
+    assert_eq!(body["data"]["notifications"], json!([]), "{}", body);
+
+    // ゴミ箱にある間は含めず、復元すると戻る。完全に削除すると通知も消える
+    let trash = r#"mutation Trash($id: ID!) { deletePost(id: $id) }"#;
+    test::call_service(&app, call(trash, json!({ "id": id }))).await;
+    let body: Value = test::call_and_read_body_json(&app, sato_notifications()).await;
+    assert!(kinds(&body).is_empty(), "{}", body);
+    assert_eq!(body["data"]["user"]["unreadNotificationCount"], 0);
+    let restore = r#"mutation Restore($id: ID!) { restorePost(id: $id) { id } }"#;
+    test::call_service(&app, call(restore, json!({ "id": id }))).await;
+    let body: Value = test::call_and_read_body_json(&app, sato_notifications()).await;
+    assert_eq!(kinds(&body), ["MENTION"], "{}", body);
+
+    let purge = r#"mutation Purge($id: ID!) { purgePost(id: $id) }"#;
+    let body: Value = test::call_and_read_body_json(&app, call(purge, json!({ "id": id }))).await;
+    assert_eq!(body["data"]["purgePost"], true, "{}", body);
+    let body: Value = test::call_and_read_body_json(&app, sato_notifications()).await;
+    assert!(kinds(&body).is_empty(), "{}", body);
+}
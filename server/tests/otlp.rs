@@ -0,0 +1,111 @@
+// OTLPへのspanの送信（traceparentのトレースにつなげ、終了時に送り切る）
+use actix_web::{rt, web, App, HttpResponse, HttpServer};
+use serde_json::json;
+use std::net::{SocketAddr, TcpListener};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+mod common;
+use common::post_json;
+
+const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+const PARENT_SPAN_ID: &str = "00f067aa0ba902b7";
+
+fn hex_bytes(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|window| window == needle)
+}
+
+// 受け取ったエクスポートの本文（protobuf）を記録するだけのコレクター
+async fn export(body: web::Bytes, received: web::Data<Mutex<Vec<u8>>>) -> HttpResponse {
+    received.lock().unwrap().extend_from_slice(&body);
+    HttpResponse::Ok().content_type("application/x-protobuf").finish()
+}
+
+async fn post_with_traceparent(addr: SocketAddr, body: &str) {
+    let traceparent = format!("00-{}-{}-01", TRACE_ID, PARENT_SPAN_ID);
+    let head = format!(
+        "POST /api/graphql HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\n\
+         traceparent: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        traceparent,
+        body.len()
+    );
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(head.as_bytes()).await.unwrap();
+    stream.write_all(body.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+}
+
+#[cfg(unix)]
+#[actix_web::test]
+async fn spans_join_the_incoming_trace_and_are_flushed_on_shutdown() {
+    let received = web::Data::new(Mutex::new(Vec::new()));
+    let collector_data = received.clone();
+    let collector = HttpServer::new(move || {
+        App::new()
+            .app_data(collector_data.clone())
+            .route("/v1/traces", web::post().to(export))
+    })
+    .workers(1)
+    .disable_signals()
+    .bind(("127.0.0.1", 0))
+    .unwrap();
+    let collector_addr = collector.addrs()[0];
+    let collector = collector.run();
+    let collector_handle = collector.handle();
+    rt::spawn(collector);
+
+    let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let mut server = Command::new(env!("CARGO_BIN_EXE_blog-server"))
+        .env_remove("STORAGE")
+        .env_remove("DATABASE_URL")
+        .env_remove("DATA_FILE")
+        .env("OTEL_EXPORTER_OTLP_ENDPOINT", format!("http://{}", collector_addr))
+        .env("BIND_ADDR", "127.0.0.1")
+        .env("PORT", addr.port().to_string())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let started = Instant::now();
+    while post_json(addr, None, json!({ "query": "{ postsCount }" })).await.is_err() {
+        assert!(started.elapsed() < Duration::from_secs(30), "server did not start");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    post_with_traceparent(addr, r#"{"query":"query TracedHome { postsCount }"}"#).await;
+
+    // バッチの送信間隔を待たず、SIGTERMでの終了時に送ること
+    let status = Command::new("kill")
+        .args(["-TERM", &server.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let started = Instant::now();
+    let exit = loop {
+        if let Some(exit) = server.try_wait().unwrap() {
+            break exit;
+        }
+        assert!(started.elapsed() < Duration::from_secs(30), "server did not stop");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    };
+    assert!(exit.success());
+    collector_handle.stop(true).await;
+
+    // protobufの中にトレースIDと親のspanのID（どちらもバイト列）、操作名のspanがある
+    let received = received.lock().unwrap();
+    assert!(contains(&received, &hex_bytes(TRACE_ID)), "trace id was not propagated");
+    assert!(contains(&received, &hex_bytes(PARENT_SPAN_ID)), "parent span was not set");
+    assert!(contains(&received, b"TracedHome"));
+    assert!(contains(&received, b"storage.count_posts"));
+}
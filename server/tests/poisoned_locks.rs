@@ -0,0 +1,55 @@
+// ロック中のパニック（ロックが汚染されても、以降のリクエストはそのまま処理する）
+use actix_web::{test, App};
+use blog_server::{build_app_state, configure_app, MemoryStorage, Seed, Storage};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+mod common;
+use common::{graphql_request, login_request, token};
+
+#[actix_web::test]
+async fn requests_succeed_after_a_panic_while_holding_the_store_locks() {
+    let storage = Arc::new(MemoryStorage::seeded(&Seed::builtin()));
+
+    // 投稿とユーザーの更新はロックを持ったまま適用するので、そこでパニックさせる
+    let poisoning = storage.clone();
+    let panicked = tokio::spawn(async move {
+        let update = Box::new(|_: &mut _| -> async_graphql::Result<()> { panic!("in update") });
+        poisoning.update_post(&"1".into(), update).await
+    })
+    .await;
+    assert!(panicked.is_err_and(|e| e.is_panic()));
+    let poisoning = storage.clone();
+    let panicked = tokio::spawn(async move {
+        let update = Box::new(|_: &mut _| -> async_graphql::Result<()> { panic!("in update") });
+        poisoning.update_user(&"1".into(), update).await
+    })
+    .await;
+    assert!(panicked.is_err_and(|e| e.is_panic()));
+
+    let state = build_app_state(storage, None).await.unwrap();
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let query = r#"{ posts { title author { name } } user(id: "2") { name } }"#;
+    let req = graphql_request(None, query, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(
+        body["data"],
+        json!({
+            "posts": [{ "title": "はじめまして", "author": { "name": "髙橋慶祐" } }],
+            "user": { "name": "佐藤太郎" },
+        }),
+        "{}",
+        body
+    );
+
+    // 書き込みもできる
+    let req = login_request("髙橋慶祐").to_request();
+    let admin = token(&test::call_and_read_body_json(&app, req).await);
+    let update = r#"mutation { updatePost(input: { id: "1", title: "更新" }) { title } }"#;
+    let req = graphql_request(Some(&admin), update, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["updatePost"]["title"], "更新", "{}", body);
+    let req = test::TestRequest::get().uri("/readyz").to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 200);
+}
@@ -0,0 +1,95 @@
+// createPost / updatePost の入力チェック（extensions.validation）
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+const CREATE: &str = r#"
+    mutation Create($input: CreatePostInput!) {
+        createPost(input: $input) { id title body tags }
+    }
+"#;
+
+const UPDATE: &str = r#"
+    mutation Update($input: UpdatePostInput!) {
+        updatePost(input: $input) { id title tags }
+    }
+"#;
+
+#[actix_web::test]
+async fn create_post_reports_each_violation() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let login: Value = test::call_and_read_body_json(&app, req).await;
+    let token = token(&login);
+
+    // 空のものと重複は個数に含めない
+    let mut tags: Vec<String> = (0..12).map(|i| format!("タグ{}", i)).collect();
+    tags[2] = "あ".repeat(51);
+    tags[3] = "  ".to_string();
+    tags.push("タグ0".to_string());
+    let variables = json!({
+        "input": { "title": "   ", "body": "", "tags": tags, "authorId": "1" }
+    });
+    let req = graphql_request(Some(&token), CREATE, variables).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+
+    let errors = body["errors"].as_array().unwrap();
+    assert_eq!(errors.len(), 1, "{}", body);
+    assert_eq!(errors[0]["extensions"]["code"], "VALIDATION_FAILED");
+    assert_eq!(
+        errors[0]["extensions"]["validation"],
+        json!([
+            { "field": "title", "rule": "required" },
+            { "field": "body", "rule": "required" },
+            { "field": "tags", "index": 2, "rule": "maxLength", "limit": 50 },
+            { "field": "tags", "rule": "maxItems", "limit": 10 },
+        ])
+    );
+}
+
+#[actix_web::test]
+async fn lengths_are_counted_in_characters() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let login: Value = test::call_and_read_body_json(&app, req).await;
+    let token = token(&login);
+
+    // 200文字の日本語は600バイトだが上限内
+    let title = "題".repeat(200);
+    let variables = json!({
+        "input": {
+            "title": format!("  {}  ", title),
+            "body": "本文",
+            "tags": [" 日本語 ", "あ".repeat(50)],
+            "authorId": "1",
+        }
+    });
+    let req = graphql_request(Some(&token), CREATE, variables).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let post = &body["data"]["createPost"];
+    assert_eq!(post["title"], title);
+    assert_eq!(post["tags"][0], "日本語");
+    let id = post["id"].clone();
+
+    let variables = json!({ "input": { "id": id, "title": "題".repeat(201) } });
+    let req = graphql_request(Some(&token), UPDATE, variables).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["errors"][0]["extensions"]["code"], "VALIDATION_FAILED");
+    assert_eq!(
+        body["errors"][0]["extensions"]["validation"],
+        json!([{ "field": "title", "rule": "maxLength", "limit": 200 }])
+    );
+
+    // 指定しなかったフィールドはチェックしない
+    let variables = json!({ "input": { "id": id, "tags": ["更新"] } });
+    let req = graphql_request(Some(&token), UPDATE, variables).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    assert_eq!(body["data"]["updatePost"]["tags"], json!(["更新"]));
+}
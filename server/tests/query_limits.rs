@@ -0,0 +1,164 @@
+// クエリの深さの制限（MAX_QUERY_DEPTHのデフォルトは10、イントロスペクションのみのクエリは20まで）
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request};
+
+// GraphiQLがスキーマを取得するときに送るクエリ（graphql-jsのgetIntrospectionQuery）
+const INTROSPECTION: &str = r#"
+    query IntrospectionQuery {
+        __schema {
+            queryType { name }
+            mutationType { name }
+            subscriptionType { name }
+            types { ...FullType }
+            directives {
+                name
+                description
+                locations
+                args { ...InputValue }
+            }
+        }
+    }
+
+    fragment FullType on __Type {
+        kind
+        name
+        description
+        fields(includeDeprecated: true) {
+            name
+            description
+            args { ...InputValue }
+            type { ...TypeRef }
+            isDeprecated
+            deprecationReason
+        }
+        inputFields { ...InputValue }
+        interfaces { ...TypeRef }
+        enumValues(includeDeprecated: true) {
+            name
+            description
+            isDeprecated
+            deprecationReason
+        }
+        possibleTypes { ...TypeRef }
+    }
+
+    fragment InputValue on __InputValue {
+        name
+        description
+        type { ...TypeRef }
+        defaultValue
+    }
+
+    fragment TypeRef on __Type {
+        kind
+        name
+        ofType {
+            kind
+            name
+            ofType {
+                kind
+                name
+                ofType {
+                    kind
+                    name
+                    ofType {
+                        kind
+                        name
+                        ofType {
+                            kind
+                            name
+                            ofType {
+                                kind
+                                name
+                                ofType {
+                                    kind
+                                    name
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+"#;
+
+fn error_code(body: &Value) -> &str {
+    body["errors"][0]["extensions"]["code"].as_str().unwrap_or_default()
+}
+
+// 投稿→著者→著者の投稿…と、指定した深さまで入れ子にする
+// （深さはルートの選択セットを1と数えるので、フィールドの入れ子は深さ - 1段）
+fn nested_query(depth: usize) -> String {
+    let mut selection = "id".to_string();
+    for level in (2..=depth).rev() {
+        let field = match level {
+            2 => "post(id: \"1\")",
+            _ if level % 2 == 1 => "author",
+            _ => "posts(limit: 1)",
+        };
+        selection = format!("{} {{ {} }}", field, selection);
+    }
+    format!("{{ {} }}", selection)
+}
+
+// __typeの型の参照を、指定した深さまで入れ子にする
+fn nested_introspection(depth: usize) -> String {
+    let mut selection = "name".to_string();
+    for _ in 3..=depth {
+        selection = format!("ofType {{ {} }}", selection);
+    }
+    format!("{{ __type(name: \"Post\") {{ {} }} }}", selection)
+}
+
+#[actix_web::test]
+async fn rejects_queries_deeper_than_the_limit() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let query = nested_query(10);
+    let req = graphql_request(None, &query, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}: {}", query, body);
+    assert!(body["data"]["post"]["author"]["posts"].is_array(), "{}", body);
+
+    let query = nested_query(11);
+    let req = graphql_request(None, &query, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(error_code(&body), "QUERY_TOO_DEEP", "{}: {}", query, body);
+    assert_eq!(body["errors"][0]["extensions"]["depth"], 11);
+    assert_eq!(body["errors"][0]["extensions"]["maxDepth"], 10);
+    assert!(body["data"].is_null(), "{}", body);
+}
+
+#[actix_web::test]
+async fn introspection_has_its_own_depth_limit() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    // GraphiQLのクエリは通常のクエリの上限より深くても通る
+    let req = graphql_request(None, INTROSPECTION, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    assert_eq!(body["data"]["__schema"]["queryType"]["name"], "Query");
+
+    let query = nested_introspection(20);
+    let req = graphql_request(None, &query, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}: {}", query, body);
+
+    let query = nested_introspection(21);
+    let req = graphql_request(None, &query, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(error_code(&body), "QUERY_TOO_DEEP", "{}: {}", query, body);
+    assert_eq!(body["errors"][0]["extensions"]["maxDepth"], 20);
+
+    // 通常のフィールドを混ぜると通常の上限になる
+    let query = nested_introspection(11).replacen("{ ", "{ postsCount ", 1);
+    let req = graphql_request(None, &query, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(error_code(&body), "QUERY_TOO_DEEP", "{}: {}", query, body);
+    assert_eq!(body["errors"][0]["extensions"]["maxDepth"], 10);
+}
@@ -0,0 +1,80 @@
+// Post.wordCount と Post.readingTimeMinutes（本文の語数と読了時間の目安）
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+const CREATE: &str = r#"
+    mutation Create($body: String!) {
+        createPost(input: { title: "語数", body: $body, authorId: "1" }) {
+            id wordCount readingTimeMinutes
+        }
+    }
+"#;
+
+fn counts(post: &Value) -> (i64, i64) {
+    (post["wordCount"].as_i64().unwrap(), post["readingTimeMinutes"].as_i64().unwrap())
+}
+
+#[actix_web::test]
+async fn counts_words_and_cjk_characters() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let token = token(&test::call_and_read_body_json(&app, req).await);
+
+    let cases = [
+        // 日本語は句読点を除いた文字数
+        ("吾輩は**猫**である。名前はまだ無い。", 14),
+        // 英語は単語数（記法やリンク先のURLは数えない）
+        (
+            "# Title\n\nThe *quick* brown fox doesn't jump over the [lazy](https://a.example) dog.",
+            11,
+        ),
+        // 日本語と英語が混ざっている場合はそれぞれを数えて合計する
+        ("Rustで`async fn`を書く", 7),
+        // コードブロックと生のHTMLは数えない
+        ("説明\n\n```rust\nfn main() { println!(\"hello world\"); }\n```\n\n<div>html</div>", 2),
+    ];
+    for (body, expected) in cases {
+        let req = graphql_request(Some(&token), CREATE, json!({ "body": body })).to_request();
+        let response: Value = test::call_and_read_body_json(&app, req).await;
+        assert!(response["errors"].is_null(), "{}", response);
+        // 短い本文でも読了時間は1分
+        assert_eq!(counts(&response["data"]["createPost"]), (expected, 1), "{}", body);
+    }
+}
+
+#[actix_web::test]
+async fn reading_time_uses_words_and_characters_per_minute() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let token = token(&test::call_and_read_body_json(&app, req).await);
+
+    // 1分あたり200語、500文字（既定値）
+    let body = format!("{}\n\n{}", "word ".repeat(300), "字".repeat(1000));
+    let req = graphql_request(Some(&token), CREATE, json!({ "body": body })).to_request();
+    let response: Value = test::call_and_read_body_json(&app, req).await;
+    let post = &response["data"]["createPost"];
+    // 1.5分 + 2分 を切り上げる
+    assert_eq!(counts(post), (1300, 4));
+
+    // 本文を変えると数え直す
+    let update = r#"
+        mutation Update($id: ID!) {
+            updatePost(input: { id: $id, body: "短い本文" }) { wordCount readingTimeMinutes }
+        }
+    "#;
+    let req = graphql_request(Some(&token), update, json!({ "id": post["id"] })).to_request();
+    let response: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(response["errors"].is_null(), "{}", response);
+    assert_eq!(counts(&response["data"]["updatePost"]), (4, 1));
+
+    // 初期データの投稿も数えてある
+    let req = graphql_request(None, "{ post(id: \"1\") { wordCount } }", json!({})).to_request();
+    let response: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(response["data"]["post"]["wordCount"], 10);
+}
@@ -0,0 +1,71 @@
+// ユーザー登録（登録したパスワードでログインでき、ハッシュはどこにも返さない）
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request, token};
+
+const REGISTER: &str = r#"
+    mutation Register($name: String!, $password: String!) {
+        register(name: $name, handle: "new_user", password: $password) { id name role }
+    }
+"#;
+
+const LOGIN: &str = r#"
+    mutation Login($name: String!, $password: String!) {
+        login(name: $name, password: $password) { token user { id } }
+    }
+"#;
+
+fn error_code(body: &Value) -> &str {
+    body["errors"][0]["extensions"]["code"].as_str().unwrap_or_default()
+}
+
+#[actix_web::test]
+async fn registered_users_can_log_in_and_see_themselves() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let credentials = json!({ "name": "新規ユーザー", "password": "correct horse" });
+
+    // 短すぎるパスワードや名前と同じパスワードは登録できない
+    for password in ["short", "新規ユーザーさん"] {
+        let variables = json!({ "name": "新規ユーザーさん", "password": password });
+        let req = graphql_request(None, REGISTER, variables).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(error_code(&body), "VALIDATION_FAILED", "{}", body);
+    }
+
+    let req = graphql_request(None, REGISTER, credentials.clone()).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let user = body["data"]["register"].clone();
+    assert_eq!(user["name"], "新規ユーザー");
+    assert_eq!(user["role"], "READER");
+    // 同じ名前では登録できない
+    let req = graphql_request(None, REGISTER, credentials.clone()).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(error_code(&body), "CONFLICT", "{}", body);
+
+    // パスワードを間違えるとログインできない
+    let variables = json!({ "name": "新規ユーザー", "password": "wrong horse" });
+    let req = graphql_request(None, LOGIN, variables).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["data"].is_null(), "{}", body);
+
+    let req = graphql_request(None, LOGIN, credentials).to_request();
+    let login: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(login["data"]["login"]["user"]["id"], user["id"], "{}", login);
+    let me = "{ me { id name role } }";
+    let req = graphql_request(Some(&token(&login)), me, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    assert_eq!(body["data"]["me"], user);
+
+    // パスワードのハッシュはスキーマにない
+    let query = r#"{ __type(name: "User") { fields { name } } }"#;
+    let req = graphql_request(None, query, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let fields = body["data"]["__type"]["fields"].as_array().unwrap();
+    assert!(fields.iter().all(|field| !field["name"].as_str().unwrap().contains("password")));
+}
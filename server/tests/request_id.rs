@@ -0,0 +1,61 @@
+// X-Request-Id（レスポンスヘッダーとGraphQLのエラーのextensions.requestId）
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request};
+
+const UNAUTHENTICATED_MUTATION: &str = r#"
+    mutation {
+        createPost(input: { title: "テスト", body: "本文", authorId: "1" }) { id }
+    }
+"#;
+
+#[actix_web::test]
+async fn generates_request_id_for_errors() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = graphql_request(None, UNAUTHENTICATED_MUTATION, json!({})).to_request();
+    let res = test::call_service(&app, req).await;
+
+    let request_id = res.headers().get("x-request-id").unwrap().to_str().unwrap().to_string();
+    assert!(uuid::Uuid::parse_str(&request_id).is_ok(), "{}", request_id);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["errors"][0]["extensions"]["code"], "UNAUTHENTICATED");
+    assert_eq!(body["errors"][0]["extensions"]["requestId"], request_id);
+}
+
+#[actix_web::test]
+async fn batch_shares_incoming_request_id() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = test::TestRequest::post()
+        .uri("/api/graphql")
+        .insert_header(("X-Request-Id", "client-abc-123"))
+        .set_json(json!([
+            { "query": UNAUTHENTICATED_MUTATION },
+            { "query": 42 },
+        ]))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+
+    assert_eq!(res.headers().get("x-request-id").unwrap(), "client-abc-123");
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body[0]["errors"][0]["extensions"]["requestId"], "client-abc-123");
+    assert_eq!(body[1]["errors"][0]["extensions"]["requestId"], "client-abc-123");
+}
+
+#[actix_web::test]
+async fn replaces_unusable_request_id() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = test::TestRequest::get()
+        .uri("/healthz")
+        .insert_header(("X-Request-Id", "a".repeat(200)))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+
+    let request_id = res.headers().get("x-request-id").unwrap().to_str().unwrap();
+    assert!(uuid::Uuid::parse_str(request_id).is_ok(), "{}", request_id);
+}
@@ -0,0 +1,107 @@
+// 読み取り専用のREST API（/api/posts、/api/posts/{id}、/api/users/{id}）
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+#[actix_web::test]
+async fn lists_published_posts() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let token = token(&test::call_and_read_body_json(&app, req).await);
+    let create = r#"
+        mutation Create($title: String!, $draft: Boolean) {
+            createPost(input: {
+                title: $title, body: "本文", tags: ["REST"], authorId: "1", draft: $draft
+            }) { id }
+        }
+    "#;
+    let mut ids = Vec::new();
+    for (title, draft) in [("REST", false), ("下書き", true)] {
+        let variables = json!({ "title": title, "draft": draft });
+        let req = graphql_request(Some(&token), create, variables).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        ids.push(body["data"]["createPost"]["id"].as_str().unwrap().to_string());
+    }
+
+    let req = test::TestRequest::get().uri("/api/posts").to_request();
+    let posts: Value = test::call_and_read_body_json(&app, req).await;
+    let titles: Vec<&str> = posts
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|post| post["title"].as_str().unwrap())
+        .collect();
+    assert_eq!(titles, ["REST", "はじめまして"]);
+
+    let req = test::TestRequest::get().uri("/api/posts?tag=rest&limit=1").to_request();
+    let posts: Value = test::call_and_read_body_json(&app, req).await;
+    let post = &posts[0];
+    assert_eq!(posts.as_array().unwrap().len(), 1);
+    assert_eq!(post["id"], ids[0]);
+    assert_eq!(post["author_id"], "1");
+    assert_eq!(post["tags"], json!(["rest"]));
+    assert!(post.get("text_stats").is_none(), "{}", post);
+
+    let req = test::TestRequest::get().uri("/api/posts?offset=1").to_request();
+    let posts: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(posts[0]["id"], "1");
+
+    // 下書きは取得できない
+    let uri = format!("/api/posts/{}", ids[1]);
+    let res = test::call_service(&app, test::TestRequest::get().uri(&uri).to_request()).await;
+    assert_eq!(res.status(), 404);
+    let uri = format!("/api/posts/{}", ids[0]);
+    let req = test::TestRequest::get().uri(&uri).to_request();
+    let post: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(post["title"], "REST");
+}
+
+#[actix_web::test]
+async fn returns_user_without_password_hash() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+
+    let req = test::TestRequest::get().uri("/api/users/1").to_request();
+    let user: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(user["name"], "髙橋慶祐");
+    assert_eq!(user["role"], "Admin");
+    assert!(user.get("password_hash").is_none(), "{}", user);
+
+    let req = test::TestRequest::get().uri("/api/users/999").to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 404);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body, json!({ "error": { "code": "NOT_FOUND", "message": "User not found" } }));
+}
+
+#[actix_web::test]
+async fn rejects_bad_params_and_writes() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+
+    for uri in ["/api/posts?limit=abc", "/api/posts?limit=1000", "/api/posts?offset=-1"] {
+        let res = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+        assert_eq!(res.status(), 400, "{}", uri);
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["error"]["code"], "VALIDATION_FAILED", "{}", uri);
+        assert!(body["error"]["message"].is_string(), "{}", body);
+    }
+    let req = test::TestRequest::get().uri("/api/posts/not-an-id").to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 400);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["error"]["code"], "INVALID_ID");
+
+    // 書き込みのメソッドは受け付けない
+    let req = test::TestRequest::delete().uri("/api/posts/1").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 405);
+    let req = test::TestRequest::post().uri("/api/posts").set_json(json!({})).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 405);
+    let req = test::TestRequest::get().uri("/api/posts/1").to_request();
+    let post: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(post["title"], "はじめまして");
+}
@@ -0,0 +1,87 @@
+// DateTimeScalarの入力の形式と出力
+use async_graphql::{Number, ScalarType, Value};
+use blog_server::DateTimeScalar;
+
+fn parse(value: Value) -> Result<String, String> {
+    match DateTimeScalar::parse(value) {
+        Ok(dt) => Ok(dt.to_value().into_json().unwrap().as_str().unwrap().to_string()),
+        Err(e) => Err(e.into_server_error(Default::default()).message),
+    }
+}
+
+fn string(s: &str) -> Value {
+    Value::String(s.to_string())
+}
+
+#[test]
+fn accepts_rfc3339_and_outputs_utc() {
+    let expected = Ok("2024-06-01T00:00:00+00:00".to_string());
+    assert_eq!(parse(string("2024-06-01T09:00:00+09:00")), expected);
+    assert_eq!(parse(string("2024-06-01T00:00:00Z")), expected);
+    // Tの代わりに空白
+    assert_eq!(parse(string("2024-06-01 09:00:00+09:00")), expected);
+    assert_eq!(
+        parse(string("2024-06-01T00:00:00.250Z")),
+        Ok("2024-06-01T00:00:00.250+00:00".to_string())
+    );
+}
+
+#[test]
+fn accepts_dates_as_midnight_utc() {
+    assert_eq!(parse(string("2024-06-01")), Ok("2024-06-01T00:00:00+00:00".to_string()));
+    assert_eq!(parse(string("2024-02-29")), Ok("2024-02-29T00:00:00+00:00".to_string()));
+}
+
+#[test]
+fn accepts_unix_seconds_and_milliseconds() {
+    let seconds = Value::Number(Number::from(1718000000));
+    assert_eq!(parse(seconds), Ok("2024-06-10T06:13:20+00:00".to_string()));
+    let millis = Value::Number(Number::from(1718000000123i64));
+    assert_eq!(parse(millis), Ok("2024-06-10T06:13:20.123+00:00".to_string()));
+    let before_epoch = Value::Number(Number::from(-86400));
+    assert_eq!(parse(before_epoch), Ok("1969-12-31T00:00:00+00:00".to_string()));
+    // ミリ秒とみなす境界
+    let boundary = Value::Number(Number::from(100_000_000_000i64));
+    assert_eq!(parse(boundary), Ok("1973-03-03T09:46:40+00:00".to_string()));
+}
+
+#[test]
+fn accepts_leap_seconds() {
+    let parsed = parse(string("2016-12-31T23:59:60Z")).unwrap();
+    assert!(parsed.starts_with("2016-12-31T23:59:60"), "{}", parsed);
+    assert!(parse(string("2016-12-31T23:59:61Z")).is_err());
+}
+
+#[test]
+fn rejects_invalid_input_with_accepted_formats() {
+    for input in [
+        "2024-02-30",
+        "2024-06-01T09:00:00",
+        "2024-06-01T24:00:00Z",
+        "2024/06/01",
+        "1718000000",
+        "",
+    ] {
+        let error = parse(string(input)).unwrap_err();
+        assert!(error.contains(&format!("Invalid DateTime \"{}\"", input)), "{}", error);
+        assert!(error.contains("expected an RFC 3339 date-time"), "{}", error);
+    }
+
+    let error = parse(Value::Number(Number::from_f64(1.5).unwrap())).unwrap_err();
+    assert!(error.contains("Invalid DateTime 1.5"), "{}", error);
+    assert!(parse(Value::Boolean(true)).is_err());
+
+    // 長い値は切り詰める
+    let error = parse(string(&"9".repeat(500))).unwrap_err();
+    assert!(error.len() < 400, "{}", error);
+}
+
+#[test]
+fn rejects_out_of_range_unix_time() {
+    for n in [i64::MAX, i64::MIN] {
+        let error = parse(Value::Number(Number::from(n))).unwrap_err();
+        assert!(error.contains("DateTime out of range"), "{}", error);
+    }
+    let error = parse(Value::Number(Number::from(u64::MAX))).unwrap_err();
+    assert!(error.contains("Invalid DateTime"), "{}", error);
+}
@@ -0,0 +1,87 @@
+// 大量の投稿（IDでの取得が投稿数に比例して遅くならないこと）とIDの重複
+use actix_web::{test, App};
+use blog_server::{build_app_state, configure_app, MemoryStorage, Seed, Storage};
+use chrono::{Duration, TimeZone, Utc};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Instant;
+
+mod common;
+use common::graphql_request;
+
+const LOOKUPS: usize = 10_000;
+
+// 1秒ずつ公開日時をずらしたcount件の投稿
+fn seed_with_posts(name: &str, count: usize) -> Seed {
+    let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+    let posts: Vec<Value> = (1..=count)
+        .map(|i| {
+            json!({
+                "id": i.to_string(),
+                "title": format!("投稿{}", i),
+                "body": "本文",
+                "author_id": "1",
+                "published_at": (start + Duration::seconds(i as i64)).to_rfc3339(),
+            })
+        })
+        .collect();
+    let users = [json!({ "id": "1", "name": "Admin", "role": "ADMIN" })];
+    let seed = json!({ "users": users, "posts": posts });
+    let file_name = format!("blog-scale-{}-{}.json", std::process::id(), name);
+    let path = std::env::temp_dir().join(file_name);
+    std::fs::write(&path, seed.to_string()).unwrap();
+    let seed = Seed::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    seed
+}
+
+// 投稿全体に散らばるIDでLOOKUPS回取得するのにかかった時間
+async fn time_lookups(storage: &MemoryStorage, count: usize) -> std::time::Duration {
+    let started = Instant::now();
+    for i in 0..LOOKUPS {
+        let id = (i * 7919 % count + 1).to_string();
+        assert!(storage.get_post(&id.into()).await.unwrap().is_some());
+    }
+    started.elapsed()
+}
+
+#[actix_web::test]
+async fn lookups_by_id_do_not_degrade_with_100k_posts() {
+    let small = MemoryStorage::seeded(&seed_with_posts("small", 100));
+    let large = Arc::new(MemoryStorage::seeded(&seed_with_posts("large", 100_000)));
+
+    // 線形に探すと1000倍かかる。マシンの揺らぎを見込んでも十分に小さい差に収まること
+    let small_time = time_lookups(&small, 100).await;
+    let large_time = time_lookups(&large, 100_000).await;
+    assert!(
+        large_time < small_time * 10 + std::time::Duration::from_millis(50),
+        "100 posts: {:?}, 100k posts: {:?}",
+        small_time,
+        large_time
+    );
+
+    // 一覧は並べ替えずに新しい順
+    let state = build_app_state(large.clone(), None).await.unwrap();
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let query = r#"{ posts(limit: 2) { id } post(id: "1") { title } postsCount }"#;
+    let req = graphql_request(None, query, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(
+        body["data"],
+        json!({
+            "posts": [{ "id": "100000" }, { "id": "99999" }],
+            "post": { "title": "投稿1" },
+            "postsCount": 100_000,
+        }),
+        "{}",
+        body
+    );
+
+    // 同じIDの投稿やユーザーは追加できない
+    let post = large.get_post(&"1".into()).await.unwrap().unwrap();
+    assert!(large.insert_post(post).await.is_err());
+    let user = large.get_user(&"1".into()).await.unwrap().unwrap();
+    let error = large.insert_user(user).await.unwrap_err();
+    assert_eq!(error.message, "Duplicate user id: 1");
+    assert_eq!(large.count_posts(&Default::default()).await.unwrap(), 100_000);
+}
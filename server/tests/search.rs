@@ -0,0 +1,66 @@
+// searchPosts（タイトルと本文の全文検索。初期データに合わせて日本語で確かめる）
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+const SEARCH: &str = r#"query Search($query: String!) { searchPosts(query: $query) { title } }"#;
+
+fn titles(body: &Value) -> Vec<&str> {
+    body["data"]["searchPosts"]
+        .as_array()
+        .unwrap_or_else(|| panic!("{}", body))
+        .iter()
+        .map(|post| post["title"].as_str().unwrap())
+        .collect()
+}
+
+#[actix_web::test]
+async fn searches_japanese_titles_and_bodies() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let admin = token(&test::call_and_read_body_json(&app, req).await);
+
+    // 公開日時は古い順
+    let create = r#"
+        mutation Create($title: String!, $body: String!, $at: DateTimeScalar!) {
+            createPost(input: { title: $title, body: $body, scheduledAt: $at }) { id }
+        }
+    "#;
+    let posts = [
+        ("旅行記", "東京から大阪まで歩いた。", "2024-01-01T00:00:00Z"),
+        ("東京の桜", "今年は早く咲いた。", "2024-02-01T00:00:00Z"),
+        ("勉強会", "東京でRustの勉強会を開いた。", "2024-03-01T00:00:00Z"),
+        ("Rustと東京", "参加者の感想。", "2024-04-01T00:00:00Z"),
+    ];
+    for (title, body, at) in posts {
+        let variables = json!({ "title": title, "body": body, "at": at });
+        let req = graphql_request(Some(&admin), create, variables).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert!(body["errors"].is_null(), "{}", body);
+    }
+    let search = |query: &str| graphql_request(None, SEARCH, json!({ "query": query }));
+
+    // タイトルに一致したものが先で、同順位は新しい順
+    let body: Value = test::call_and_read_body_json(&app, search("東京").to_request()).await;
+    assert_eq!(titles(&body), ["Rustと東京", "東京の桜", "勉強会", "旅行記"]);
+
+    // 複数語はAND。大文字小文字と全角スペースの区切りも区別しない
+    for query in ["rust 東京", "東京　RUST"] {
+        let body: Value = test::call_and_read_body_json(&app, search(query).to_request()).await;
+        assert_eq!(titles(&body), ["Rustと東京", "勉強会"], "{}", query);
+    }
+    let body: Value = test::call_and_read_body_json(&app, search("最初の投稿").to_request()).await;
+    assert_eq!(titles(&body), ["はじめまして"]);
+    let body: Value = test::call_and_read_body_json(&app, search("京都").to_request()).await;
+    assert_eq!(titles(&body), Vec::<&str>::new());
+
+    // 空白だけなら全件ではなく空
+    for query in ["", "  ", "　"] {
+        let body: Value = test::call_and_read_body_json(&app, search(query).to_request()).await;
+        assert_eq!(titles(&body), Vec::<&str>::new(), "{:?}", query);
+    }
+}
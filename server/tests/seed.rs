@@ -0,0 +1,96 @@
+// SEED_FILEの読み込みと検証
+use blog_server::Seed;
+use std::path::PathBuf;
+
+// テストごとに別の一時ファイルに書く
+fn write_seed(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("blog-seed-{}-{}", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn loads_json_seed() {
+    let path = write_seed(
+        "valid.json",
+        r#"{
+            "users": [{ "id": "10", "name": "Alice", "role": "ADMIN" }],
+            "posts": [{
+                "id": "20", "title": "Hello", "body": "本文", "author_id": "10",
+                "tags": ["x"], "published_at": "2024-03-01T09:00:00+09:00"
+            }]
+        }"#,
+    );
+    let seed = Seed::load(&path).unwrap();
+    assert_eq!(seed.user_count(), 1);
+    assert_eq!(seed.post_count(), 1);
+}
+
+#[test]
+fn loads_toml_seed() {
+    let path = write_seed(
+        "valid.toml",
+        r#"
+        [[users]]
+        id = "10"
+        name = "Alice"
+
+        [[posts]]
+        id = "20"
+        title = "Hello"
+        body = "本文"
+        author_id = "10"
+        published_at = 2024-03-01T00:00:00Z
+        "#,
+    );
+    let seed = Seed::load(&path).unwrap();
+    assert_eq!(seed.post_count(), 1);
+}
+
+#[test]
+fn reports_every_invalid_field() {
+    let path = write_seed(
+        "invalid.json",
+        r#"{
+            "users": [{ "id": "10", "name": "Alice", "role": "OWNER", "avatar_url": "htp://oops" }],
+            "posts": [
+                { "id": "20", "title": "Hello", "body": "", "author_id": "nobody" },
+                { "id": "20", "title": "Hello", "body": "", "author_id": "10" },
+                { "id": "hello", "title": "Hello", "body": "", "author_id": "10" }
+            ]
+        }"#,
+    );
+    let errors = Seed::load(&path).err().unwrap();
+    assert!(errors.contains("users[0].role: expected ADMIN, AUTHOR or READER, got OWNER"));
+    assert!(errors.contains(r#"users[0].avatar_url: Unsupported URL scheme "htp""#), "{}", errors);
+    assert!(errors.contains(r#"posts[0].author_id: user "nobody" does not exist"#));
+    assert!(errors.contains(r#"posts[1].id: duplicate post id "20""#));
+    assert!(errors.contains(r#"posts[2].id: expected a UUID or an integer, got "hello""#));
+}
+
+#[test]
+fn reports_syntax_error_position() {
+    let path = write_seed("syntax.json", "{\n  \"users\": [\n    { \"id\": \"a\" }\n  ]\n}");
+    let errors = Seed::load(&path).err().unwrap();
+    assert!(errors.contains("missing field `name` at line 3"), "{}", errors);
+}
+
+#[test]
+fn reports_invalid_and_duplicate_handles() {
+    let path = write_seed(
+        "handles.json",
+        r#"{
+            "users": [
+                { "id": "10", "name": "Alice", "handle": "Alice" },
+                { "id": "11", "name": "Bob", "handle": "alice" },
+                { "id": "12", "name": "Carol", "handle": "c-a-r-o-l" },
+                { "id": "13", "name": "Alice" }
+            ]
+        }"#,
+    );
+    let errors = Seed::load(&path).err().unwrap();
+    assert!(errors.contains(r#"users[1].handle: handle "alice" is already used"#), "{}", errors);
+    assert!(errors.contains("users[2].handle: Handle must be 3 to 30"), "{}", errors);
+    // 省略したハンドルは指定されたものと重ならないように作る
+    assert!(!errors.contains("users[3]"), "{}", errors);
+}
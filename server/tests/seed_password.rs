@@ -0,0 +1,40 @@
+// 初期ユーザーのパスワード（SEED_USER_PASSWORD、未指定ならランダム）
+// 環境変数を書き換えるので、このファイルのテストは1つにまとめる
+use actix_web::{test, App};
+use blog_server::{build_app_state, configure_app, MemoryStorage, Seed};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+mod common;
+use common::graphql_request;
+
+async fn login(seed: &Seed, password: &str) -> Value {
+    let state = build_app_state(Arc::new(MemoryStorage::seeded(seed)), None).await.unwrap();
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let query = r#"
+        mutation Login($password: String!) {
+            login(name: "髙橋慶祐", password: $password) { user { id } }
+        }
+    "#;
+    let req = graphql_request(None, query, json!({ "password": password })).to_request();
+    test::call_and_read_body_json(&app, req).await
+}
+
+#[actix_web::test]
+async fn seed_users_get_the_configured_or_a_generated_password() {
+    // 未指定なら開発用のパスワードではログインできない
+    std::env::remove_var("SEED_USER_PASSWORD");
+    let seed = Seed::from_env().unwrap();
+    let body = login(&seed, "password").await;
+    assert!(body["data"].is_null(), "{}", body);
+
+    std::env::set_var("SEED_USER_PASSWORD", "correct horse battery");
+    let seed = Seed::from_env();
+    std::env::remove_var("SEED_USER_PASSWORD");
+    let body = login(&seed.unwrap(), "correct horse battery").await;
+    assert_eq!(body["data"]["login"]["user"]["id"], "1", "{}", body);
+
+    // 組み込みのデータをそのまま使うテストでは開発用のパスワード
+    let body = login(&Seed::builtin(), "password").await;
+    assert_eq!(body["data"]["login"]["user"]["id"], "1", "{}", body);
+}
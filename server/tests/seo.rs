@@ -0,0 +1,85 @@
+// 投稿のSEO用フィールド（seoDescription / canonicalUrl）
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+const CREATE: &str = r#"
+    mutation Create($description: String, $url: Url) {
+        createPost(input: {
+            title: "SEO", body: "**本文**の抜粋です。", authorId: "1",
+            seoDescription: $description, canonicalUrl: $url
+        }) { id seoDescription canonicalUrl }
+    }
+"#;
+
+#[actix_web::test]
+async fn seo_description_falls_back_to_excerpt() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let token = token(&test::call_and_read_body_json(&app, req).await);
+
+    let req = graphql_request(Some(&token), CREATE, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let post = &body["data"]["createPost"];
+    assert_eq!(post["seoDescription"], "本文の抜粋です。");
+    assert_eq!(post["canonicalUrl"], Value::Null);
+
+    let update = r#"
+        mutation Update($input: UpdatePostInput!) {
+            updatePost(input: $input) { seoDescription canonicalUrl }
+        }
+    "#;
+    let input = json!({
+        "id": post["id"],
+        "seoDescription": " 検索結果に出す説明 ",
+        "canonicalUrl": "https://example.com/posts/seo",
+    });
+    let req = graphql_request(Some(&token), update, json!({ "input": input })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(
+        body["data"]["updatePost"],
+        json!({
+            "seoDescription": "検索結果に出す説明",
+            "canonicalUrl": "https://example.com/posts/seo",
+        })
+    );
+
+    // nullで削除すると抜粋に戻る
+    let input = json!({ "id": post["id"], "seoDescription": null, "canonicalUrl": null });
+    let req = graphql_request(Some(&token), update, json!({ "input": input })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(
+        body["data"]["updatePost"],
+        json!({ "seoDescription": "本文の抜粋です。", "canonicalUrl": null })
+    );
+}
+
+#[actix_web::test]
+async fn rejects_long_description_and_invalid_url() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let token = token(&test::call_and_read_body_json(&app, req).await);
+
+    let variables = json!({ "description": "説".repeat(161) });
+    let req = graphql_request(Some(&token), CREATE, variables).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let error = &body["errors"][0];
+    assert_eq!(error["extensions"]["code"], "VALIDATION_FAILED");
+    assert_eq!(
+        error["extensions"]["validation"],
+        json!([{ "field": "seoDescription", "rule": "maxLength", "limit": 160 }])
+    );
+
+    let variables = json!({ "description": "説".repeat(160), "url": "ftp://example.com/" });
+    let req = graphql_request(Some(&token), CREATE, variables).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["data"].is_null(), "{}", body);
+    let message = body["errors"][0]["message"].as_str().unwrap();
+    assert!(message.contains("Unsupported URL scheme"), "{}", message);
+}
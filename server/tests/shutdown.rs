@@ -0,0 +1,56 @@
+// 終了処理（新しい接続を断り、処理中のリクエストは最後まで返す）
+use actix_web::{rt, App, HttpServer};
+use blog_server::{configure_app, spawn_background_tasks};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+mod common;
+use common::app_state;
+
+#[actix_web::test]
+async fn in_flight_request_completes_during_shutdown() {
+    let state = app_state().await;
+    let server_state = state.clone();
+    let server = HttpServer::new(move || {
+        App::new().configure(|cfg| configure_app(cfg, &server_state))
+    })
+    .workers(1)
+    .shutdown_timeout(5)
+    .disable_signals()
+    .bind(("127.0.0.1", 0))
+    .unwrap();
+    let addr = server.addrs()[0];
+    let server = server.run();
+    let handle = server.handle();
+    let server = rt::spawn(server);
+    let background_tasks = spawn_background_tasks(&state);
+
+    // 本文を途中まで送った状態で終了を始め、遅いクライアントのリクエストを再現する
+    let body = r#"{"query":"{ postsCount }"}"#;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let head = format!(
+        "POST /api/graphql HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await.unwrap();
+    stream.write_all(&body.as_bytes()[..10]).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let stop = rt::spawn(async move { handle.stop(true).await });
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(TcpStream::connect(addr).await.is_err(), "new connections should be refused");
+    assert!(!stop.is_finished(), "shutdown should wait for the in-flight request");
+
+    stream.write_all(&body.as_bytes()[10..]).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+    assert!(response.ends_with(r#"{"data":{"postsCount":1}}"#), "{}", response);
+
+    stop.await.unwrap();
+    server.await.unwrap().unwrap();
+    background_tasks.shutdown().await;
+    state.close().await;
+}
@@ -0,0 +1,62 @@
+// /sitemap.xml（公開済みの投稿のサイトマップ）
+use actix_web::dev::ServiceResponse;
+use actix_web::http::header;
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+async fn xml_body(res: ServiceResponse) -> String {
+    assert_eq!(res.status(), 200);
+    let content_type = res.headers().get(header::CONTENT_TYPE).unwrap();
+    assert_eq!(content_type, "application/xml; charset=utf-8");
+    String::from_utf8(test::read_body(res).await.to_vec()).unwrap()
+}
+
+#[actix_web::test]
+async fn sitemap_lists_published_posts() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+
+    let req = test::TestRequest::get().uri("/sitemap.xml").to_request();
+    let xml = xml_body(test::call_service(&app, req).await).await;
+    assert!(xml.contains("<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">"));
+    assert_eq!(xml.matches("<url>").count(), 1, "{}", xml);
+    assert!(xml.contains("<changefreq>monthly</changefreq><priority>0.5</priority>"), "{}", xml);
+
+    // 投稿を追加すると（データの版が変わるので）作り直す。下書きは載せない
+    let req = login_request("髙橋慶祐").to_request();
+    let token = token(&test::call_and_read_body_json(&app, req).await);
+    let create = r#"
+        mutation Create($title: String!, $draft: Boolean) {
+            createPost(input: { title: $title, body: "本文", authorId: "1", draft: $draft }) {
+                slug updatedAt
+            }
+        }
+    "#;
+    let mut created = Vec::new();
+    for (title, draft) in [("Sitemap Entry", false), ("Draft Entry", true)] {
+        let variables = json!({ "title": title, "draft": draft });
+        let req = graphql_request(Some(&token), create, variables).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert!(body["errors"].is_null(), "{}", body);
+        created.push(body["data"]["createPost"].clone());
+    }
+
+    let req = test::TestRequest::get().uri("/sitemap.xml").to_request();
+    let xml = xml_body(test::call_service(&app, req).await).await;
+    assert_eq!(xml.matches("<url>").count(), 2, "{}", xml);
+    assert!(xml.contains("<loc>http://localhost:8000/posts/sitemap-entry</loc>"), "{}", xml);
+    assert!(!xml.contains("draft-entry"), "{}", xml);
+    // lastmodは秒までのRFC 3339
+    let updated_at = created[0]["updatedAt"].as_str().unwrap();
+    assert!(xml.contains(&format!("<lastmod>{}", &updated_at[..19])), "{}", xml);
+
+    // 1ページに収まる場合は1ページ目だけがある
+    let req = test::TestRequest::get().uri("/sitemap.xml?page=1").to_request();
+    assert_eq!(xml_body(test::call_service(&app, req).await).await, xml);
+    let req = test::TestRequest::get().uri("/sitemap.xml?page=2").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 404);
+}
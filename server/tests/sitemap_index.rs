@@ -0,0 +1,47 @@
+// SITEMAP_PAGE_SIZEを超える場合のサイトマップインデックス
+// 環境変数を書き換えるので、このファイルのテストは1つにまとめる
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+#[actix_web::test]
+async fn sitemap_index_points_to_pages() {
+    std::env::set_var("SITEMAP_PAGE_SIZE", "2");
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let token = token(&test::call_and_read_body_json(&app, req).await);
+    let create = r#"
+        mutation Create($title: String!) {
+            createPost(input: { title: $title, body: "本文", authorId: "1" }) { id }
+        }
+    "#;
+    for title in ["one", "two"] {
+        let req = graphql_request(Some(&token), create, json!({ "title": title })).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert!(body["errors"].is_null(), "{}", body);
+    }
+
+    // 初期データと合わせて3件なので2ページ
+    let req = test::TestRequest::get().uri("/sitemap.xml").to_request();
+    let index = String::from_utf8(test::call_and_read_body(&app, req).await.to_vec()).unwrap();
+    assert!(index.contains("<sitemapindex"), "{}", index);
+    assert!(index.contains("<loc>http://localhost:8000/sitemap.xml?page=1</loc>"), "{}", index);
+    assert!(index.contains("<loc>http://localhost:8000/sitemap.xml?page=2</loc>"), "{}", index);
+    assert_eq!(index.matches("<sitemap>").count(), 2);
+
+    let mut urls = 0;
+    for page in [1, 2] {
+        let uri = format!("/sitemap.xml?page={}", page);
+        let req = test::TestRequest::get().uri(&uri).to_request();
+        let xml = String::from_utf8(test::call_and_read_body(&app, req).await.to_vec()).unwrap();
+        assert!(xml.contains("<urlset"), "{}", xml);
+        urls += xml.matches("<url>").count();
+    }
+    assert_eq!(urls, 3);
+    let req = test::TestRequest::get().uri("/sitemap.xml?page=3").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 404);
+}
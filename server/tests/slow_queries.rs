@@ -0,0 +1,45 @@
+// 遅い操作（SLOW_QUERY_MSを超えた操作をWARNで出し、メトリクスで数える）
+// 環境変数を書き換えるので、このファイルのテストは1つにまとめる
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::json;
+use tracing_subscriber::layer::SubscriberExt;
+
+mod common;
+use common::{app_state, graphql_request, LogCapture};
+
+#[actix_web::test]
+async fn operations_over_the_threshold_are_logged_and_counted() {
+    let logs = LogCapture::default();
+    let subscriber = tracing_subscriber::registry().with(logs.clone());
+    let _guard = tracing::subscriber::set_default(subscriber);
+    // 0msにすると全ての操作が遅い操作になる
+    std::env::set_var("SLOW_QUERY_MS", "0");
+    let state = app_state().await;
+    std::env::remove_var("SLOW_QUERY_MS");
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+
+    let query = "query Search($tag: String) { posts(tag: $tag) { title } }";
+    let long_query = format!("{}{}", query, " ".repeat(600));
+    for query in [query, long_query.as_str()] {
+        let req = graphql_request(None, query, json!({ "tag": "秘密のタグ" })).to_request();
+        test::call_service(&app, req).await;
+    }
+
+    let req = test::TestRequest::get().uri("/metrics").to_request();
+    let body = test::call_and_read_body(&app, req).await;
+    let text = String::from_utf8(body.to_vec()).unwrap();
+    assert!(text.contains("graphql_slow_operations_total 2"), "{}", text);
+
+    // 変数は名前だけを出し、クエリは500文字で切り詰める
+    let slow = logs.events("slow graphql operation");
+    assert_eq!(slow.len(), 2);
+    assert_eq!(slow[0]["level"], "WARN");
+    assert_eq!(slow[0]["operation"], "Search");
+    assert_eq!(slow[0]["query"], query);
+    assert_eq!(slow[0]["variables"], "tag");
+    assert_eq!(slow[0]["threshold_ms"], "0");
+    assert!(!slow[0].values().any(|value| value.contains("秘密のタグ")));
+    assert_eq!(slow[1]["query"].chars().count(), 501);
+    assert!(slow[1]["query"].ends_with('…'));
+}
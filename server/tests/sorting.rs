@@ -0,0 +1,76 @@
+// postsのsort引数（タグでの絞り込みとページングとの組み合わせ）
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+const POSTS: &str = r#"
+    query Posts(
+        $tag: String, $sort: PostSort! = PUBLISHED_AT_DESC, $limit: Int! = 100, $offset: Int! = 0
+    ) {
+        posts(tag: $tag, sort: $sort, limit: $limit, offset: $offset) { title }
+    }
+"#;
+
+fn titles(body: &Value) -> Vec<&str> {
+    body["data"]["posts"]
+        .as_array()
+        .unwrap_or_else(|| panic!("{}", body))
+        .iter()
+        .map(|post| post["title"].as_str().unwrap())
+        .collect()
+}
+
+#[actix_web::test]
+async fn sorts_posts_and_composes_with_tag_and_pagination() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let admin = token(&test::call_and_read_body_json(&app, req).await);
+
+    // 公開日時は並べた順（1月から）
+    let create = r#"
+        mutation Create($title: String!, $tags: [String!]!, $at: DateTimeScalar!) {
+            createPost(input: { title: $title, body: "本文", tags: $tags, scheduledAt: $at }) {
+                id
+            }
+        }
+    "#;
+    let posts = ["カメラ", "apple", "漢字", "Banana", "いちご", "Apple", "cherry"];
+    for (i, title) in posts.iter().enumerate() {
+        let variables = json!({
+            "title": title,
+            "tags": ["blog"],
+            "at": format!("2024-{:02}-01T00:00:00Z", i + 1),
+        });
+        let req = graphql_request(Some(&admin), create, variables).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert!(body["errors"].is_null(), "{}", body);
+    }
+    let list = |variables: Value| graphql_request(None, POSTS, variables).to_request();
+
+    // デフォルトは新しい順
+    let body: Value = test::call_and_read_body_json(&app, list(json!({ "limit": 3 }))).await;
+    assert_eq!(titles(&body), ["はじめまして", "cherry", "Apple"]);
+    let req = list(json!({ "tag": "blog", "sort": "PUBLISHED_AT_ASC", "limit": 3 }));
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(titles(&body), ["カメラ", "apple", "漢字"]);
+
+    // 大文字小文字を区別せず、同じなら大文字が先。かな・漢字はコードポイント順
+    let req = list(json!({ "tag": "blog", "sort": "TITLE_ASC", "limit": 5 }));
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(titles(&body), ["Apple", "apple", "Banana", "cherry", "いちご"]);
+    let req = list(json!({ "tag": "blog", "sort": "TITLE_ASC", "limit": 5, "offset": 5 }));
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(titles(&body), ["カメラ", "漢字"]);
+    let req = list(json!({ "tag": "blog", "sort": "TITLE_DESC", "limit": 3 }));
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(titles(&body), ["漢字", "カメラ", "いちご"]);
+
+    // 絞り込まなければ初期データの投稿も並ぶ
+    let req = list(json!({ "sort": "TITLE_DESC" }));
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(titles(&body)[..3], ["漢字", "カメラ", "はじめまして"]);
+}
@@ -0,0 +1,92 @@
+// SQLiteのストレージ（開き直してもタグの順序と日時がそのまま戻ること）
+use actix_web::{test, App};
+use blog_server::{build_app_state, configure_app, open_database, Seed};
+use serde_json::{json, Value};
+
+mod common;
+use common::{graphql_request, login_request, token};
+
+const POSTS: &str = r#"
+    query Posts($tag: String) {
+        posts(tag: $tag, publishedBefore: "2025-01-01T00:00:00Z") { title tags publishedAt }
+        scheduledPosts { title scheduledAt }
+    }
+"#;
+
+// 同じファイルを開き直すたびに、アプリケーションを組み立て直して問い合わせる
+async fn query_posts(url: &str, tag: Option<&str>) -> Value {
+    let storage = open_database(url, &Seed::builtin()).await.unwrap();
+    let state = build_app_state(storage, None).await.unwrap();
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let admin = token(&test::call_and_read_body_json(&app, req).await);
+    let req = graphql_request(Some(&admin), POSTS, json!({ "tag": tag })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    state.close().await;
+    body
+}
+
+#[actix_web::test]
+async fn tags_and_datetimes_round_trip_through_sqlite() {
+    let path = std::env::temp_dir().join(format!("blog-sqlite-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let url = format!("sqlite:{}", path.display());
+
+    let storage = open_database(&url, &Seed::builtin()).await.unwrap();
+    let state = build_app_state(storage, None).await.unwrap();
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let admin = token(&test::call_and_read_body_json(&app, req).await);
+    let create = r#"
+        mutation Create($title: String!, $tags: [String!], $at: DateTimeScalar!) {
+            createPost(input: { title: $title, body: "本文", tags: $tags, scheduledAt: $at }) {
+                title
+            }
+        }
+    "#;
+    // 1ナノ秒違いの2件（オフセット付きで渡してもUTCで保存する）と、タグなしの予約投稿
+    let tags = json!(["日本語", "with \"quotes\"", "a,b", "[]"]);
+    let posts = [
+        ("先", tags.clone(), "2024-06-02T00:30:00.123456789+09:00"),
+        ("後", json!(["z", "a"]), "2024-06-01T15:30:00.12345679Z"),
+        ("予約", json!(null), "2099-12-31T23:59:59.5Z"),
+    ];
+    for (title, tags, at) in posts {
+        let variables = json!({ "title": title, "tags": tags, "at": at });
+        let req = graphql_request(Some(&admin), create, variables).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert!(body["errors"].is_null(), "{}", body);
+    }
+    state.close().await;
+
+    let body = query_posts(&url, None).await;
+    assert_eq!(
+        body["data"],
+        json!({
+            "posts": [
+                {
+                    "title": "後",
+                    "tags": ["z", "a"],
+                    "publishedAt": "2024-06-01T15:30:00.123456790+00:00",
+                },
+                {
+                    "title": "先",
+                    "tags": tags,
+                    "publishedAt": "2024-06-01T15:30:00.123456789+00:00",
+                },
+            ],
+            "scheduledPosts": [{ "title": "予約", "scheduledAt": "2099-12-31T23:59:59.500+00:00" }],
+        }),
+        "{}",
+        body
+    );
+    let body = query_posts(&url, Some("A,B")).await;
+    let titles: Vec<&str> = body["data"]["posts"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|post| post["title"].as_str().unwrap())
+        .collect();
+    assert_eq!(titles, ["先"], "{}", body);
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,355 @@
+// サブスクリプション（/api/graphql/ws のWebSocketと、/api/graphql/sse のSSE。SSEはミューテーションの拒否とレート制限を含む）
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::{rt, test, App, HttpServer};
+use blog_server::{configure_app, spawn_background_tasks};
+use chrono::Utc;
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+mod common;
+use common::{app_state, graphql_request, login_request, post_json, token};
+
+fn sse_request(query: &str) -> test::TestRequest {
+    let query: String = url::form_urlencoded::byte_serialize(query.as_bytes()).collect();
+    test::TestRequest::get().uri(&format!("/api/graphql/sse?query={}", query))
+}
+
+// キープアライブを読み飛ばして次のイベントのデータを返す
+async fn next_event(body: &mut BoxBody) -> Value {
+    loop {
+        let chunk = futures_util::future::poll_fn(|cx| Pin::new(&mut *body).poll_next(cx))
+            .await
+            .expect("stream ended")
+            .unwrap();
+        let chunk = String::from_utf8(chunk.to_vec()).unwrap();
+        if let Some(data) = chunk.strip_prefix("event: next\ndata: ") {
+            return serde_json::from_str(data.trim_end()).unwrap();
+        }
+    }
+}
+
+// テキストフレームだけを扱う最小限のWebSocketクライアント
+struct WsClient(BufReader<TcpStream>);
+
+impl WsClient {
+    async fn connect(addr: SocketAddr, path: &str, protocol: &str) -> Self {
+        let mut stream = BufReader::new(TcpStream::connect(addr).await.unwrap());
+        let head = format!(
+            "GET {} HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\
+             Sec-WebSocket-Protocol: {}\r\n\r\n",
+            path, protocol
+        );
+        stream.get_mut().write_all(head.as_bytes()).await.unwrap();
+        let mut status = String::new();
+        stream.read_line(&mut status).await.unwrap();
+        assert!(status.starts_with("HTTP/1.1 101"), "{}", status);
+        let mut line = String::new();
+        while line != "\r\n" {
+            line.clear();
+            stream.read_line(&mut line).await.unwrap();
+        }
+        Self(stream)
+    }
+
+    // クライアントからのフレームはマスクが必須（キーは0でよい）
+    async fn send(&mut self, message: Value) {
+        let payload = message.to_string().into_bytes();
+        let mut frame = vec![0x81];
+        match payload.len() {
+            len @ 0..=125 => frame.push(0x80 | len as u8),
+            len => {
+                frame.push(0x80 | 126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+        }
+        frame.extend_from_slice(&[0; 4]);
+        frame.extend_from_slice(&payload);
+        self.0.get_mut().write_all(&frame).await.unwrap();
+    }
+
+    // pingなどの制御フレームは読み飛ばす
+    async fn receive(&mut self) -> Value {
+        loop {
+            let mut head = [0; 2];
+            self.0.read_exact(&mut head).await.unwrap();
+            let len = match head[1] & 0x7f {
+                126 => self.0.read_u16().await.unwrap() as usize,
+                127 => self.0.read_u64().await.unwrap() as usize,
+                len => len as usize,
+            };
+            let mut payload = vec![0; len];
+            self.0.read_exact(&mut payload).await.unwrap();
+            if head[0] & 0x0f == 1 {
+                return serde_json::from_slice(&payload).unwrap();
+            }
+        }
+    }
+}
+
+#[actix_web::test]
+async fn streams_post_created_over_websocket() {
+    let state = app_state().await;
+    let server_state = state.clone();
+    let server = HttpServer::new(move || {
+        App::new().configure(|cfg| configure_app(cfg, &server_state))
+    })
+    .workers(1)
+    .disable_signals()
+    .bind(("127.0.0.1", 0))
+    .unwrap();
+    let addr = server.addrs()[0];
+    let server = server.run();
+    let handle = server.handle();
+    let server = rt::spawn(server);
+
+    let login = r#"mutation { login(name: "髙橋慶祐", password: "password") { token } }"#;
+    let body = post_json(addr, None, json!({ "query": login })).await.unwrap();
+    let admin = token(&body);
+    let subscription = "subscription { postCreated { title } }";
+
+    // graphql-transport-wsと、以前のgraphql-ws（subscriptions-transport-ws）の両方
+    let protocols = [
+        ("graphql-transport-ws", "subscribe", "next"),
+        ("graphql-ws", "start", "data"),
+    ];
+    for (i, (protocol, subscribe, next)) in protocols.into_iter().enumerate() {
+        let mut client = WsClient::connect(addr, "/api/graphql/ws", protocol).await;
+        client.send(json!({ "type": "connection_init" })).await;
+        assert_eq!(client.receive().await["type"], "connection_ack");
+        let message = json!({ "id": "1", "type": subscribe, "payload": { "query": subscription } });
+        client.send(message).await;
+        // 投稿されるまでは何も届かず待ち続ける
+        let waiting = tokio::time::timeout(Duration::from_millis(200), client.receive()).await;
+        assert!(waiting.is_err(), "{:?}", waiting);
+
+        let title = format!("WebSocketで届く投稿{}", i);
+        let create = r#"
+            mutation Create($title: String!) {
+                createPost(input: { title: $title, body: "本文" }) { id }
+            }
+        "#;
+        let request = json!({ "query": create, "variables": { "title": title } });
+        let body = post_json(addr, Some(&admin), request).await.unwrap();
+        assert!(body["errors"].is_null(), "{}", body);
+        let event = client.receive().await;
+        assert_eq!(
+            event,
+            json!({
+                "id": "1",
+                "type": next,
+                "payload": { "data": { "postCreated": { "title": title } } },
+            })
+        );
+    }
+
+    handle.stop(true).await;
+    server.await.unwrap().unwrap();
+}
+
+#[actix_web::test]
+async fn streams_post_created_over_sse() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let admin = token(&test::call_and_read_body_json(&app, req).await);
+
+    let req = sse_request("subscription { postCreated { title } }").to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers().get("Content-Type").unwrap(), "text/event-stream");
+    let mut body = res.into_body();
+
+    // 購読はストリームを読み始めたときに始まるので、読みながら投稿する
+    let create = r#"
+        mutation { createPost(input: { title: "SSEで届く投稿", body: "本文", authorId: "1" }) { id } }
+    "#;
+    let publish = async {
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let req = graphql_request(Some(&admin), create, json!({})).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert!(body["errors"].is_null(), "{}", body);
+    };
+    let (event, ()) = futures_util::join!(next_event(&mut body), publish);
+    assert_eq!(event, json!({ "data": { "postCreated": { "title": "SSEで届く投稿" } } }));
+}
+
+#[actix_web::test]
+async fn streams_scheduled_posts_when_they_are_published() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let admin = token(&test::call_and_read_body_json(&app, req).await);
+    let create = r#"
+        mutation Create($at: DateTimeScalar!) {
+            createPost(input: { title: "予約した投稿", body: "本文", scheduledAt: $at }) { id }
+        }
+    "#;
+    let at = (Utc::now() + chrono::Duration::seconds(1)).to_rfc3339();
+    let req = graphql_request(Some(&admin), create, json!({ "at": at })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+
+    let req = sse_request("subscription { postCreated { title } }").to_request();
+    let mut body = test::call_service(&app, req).await.into_body();
+    // 予約日時を過ぎてから公開のタスクを始める（最初の確認はすぐに行う）
+    let publish = async {
+        tokio::time::sleep(Duration::from_millis(1200)).await;
+        spawn_background_tasks(&state)
+    };
+    let events = async { futures_util::join!(next_event(&mut body), publish) };
+    let (event, background_tasks) = tokio::time::timeout(Duration::from_secs(10), events)
+        .await
+        .expect("the scheduled post should be streamed");
+    assert_eq!(event, json!({ "data": { "postCreated": { "title": "予約した投稿" } } }));
+    background_tasks.shutdown().await;
+}
+
+#[actix_web::test]
+async fn sse_accepts_only_subscriptions() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+
+    let req = sse_request("{ posts { id } }").to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 400);
+
+    // 不正な認証情報は受け付けない
+    let req = sse_request("subscription { postCreated { id } }")
+        .insert_header(("Authorization", "Bearer invalid"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 401);
+}
+
+#[actix_web::test]
+async fn sse_does_not_run_mutations() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+
+    // GETなのでリンクを踏ませるだけで実行されてしまう
+    let submit = r#"
+        mutation {
+            submitContactMessage(name: "Mallory", email: "m@example.com", message: "SSEから送った本文")
+        }
+    "#;
+    let req = sse_request(submit).to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 400);
+
+    let req = login_request("髙橋慶祐").to_request();
+    let admin = token(&test::call_and_read_body_json(&app, req).await);
+    let messages = "{ contactMessages { id } }";
+    let req = graphql_request(Some(&admin), messages, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["contactMessages"], json!([]), "{}", body);
+}
+
+#[actix_web::test]
+async fn sse_is_rate_limited_per_client_ip() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let peer: SocketAddr = "203.0.113.1:4000".parse().unwrap();
+    let subscribe = || sse_request("subscription { postCreated { id } }").peer_addr(peer);
+
+    // クエリと同じバケット（デフォルトは1分に300回）から消費する
+    for _ in 0..300 {
+        let res = test::call_service(&app, subscribe().to_request()).await;
+        assert_eq!(res.status(), 200);
+    }
+    // 送っている間にも少しずつ補充されるので、使い切るまで送る
+    let mut limited = None;
+    for _ in 0..100 {
+        let res = test::call_service(&app, subscribe().to_request()).await;
+        if res.status() == 429 {
+            limited = Some(res);
+            break;
+        }
+    }
+    let res = limited.expect("SSE subscriptions should be rate limited");
+    assert!(res.headers().contains_key("Retry-After"));
+}
+
+#[actix_web::test]
+async fn websocket_does_not_run_queries_or_mutations() {
+    let state = app_state().await;
+    let server_state = state.clone();
+    let server = HttpServer::new(move || {
+        App::new().configure(|cfg| configure_app(cfg, &server_state))
+    })
+    .workers(1)
+    .disable_signals()
+    .bind(("127.0.0.1", 0))
+    .unwrap();
+    let addr = server.addrs()[0];
+    let server = server.run();
+    let handle = server.handle();
+    let server = rt::spawn(server);
+
+    // /api/graphqlのレート制限やメールの送信回数の制限を迂回させない
+    let submit = r#"
+        mutation {
+            submitContactMessage(name: "Mallory", email: "m@example.com", message: "WSから")
+        }
+    "#;
+    let mut client = WsClient::connect(addr, "/api/graphql/ws", "graphql-transport-ws").await;
+    client.send(json!({ "type": "connection_init" })).await;
+    assert_eq!(client.receive().await["type"], "connection_ack");
+    for (id, query) in [("1", submit), ("2", "{ posts { id } }")] {
+        client.send(json!({ "id": id, "type": "subscribe", "payload": { "query": query } })).await;
+        let message = "Only subscriptions can be executed over WebSocket; use the GraphQL endpoint";
+        let error = json!({ "message": message, "extensions": { "code": "FORBIDDEN" } });
+        let payload = json!({ "data": null, "errors": [error] });
+        assert_eq!(client.receive().await, json!({ "id": id, "type": "next", "payload": payload }));
+        assert_eq!(client.receive().await, json!({ "id": id, "type": "complete" }));
+    }
+    drop(client);
+
+    let login = r#"mutation { login(name: "髙橋慶祐", password: "password") { token } }"#;
+    let body = post_json(addr, None, json!({ "query": login })).await.unwrap();
+    let admin = token(&body);
+    let messages = json!({ "query": "{ contactMessages { id } }" });
+    let body = post_json(addr, Some(&admin), messages).await.unwrap();
+    assert_eq!(body["data"]["contactMessages"], json!([]), "{}", body);
+
+    handle.stop(true).await;
+    server.await.unwrap().unwrap();
+}
+
+#[actix_web::test]
+async fn websocket_checks_credentials_and_rate_limit_on_upgrade() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let peer: SocketAddr = "203.0.113.2:4000".parse().unwrap();
+    let upgrade = || {
+        test::TestRequest::get()
+            .uri("/api/graphql/ws")
+            .peer_addr(peer)
+            .insert_header(("Upgrade", "websocket"))
+            .insert_header(("Connection", "Upgrade"))
+            .insert_header(("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ=="))
+            .insert_header(("Sec-WebSocket-Version", "13"))
+            .insert_header(("Sec-WebSocket-Protocol", "graphql-transport-ws"))
+    };
+
+    let req = upgrade().insert_header(("Authorization", "Bearer invalid")).to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 401);
+
+    // 接続の開始はクエリと同じバケットから消費する
+    let mut limited = None;
+    for _ in 0..400 {
+        let res = test::call_service(&app, upgrade().to_request()).await;
+        if res.status() == 429 {
+            limited = Some(res);
+            break;
+        }
+        assert_eq!(res.status(), 101);
+    }
+    let res = limited.expect("WebSocket connections should be rate limited");
+    assert!(res.headers().contains_key("Retry-After"));
+}
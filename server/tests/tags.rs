@@ -0,0 +1,140 @@
+// タグの正規化（作成・更新時とnormalizeTags）とタグごとの投稿数
+use actix_web::{test, App};
+use blog_server::{build_app_state, configure_app, AppStorage, MemoryStorage, Seed};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+const CREATE: &str = r#"
+    mutation Create($input: CreatePostInput!) {
+        createPost(input: $input) { id tags }
+    }
+"#;
+
+#[actix_web::test]
+async fn tags_are_normalized_on_write() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let login: Value = test::call_and_read_body_json(&app, req).await;
+    let token = token(&login);
+
+    let variables = json!({
+        "input": {
+            "title": "タグ",
+            "body": "本文",
+            "tags": ["Rust", " rust ", "ＲＵＳＴ", "Web   Dev", "　", "ｶﾀｶﾅ", "カタカナ", "Ä"],
+            "authorId": "1",
+        }
+    });
+    let req = graphql_request(Some(&token), CREATE, variables).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let post = &body["data"]["createPost"];
+    assert_eq!(post["tags"], json!(["rust", "web dev", "カタカナ", "Ä"]));
+
+    let update = r#"
+        mutation Update($id: ID!) {
+            updatePost(input: { id: $id, tags: ["Web Dev", "ＷＥＢ　ＤＥＶ", "GraphQL"] }) { tags }
+        }
+    "#;
+    let req = graphql_request(Some(&token), update, json!({ "id": post["id"] })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["updatePost"]["tags"], json!(["web dev", "graphql"]));
+
+    let req = graphql_request(None, r#"{ posts(tag: "ＧｒａｐｈＱＬ") { id } }"#, json!({}))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["posts"][0]["id"], post["id"]);
+}
+
+// 正規化する前に保存されたタグを持つ初期データ
+const SEED: &str = r#"{
+    "users": [{ "id": "10", "name": "Admin", "role": "ADMIN" }],
+    "posts": [
+        { "id": "20", "title": "古い投稿", "body": "本文", "author_id": "10",
+          "tags": ["Rust", "ＲＵＳＴ", " お知らせ "] },
+        { "id": "21", "title": "新しい投稿", "body": "本文", "author_id": "10", "tags": ["rust"] }
+    ]
+}"#;
+
+#[actix_web::test]
+async fn normalize_tags_rewrites_existing_posts() {
+    let path = std::env::temp_dir().join(format!("blog-tags-{}.json", std::process::id()));
+    std::fs::write(&path, SEED).unwrap();
+    let storage: AppStorage = Arc::new(MemoryStorage::seeded(&Seed::load(&path).unwrap()));
+    let state = build_app_state(storage, None).await.unwrap();
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("Admin").to_request();
+    let login: Value = test::call_and_read_body_json(&app, req).await;
+    let token = token(&login);
+
+    // 集計は保存されている表記の違いをまとめる
+    let tags_query = "{ tags { name count } }";
+    let req = graphql_request(None, tags_query, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let expected = json!([{ "name": "rust", "count": 2 }, { "name": "お知らせ", "count": 1 }]);
+    assert_eq!(body["data"]["tags"], expected);
+
+    let mutation = "mutation { normalizeTags }";
+    let req = graphql_request(None, mutation, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["errors"][0]["extensions"]["code"], "UNAUTHENTICATED");
+
+    let req = graphql_request(Some(&token), mutation, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["normalizeTags"], 1, "{}", body);
+    let req = graphql_request(None, r#"{ post(id: "20") { tags } }"#, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["post"]["tags"], json!(["rust", "お知らせ"]));
+
+    // 2回目は何も変わらない
+    let req = graphql_request(Some(&token), mutation, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["normalizeTags"], 0);
+
+    let req = graphql_request(None, tags_query, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["tags"], expected);
+}
+
+#[actix_web::test]
+async fn tag_counts_handle_duplicate_and_missing_tags() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let token = token(&test::call_and_read_body_json(&app, req).await);
+
+    // 同じ投稿で重複したタグは1回、タグのない投稿は何も数えない
+    let inputs = [
+        json!({ "title": "重複", "body": "本文", "tags": ["Rust", "rust", " RUST "] }),
+        json!({ "title": "空", "body": "本文", "tags": [] }),
+        json!({ "title": "省略", "body": "本文" }),
+        json!({ "title": "二つ", "body": "本文", "tags": ["rust", "ブログ"] }),
+    ];
+    for input in inputs {
+        let req = graphql_request(Some(&token), CREATE, json!({ "input": input })).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert!(body["errors"].is_null(), "{}", body);
+    }
+
+    let tags = |limit: Option<i32>| {
+        let query = "query Tags($limit: Int) { tags(limit: $limit) { name count } }";
+        graphql_request(None, query, json!({ "limit": limit })).to_request()
+    };
+    let body: Value = test::call_and_read_body_json(&app, tags(None)).await;
+    assert_eq!(
+        body["data"]["tags"],
+        json!([
+            { "name": "rust", "count": 2 },
+            { "name": "ブログ", "count": 2 },
+            { "name": "はじめに", "count": 1 },
+        ])
+    );
+    let body: Value = test::call_and_read_body_json(&app, tags(Some(1))).await;
+    assert_eq!(body["data"]["tags"], json!([{ "name": "rust", "count": 2 }]));
+    let body: Value = test::call_and_read_body_json(&app, tags(Some(0))).await;
+    assert_eq!(body["data"]["tags"], json!([]));
+}
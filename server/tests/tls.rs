@@ -0,0 +1,46 @@
+// HTTPSの設定（証明書と秘密鍵は両方そろって指定し、読めないファイルは理由を添えて拒否する）
+// 環境変数を書き換えるので、このファイルのテストは1つにまとめる
+use blog_server::{ServerConfig, TlsCertificates};
+use clap::error::ErrorKind;
+use clap::Parser;
+
+#[test]
+fn certificate_settings_are_validated_at_startup() {
+    std::env::remove_var("TLS_KEY_PATH");
+    std::env::remove_var("HTTP_REDIRECT_PORT");
+    std::env::set_var("TLS_CERT_PATH", "/etc/blog/cert.pem");
+    let error = ServerConfig::try_parse_from(["blog-server"]).err().unwrap();
+    assert_eq!(error.kind(), ErrorKind::MissingRequiredArgument);
+    assert!(error.to_string().contains("--tls-key-path"), "{}", error);
+
+    // フラグで補えば起動できる
+    let args = ["blog-server", "--tls-key-path", "/etc/blog/key.pem"];
+    let config = ServerConfig::try_parse_from(args).unwrap();
+    assert_eq!(config.tls_cert_path.unwrap().to_str(), Some("/etc/blog/cert.pem"));
+    assert_eq!(config.tls_key_path.unwrap().to_str(), Some("/etc/blog/key.pem"));
+
+    // 鍵だけ、転送用のポートだけでも起動しない
+    std::env::remove_var("TLS_CERT_PATH");
+    for args in [
+        ["blog-server", "--tls-key-path", "/etc/blog/key.pem"],
+        ["blog-server", "--http-redirect-port", "8080"],
+    ] {
+        let error = ServerConfig::try_parse_from(args).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::MissingRequiredArgument);
+        assert!(error.to_string().contains("--tls-cert-path"), "{}", error);
+    }
+
+    // 証明書のファイルがない・PEMでない場合は、どのファイルが悪いかを返す
+    let dir = std::env::temp_dir().join(format!("blog-tls-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let missing = dir.join("missing.pem");
+    let error = TlsCertificates::load(&missing, &missing).unwrap_err();
+    assert!(error.starts_with("Failed to read TLS_CERT_PATH"), "{}", error);
+    assert!(error.contains("missing.pem"), "{}", error);
+    let not_pem = dir.join("cert.pem");
+    std::fs::write(&not_pem, "not a certificate").unwrap();
+    let error = TlsCertificates::load(&not_pem, &missing).unwrap_err();
+    assert!(error.starts_with("Failed to read TLS_CERT_PATH"), "{}", error);
+    assert!(error.ends_with("no certificates found"), "{}", error);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,60 @@
+// updateUser（名前やアバターの変更は、既存の投稿の著者にもそのまま反映される）
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+const UPDATE: &str = r#"
+    mutation Update($id: ID!) {
+        updateUser(input: { id: $id, name: "佐藤次郎", avatarUrl: "https://example.com/sato.png" }) {
+            id name avatarUrl
+        }
+    }
+"#;
+
+fn error_code(body: &Value) -> &str {
+    body["errors"][0]["extensions"]["code"].as_str().unwrap_or_default()
+}
+
+#[actix_web::test]
+async fn renamed_authors_are_shown_on_their_posts() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let admin = token(&test::call_and_read_body_json(&app, req).await);
+    let create = r#"
+        mutation Create($title: String!) {
+            createPost(input: { title: $title, body: "本文", authorId: "2" }) { id }
+        }
+    "#;
+    for title in ["佐藤の投稿1", "佐藤の投稿2"] {
+        let req = graphql_request(Some(&admin), create, json!({ "title": title })).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert!(body["errors"].is_null(), "{}", body);
+    }
+
+    // 存在しないユーザーはNOT_FOUND
+    let req = graphql_request(Some(&admin), UPDATE, json!({ "id": "999" })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(error_code(&body), "NOT_FOUND", "{}", body);
+    assert_eq!(body["errors"][0]["extensions"]["entity"], "User", "{}", body);
+
+    let req = graphql_request(Some(&admin), UPDATE, json!({ "id": "2" })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let renamed = body["data"]["updateUser"].clone();
+    assert_eq!(renamed["name"], "佐藤次郎");
+
+    // 変更後の一覧では、佐藤さんの投稿は全て新しい名前とアバター、他の著者はそのまま
+    let query = "{ posts(limit: 100) { title author { id name avatarUrl } } }";
+    let req = graphql_request(None, query, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let posts = body["data"]["posts"].as_array().unwrap_or_else(|| panic!("{}", body));
+    let by_sato: Vec<&Value> = posts.iter().filter(|p| p["author"]["id"] == "2").collect();
+    assert!(by_sato.len() >= 2, "{}", body);
+    assert!(by_sato.iter().all(|p| p["author"] == renamed), "{}", body);
+    let mut others = posts.iter().filter(|p| p["author"]["id"] != "2");
+    assert!(others.all(|p| p["author"]["name"] != "佐藤次郎"), "{}", body);
+}
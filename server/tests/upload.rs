@@ -0,0 +1,180 @@
+// uploadImage（GraphQL multipart requestでの画像のアップロード）と /uploads/{filename}
+// 環境変数を書き換えるので、このファイルのテストは1つにまとめる
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+const UPLOAD: &str = r#"
+    mutation Upload($file: Upload!, $purpose: ImagePurpose!) {
+        uploadImage(file: $file, purpose: $purpose)
+    }
+"#;
+
+// 1x1のグレースケールのPNG
+const PNG: &[u8] = &[
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44,
+    0x52, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00, 0x00, 0x00, 0x00, 0x3a,
+    0x7e, 0x9b, 0x55, 0x00, 0x00, 0x00, 0x0a, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0x60,
+    0x00, 0x00, 0x00, 0x02, 0x00, 0x01, 0x48, 0xaf, 0xa4, 0x71, 0x00, 0x00, 0x00, 0x00, 0x49,
+    0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+];
+
+const BOUNDARY: &str = "----blog-upload-test";
+
+// operations、map、ファイルの順に並べたmultipart/form-data
+fn upload_request(
+    token: Option<&str>,
+    purpose: &str,
+    content_type: &str,
+    content: &[u8],
+) -> test::TestRequest {
+    let operations = json!({
+        "query": UPLOAD,
+        "variables": { "file": null, "purpose": purpose },
+    });
+    let mut body = Vec::new();
+    for (name, value) in [
+        ("operations", operations.to_string()),
+        ("map", json!({ "0": ["variables.file"] }).to_string()),
+    ] {
+        body.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+                BOUNDARY, name, value
+            )
+            .as_bytes(),
+        );
+    }
+    body.extend_from_slice(
+        format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"0\"; filename=\"image\"\r\n\
+             Content-Type: {}\r\n\r\n",
+            BOUNDARY, content_type
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(content);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", BOUNDARY).as_bytes());
+
+    let mut req = test::TestRequest::post()
+        .uri("/api/graphql")
+        .insert_header((
+            "Content-Type",
+            format!("multipart/form-data; boundary={}", BOUNDARY),
+        ))
+        .set_payload(body);
+    if let Some(token) = token {
+        req = req.insert_header(("Authorization", format!("Bearer {}", token)));
+    }
+    req
+}
+
+fn error_code(body: &Value) -> &str {
+    body["errors"][0]["extensions"]["code"].as_str().unwrap_or_default()
+}
+
+#[actix_web::test]
+async fn uploads_images_and_serves_them() {
+    let uploads_dir = std::env::temp_dir().join(format!("blog-uploads-{}", std::process::id()));
+    std::env::set_var("UPLOADS_DIR", &uploads_dir);
+    std::env::set_var("MAX_UPLOAD_SIZE", "1024");
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let admin = token(&test::call_and_read_body_json(&app, req).await);
+
+    // 内容のハッシュのファイル名で保存し、SITE_BASE_URLのURLを返す
+    let req = upload_request(Some(&admin), "POST_IMAGE", "image/png", PNG).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let url = body["data"]["uploadImage"].as_str().unwrap().to_string();
+    let path = url.strip_prefix("http://localhost:8000").unwrap().to_string();
+    assert!(path.starts_with("/uploads/") && path.ends_with(".png"), "{}", url);
+    assert_eq!(path.len(), "/uploads/".len() + 64 + ".png".len());
+    let filename = path.trim_start_matches("/uploads/");
+    assert_eq!(std::fs::read(uploads_dir.join(filename)).unwrap(), PNG);
+
+    let req = test::TestRequest::get().uri(&path).to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers().get("content-type").unwrap(), "image/png");
+    assert_eq!(test::read_body(res).await, PNG);
+
+    // 同じ内容なら同じURL
+    let req = upload_request(Some(&admin), "AVATAR", "image/png", PNG).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["uploadImage"], url.as_str());
+
+    // アバターと投稿のカバー画像にそのまま使える
+    let update = r#"
+        mutation Update($url: Url!) {
+            updateUser(input: { id: "2", avatarUrl: $url }) { avatarUrl }
+        }
+    "#;
+    let req = graphql_request(Some(&admin), update, json!({ "url": url })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["updateUser"]["avatarUrl"], url.as_str(), "{}", body);
+    let create = r#"
+        mutation Create($url: Url!) {
+            createPost(input: { title: "画像", body: "本文", authorId: "1", coverImageUrl: $url }) {
+                coverImageUrl
+            }
+        }
+    "#;
+    let req = graphql_request(Some(&admin), create, json!({ "url": url })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["createPost"]["coverImageUrl"], url.as_str(), "{}", body);
+
+    // 対応していない形式と、Content-Typeと中身が一致しないファイルは受け付けない
+    let cases: [(&str, &[u8]); 3] = [
+        ("image/gif", b"GIF89a\x01\x00\x01\x00"),
+        ("image/jpeg", PNG),
+        ("image/webp", b"RIFF\x00\x00\x00\x00WAVE"),
+    ];
+    for (content_type, content) in cases {
+        let req = upload_request(Some(&admin), "POST_IMAGE", content_type, content).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(error_code(&body), "VALIDATION_FAILED", "{}: {}", content_type, body);
+    }
+
+    // MAX_UPLOAD_SIZEを超えるファイルは413
+    let large = [PNG, &[0; 1024]].concat();
+    let req = upload_request(Some(&admin), "POST_IMAGE", "image/png", &large).to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 413);
+
+    // 未ログインでは使えず、投稿用の画像は著者と管理者だけ
+    let req = upload_request(None, "AVATAR", "image/png", PNG).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(error_code(&body), "UNAUTHENTICATED", "{}", body);
+    let register = r#"
+        mutation { register(name: "読者", handle: "reader", password: "password123") { id } }
+    "#;
+    let req = graphql_request(None, register, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let req = graphql_request(
+        None,
+        "mutation { login(name: \"読者\", password: \"password123\") { token } }",
+        json!({}),
+    )
+    .to_request();
+    let reader = token(&test::call_and_read_body_json(&app, req).await);
+    let req = upload_request(Some(&reader), "POST_IMAGE", "image/png", PNG).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(error_code(&body), "FORBIDDEN", "{}", body);
+    let req = upload_request(Some(&reader), "AVATAR", "image/png", PNG).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["uploadImage"], url.as_str(), "{}", body);
+
+    // 保存した形式のファイル名以外は配信しない
+    for uri in ["/uploads/missing.png", "/uploads/..%2FCargo.toml", &path.replace(".png", ".gif")] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 404, "{}", uri);
+    }
+
+    let _ = std::fs::remove_dir_all(&uploads_dir);
+}
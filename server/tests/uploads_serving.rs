@@ -0,0 +1,68 @@
+// GET /uploads/{hash}.{ext}（Cache-Control、ETag、Rangeリクエスト、不正なパス）
+// 環境変数を書き換えるので、このファイルのテストは1つにまとめる
+use actix_web::{test, App};
+use blog_server::configure_app;
+
+mod common;
+use common::app_state;
+
+#[actix_web::test]
+async fn serves_uploads_with_cache_headers_and_ranges() {
+    let uploads_dir = std::env::temp_dir().join(format!("blog-serving-{}", std::process::id()));
+    std::fs::create_dir_all(&uploads_dir).unwrap();
+    let hash = "0123456789abcdef".repeat(4);
+    let content: Vec<u8> = b"\x89PNG\r\n\x1a\n".iter().copied().chain(0..=255).collect();
+    std::fs::write(uploads_dir.join(format!("{}.png", hash)), &content).unwrap();
+    std::env::set_var("UPLOADS_DIR", &uploads_dir);
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let uri = format!("/uploads/{}.png", hash);
+
+    let req = test::TestRequest::get().uri(&uri).to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 200);
+    let header = |name: &str| res.headers().get(name).unwrap().to_str().unwrap().to_string();
+    assert_eq!(header("content-type"), "image/png");
+    assert_eq!(header("cache-control"), "public, max-age=31536000, immutable");
+    assert_eq!(header("etag"), format!("\"{}\"", hash));
+    assert_eq!(header("accept-ranges"), "bytes");
+    assert_eq!(test::read_body(res).await, content);
+
+    // ETagが一致すれば本文を返さない
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header(("If-None-Match", format!("\"{}\"", hash)))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 304);
+    assert!(test::read_body(res).await.is_empty());
+
+    // 一部だけ取得する
+    let range = |range: &str| {
+        test::TestRequest::get().uri(&uri).insert_header(("Range", range.to_string())).to_request()
+    };
+    let res = test::call_service(&app, range("bytes=0-7")).await;
+    assert_eq!(res.status(), 206);
+    let content_range = res.headers().get("content-range").unwrap().to_str().unwrap();
+    assert_eq!(content_range, format!("bytes 0-7/{}", content.len()));
+    assert_eq!(test::read_body(res).await, &content[..8]);
+    assert_eq!(test::call_service(&app, range("bytes=9999-")).await.status(), 416);
+
+    // ディレクトリの外や存在しないファイルは404（ファイルシステムのパスは返さない）
+    std::fs::write(uploads_dir.join("secret.txt"), "secret").unwrap();
+    for uri in [
+        "/uploads/..%2F..%2Fetc%2Fpasswd",
+        "/uploads/%2e%2e%2fsecret.txt",
+        "/uploads/secret.txt",
+        &format!("/uploads/{}.png", "f".repeat(64)),
+        &format!("/uploads/{}.PNG", hash),
+        &format!("/uploads/{}.png.txt", hash),
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), 404, "{}", uri);
+        assert!(test::read_body(res).await.is_empty(), "{}", uri);
+    }
+
+    let _ = std::fs::remove_dir_all(&uploads_dir);
+}
@@ -0,0 +1,61 @@
+// Urlスカラー（avatarUrlなど）の検証と正規化
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request};
+
+const REGISTER: &str = r#"
+    mutation Register($name: String!, $avatarUrl: Url) {
+        register(
+            name: $name, handle: "new_user", password: "password123", avatarUrl: $avatarUrl
+        ) {
+            avatarUrl
+        }
+    }
+"#;
+
+async fn register(avatar_url: &str) -> Value {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let variables = json!({ "name": "新規ユーザー", "avatarUrl": avatar_url });
+    let req = graphql_request(None, REGISTER, variables).to_request();
+    test::call_and_read_body_json(&app, req).await
+}
+
+fn error_message(body: &Value) -> &str {
+    body["errors"][0]["message"].as_str().expect("expected an error")
+}
+
+#[actix_web::test]
+async fn normalizes_http_urls() {
+    let body = register("HTTPS://Example.COM:443/Avatar.png").await;
+    assert!(body["errors"].is_null(), "{}", body);
+    assert_eq!(body["data"]["register"]["avatarUrl"], "https://example.com/Avatar.png");
+
+    let body = register("http://example.com:8080").await;
+    assert_eq!(body["data"]["register"]["avatarUrl"], "http://example.com:8080/");
+}
+
+#[actix_web::test]
+async fn rejects_other_schemes_and_relative_urls() {
+    let body = register("htp://oops").await;
+    let message = error_message(&body);
+    assert!(message.contains(r#"Unsupported URL scheme "htp" in "htp://oops""#), "{}", message);
+
+    let body = register("javascript:alert(1)").await;
+    let message = error_message(&body);
+    assert!(message.contains("javascript: URLs are not allowed"), "{}", message);
+
+    let body = register("/avatar.png").await;
+    let message = error_message(&body);
+    assert!(message.contains("expected an absolute http or https URL"), "{}", message);
+
+    // 長い値は切り詰めてメッセージに含める
+    let long = format!("data:image/png;base64,{}", "A".repeat(1000));
+    let body = register(&long).await;
+    let message = error_message(&body);
+    assert!(message.contains(&format!("{}…", &long[..100])), "{}", message);
+    assert!(message.len() < 300, "{}", message);
+}
@@ -0,0 +1,83 @@
+// User.handle（登録時に指定する一意の名前）と userByHandle
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+const REGISTER: &str = r#"
+    mutation Register($name: String!, $handle: String!) {
+        register(name: $name, handle: $handle, password: "password123") { id handle }
+    }
+"#;
+
+const BY_HANDLE: &str = r#"
+    query ByHandle($handle: String!) { userByHandle(handle: $handle) { id name handle } }
+"#;
+
+fn error_code(body: &Value) -> &str {
+    body["errors"][0]["extensions"]["code"].as_str().unwrap_or_default()
+}
+
+#[actix_web::test]
+async fn registers_unique_handles() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let register = |name: &str, handle: &str| {
+        graphql_request(None, REGISTER, json!({ "name": name, "handle": handle })).to_request()
+    };
+
+    // 前後の空白を除いて小文字にそろえる
+    let body: Value = test::call_and_read_body_json(&app, register("新規", " New_User1 ")).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    assert_eq!(body["data"]["register"]["handle"], "new_user1");
+    let id = body["data"]["register"]["id"].clone();
+
+    let req = graphql_request(None, BY_HANDLE, json!({ "handle": "NEW_USER1" })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["userByHandle"]["id"], id, "{}", body);
+
+    // 大文字小文字だけが違うハンドルは使えない
+    let body: Value = test::call_and_read_body_json(&app, register("別人", "new_USER1")).await;
+    assert_eq!(error_code(&body), "CONFLICT", "{}", body);
+    assert_eq!(body["errors"][0]["message"], "Handle is already taken: new_user1");
+
+    for handle in ["ab", "a".repeat(31).as_str(), "has-dash", "はんどる", "dot.dot"] {
+        let body: Value = test::call_and_read_body_json(&app, register("別人", handle)).await;
+        assert_eq!(error_code(&body), "VALIDATION_FAILED", "{}: {}", handle, body);
+    }
+    let body: Value = test::call_and_read_body_json(&app, register("別人", "abc")).await;
+    assert!(body["errors"].is_null(), "{}", body);
+
+    // 管理者の作成でも重複はCONFLICT
+    let req = login_request("髙橋慶祐").to_request();
+    let token = token(&test::call_and_read_body_json(&app, req).await);
+    let create = r#"mutation { createUser(input: { name: "作成", handle: "ABC" }) { id } }"#;
+    let req = graphql_request(Some(&token), create, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(error_code(&body), "CONFLICT", "{}", body);
+    assert_eq!(body["errors"][0]["message"], "Handle is already taken: abc");
+
+    // 作成後は変えられない
+    let update = r#"mutation { updateUser(input: { id: "2", handle: "sato" }) { id } }"#;
+    let req = graphql_request(Some(&token), update, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(!body["errors"].is_null(), "{}", body);
+}
+
+#[actix_web::test]
+async fn seed_users_get_derived_handles() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+
+    // 組み込みの初期データの名前は英数字を含まないのでIDから作る
+    let req = graphql_request(None, BY_HANDLE, json!({ "handle": "user_2" })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["userByHandle"]["name"], "佐藤太郎", "{}", body);
+
+    let req = graphql_request(None, BY_HANDLE, json!({ "handle": "nobody" })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(error_code(&body), "NOT_FOUND", "{}", body);
+    assert!(body["data"]["userByHandle"].is_null());
+}
@@ -0,0 +1,98 @@
+// ユーザーのプロフィール（bio / bioHtml / website / location）と自己紹介での検索
+use actix_web::{test, App};
+use blog_server::configure_app;
+use serde_json::{json, Value};
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+const UPDATE: &str = r#"
+    mutation Update($input: UpdateUserInput!) {
+        updateUser(input: $input) { bio bioHtml website location }
+    }
+"#;
+
+#[actix_web::test]
+async fn updates_profile_fields() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let token = token(&test::call_and_read_body_json(&app, req).await);
+    let update = |input: Value| {
+        graphql_request(Some(&token), UPDATE, json!({ "input": input })).to_request()
+    };
+
+    // 自己紹介は投稿の本文と同じく描画し、生のHTMLのタグは取り除く
+    let input = json!({
+        "id": "2",
+        "bio": "  **Rust** が好きです<script>alert(1)</script>  ",
+        "website": "https://example.com/sato",
+        "location": " 東京 ",
+    });
+    let body: Value = test::call_and_read_body_json(&app, update(input)).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let user = &body["data"]["updateUser"];
+    assert_eq!(user["bio"], "**Rust** が好きです<script>alert(1)</script>");
+    assert_eq!(user["bioHtml"], "<p><strong>Rust</strong> が好きですalert(1)</p>\n");
+    assert_eq!(user["website"], "https://example.com/sato");
+    assert_eq!(user["location"], "東京");
+
+    // 省略したフィールドは変えず、nullや空白だけなら削除する
+    let input = json!({ "id": "2", "bio": "   ", "website": null });
+    let body: Value = test::call_and_read_body_json(&app, update(input)).await;
+    let user = &body["data"]["updateUser"];
+    assert!(user["bio"].is_null() && user["bioHtml"].is_null(), "{}", body);
+    assert!(user["website"].is_null(), "{}", body);
+    assert_eq!(user["location"], "東京");
+
+    // 違反はフィールドごとにextensions.validationに入れる（文字数で数える）
+    let input = json!({ "id": "2", "bio": "あ".repeat(501), "location": "い".repeat(101) });
+    let body: Value = test::call_and_read_body_json(&app, update(input)).await;
+    assert_eq!(body["errors"][0]["extensions"]["code"], "VALIDATION_FAILED", "{}", body);
+    assert_eq!(
+        body["errors"][0]["extensions"]["validation"],
+        json!([
+            { "field": "bio", "rule": "maxLength", "limit": 500 },
+            { "field": "location", "rule": "maxLength", "limit": 100 },
+        ])
+    );
+    let input = json!({ "id": "2", "bio": "あ".repeat(500), "location": "い".repeat(100) });
+    let body: Value = test::call_and_read_body_json(&app, update(input)).await;
+    assert!(body["errors"].is_null(), "{}", body);
+
+    let input = json!({ "id": "2", "website": "javascript:alert(1)" });
+    let body: Value = test::call_and_read_body_json(&app, update(input)).await;
+    assert!(!body["errors"].is_null(), "{}", body);
+}
+
+#[actix_web::test]
+async fn users_search_optionally_matches_bio() {
+    let state = app_state().await;
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let req = login_request("髙橋慶祐").to_request();
+    let token = token(&test::call_and_read_body_json(&app, req).await);
+    let input = json!({ "id": "2", "bio": "Rustacean です" });
+    let req = graphql_request(Some(&token), UPDATE, json!({ "input": input })).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["errors"].is_null(), "{}", body);
+
+    let search = r#"
+        query Search($search: String!, $includeBio: Boolean!) {
+            users(search: $search, includeBio: $includeBio) { id }
+            usersCount(search: $search, includeBio: $includeBio)
+        }
+    "#;
+    for (include_bio, expected) in [(false, json!([])), (true, json!([{ "id": "2" }]))] {
+        let variables = json!({ "search": "RUSTACEAN", "includeBio": include_bio });
+        let req = graphql_request(None, search, variables).to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["data"]["users"], expected, "{}", body);
+        assert_eq!(body["data"]["usersCount"], expected.as_array().unwrap().len());
+    }
+
+    // 名前での検索はこれまでどおり
+    let query = r#"{ users(search: "佐藤", includeBio: true) { name } }"#;
+    let req = graphql_request(None, query, json!({})).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["users"], json!([{ "name": "佐藤太郎" }]), "{}", body);
+}
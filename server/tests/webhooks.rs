@@ -0,0 +1,235 @@
+// Webhook（registerWebhook / deleteWebhook / webhooks と送信・署名・再試行）
+// 環境変数を書き換えるので、このファイルのテストは1つにまとめる
+use actix_web::{test, web, App, HttpRequest, HttpResponse, HttpServer};
+use blog_server::{configure_app, spawn_background_tasks};
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::sync::Mutex;
+use std::time::Duration;
+
+mod common;
+use common::{app_state, graphql_request, login_request, token};
+
+const REGISTER: &str = r#"
+    mutation Register($url: Url!, $events: [WebhookEvent!]!, $secret: String!) {
+        registerWebhook(url: $url, events: $events, secret: $secret) {
+            id url events lastDelivery { event }
+        }
+    }
+"#;
+
+const WEBHOOKS: &str = r#"
+    {
+        webhooks {
+            id
+            lastDelivery { event succeeded statusCode error attempts deliveredAt }
+        }
+    }
+"#;
+
+struct Received {
+    event: String,
+    delivery: String,
+    signature: String,
+    body: String,
+}
+
+// 受け取ったリクエストを記録する。再試行を確かめるため最初のリクエストだけ500を返す
+async fn receive(
+    req: HttpRequest,
+    body: web::Bytes,
+    received: web::Data<Mutex<Vec<Received>>>,
+) -> HttpResponse {
+    let header = |name: &str| {
+        let value = req.headers().get(name).and_then(|v| v.to_str().ok());
+        value.unwrap_or_default().to_string()
+    };
+    let mut received = received.lock().unwrap();
+    received.push(Received {
+        event: header("X-Blog-Event"),
+        delivery: header("X-Blog-Delivery"),
+        signature: header("X-Blog-Signature"),
+        body: String::from_utf8(body.to_vec()).unwrap(),
+    });
+    if received.len() == 1 {
+        HttpResponse::InternalServerError().finish()
+    } else {
+        HttpResponse::Ok().finish()
+    }
+}
+
+fn sign(secret: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(body.as_bytes());
+    format!("sha256={:x}", mac.finalize().into_bytes())
+}
+
+#[actix_web::test]
+async fn delivers_signed_events_with_retries() {
+    std::env::set_var("WEBHOOK_RETRY_BASE_MS", "10");
+    std::env::set_var("WEBHOOK_MAX_RETRIES", "2");
+    let state = app_state().await;
+    std::env::remove_var("WEBHOOK_RETRY_BASE_MS");
+    std::env::remove_var("WEBHOOK_MAX_RETRIES");
+    let app = test::init_service(App::new().configure(|cfg| configure_app(cfg, &state))).await;
+    let background_tasks = spawn_background_tasks(&state);
+
+    let received = web::Data::new(Mutex::new(Vec::<Received>::new()));
+    let server_data = received.clone();
+    let server = HttpServer::new(move || {
+        App::new().app_data(server_data.clone()).default_service(web::to(receive))
+    })
+    .workers(1)
+    .disable_signals()
+    .bind(("127.0.0.1", 0))
+    .unwrap();
+    let receiver_url = format!("http://{}/hook", server.addrs()[0]);
+    let server = server.run();
+    let server_handle = server.handle();
+    actix_web::rt::spawn(server);
+
+    let req = login_request("髙橋慶祐").to_request();
+    let admin = token(&test::call_and_read_body_json(&app, req).await);
+    let req = login_request("佐藤太郎").to_request();
+    let author = token(&test::call_and_read_body_json(&app, req).await);
+    let call = |token: &str, query: &str, variables: Value| {
+        graphql_request(Some(token), query, variables).to_request()
+    };
+    let received_events = || -> Vec<String> {
+        received.lock().unwrap().iter().map(|r| r.event.clone()).collect()
+    };
+    let wait_for_requests = |count: usize| {
+        let received = received.clone();
+        async move {
+            for _ in 0..200 {
+                if received.lock().unwrap().len() >= count {
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(25)).await;
+            }
+            panic!("webhook was not delivered {} time(s)", count);
+        }
+    };
+
+    // 管理者のみ
+    let variables = json!({ "url": receiver_url, "events": ["POST_CREATED"], "secret": "s" });
+    let body: Value = test::call_and_read_body_json(&app, call(&author, REGISTER, variables)).await;
+    assert_eq!(body["errors"][0]["extensions"]["code"], "FORBIDDEN", "{}", body);
+    let body: Value = test::call_and_read_body_json(&app, call(&author, WEBHOOKS, json!({}))).await;
+    assert_eq!(body["errors"][0]["extensions"]["code"], "FORBIDDEN", "{}", body);
+
+    for (events, secret) in [(json!([]), "s3cret"), (json!(["POST_CREATED"]), "")] {
+        let variables = json!({ "url": receiver_url, "events": events, "secret": secret });
+        let req = call(&admin, REGISTER, variables);
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["errors"][0]["extensions"]["code"], "VALIDATION_FAILED", "{}", body);
+    }
+
+    // 重複したイベントはまとめる
+    let events = json!(["POST_CREATED", "COMMENT_ADDED", "POST_CREATED"]);
+    let variables = json!({ "url": receiver_url, "events": events, "secret": "s3cret" });
+    let body: Value = test::call_and_read_body_json(&app, call(&admin, REGISTER, variables)).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let hook = &body["data"]["registerWebhook"];
+    assert_eq!(hook["events"], json!(["POST_CREATED", "COMMENT_ADDED"]));
+    assert!(hook["lastDelivery"].is_null());
+    let hook_id = hook["id"].as_str().unwrap().to_string();
+    // 接続できないURLへの送信が失敗してもミューテーションは成功する
+    let unreachable = "http://127.0.0.1:1/";
+    let variables = json!({ "url": unreachable, "events": ["POST_UPDATED"], "secret": "x" });
+    let body: Value = test::call_and_read_body_json(&app, call(&admin, REGISTER, variables)).await;
+    let unreachable_id = body["data"]["registerWebhook"]["id"].as_str().unwrap().to_string();
+
+    // 最初の送信は500なので再試行し、同じ本文と署名を送る
+    let create = r#"
+        mutation { createPost(input: { title: "Webhook", body: "本文", authorId: "1" }) { id } }
+    "#;
+    let body: Value = test::call_and_read_body_json(&app, call(&admin, create, json!({}))).await;
+    assert!(body["errors"].is_null(), "{}", body);
+    let post_id = body["data"]["createPost"]["id"].as_str().unwrap().to_string();
+    wait_for_requests(2).await;
+    {
+        let requests = received.lock().unwrap();
+        let (first, second) = (&requests[0], &requests[1]);
+        assert_eq!(first.event, "POST_CREATED");
+        assert_eq!(first.signature, sign("s3cret", &first.body));
+        assert_eq!(
+            (&second.delivery, &second.body, &second.signature),
+            (&first.delivery, &first.body, &first.signature)
+        );
+        let payload: Value = serde_json::from_str(&first.body).unwrap();
+        assert_eq!(payload["id"], first.delivery.as_str());
+        assert_eq!(payload["event"], "POST_CREATED");
+        assert_eq!(payload["post"]["id"], post_id.as_str());
+        assert_eq!(payload["post"]["title"], "Webhook");
+        assert_eq!(payload["post"]["status"], "PUBLISHED");
+    }
+
+    // 登録していないイベント（更新）は送らない
+    let update = r#"
+        mutation Update($id: ID!) { updatePost(input: { id: $id, title: "更新" }) { title } }
+    "#;
+    let req = call(&admin, update, json!({ "id": post_id }));
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["updatePost"]["title"], "更新", "{}", body);
+
+    let add = r#"
+        mutation Add($postId: ID!) {
+            addComment(input: { postId: $postId, authorId: "2", body: "コメント" }) { id }
+        }
+    "#;
+    let req = call(&admin, add, json!({ "postId": post_id }));
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let comment_id = body["data"]["addComment"]["id"].as_str().unwrap().to_string();
+    wait_for_requests(3).await;
+    assert_eq!(received_events(), ["POST_CREATED", "POST_CREATED", "COMMENT_ADDED"]);
+    let payload: Value = serde_json::from_str(&received.lock().unwrap()[2].body).unwrap();
+    assert_eq!(payload["comment"]["id"], comment_id.as_str());
+    assert_eq!(payload["comment"]["postId"], post_id.as_str());
+    assert!(payload["comment"]["parentCommentId"].is_null());
+
+    // 最後の送信の結果を記録する（接続できない方は1 + WEBHOOK_MAX_RETRIES回で諦める）
+    let mut webhooks = Vec::new();
+    for _ in 0..200 {
+        let body: Value =
+            test::call_and_read_body_json(&app, call(&admin, WEBHOOKS, json!({}))).await;
+        webhooks = body["data"]["webhooks"].as_array().unwrap().clone();
+        if webhooks.iter().all(|w| !w["lastDelivery"].is_null()) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(25)).await;
+    }
+    let delivery = |id: &str| {
+        let webhook = webhooks.iter().find(|w| w["id"] == id).unwrap();
+        webhook["lastDelivery"].clone()
+    };
+    let last = delivery(&hook_id);
+    assert_eq!(last["event"], "COMMENT_ADDED", "{}", last);
+    assert_eq!(last["succeeded"], true);
+    assert_eq!(last["statusCode"], 200);
+    assert_eq!(last["attempts"], 1);
+    assert!(last["error"].is_null());
+    let failed = delivery(&unreachable_id);
+    assert_eq!(failed["event"], "POST_UPDATED", "{}", failed);
+    assert_eq!(failed["succeeded"], false);
+    assert!(failed["statusCode"].is_null());
+    assert!(failed["error"].is_string());
+    assert_eq!(failed["attempts"], 3);
+
+    // 削除すると送らない
+    let delete = r#"mutation Delete($id: ID!) { deleteWebhook(id: $id) }"#;
+    let req = call(&admin, delete, json!({ "id": hook_id }));
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["deleteWebhook"], true, "{}", body);
+    let req = call(&admin, delete, json!({ "id": hook_id }));
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["deleteWebhook"], false, "{}", body);
+    let req = call(&admin, add, json!({ "postId": post_id }));
+    test::call_service(&app, req).await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(received.lock().unwrap().len(), 3);
+
+    background_tasks.shutdown().await;
+    server_handle.stop(true).await;
+}